@@ -0,0 +1,81 @@
+//! Closest hits against the teapot, testing every triangle against testing
+//! only the ones the BVH leads to, and how long building the tree takes.
+//! `cargo bench --bench bvh`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use opengl_rust::bvh::Bvh;
+use opengl_rust::math::Vec3;
+use opengl_rust::model_loader::Model;
+use opengl_rust::picking::{self, Ray};
+use opengl_rust::primitives;
+
+/// Rays from all around the model towards points near its middle, the same
+/// ones every run.
+fn rays(count: usize) -> Vec<Ray> {
+    let mut state = 0x9e37_79b9_u32;
+    let mut random = || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+    (0..count)
+        .map(|_| {
+            let origin = Vec3::new(random(), random(), random()).normalize() * 8.0;
+            let target = Vec3::new(random(), random(), random()) * 3.0;
+            Ray {
+                origin,
+                direction: (target - origin).normalize(),
+            }
+        })
+        .collect()
+}
+
+fn brute_force(model: &Model, ray: &Ray) -> Option<f32> {
+    model
+        .indexes
+        .chunks_exact(3)
+        .filter_map(|triangle| {
+            let corner = |index: u32| Vec3::from(model.vertices[index as usize].position);
+            let corners = [
+                corner(triangle[0]),
+                corner(triangle[1]),
+                corner(triangle[2]),
+            ];
+            picking::intersect_triangle(ray, corners).map(|(distance, _, _)| distance)
+        })
+        .min_by(f32::total_cmp)
+}
+
+fn closest_hits(c: &mut Criterion) {
+    let rays = rays(1000);
+    let mut group = c.benchmark_group("closest_hits");
+    for resolution in [4, 10, 20] {
+        let model = primitives::teapot(resolution);
+        let bvh = Bvh::build(&model);
+        let triangles = model.indexes.len() / 3;
+        group.bench_function(BenchmarkId::new("brute_force", triangles), |b| {
+            b.iter(|| {
+                rays.iter()
+                    .filter_map(|ray| brute_force(&model, ray))
+                    .count()
+            })
+        });
+        group.bench_function(BenchmarkId::new("bvh", triangles), |b| {
+            b.iter(|| {
+                rays.iter()
+                    .filter_map(|ray| bvh.intersect_ray(&model, ray))
+                    .count()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn build(c: &mut Criterion) {
+    let model = primitives::teapot(20);
+    c.bench_function("build", |b| b.iter(|| Bvh::build(&model)));
+}
+
+criterion_group!(benches, closest_hits, build);
+criterion_main!(benches);
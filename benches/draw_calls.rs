@@ -0,0 +1,100 @@
+//! Frames of many objects drawn one call each, which all share the pass's
+//! matrices and lights. Desktop GL binds those once as the `u_frame`
+//! uniform buffer; OpenGL ES is sent them with every draw, as every context
+//! was before the buffer. Also the instanced stress grid, where the same
+//! number of objects takes a handful of calls. Renders through Mesa's
+//! surfaceless platform, and is skipped where there is none.
+//! `cargo bench --bench draw_calls`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use glium::framebuffer::{DepthStencilRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{DepthStencilFormat, Texture2d};
+use opengl_rust::args::Args;
+use opengl_rust::camera::Camera;
+use opengl_rust::context;
+use opengl_rust::math::Vec3;
+use opengl_rust::primitives;
+use opengl_rust::renderer::Renderer;
+use opengl_rust::scene::{Light, LightKind, Scene, Transform};
+
+const SIZE: (u32, u32) = (320, 240);
+/// Objects in the grid.
+const OBJECTS: usize = 256;
+
+/// `OBJECTS` cubes in a square grid, each its own mesh so each is its own
+/// draw call, or all the one mesh so they're drawn instanced.
+fn grid(renderer: &mut Renderer, instanced: bool) -> Scene {
+    let mut scene = Scene::default();
+    scene.lights.push(Light {
+        kind: LightKind::Directional {
+            direction: Vec3::new(1.0, 2.0, 1.5),
+        },
+        color: [1.0, 1.0, 1.0],
+        intensity: 1.0,
+    });
+    scene
+        .lights
+        .push(Light::point(Vec3::new(0.0, 3.0, 0.0), 0.1));
+    let side = (OBJECTS as f32).sqrt().ceil() as usize;
+    let shared = instanced.then(|| renderer.add_mesh(primitives::cube(0.5)));
+    for i in 0..OBJECTS {
+        let mesh = shared.unwrap_or_else(|| renderer.add_mesh(primitives::cube(0.5)));
+        let transform = Transform {
+            translation: Vec3::new(
+                (i % side) as f32 - side as f32 / 2.0,
+                0.25,
+                (i / side) as f32 - side as f32 / 2.0,
+            ),
+            ..Default::default()
+        };
+        scene.add(mesh, transform);
+    }
+    scene
+}
+
+fn frames(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame");
+    for (name, flags) in [("gl", &[][..]), ("gles", &["--gles"][..])] {
+        let mut args = Args::parse_from(flags.iter().map(|flag| flag.to_string())).unwrap();
+        (args.width, args.height) = SIZE;
+        let facade = match context::create_surfaceless(&args) {
+            Ok(facade) => facade,
+            Err(error) => {
+                eprintln!("skipping {}, no context: {}", name, error);
+                continue;
+            }
+        };
+        let color = Texture2d::empty(&facade, SIZE.0, SIZE.1).unwrap();
+        let depth =
+            DepthStencilRenderBuffer::new(&facade, DepthStencilFormat::I24I8, SIZE.0, SIZE.1)
+                .unwrap();
+        let mut target =
+            SimpleFrameBuffer::with_depth_stencil_buffer(&facade, &color, &depth).unwrap();
+        let camera = Camera::looking_at([0.0, 12.0, 18.0], [0.0, 0.0, 0.0]);
+        for instanced in [false, true] {
+            let mut renderer = Renderer::new(&facade).unwrap();
+            renderer.resize(SIZE.into());
+            let scene = grid(&mut renderer, instanced);
+            let draw_calls = renderer.render(&mut target, &scene, &camera).draw_calls;
+            let scene_name = match instanced {
+                true => "instanced",
+                false => "separate",
+            };
+            let id = BenchmarkId::new(name, format!("{} ({} draws)", scene_name, draw_calls));
+            group.bench_function(id, |b| {
+                b.iter(|| {
+                    renderer.render(&mut target, &scene, &camera);
+                    facade.finish();
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = frames
+}
+criterion_main!(benches);
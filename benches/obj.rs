@@ -0,0 +1,74 @@
+//! How fast OBJ files parse, on generated grids with positions, texture
+//! coordinates and normals, which is what scanned and exported models look
+//! like, and how that scales with threads. `cargo bench --bench obj`.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use opengl_rust::model_loader::{parse_obj, LoadProgress};
+
+/// An OBJ of a wavy `size` by `size` grid of quads, with each corner
+/// written as `v/vt/vn`.
+fn grid_obj(size: usize) -> String {
+    let mut obj = String::new();
+    let side = size + 1;
+    for row in 0..side {
+        for column in 0..side {
+            let (x, z) = (column as f32 / size as f32, row as f32 / size as f32);
+            let y = (x * 12.9898).sin() * (z * 78.233).cos() * 0.1;
+            writeln!(obj, "v {:.6} {:.6} {:.6}", x, y, z).unwrap();
+            writeln!(obj, "vt {:.6} {:.6}", x, z).unwrap();
+            writeln!(obj, "vn {:.6} {:.6} {:.6}", -y, 1.0, y * 0.5).unwrap();
+        }
+    }
+    for row in 0..size {
+        for column in 0..size {
+            let corner = row * side + column + 1;
+            let corners = [corner, corner + 1, corner + side + 1, corner + side];
+            obj += "f";
+            for index in corners {
+                write!(obj, " {}/{}/{}", index, index, index).unwrap();
+            }
+            obj += "\n";
+        }
+    }
+    obj
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_obj");
+    group.sample_size(10);
+    for size in [64, 256, 512] {
+        let obj = grid_obj(size);
+        group.throughput(Throughput::Bytes(obj.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &obj, |b, obj| {
+            b.iter(|| parse_obj(obj.as_bytes(), Path::new("."), &LoadProgress::default()))
+        });
+    }
+    group.finish();
+}
+
+/// The same big grid parsed on thread pools of growing size, to see how
+/// well the pieces spread out.
+fn threads(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_obj_threads");
+    group.sample_size(10);
+    let obj = grid_obj(512);
+    group.throughput(Throughput::Bytes(obj.len() as u64));
+    for threads in [1, 2, 4, 8] {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &obj, |b, obj| {
+            b.iter(|| {
+                pool.install(|| parse_obj(obj.as_bytes(), Path::new("."), &LoadProgress::default()))
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse, threads);
+criterion_main!(benches);
@@ -0,0 +1,371 @@
+#version 150
+
+in vec3 v_normal;
+in vec3 v_tangent;
+in vec3 v_bitangent;
+in vec3 v_position;
+in vec2 v_tex_coords;
+in vec3 v_color;
+in vec3 v_tint;
+in vec4 v_light_space_position;
+in float v_world_y;
+in float v_occlusion;
+out vec4 color;
+#ifdef EDGE_OVERLAY
+// Pixels to the triangle's three edges, and how wide their lines are.
+noperspective in vec3 v_edge_distance;
+uniform float u_edge_width;
+#endif
+uniform sampler2D u_diffuse_map;
+// Tangent-space normals, used where u_normal_mapping is set: the material
+// has a map and the mesh has tangents to map it by.
+uniform sampler2D u_normal_map;
+uniform bool u_normal_mapping;
+
+// Everything that stays the same for a whole pass over the scene, written
+// once per pass rather than with every draw. Declared alike in both stages;
+// `FrameUniforms` in renderer.rs matches its std140 layout.
+const int MAX_LIGHTS = 8;
+layout(std140) uniform u_frame {
+    mat4 perspective;
+    mat4 view;
+    mat4 u_inverse_view;
+    mat4 u_light_space;
+    // In world space, and seconds since the renderer started.
+    vec3 u_camera_position;
+    float u_time;
+    // The planes the projection was built with.
+    float u_znear;
+    float u_zfar;
+    // Which light the shadow map was rendered for, or -1 for none.
+    int u_shadow_light;
+    int u_light_count;
+    // Towards a directional light, w = 0, or a point light's position,
+    // w = 1, in view space.
+    vec4 u_light_vector[MAX_LIGHTS];
+    // Color times intensity, and attenuation in alpha.
+    vec4 u_light_color[MAX_LIGHTS];
+};
+uniform sampler2D u_shadow_map;
+// The models mirrored under the ground, looked up by screen position, and
+// how much of it shows: 0 on everything but the ground.
+uniform sampler2D u_reflection;
+uniform float u_reflectivity;
+// Set while drawing that mirror image, where what's under the ground would
+// end up above it.
+uniform bool u_clip_below_ground;
+// The sky, mirrored by u_environment_reflectivity of the shaded color.
+// Looked up by world direction, hence the rotation back out of view space.
+uniform samplerCube u_environment;
+uniform float u_environment_reflectivity;
+// How open each pixel is to the ambient light, from 0 where the models
+// enclose it to 1, looked up by screen position when u_ambient_occlusion.
+uniform bool u_ambient_occlusion;
+uniform sampler2D u_occlusion;
+// Whether v_occlusion, baked into the vertices, dims the ambient and
+// diffuse light as well.
+uniform bool u_baked_occlusion;
+
+uniform vec3 u_ambient;
+uniform vec3 u_diffuse;
+uniform vec3 u_specular;
+uniform float u_shininess;
+// Blended over what's behind when less than 1, drawn after everything
+// opaque.
+uniform float u_opacity;
+// The discriminant of renderer::FogMode: 0 for none, 1 linear from
+// u_fog_start to u_fog_end, 2 exponential by u_fog_density. In linear
+// RGB.
+uniform int u_fog_mode;
+uniform vec3 u_fog_color;
+uniform float u_fog_density;
+uniform float u_fog_start;
+uniform float u_fog_end;
+// The PBR shading's terms, scaled by the map's blue and green, which is
+// white when the material has none.
+uniform float u_metallic;
+uniform float u_roughness;
+uniform sampler2D u_metallic_roughness_map;
+uniform bool u_has_vertex_colors;
+uniform bool u_wireframe;
+// See `RenderMode` and `ShadingModel` in renderer.rs.
+uniform int u_render_mode;
+uniform int u_shading_model;
+uniform int u_toon_bands;
+// Which kind of projection `perspective` is.
+uniform bool u_orthographic;
+
+// Vertex colors are sRGB-encoded like material colors; lighting is linear.
+vec3 srgb_to_linear(vec3 srgb) {
+    vec3 curve = pow((srgb + 0.055) / 1.055, vec3(2.4));
+    return mix(srgb / 12.92, curve, step(0.04045, srgb));
+}
+
+const float PI = 3.14159265;
+// The smoothest the PBR shading goes. At 0 the GGX distribution is
+// infinitely sharp, 0 / 0 where the half vector meets the normal.
+const float MIN_ROUGHNESS = 0.045;
+// Light reaching every surface from all around, for the PBR shading.
+const vec3 PBR_AMBIENT = vec3(0.1);
+// Reflectance of dielectrics head on, about 4% for most of them.
+const vec3 DIELECTRIC_F0 = vec3(0.04);
+
+// What fraction of the microfacets face along the half vector, by the GGX
+// (Trowbridge-Reitz) distribution.
+float distribution_ggx(float n_dot_h, float alpha) {
+    float alpha2 = alpha * alpha;
+    float d = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    return alpha2 / (PI * d * d);
+}
+
+// Smith's term for microfacets shadowing and masking each other, each
+// side by Schlick's approximation with the direct lighting's k.
+float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    float masking = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    float shadowing = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return masking * shadowing;
+}
+
+// Schlick's approximation of the reflectance at `cosine` from the normal.
+vec3 fresnel_schlick(float cosine, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(1.0 - cosine, 5.0);
+}
+
+// Cook-Torrance: the light of unit color from `light_dir` that leaves
+// toward the camera, diffuse and specular. Times pi, so a white light on a
+// white surface is as bright as it is with Blinn-Phong.
+vec3 cook_torrance(
+    vec3 normal, vec3 camera_dir, vec3 light_dir, vec3 albedo, float metallic, float roughness
+) {
+    float n_dot_l = max(dot(normal, light_dir), 0.0);
+    if (n_dot_l == 0.0) {
+        return vec3(0.0);
+    }
+    // Glancing views of faces turned away by interpolation or the normal
+    // map would divide by zero.
+    float n_dot_v = max(dot(normal, camera_dir), 1e-4);
+    vec3 half_direction = normalize(light_dir + camera_dir);
+    float n_dot_h = max(dot(normal, half_direction), 0.0);
+    vec3 f0 = mix(DIELECTRIC_F0, albedo, metallic);
+    vec3 fresnel = fresnel_schlick(max(dot(half_direction, camera_dir), 0.0), f0);
+    float alpha = roughness * roughness;
+    vec3 specular = distribution_ggx(n_dot_h, alpha) * geometry_smith(n_dot_v, n_dot_l, roughness)
+        * fresnel / (4.0 * n_dot_v * n_dot_l);
+    // What isn't reflected goes in, and metals absorb it all.
+    vec3 diffuse = (1.0 - fresnel) * (1.0 - metallic) * albedo / PI;
+    return (diffuse + specular) * n_dot_l * PI;
+}
+
+// The tangent and bitangent made perpendicular to `normal` and each other,
+// the bitangent keeping the side it was on where the texture is mirrored.
+mat3 tangent_basis(vec3 normal) {
+    vec3 tangent = normalize(v_tangent - normal * dot(normal, v_tangent));
+    vec3 bitangent = cross(normal, tangent);
+    if (dot(bitangent, v_bitangent) < 0.0) {
+        bitangent = -bitangent;
+    }
+    return mat3(tangent, bitangent, normal);
+}
+
+// The fraction of the shadow-casting light reaching this fragment, averaged
+// over the 3x3 texels around it to soften the edges.
+float shadow_factor(vec3 normal, vec3 light_dir) {
+    vec3 coords = v_light_space_position.xyz / v_light_space_position.w * 0.5 + 0.5;
+    bool outside = any(lessThan(coords.xy, vec2(0.0))) || any(greaterThan(coords.xy, vec2(1.0)));
+    if (outside || coords.z > 1.0) {
+        return 1.0;
+    }
+    // Surfaces facing away from the light need more bias to not shadow
+    // themselves.
+    float bias = max(0.005 * (1.0 - dot(normal, light_dir)), 0.0005);
+    vec2 texel = 1.0 / vec2(textureSize(u_shadow_map, 0));
+    float lit = 0.0;
+    for (int x = -1; x <= 1; x++) {
+        for (int y = -1; y <= 1; y++) {
+            float depth = texture(u_shadow_map, coords.xy + vec2(x, y) * texel).r;
+            lit += coords.z - bias > depth ? 0.0 : 1.0;
+        }
+    }
+    return lit / 9.0;
+}
+
+// The reflection at this pixel, slightly blurred.
+vec3 reflection() {
+    vec2 size = vec2(textureSize(u_reflection, 0));
+    vec2 uv = gl_FragCoord.xy / size;
+    vec2 offset = 1.5 / size;
+    vec3 sum = texture(u_reflection, uv).rgb * 2.0;
+    sum += texture(u_reflection, uv + vec2(offset.x, 0.0)).rgb;
+    sum += texture(u_reflection, uv - vec2(offset.x, 0.0)).rgb;
+    sum += texture(u_reflection, uv + vec2(0.0, offset.y)).rgb;
+    sum += texture(u_reflection, uv - vec2(0.0, offset.y)).rgb;
+    return sum / 6.0;
+}
+
+// The fragment's color, before any overlay.
+void shade() {
+    if (u_clip_below_ground && v_world_y < 0.0) {
+        discard;
+    }
+    if (u_wireframe) {
+        color = vec4(vec3(0.79), 1.0);
+        return;
+    }
+    switch (u_render_mode) {
+    case 1:
+        color = vec4(normalize(v_normal) * 0.5 + 0.5, 1.0);
+        return;
+    case 2: {
+        // Undo the projection to get the distance along the view axis,
+        // then show it on a log scale: the far plane is so much further
+        // than the near one that a linear ramp would be black.
+        float ndc = gl_FragCoord.z * 2.0 - 1.0;
+        float linear = u_orthographic
+            ? (ndc * (u_zfar - u_znear) + u_zfar + u_znear) / 2.0
+            : 2.0 * u_znear * u_zfar / (u_zfar + u_znear - ndc * (u_zfar - u_znear));
+        float gray = log(linear / u_znear) / log(u_zfar / u_znear);
+        color = vec4(vec3(gray), 1.0);
+        return;
+    }
+    case 3: {
+        // Halved where the texture is mirrored, so seams between the two
+        // show up. Gray without tangents.
+        if (v_tangent == vec3(0.0)) {
+            color = vec4(vec3(0.5), 1.0);
+            return;
+        }
+        mat3 basis = tangent_basis(normalize(v_normal));
+        bool mirrored = dot(basis[1], cross(basis[2], basis[0])) < 0.0;
+        color = vec4((basis[0] * 0.5 + 0.5) * (mirrored ? 0.5 : 1.0), 1.0);
+        return;
+    }
+    }
+
+    // Interpolated normals are shorter between vertices, which would make
+    // the reflections warble.
+    vec3 normal = normalize(v_normal);
+    if (u_normal_mapping) {
+        vec3 mapped = texture(u_normal_map, v_tex_coords).xyz * 2.0 - 1.0;
+        normal = normalize(tangent_basis(normal) * mapped);
+    }
+    // Every view ray is parallel in an orthographic view.
+    vec3 camera_dir = u_orthographic ? vec3(0.0, 0.0, -1.0) : normalize(-v_position);
+    vec3 base_color = (u_has_vertex_colors ? srgb_to_linear(v_color) : u_diffuse) * v_tint;
+    vec3 diffuse_color = base_color * texture(u_diffuse_map, v_tex_coords).rgb;
+    bool pbr = u_shading_model == 3;
+    vec4 metallic_roughness = texture(u_metallic_roughness_map, v_tex_coords);
+    float metallic = clamp(u_metallic * metallic_roughness.b, 0.0, 1.0);
+    float roughness = clamp(u_roughness * metallic_roughness.g, MIN_ROUGHNESS, 1.0);
+    vec3 diffuse = vec3(0.0);
+    vec3 specular = vec3(0.0);
+    // Everything the lights give in the PBR shading.
+    vec3 radiance = vec3(0.0);
+    for (int i = 0; i < u_light_count; i++) {
+        if (u_light_color[i].rgb == vec3(0.0)) {
+            continue;
+        }
+        vec3 light_dir = normalize(u_light_vector[i].xyz);
+        vec3 light_color = u_light_color[i].rgb;
+        if (u_light_vector[i].w == 1.0) {
+            vec3 to_light = u_light_vector[i].xyz - v_position;
+            float distance = length(to_light);
+            light_dir = to_light / distance;
+            light_color /= 1.0 + u_light_color[i].a * distance * distance;
+        }
+        if (i == u_shadow_light) {
+            light_color *= shadow_factor(normal, light_dir);
+        }
+        if (pbr) {
+            radiance += cook_torrance(
+                normal, camera_dir, light_dir, diffuse_color, metallic, roughness
+            ) * light_color;
+            continue;
+        }
+        float lambert = max(dot(normal, light_dir), 0.0);
+        float highlight;
+        if (u_shading_model == 1) {
+            vec3 reflected = reflect(-light_dir, normal);
+            highlight = pow(max(dot(reflected, camera_dir), 0.0), u_shininess);
+        } else {
+            vec3 half_direction = normalize(light_dir + camera_dir);
+            highlight = pow(max(dot(half_direction, normal), 0.0), u_shininess);
+        }
+        if (u_shading_model == 2) {
+            float bands = float(u_toon_bands);
+            lambert = ceil(lambert * bands) / bands;
+            highlight = step(0.5, highlight);
+        }
+        diffuse += lambert * light_color;
+        specular += highlight * light_color;
+    }
+
+    vec3 reflected = mat3(u_inverse_view) * reflect(-camera_dir, normal);
+    float occlusion = 1.0;
+    if (u_ambient_occlusion) {
+        ivec2 texel = min(ivec2(gl_FragCoord.xy), textureSize(u_occlusion, 0) - 1);
+        occlusion = texelFetch(u_occlusion, texel, 0).r;
+    }
+    float baked = u_baked_occlusion ? v_occlusion : 1.0;
+    occlusion *= baked;
+    if (pbr) {
+        // The sky reflects by the Fresnel term too, less as the surface
+        // roughens and blurs it away, since it isn't prefiltered.
+        float n_dot_v = max(dot(normal, camera_dir), 0.0);
+        vec3 f0 = mix(DIELECTRIC_F0, diffuse_color, metallic);
+        vec3 fresnel = f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(1.0 - n_dot_v, 5.0);
+        vec3 environment = texture(u_environment, reflected).rgb;
+        vec3 ambient = PBR_AMBIENT * diffuse_color * (1.0 - metallic) * (1.0 - fresnel)
+            * occlusion;
+        vec3 sky = environment * fresnel * (1.0 - roughness) * (1.0 - roughness);
+        // The direct light can't be split back into its diffuse part.
+        color = vec4(ambient + sky + radiance * baked, 1.0);
+    } else {
+        color = vec4(
+            u_ambient * occlusion + diffuse * baked * diffuse_color + specular * u_specular,
+            1.0
+        );
+        if (u_environment_reflectivity > 0.0) {
+            vec3 environment = texture(u_environment, reflected).rgb;
+            color.rgb = mix(color.rgb, environment, u_environment_reflectivity);
+        }
+    }
+    if (u_reflectivity > 0.0) {
+        color.rgb = mix(color.rgb, reflection(), u_reflectivity);
+    }
+    // Faces turning away from the camera make a cheap outline.
+    if (u_shading_model == 2 && dot(normal, camera_dir) < 0.25) {
+        color.rgb *= 0.1;
+    }
+}
+
+// How much of the fog's color replaces a face's, by its depth along the
+// view axis: v_position is in view space, looking down -Z.
+float fog_amount() {
+    float depth = -v_position.z;
+    switch (u_fog_mode) {
+    case 1:
+        return clamp((depth - u_fog_start) / max(u_fog_end - u_fog_start, 1e-4), 0.0, 1.0);
+    case 2:
+        return 1.0 - exp(-u_fog_density * max(depth, 0.0));
+    default:
+        return 0.0;
+    }
+}
+
+void main() {
+    shade();
+    color.a = u_opacity;
+#ifdef EDGE_OVERLAY
+    // Darkest within the line, fading out over a pixel at its sides.
+    float nearest = min(v_edge_distance.x, min(v_edge_distance.y, v_edge_distance.z));
+    float half_width = u_edge_width * 0.5;
+    float edge = 1.0 - smoothstep(half_width - 0.5, half_width + 0.5, nearest);
+    color.rgb = mix(color.rgb, vec3(0.0), edge);
+#endif
+    // Over the lines too, so they fade with their faces. The debug views
+    // are left clear.
+    if (u_render_mode == 0) {
+        color.rgb = mix(color.rgb, u_fog_color, fog_amount());
+    }
+}
@@ -0,0 +1,115 @@
+#version 150
+
+// With the wireframe overlay's geometry shader in between, which takes the
+// outputs under these names and passes them on under their own.
+#ifdef EDGE_OVERLAY
+#define v_normal gs_normal
+#define v_tangent gs_tangent
+#define v_bitangent gs_bitangent
+#define v_position gs_position
+#define v_tex_coords gs_tex_coords
+#define v_color gs_color
+#define v_tint gs_tint
+#define v_light_space_position gs_light_space_position
+#define v_world_y gs_world_y
+#define v_occlusion gs_occlusion
+#endif
+
+// Each instance carries its model matrix as four columns, its normal matrix
+// as three and a tint, so one draw call covers every object sharing a mesh.
+in vec3 position;
+in vec3 normal;
+in vec2 tex_coords;
+in vec3 color;
+// Along u, with w saying which way v runs from it, zero without texture
+// coordinates. See `Tangent` in model_loader/tangents.rs.
+in vec4 tangent;
+// How open the vertex is to the ambient light, baked by `bake_occlusion` in
+// model_loader/occlusion.rs, or 1.
+in float occlusion;
+in vec4 i_model_0;
+in vec4 i_model_1;
+in vec4 i_model_2;
+in vec4 i_model_3;
+in vec3 i_normal_0;
+in vec3 i_normal_1;
+in vec3 i_normal_2;
+in vec3 i_tint;
+// Both in view space, like the light direction.
+out vec3 v_normal;
+out vec3 v_position;
+// Along u and v in view space, the rest of the basis the normal map is in.
+// The fragment shader straightens them against the interpolated normal.
+out vec3 v_tangent;
+out vec3 v_bitangent;
+out vec2 v_tex_coords;
+out vec3 v_color;
+out vec3 v_tint;
+// Clip space of the light casting shadows.
+out vec4 v_light_space_position;
+// Height above the ground, before any mirroring by the view.
+out float v_world_y;
+out float v_occlusion;
+
+// Everything that stays the same for a whole pass over the scene, written
+// once per pass rather than with every draw. Declared alike in both stages;
+// `FrameUniforms` in renderer.rs matches its std140 layout.
+const int MAX_LIGHTS = 8;
+layout(std140) uniform u_frame {
+    mat4 perspective;
+    mat4 view;
+    mat4 u_inverse_view;
+    mat4 u_light_space;
+    // In world space, and seconds since the renderer started.
+    vec3 u_camera_position;
+    float u_time;
+    // The planes the projection was built with.
+    float u_znear;
+    float u_zfar;
+    // Which light the shadow map was rendered for, or -1 for none.
+    int u_shadow_light;
+    int u_light_count;
+    // Towards a directional light, w = 0, or a point light's position,
+    // w = 1, in view space.
+    vec4 u_light_vector[MAX_LIGHTS];
+    // Color times intensity, and attenuation in alpha.
+    vec4 u_light_color[MAX_LIGHTS];
+};
+
+// Rings spreading from u_ripple_center, u_ripple_wavelength apart, moving
+// vertices along their normals by up to u_ripple_amplitude, 0 for none. The
+// same as `Ripple` in renderer/ripple.rs, which does it on the CPU instead.
+uniform vec3 u_ripple_center;
+uniform float u_ripple_amplitude;
+uniform float u_ripple_wavelength;
+uniform float u_ripple_frequency;
+
+vec3 ripple(vec3 position) {
+    float phase = length(position - u_ripple_center) / u_ripple_wavelength
+        - u_time * u_ripple_frequency;
+    return position + normal * u_ripple_amplitude * sin(phase * 6.2831853);
+}
+
+void main() {
+    vec3 rippled = u_ripple_amplitude == 0.0 ? position : ripple(position);
+    mat4 model = mat4(i_model_0, i_model_1, i_model_2, i_model_3);
+    mat4 modelview = view * model;
+    // The view only rotates, and maybe mirrors, so it turns normals as it
+    // turns everything else.
+    v_normal = mat3(view) * (mat3(i_normal_0, i_normal_1, i_normal_2) * normal);
+    // Directions in the surface turn like the surface does, which also
+    // keeps the handedness right through mirrors and negative scales.
+    vec3 bitangent = cross(normal, tangent.xyz) * tangent.w;
+    v_tangent = mat3(modelview) * tangent.xyz;
+    v_bitangent = mat3(modelview) * bitangent;
+    vec4 view_position = modelview * vec4(rippled, 1.0);
+    v_position = view_position.xyz;
+    gl_Position = perspective * view_position;
+    v_tex_coords = tex_coords;
+    v_color = color;
+    v_tint = i_tint;
+    vec4 world_position = model * vec4(rippled, 1.0);
+    v_light_space_position = u_light_space * world_position;
+    v_world_y = world_position.y;
+    v_occlusion = occlusion;
+}
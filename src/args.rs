@@ -0,0 +1,444 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::config::{self, Config};
+use crate::model_loader::{self, BakeSettings, CacheMode, NormalMode};
+use crate::primitives::Primitive;
+use crate::terrain;
+
+pub const USAGE: &str = "usage: opengl_rust [options] [model ...]
+
+Loads each model (obj, stl, ply, gltf, glb) side by side, defaulting to
+./teapot-3.obj.
+
+options:
+    --primitive <shape> add a generated model instead of a file: plane[:n] with
+                        n squares a side, cube, sphere, torus or cylinder[:n]
+                        with n segments around (default 32), or teapot[:n]
+                        with n by n quads per Bézier patch
+    --teapot-res <n>    quads a side per patch for --primitive teapot without
+                        its own :n, up to 40 (default 10)
+    --terrain <image>   add a terrain built from a greyscale heightmap image,
+                        one vertex per pixel
+    --height-scale <h>  how high white is in --terrain heightmaps (default 1)
+    --config <file>     read settings from file instead of ./viewer.toml (F2
+                        saves the current ones back to it)
+    --normals <mode>    regenerate normals: flat, smooth or smooth:<degrees>
+    --flip-winding      reverse triangle winding, for inside-out models
+    --normalize         center each model file on the origin and scale it to
+                        fit a unit box, printing by how much (the default)
+    --keep-units        leave the models' coordinates as the files have them,
+                        for measuring
+    --points            draw every model as a point cloud of its vertices, as
+                        files without faces always are (Alt+[ and Alt+] resize
+                        the points)
+    --validate          report duplicate and unreferenced vertices, degenerate
+                        triangles, non-manifold edges, inconsistent winding and
+                        NaN or infinite coordinates in each model
+    --drop-degenerate   remove triangles with no area
+    --strip-unused      remove vertices no triangle or line uses
+    --fix-winding       turn over triangles facing the other way from their
+                        neighbours
+    --subdivide <n>     smooth the models by splitting every triangle into
+                        four, n times over
+    --no-optimize       keep the file's triangle order instead of reordering
+                        it for the vertex cache
+    --no-lod            always draw models at full detail, without building
+                        simplified levels of detail for the big ones
+    --bake-ao <rays>    bake ambient occlusion into the models' vertices by
+                        casting this many rays from each, kept in the mesh
+                        cache (Ctrl+K compares with and without it)
+    --ao-bias <b>       how far off the surface --bake-ao's rays start, as a
+                        fraction of the model's size (default 0.002)
+    --threads <n>       threads for parsing OBJ files (default: one per core)
+    --no-cache          always parse the models, without reading or writing the
+                        <model>.meshcache files that models over 1 MB get
+    --rebuild-cache     parse the models and rewrite their caches, whatever
+                        their size
+    --watch             reload the models when their files change, keeping the
+                        camera where it is
+    --width <pixels>    initial window width (default 1024)
+    --height <pixels>   initial window height (default 768)
+    --title <title>     window title
+    --msaa <samples>    multisampling: 0, 2, 4 or 8 (default 4)
+    --gles              only ask for OpenGL ES 3.0, as on a Raspberry Pi or
+                        ANGLE, to try the ES shaders on a desktop
+    --no-vsync          don't wait for the display, use the --fps limiter instead
+    --fps <n>           frame rate cap without vsync, 0 for uncapped (default 60)
+    --background-fps <n>
+                        frame rate while the window is minimized or out of
+                        focus, 0 to only draw when asked to (default 0)
+    --no-srgb           show the linear frame without gamma correction (F8
+                        compares the two side by side)
+    --wireframe         start in wireframe mode (W cycles it at runtime)
+    --toon-bands <n>    light steps in toon shading, picked with V (default 4)
+    --reflectivity <r>  how much the ground mirrors the models, 0 to 1, J turns
+                        it off (default 0.3)
+    --skybox <path>     surround the scene with the six images in a directory,
+                        named px, nx, py, ny, pz, nz or right, left, top,
+                        bottom, front, back, or with one horizontal cross
+                        image (default: a gradient)
+    --turntable <s>     orbit the camera once around the models over s seconds,
+                        then exit
+    --record <dir>      render --frames frames at a fixed 60 fps timestep and save
+                        them as dir/frame_000001.png onwards, then exit
+    --frames <n>        how many frames --record saves (default 600)
+    --stress <n>        draw n instanced copies of the models in a grid
+    --gpu-deform        ripple the models (H) in the vertex shader instead of
+                        rewriting their vertex buffers every frame
+    --quiet             don't show frame statistics in the window title
+    --screenshot <file> render one frame offscreen at the window size, save it as
+                        a PNG and exit (F12 saves a screenshot at runtime)
+    --headless          with --output, like --screenshot but without a window
+                        where the platform allows it, or a display server
+                        where EGL does
+    --output <file>     where --headless saves its PNG
+    --list-objects      print every object's name and triangle count, then exit
+    --stats-only        print each model's statistics, then exit
+    --export <file>     save the models as they are after the options above to
+                        an OBJ file and an MTL library beside it, then exit
+    --help              show this message";
+
+const DEFAULT_MODEL: &str = "./teapot-3.obj";
+const DEFAULT_FPS: u32 = 60;
+const DEFAULT_TOON_BANDS: u32 = 4;
+const DEFAULT_REFLECTIVITY: f32 = 0.3;
+const DEFAULT_RECORD_FRAMES: usize = 600;
+const DEFAULT_HEIGHT_SCALE: f32 = 1.0;
+const DEFAULT_AO_BIAS: f32 = 0.002;
+/// Past this many rays a vertex, more only make the bake slower.
+const MAX_AO_RAYS: u32 = 4096;
+
+#[derive(Clone)]
+pub struct Args {
+    /// The model files, with the `--primitive` shapes and `--terrain`
+    /// heightmaps among them in the order they were given.
+    pub paths: Vec<String>,
+    pub height_scale: f32,
+    pub normal_mode: Option<NormalMode>,
+    pub flip_winding: bool,
+    /// Whether model files are recentered and scaled to a unit box as they
+    /// load, off with `--keep-units`.
+    pub normalize: bool,
+    /// Draws the models' vertices as points instead of their triangles.
+    pub points: bool,
+    /// Whether each model is checked for problems as it loads.
+    pub validate: bool,
+    pub drop_degenerate: bool,
+    pub strip_unreferenced: bool,
+    pub fix_winding: bool,
+    /// Rounds of Loop subdivision, 0 for none.
+    pub subdivide: u32,
+    pub optimize: bool,
+    /// Whether big models get simplified levels of detail.
+    pub lod: bool,
+    /// How to bake ambient occlusion into the models, if at all.
+    pub bake_ao: Option<BakeSettings>,
+    /// Parser threads; `None` leaves rayon to use one per core.
+    pub threads: Option<usize>,
+    pub cache: CacheMode,
+    pub watch: bool,
+    pub width: u32,
+    pub height: u32,
+    pub title: String,
+    pub msaa: u16,
+    /// Only OpenGL ES contexts are asked for.
+    pub gles: bool,
+    pub vsync: bool,
+    pub srgb: bool,
+    pub fps: u32,
+    /// 0 pauses drawing in the background.
+    pub background_fps: u32,
+    pub stress: usize,
+    pub gpu_deform: bool,
+    pub quiet: bool,
+    pub wireframe: bool,
+    pub toon_bands: u32,
+    pub reflectivity: f32,
+    pub skybox: Option<String>,
+    /// Seconds for one orbit, after which the viewer exits.
+    pub turntable: Option<f32>,
+    pub record: Option<String>,
+    pub frames: usize,
+    pub screenshot: Option<String>,
+    /// Set only with `--headless`, which requires it.
+    pub output: Option<String>,
+    pub list_objects: bool,
+    pub stats_only: bool,
+    /// Where to save the processed models as OBJ instead of showing them.
+    pub export: Option<String>,
+    pub help: bool,
+    /// Where the window options above default from, along with the rest of
+    /// the startup settings.
+    pub config: Config,
+    pub config_path: PathBuf,
+}
+
+impl Args {
+    pub fn parse() -> Result<Args, String> {
+        Args::parse_from(std::env::args().skip(1))
+    }
+
+    /// Reads the config file first, so the options can override it.
+    pub fn parse_from<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+        let args = args.into_iter().collect::<Vec<String>>();
+        let (config_path, required) = match args.iter().position(|arg| arg == "--config") {
+            Some(index) => {
+                let path: String = value("--config", args.get(index + 1).cloned())?;
+                (PathBuf::from(path), true)
+            }
+            None => (PathBuf::from(config::DEFAULT_PATH), false),
+        };
+        let config = Config::load(&config_path, required)?;
+        let window = &config.window;
+        let mut parsed = Args {
+            paths: Vec::new(),
+            height_scale: DEFAULT_HEIGHT_SCALE,
+            normal_mode: None,
+            flip_winding: false,
+            normalize: true,
+            points: false,
+            validate: false,
+            drop_degenerate: false,
+            strip_unreferenced: false,
+            fix_winding: false,
+            subdivide: 0,
+            optimize: true,
+            lod: true,
+            bake_ao: None,
+            threads: None,
+            cache: CacheMode::Use,
+            watch: false,
+            width: window.width,
+            height: window.height,
+            title: window.title.clone(),
+            msaa: window.msaa,
+            gles: false,
+            vsync: window.vsync,
+            srgb: true,
+            fps: DEFAULT_FPS,
+            background_fps: 0,
+            stress: 0,
+            gpu_deform: false,
+            quiet: false,
+            wireframe: config.rendering.wireframe,
+            toon_bands: DEFAULT_TOON_BANDS,
+            reflectivity: DEFAULT_REFLECTIVITY,
+            skybox: None,
+            turntable: None,
+            record: None,
+            frames: DEFAULT_RECORD_FRAMES,
+            screenshot: None,
+            output: None,
+            list_objects: false,
+            stats_only: false,
+            export: None,
+            help: false,
+            config,
+            config_path,
+        };
+
+        let mut headless = false;
+        let mut frames = None;
+        let mut no_cache = false;
+        let mut rebuild_cache = false;
+        let mut teapot_res: Option<u32> = None;
+        let mut height_scale: Option<f32> = None;
+        let mut ao_rays: Option<u32> = None;
+        let mut ao_bias: Option<f32> = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                // Already read above.
+                "--config" => {
+                    args.next();
+                }
+                "--primitive" => {
+                    let shape: String = value(&arg, args.next())?;
+                    shape
+                        .parse::<Primitive>()
+                        .map_err(|error| format!("{} {}: {}", arg, shape, error))?;
+                    parsed.paths.push(shape);
+                }
+                "--teapot-res" => teapot_res = Some(value(&arg, args.next())?),
+                "--terrain" => {
+                    let image: String = value(&arg, args.next())?;
+                    if !terrain::is_heightmap(&image) {
+                        return Err(format!("--terrain {}: not a supported image", image));
+                    }
+                    parsed.paths.push(image);
+                }
+                "--height-scale" => height_scale = Some(value(&arg, args.next())?),
+                "--normals" => parsed.normal_mode = Some(value(&arg, args.next())?),
+                "--width" => parsed.width = value(&arg, args.next())?,
+                "--height" => parsed.height = value(&arg, args.next())?,
+                "--title" => parsed.title = value(&arg, args.next())?,
+                "--msaa" => parsed.msaa = value(&arg, args.next())?,
+                "--gles" => parsed.gles = true,
+                "--no-vsync" => parsed.vsync = false,
+                "--no-srgb" => parsed.srgb = false,
+                "--fps" => parsed.fps = value(&arg, args.next())?,
+                "--background-fps" => parsed.background_fps = value(&arg, args.next())?,
+                "--reflectivity" => parsed.reflectivity = value(&arg, args.next())?,
+                "--skybox" => parsed.skybox = Some(value(&arg, args.next())?),
+                "--turntable" => parsed.turntable = Some(value(&arg, args.next())?),
+                "--record" => parsed.record = Some(value(&arg, args.next())?),
+                "--frames" => frames = Some(value(&arg, args.next())?),
+                "--stress" => parsed.stress = value(&arg, args.next())?,
+                "--gpu-deform" => parsed.gpu_deform = true,
+                "--quiet" => parsed.quiet = true,
+                "--flip-winding" => parsed.flip_winding = true,
+                "--normalize" => parsed.normalize = true,
+                "--keep-units" => parsed.normalize = false,
+                "--points" => parsed.points = true,
+                "--validate" => parsed.validate = true,
+                "--drop-degenerate" => parsed.drop_degenerate = true,
+                "--strip-unused" => parsed.strip_unreferenced = true,
+                "--fix-winding" => parsed.fix_winding = true,
+                "--subdivide" => parsed.subdivide = value(&arg, args.next())?,
+                "--no-optimize" => parsed.optimize = false,
+                "--no-lod" => parsed.lod = false,
+                "--bake-ao" => ao_rays = Some(value(&arg, args.next())?),
+                "--ao-bias" => ao_bias = Some(value(&arg, args.next())?),
+                "--threads" => parsed.threads = Some(value(&arg, args.next())?),
+                "--no-cache" => no_cache = true,
+                "--rebuild-cache" => rebuild_cache = true,
+                "--watch" => parsed.watch = true,
+                "--wireframe" => parsed.wireframe = true,
+                "--toon-bands" => parsed.toon_bands = value(&arg, args.next())?,
+                "--screenshot" => parsed.screenshot = Some(value(&arg, args.next())?),
+                "--headless" => headless = true,
+                "--output" => parsed.output = Some(value(&arg, args.next())?),
+                "--list-objects" => parsed.list_objects = true,
+                "--stats-only" => parsed.stats_only = true,
+                "--export" => parsed.export = Some(value(&arg, args.next())?),
+                "--help" | "-h" => parsed.help = true,
+                flag if flag.starts_with("--") => {
+                    return Err(format!("unknown option '{}'", flag));
+                }
+                _ => {
+                    check_model_path(&arg)?;
+                    parsed.paths.push(arg);
+                }
+            }
+        }
+
+        if parsed.width == 0 || parsed.height == 0 {
+            return Err("window size must be at least 1x1".to_string());
+        }
+        if ![0, 2, 4, 8].contains(&parsed.msaa) {
+            return Err(format!("--msaa {}: expected 0, 2, 4 or 8", parsed.msaa));
+        }
+        parsed.cache = match (no_cache, rebuild_cache) {
+            (true, true) => {
+                return Err("--no-cache and --rebuild-cache can't be used together".to_string())
+            }
+            (true, false) => CacheMode::Off,
+            (false, true) => CacheMode::Rebuild,
+            (false, false) => CacheMode::Use,
+        };
+        if parsed.threads == Some(0) {
+            return Err("--threads must be at least 1".to_string());
+        }
+        if parsed.toon_bands == 0 {
+            return Err("--toon-bands must be at least 1".to_string());
+        }
+        match (headless, &parsed.output) {
+            (true, None) => return Err("--headless needs --output <file>".to_string()),
+            (false, Some(_)) => return Err("--output only applies to --headless".to_string()),
+            _ => {}
+        }
+        if !(0.0..=1.0).contains(&parsed.reflectivity) {
+            return Err(format!(
+                "--reflectivity {}: expected 0 to 1",
+                parsed.reflectivity
+            ));
+        }
+        match (frames, &parsed.record) {
+            (Some(_), None) => return Err("--frames only applies to --record".to_string()),
+            (Some(0), _) => return Err("--frames must be at least 1".to_string()),
+            (Some(frames), _) => parsed.frames = frames,
+            (None, _) => {}
+        }
+        if let Some(seconds) = parsed.turntable {
+            if !(seconds > 0.0 && seconds.is_finite()) {
+                return Err(format!("--turntable {}: expected a positive time", seconds));
+            }
+        }
+        if let Some(resolution) = teapot_res {
+            let teapot = format!("teapot:{}", resolution);
+            teapot
+                .parse::<Primitive>()
+                .map_err(|error| format!("--teapot-res {}: {}", resolution, error))?;
+            let mut found = false;
+            for path in parsed.paths.iter_mut().filter(|path| *path == "teapot") {
+                *path = teapot.clone();
+                found = true;
+            }
+            if !found {
+                return Err("--teapot-res only applies to --primitive teapot".to_string());
+            }
+        }
+        if let Some(scale) = height_scale {
+            if !(scale > 0.0 && scale.is_finite()) {
+                return Err(format!("--height-scale {}: expected a positive height", scale));
+            }
+            if !parsed.paths.iter().any(|path| terrain::is_heightmap(path)) {
+                return Err("--height-scale only applies to --terrain".to_string());
+            }
+            parsed.height_scale = scale;
+        }
+        if let Some(bias) = ao_bias {
+            if !(bias >= 0.0 && bias.is_finite()) {
+                return Err(format!("--ao-bias {}: expected 0 or more", bias));
+            }
+        }
+        parsed.bake_ao = match (ao_rays, ao_bias) {
+            (None, Some(_)) => return Err("--ao-bias only applies to --bake-ao".to_string()),
+            (Some(rays), _) if !(1..=MAX_AO_RAYS).contains(&rays) => {
+                return Err(format!("--bake-ao {}: expected 1 to {}", rays, MAX_AO_RAYS))
+            }
+            (Some(rays), bias) => Some(BakeSettings {
+                rays,
+                bias: bias.unwrap_or(DEFAULT_AO_BIAS),
+            }),
+            (None, None) => None,
+        };
+        if parsed.paths.is_empty() {
+            parsed.paths.push(DEFAULT_MODEL.to_string());
+        }
+        Ok(parsed)
+    }
+}
+
+/// Parses the value following `flag`.
+fn value<T>(flag: &str, value: Option<String>) -> Result<T, String>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = value.ok_or_else(|| format!("{} expects a value", flag))?;
+    value
+        .parse()
+        .map_err(|error| format!("{} {}: {}", flag, value, error))
+}
+
+/// Rejects directories and unknown extensions before trying to load them, so
+/// they get a usage message instead of a parse error.
+pub fn check_model_path(path: &str) -> Result<(), String> {
+    if Path::new(path).is_dir() {
+        return Err(format!("{} is a directory, expected a model file", path));
+    }
+    let extension = Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !model_loader::SUPPORTED_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(format!(
+            "{}: unsupported extension, expected one of {}",
+            path,
+            model_loader::SUPPORTED_EXTENSIONS.join(", ")
+        ));
+    }
+    Ok(())
+}
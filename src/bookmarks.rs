@@ -0,0 +1,154 @@
+use std::f32::consts::{PI, TAU};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{self, Camera, Projection};
+use crate::math::Vec3;
+
+const SLOTS: usize = 9;
+/// Seconds a restored bookmark takes to swing the camera into place.
+const TRANSITION_TIME: f32 = 0.3;
+
+/// Where a camera was, stored as orbit angles rather than a matrix so that
+/// blending two of them never passes through a degenerate up vector.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub target: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    /// Vertical field of view in radians.
+    pub fov: f32,
+    pub orthographic: bool,
+    pub ortho_scale: f32,
+}
+
+impl Bookmark {
+    pub fn from_camera(camera: &Camera) -> Self {
+        Bookmark {
+            target: camera.target,
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            distance: camera.distance,
+            fov: camera.fov,
+            orthographic: camera.projection == Projection::Orthographic,
+            ortho_scale: camera.ortho_scale,
+        }
+    }
+
+    /// Moves `camera` here, keeping its orbit or fly mode.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.target = self.target;
+        camera.yaw = self.yaw;
+        camera.pitch = self.pitch.clamp(-camera::MAX_PITCH, camera::MAX_PITCH);
+        camera.distance = self.distance;
+        camera.set_fov(self.fov);
+        camera.projection = match self.orthographic {
+            true => Projection::Orthographic,
+            false => Projection::Perspective,
+        };
+        camera.ortho_scale = self.ortho_scale;
+    }
+
+    /// `amount` of the way from `self` to `other`. Yaw turns the short way
+    /// round and the distance changes geometrically, so zooming feels even.
+    /// The projection switches at the start.
+    fn blend(&self, other: &Bookmark, amount: f32) -> Bookmark {
+        let lerp = |from: f32, to: f32| from + (to - from) * amount;
+        let turn = (other.yaw - self.yaw + PI).rem_euclid(TAU) - PI;
+        let from = Vec3::from(self.target);
+        let target = from + (Vec3::from(other.target) - from) * amount;
+        Bookmark {
+            target: target.into(),
+            yaw: self.yaw + turn * amount,
+            pitch: lerp(self.pitch, other.pitch),
+            distance: self.distance * (other.distance / self.distance).powf(amount),
+            fov: lerp(self.fov, other.fov),
+            orthographic: other.orthographic,
+            ortho_scale: self.ortho_scale * (other.ortho_scale / self.ortho_scale).powf(amount),
+        }
+    }
+}
+
+struct Transition {
+    from: Bookmark,
+    to: Bookmark,
+    elapsed: f32,
+}
+
+/// Camera bookmarks for one model, stored with Ctrl+1 to 9 and restored
+/// with 1 to 9. They're kept in a JSON file next to the model, e.g.
+/// `teapot-3.obj.cameras.json`, so they come back when it's opened again.
+pub struct Bookmarks {
+    path: PathBuf,
+    slots: [Option<Bookmark>; SLOTS],
+    transition: Option<Transition>,
+}
+
+impl Bookmarks {
+    /// Reads the bookmarks saved for `model`, if any. A file that can't be
+    /// read is reported and then overwritten by the next store.
+    pub fn load(model: &Path) -> Self {
+        let mut path = model.as_os_str().to_owned();
+        path.push(".cameras.json");
+        let path = PathBuf::from(path);
+        let slots = match fs::read_to_string(&path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|error| {
+                eprintln!("warning: ignoring {}: {}", path.display(), error);
+                Default::default()
+            }),
+            Err(_) => Default::default(),
+        };
+        Bookmarks {
+            path,
+            slots,
+            transition: None,
+        }
+    }
+
+    /// Remembers `camera` in `slot`, from 0, and saves the file.
+    pub fn store(&mut self, slot: usize, camera: &Camera) {
+        self.slots[slot] = Some(Bookmark::from_camera(camera));
+        let saved = serde_json::to_string_pretty(&self.slots)
+            .map_err(|error| error.to_string())
+            .and_then(|text| fs::write(&self.path, text).map_err(|error| error.to_string()));
+        match saved {
+            Ok(()) => println!("bookmark {} saved to {}", slot + 1, self.path.display()),
+            Err(error) => eprintln!("failed to save {}: {}", self.path.display(), error),
+        }
+    }
+
+    /// Starts moving `camera` to the bookmark in `slot`, returning false if
+    /// it's empty.
+    pub fn restore(&mut self, slot: usize, camera: &Camera) -> bool {
+        match self.slots[slot] {
+            Some(to) => {
+                self.transition = Some(Transition {
+                    from: Bookmark::from_camera(camera),
+                    to,
+                    elapsed: 0.0,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Advances a restore in progress by `dt` seconds.
+    pub fn update(&mut self, camera: &mut Camera, dt: f32) {
+        let transition = match &mut self.transition {
+            Some(transition) => transition,
+            None => return,
+        };
+        transition.elapsed += dt;
+        let t = (transition.elapsed / TRANSITION_TIME).min(1.0);
+        // Eases in and out.
+        let amount = t * t * (3.0 - 2.0 * t);
+        transition.from.blend(&transition.to, amount).apply(camera);
+        if t >= 1.0 {
+            self.transition = None;
+        }
+    }
+}
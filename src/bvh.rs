@@ -0,0 +1,316 @@
+use crate::math::Vec3;
+use crate::model_loader::{Aabb, Model};
+use crate::picking::{self, Ray};
+
+/// Nodes holding this many triangles or fewer aren't split any further.
+const LEAF_SIZE: usize = 4;
+
+/// A binary tree of bounding boxes over a model's triangles, so a ray or box
+/// only has to be tested against the few triangles near it. Build it again
+/// whenever the model's vertices or indexes change.
+pub struct Bvh {
+    /// Depth first, each interior node followed by its left child.
+    nodes: Vec<Node>,
+    /// Triangle indexes, ordered so each leaf's are together.
+    triangles: Vec<u32>,
+}
+
+struct Node {
+    bounds: Aabb,
+    /// For a leaf, where its triangles start in `Bvh::triangles`. For an
+    /// interior node, the index of its right child.
+    first: u32,
+    /// How many triangles a leaf holds; 0 for interior nodes.
+    count: u32,
+}
+
+/// Where a ray first meets a model's triangles.
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub triangle: usize,
+    /// Along the ray, in multiples of its direction.
+    pub distance: f32,
+    /// Barycentric weights of the triangle's second and third corners.
+    pub u: f32,
+    pub v: f32,
+}
+
+impl Bvh {
+    /// Splits the triangles in half at the median of their centers along
+    /// the longest axis those centers span, until the leaves are small.
+    pub fn build(model: &Model) -> Self {
+        let triangle_count = model.indexes.len() / 3;
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity((triangle_count / LEAF_SIZE + 1) * 2),
+            triangles: (0..triangle_count as u32).collect(),
+        };
+        let centers = (0..triangle_count)
+            .map(|triangle| {
+                let [a, b, c] = corners(model, triangle);
+                (a + b + c) * (1.0 / 3.0)
+            })
+            .collect::<Vec<Vec3>>();
+        if triangle_count > 0 {
+            bvh.split(model, &centers, 0, triangle_count);
+        }
+        bvh
+    }
+
+    /// Adds the node for `triangles[start..end]` and everything below it.
+    fn split(&mut self, model: &Model, centers: &[Vec3], start: usize, end: usize) {
+        let node = self.nodes.len();
+        let triangles = &mut self.triangles[start..end];
+        let mut bounds = triangle_bounds(model, triangles[0] as usize);
+        let mut center_min = centers[triangles[0] as usize].to_array();
+        let mut center_max = center_min;
+        for &triangle in triangles.iter() {
+            bounds = bounds.union(&triangle_bounds(model, triangle as usize));
+            let center = centers[triangle as usize].to_array();
+            for axis in 0..3 {
+                center_min[axis] = center_min[axis].min(center[axis]);
+                center_max[axis] = center_max[axis].max(center[axis]);
+            }
+        }
+        self.nodes.push(Node {
+            bounds,
+            first: start as u32,
+            count: triangles.len() as u32,
+        });
+        if triangles.len() <= LEAF_SIZE {
+            return;
+        }
+
+        let extent = |axis: usize| center_max[axis] - center_min[axis];
+        let axis = (0..3)
+            .max_by(|&a, &b| extent(a).total_cmp(&extent(b)))
+            .unwrap_or(0);
+        let middle = triangles.len() / 2;
+        triangles.select_nth_unstable_by(middle, |&a, &b| {
+            let (a, b) = (
+                centers[a as usize].to_array(),
+                centers[b as usize].to_array(),
+            );
+            a[axis].total_cmp(&b[axis])
+        });
+        self.split(model, centers, start, start + middle);
+        self.nodes[node].first = self.nodes.len() as u32;
+        self.nodes[node].count = 0;
+        self.split(model, centers, start + middle, end);
+    }
+
+    /// The closest triangle of `model` that `ray` hits, which must be the
+    /// model the tree was built from. Nearer children are visited first, and
+    /// boxes further away than the best hit so far are skipped.
+    pub fn intersect_ray(&self, model: &Model, ray: &Ray) -> Option<RayHit> {
+        let mut closest: Option<RayHit> = None;
+        // Nodes still to visit, with how far along the ray their boxes start.
+        let mut stack: Vec<(usize, f32)> = Vec::new();
+        if let Some(root) = self.nodes.first() {
+            if let Some(distance) = ray.box_distance(&root.bounds, f32::MAX) {
+                stack.push((0, distance));
+            }
+        }
+        while let Some((index, entry)) = stack.pop() {
+            let nearest = closest.map_or(f32::MAX, |hit| hit.distance);
+            if entry > nearest {
+                continue;
+            }
+            let node = &self.nodes[index];
+            if node.count > 0 {
+                let first = node.first as usize;
+                for &triangle in &self.triangles[first..first + node.count as usize] {
+                    let triangle = triangle as usize;
+                    let hit = picking::intersect_triangle(ray, corners(model, triangle));
+                    if let Some((distance, u, v)) = hit {
+                        if closest.is_none_or(|hit| distance < hit.distance) {
+                            closest = Some(RayHit {
+                                triangle,
+                                distance,
+                                u,
+                                v,
+                            });
+                        }
+                    }
+                }
+                continue;
+            }
+            let mut children = [index + 1, node.first as usize]
+                .into_iter()
+                .filter_map(|child| {
+                    let distance = ray.box_distance(&self.nodes[child].bounds, nearest)?;
+                    Some((child, distance))
+                })
+                .collect::<Vec<(usize, f32)>>();
+            // The farther one goes on the stack first, so the nearer one is
+            // looked at next.
+            children.sort_by(|a, b| b.1.total_cmp(&a.1));
+            stack.extend(children);
+        }
+        closest
+    }
+
+    /// Whether `ray` hits any of `model`'s triangles, stopping at the first
+    /// one found rather than looking for the closest.
+    pub fn hits_any(&self, model: &Model, ray: &Ray) -> bool {
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if ray.box_distance(&node.bounds, f32::MAX).is_none() {
+                continue;
+            }
+            if node.count == 0 {
+                stack.extend_from_slice(&[index + 1, node.first as usize]);
+                continue;
+            }
+            let first = node.first as usize;
+            let hit = self.triangles[first..first + node.count as usize]
+                .iter()
+                .any(|&triangle| {
+                    picking::intersect_triangle(ray, corners(model, triangle as usize)).is_some()
+                });
+            if hit {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Every triangle whose bounding box overlaps `bounds`, in no particular
+    /// order.
+    #[allow(dead_code)]
+    pub fn intersect_aabb(&self, model: &Model, bounds: &Aabb) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !overlaps(&node.bounds, bounds) {
+                continue;
+            }
+            if node.count == 0 {
+                stack.extend_from_slice(&[index + 1, node.first as usize]);
+                continue;
+            }
+            let first = node.first as usize;
+            for &triangle in &self.triangles[first..first + node.count as usize] {
+                if overlaps(&triangle_bounds(model, triangle as usize), bounds) {
+                    found.push(triangle as usize);
+                }
+            }
+        }
+        found
+    }
+}
+
+fn corners(model: &Model, triangle: usize) -> [Vec3; 3] {
+    let indexes = &model.indexes[triangle * 3..triangle * 3 + 3];
+    [0, 1, 2].map(|corner| Vec3::from(model.vertices[indexes[corner] as usize].position))
+}
+
+fn triangle_bounds(model: &Model, triangle: usize) -> Aabb {
+    let [a, b, c] = corners(model, triangle).map(Vec3::to_array);
+    Aabb {
+        min: [0, 1, 2].map(|axis| a[axis].min(b[axis]).min(c[axis])),
+        max: [0, 1, 2].map(|axis| a[axis].max(b[axis]).max(c[axis])),
+    }
+}
+
+fn overlaps(a: &Aabb, b: &Aabb) -> bool {
+    (0..3).all(|axis| a.min[axis] <= b.max[axis] && b.min[axis] <= a.max[axis])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    /// Xorshift, so the rays are the same on every run.
+    struct Random(u32);
+
+    impl Random {
+        /// Uniform in -1 to 1.
+        fn next(&mut self) -> f32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0 as f32 / u32::MAX as f32 * 2.0 - 1.0
+        }
+
+        fn vector(&mut self) -> Vec3 {
+            Vec3::new(self.next(), self.next(), self.next())
+        }
+    }
+
+    fn brute_force(model: &Model, ray: &Ray) -> Option<(usize, f32)> {
+        (0..model.indexes.len() / 3)
+            .filter_map(|triangle| {
+                let (distance, _, _) = picking::intersect_triangle(ray, corners(model, triangle))?;
+                Some((triangle, distance))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    #[test]
+    fn agrees_with_brute_force_on_random_rays() {
+        let model = primitives::teapot(6);
+        let bvh = Bvh::build(&model);
+        let mut random = Random(0x9e37_79b9);
+        let mut hits = 0;
+        for _ in 0..2000 {
+            // From around the teapot towards somewhere near it, so about
+            // half the rays hit.
+            let origin = random.vector().normalize() * 8.0;
+            let target = random.vector() * 3.0;
+            let ray = Ray {
+                origin,
+                direction: (target - origin).normalize(),
+            };
+            let expected = brute_force(&model, &ray);
+            let found = bvh.intersect_ray(&model, &ray);
+            assert_eq!(bvh.hits_any(&model, &ray), expected.is_some());
+            match (found, expected) {
+                (None, None) => (),
+                (Some(hit), Some((triangle, distance))) => {
+                    hits += 1;
+                    // Rays through an edge hit both triangles at the same
+                    // distance, so either may be the one found.
+                    assert_eq!(hit.distance, distance, "{:?}", ray);
+                    if hit.triangle != triangle {
+                        let other =
+                            picking::intersect_triangle(&ray, corners(&model, hit.triangle));
+                        assert_eq!(other.map(|(distance, _, _)| distance), Some(distance));
+                    }
+                }
+                (found, expected) => panic!("{:?} found {:?}, expected {:?}", ray, found, expected),
+            }
+        }
+        assert!(hits > 500 && hits < 1500, "{} of 2000 rays hit", hits);
+    }
+
+    #[test]
+    fn box_query_agrees_with_brute_force() {
+        let model = primitives::teapot(6);
+        let bvh = Bvh::build(&model);
+        let mut random = Random(12345);
+        for _ in 0..200 {
+            let center = random.vector() * 3.0;
+            let half = random.vector().to_array().map(|value| value.abs() * 0.5);
+            let center = center.to_array();
+            let bounds = Aabb {
+                min: [0, 1, 2].map(|axis| center[axis] - half[axis]),
+                max: [0, 1, 2].map(|axis| center[axis] + half[axis]),
+            };
+            let mut found = bvh.intersect_aabb(&model, &bounds);
+            found.sort_unstable();
+            let expected = (0..model.indexes.len() / 3)
+                .filter(|&triangle| overlaps(&triangle_bounds(&model, triangle), &bounds))
+                .collect::<Vec<usize>>();
+            assert_eq!(found, expected);
+        }
+    }
+}
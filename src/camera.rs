@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+
+use glium::glutin::event::{
+    DeviceEvent, ElementState, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode,
+    WindowEvent,
+};
+use glium::glutin::window::{CursorGrabMode, Window};
+
+use crate::gamepad;
+use crate::keybindings::Action;
+use crate::math::Vec3;
+
+/// Pitch stops just short of straight up/down, where the view's up vector
+/// would be parallel to the view direction.
+pub const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+const MIN_DISTANCE: f32 = 0.05;
+const ROTATE_SPEED: f32 = 0.01;
+const PAN_SPEED: f32 = 0.002;
+const ZOOM_STEP: f32 = 0.9;
+/// Vertical field of view in radians, and the range Ctrl+wheel and Z/X can
+/// take it to, well clear of 0 and 180 degrees where the projection breaks.
+const DEFAULT_FOV: f32 = std::f32::consts::PI / 3.0;
+const MIN_FOV: f32 = 10.0 * std::f32::consts::PI / 180.0;
+const MAX_FOV: f32 = 120.0 * std::f32::consts::PI / 180.0;
+/// What one wheel step or key press changes the field of view by.
+const FOV_STEP: f32 = 5.0 * std::f32::consts::PI / 180.0;
+/// How quickly the field of view eases towards where it's heading, per
+/// second; higher is snappier.
+const FOV_SMOOTHING: f32 = 12.0;
+/// Limits on `Camera::ortho_scale`, so zooming can't collapse the view.
+const MIN_ORTHO_SCALE: f32 = 0.001;
+const MAX_ORTHO_SCALE: f32 = 1000.0;
+/// Fly speed in world units per second, multiplied while Shift is held.
+const FLY_SPEED: f32 = 1.0;
+const FLY_BOOST: f32 = 4.0;
+/// What a stick pushed all the way turns the camera by per second, in the
+/// mouse pixels `rotate` and `look` take.
+const STICK_TURN_SPEED: f32 = 250.0;
+/// What a stick pushed all the way pans by per second, in mouse pixels.
+const STICK_PAN_SPEED: f32 = 400.0;
+/// Wheel steps per second a trigger pulled all the way zooms by.
+const TRIGGER_ZOOM_SPEED: f32 = 6.0;
+
+pub const UP: [f32; 3] = [0.0, 1.0, 0.0];
+/// Direction from the target to the eye that framing looks from, the angle
+/// the viewer has always shown models at.
+const FRAMING_VIEW: [f32; 3] = [2.0, -1.0, -1.0];
+/// Room left around a framed model, as a factor of its bounding sphere.
+const FRAMING_MARGIN: f32 = 1.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraMode {
+    Orbit,
+    Fly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    /// Parallel lines stay parallel, for checking proportions. Shows what
+    /// the perspective view shows at the target's distance, times
+    /// `Camera::ortho_scale`.
+    Orthographic,
+}
+
+/// A camera at `distance` from `target`, looking at it from `yaw`/`pitch`.
+/// Orbiting keeps the target fixed, flying keeps the eye fixed while looking
+/// around and moves both together, so switching modes never jumps.
+pub struct Camera {
+    pub target: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub distance: f32,
+    pub mode: CameraMode,
+    pub projection: Projection,
+    /// Size of the orthographic view, changed by zooming instead of the
+    /// distance while it's in use.
+    pub ortho_scale: f32,
+    /// Vertical field of view in radians, easing towards `target_fov`.
+    pub fov: f32,
+    target_fov: f32,
+}
+
+impl Camera {
+    /// Builds a camera at `eye` looking at `target`.
+    pub fn looking_at(eye: [f32; 3], target: [f32; 3]) -> Self {
+        let offset = Vec3::from(eye) - Vec3::from(target);
+        let distance = offset.length().max(MIN_DISTANCE);
+        Camera {
+            target,
+            yaw: offset.x.atan2(offset.z),
+            pitch: (offset.y / distance).asin().clamp(-MAX_PITCH, MAX_PITCH),
+            distance,
+            mode: CameraMode::Orbit,
+            projection: Projection::Perspective,
+            ortho_scale: 1.0,
+            fov: DEFAULT_FOV,
+            target_fov: DEFAULT_FOV,
+        }
+    }
+
+    pub fn eye(&self) -> [f32; 3] {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        [
+            self.target[0] + self.distance * cos_pitch * sin_yaw,
+            self.target[1] + self.distance * sin_pitch,
+            self.target[2] + self.distance * cos_pitch * cos_yaw,
+        ]
+    }
+
+    /// Unit vector from the eye towards the target.
+    pub fn direction(&self) -> [f32; 3] {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        [-cos_pitch * sin_yaw, -sin_pitch, -cos_pitch * cos_yaw]
+    }
+
+    pub fn rotate(&mut self, dx: f32, dy: f32) {
+        self.yaw -= dx * ROTATE_SPEED;
+        self.pitch = (self.pitch + dy * ROTATE_SPEED).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    /// Turns the view around the eye, as in a first-person game.
+    pub fn look(&mut self, dx: f32, dy: f32) {
+        let eye = Vec3::from(self.eye());
+        self.yaw += dx * ROTATE_SPEED;
+        self.pitch = (self.pitch + dy * ROTATE_SPEED).clamp(-MAX_PITCH, MAX_PITCH);
+        self.target = (eye + Vec3::from(self.direction()) * self.distance).into();
+    }
+
+    /// Moves eye and target together; `forward` follows the view direction,
+    /// `right` and `up` are relative to it.
+    pub fn translate(&mut self, forward: f32, right: f32, up: f32) {
+        let f = Vec3::from(self.direction());
+        let r = Vec3::from(UP).cross(f).normalize();
+        let target = Vec3::from(self.target) + f * forward + r * right + Vec3::from(UP) * up;
+        self.target = target.into();
+    }
+
+    /// Moves the target in the view plane, scaled by distance so the model
+    /// follows the cursor at any zoom level.
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let f = Vec3::from(self.direction());
+        let right = Vec3::from(UP).cross(f).normalize();
+        let up = f.cross(right);
+        let scale = self.distance * PAN_SPEED;
+        self.target = (Vec3::from(self.target) + (up * dy - right * dx) * scale).into();
+    }
+
+    /// Moves back to the framing view, close enough that a sphere at
+    /// `center` fills the narrower of the two fields of view. Keeps the
+    /// mode, projection and field of view.
+    pub fn frame(&mut self, center: [f32; 3], radius: f32, aspect_ratio: f32) {
+        let vertical = self.target_fov / 2.0;
+        let horizontal = (vertical.tan() * aspect_ratio).atan();
+        let distance = radius * FRAMING_MARGIN / vertical.min(horizontal).sin();
+        let eye = Vec3::from(center) + Vec3::from(FRAMING_VIEW).normalize() * distance;
+        *self = Camera {
+            mode: self.mode,
+            projection: self.projection,
+            fov: self.fov,
+            target_fov: self.target_fov,
+            ..Camera::looking_at(eye.into(), center)
+        };
+    }
+
+    /// Positive steps move closer, or shrink the orthographic view.
+    pub fn zoom(&mut self, steps: f32) {
+        let factor = ZOOM_STEP.powf(steps);
+        match self.projection {
+            Projection::Perspective => {
+                self.distance = (self.distance * factor).max(MIN_DISTANCE);
+            }
+            Projection::Orthographic => {
+                self.ortho_scale =
+                    (self.ortho_scale * factor).clamp(MIN_ORTHO_SCALE, MAX_ORTHO_SCALE);
+            }
+        }
+    }
+
+    /// Jumps straight to a field of view of `fov` radians, within the
+    /// limits.
+    pub fn set_fov(&mut self, fov: f32) {
+        self.target_fov = fov.clamp(MIN_FOV, MAX_FOV);
+        self.fov = self.target_fov;
+    }
+
+    /// Widens the field of view by `angle` radians, or narrows it for a
+    /// negative one, within the limits. `update_fov` animates the change.
+    pub fn change_fov(&mut self, angle: f32) {
+        self.target_fov = (self.target_fov + angle).clamp(MIN_FOV, MAX_FOV);
+    }
+
+    /// Eases the field of view towards its target over `dt` seconds, by a
+    /// fraction that doesn't depend on the frame rate.
+    pub fn update_fov(&mut self, dt: f32) {
+        let blend = 1.0 - (-FOV_SMOOTHING * dt).exp();
+        self.fov += (self.target_fov - self.fov) * blend;
+    }
+
+    /// Switches between perspective and orthographic. The orthographic view
+    /// starts at the scale that matches the perspective one at the target.
+    pub fn toggle_projection(&mut self) {
+        self.projection = match self.projection {
+            Projection::Perspective => Projection::Orthographic,
+            Projection::Orthographic => Projection::Perspective,
+        };
+        self.ortho_scale = 1.0;
+    }
+}
+
+impl Default for Camera {
+    /// Looks at the origin from the framing view.
+    fn default() -> Self {
+        Camera::looking_at(FRAMING_VIEW, [0.0, 0.0, 0.0])
+    }
+}
+
+/// Turns input into camera movement. In orbit mode left drag rotates,
+/// middle drag pans and the wheel zooms. In fly mode the movement actions
+/// move and holding the right button looks around with the cursor grabbed;
+/// the wheel only zooms an orthographic view. In both, Ctrl+wheel narrows
+/// and widens the field of view. The keys are in `keybindings`.
+///
+/// A gamepad's right stick turns the camera the way the mouse does. In
+/// orbit mode the left stick pans and the triggers zoom; in fly mode the
+/// left stick moves and strafes and the triggers move up and down.
+#[derive(Default)]
+pub struct CameraControls {
+    rotating: bool,
+    panning: bool,
+    looking: bool,
+    cursor: Option<(f64, f64)>,
+    modifiers: ModifiersState,
+    /// Keys down for movement actions, and the action each started.
+    held: HashMap<VirtualKeyCode, Action>,
+    gamepad: gamepad::Input,
+}
+
+impl CameraControls {
+    /// Does the camera's part of a pressed key's action, if it has one.
+    pub fn handle_action(&mut self, action: Action, camera: &mut Camera, window: &Window) {
+        match action {
+            Action::ToggleCameraMode => {
+                camera.mode = match camera.mode {
+                    CameraMode::Orbit => CameraMode::Fly,
+                    CameraMode::Fly => CameraMode::Orbit,
+                };
+                self.rotating = false;
+                self.panning = false;
+                self.set_looking(false, window);
+                println!("camera mode: {:?}", camera.mode);
+            }
+            Action::NarrowFov => camera.change_fov(-FOV_STEP),
+            Action::WidenFov => camera.change_fov(FOV_STEP),
+            Action::ToggleProjection => {
+                camera.toggle_projection();
+                println!("projection: {:?}", camera.projection);
+            }
+            Action::Release => self.set_looking(false, window),
+            _ => (),
+        }
+    }
+
+    /// Starts a movement action, lasting until `key` is released.
+    pub fn hold(&mut self, key: VirtualKeyCode, action: Action) {
+        self.held.insert(key, action);
+    }
+
+    pub fn release(&mut self, key: VirtualKeyCode) {
+        self.held.remove(&key);
+    }
+
+    /// The gamepad input `update` applies until the next call.
+    pub fn set_gamepad(&mut self, input: gamepad::Input) {
+        self.gamepad = input;
+    }
+
+    pub fn handle_event(&mut self, event: &WindowEvent, camera: &mut Camera, window: &Window) {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = *modifiers,
+            // Keys released while unfocused never send a release event.
+            WindowEvent::Focused(false) => {
+                self.held.clear();
+                self.set_looking(false, window);
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let pressed = *state == ElementState::Pressed;
+                match (camera.mode, button) {
+                    (CameraMode::Orbit, MouseButton::Left) => self.rotating = pressed,
+                    (CameraMode::Orbit, MouseButton::Middle) => self.panning = pressed,
+                    (CameraMode::Fly, MouseButton::Right) => self.set_looking(pressed, window),
+                    _ => (),
+                }
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                if let Some((x, y)) = self.cursor {
+                    let dx = (position.x - x) as f32;
+                    let dy = (position.y - y) as f32;
+                    if self.rotating {
+                        camera.rotate(dx, dy);
+                    }
+                    if self.panning {
+                        camera.pan(dx, dy);
+                    }
+                }
+                self.cursor = Some((position.x, position.y));
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let steps = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / 40.0,
+                };
+                if self.modifiers.ctrl() {
+                    camera.change_fov(-steps * FOV_STEP);
+                } else if camera.mode == CameraMode::Orbit
+                    || camera.projection == Projection::Orthographic
+                {
+                    camera.zoom(steps);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Raw mouse motion keeps arriving while the cursor is grabbed, unlike
+    /// `CursorMoved`, so mouse look is driven from device events.
+    pub fn handle_device_event(&mut self, event: &DeviceEvent, camera: &mut Camera) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            if self.looking {
+                camera.look(delta.0 as f32, delta.1 as f32);
+            }
+        }
+    }
+
+    /// Eases the field of view and applies held movement actions and the
+    /// gamepad, scaled by the seconds since the last frame.
+    pub fn update(&self, camera: &mut Camera, dt: f32) {
+        camera.update_fov(dt);
+        let gamepad = self.gamepad;
+        if gamepad != gamepad::Input::default() {
+            let [turn_x, turn_y] = gamepad.right_stick.map(|axis| axis * STICK_TURN_SPEED * dt);
+            match camera.mode {
+                CameraMode::Orbit => {
+                    camera.rotate(turn_x, turn_y);
+                    // The view follows the stick, so the target moves the
+                    // opposite way to a mouse drag.
+                    let [pan_x, pan_y] =
+                        gamepad.left_stick.map(|axis| -axis * STICK_PAN_SPEED * dt);
+                    camera.pan(pan_x, pan_y);
+                    camera.zoom(gamepad.triggers * TRIGGER_ZOOM_SPEED * dt);
+                }
+                CameraMode::Fly => camera.look(turn_x, turn_y),
+            }
+        }
+        if camera.mode != CameraMode::Fly {
+            return;
+        }
+        let held = |action: Action| self.held.values().any(|&held| held == action);
+        let axis = |positive: Action, negative: Action| {
+            held(positive) as i32 as f32 - held(negative) as i32 as f32
+        };
+        let mut speed = FLY_SPEED * dt;
+        if held(Action::MoveFaster) {
+            speed *= FLY_BOOST;
+        }
+        let [strafe, push] = gamepad.left_stick;
+        let combine = |keys: f32, stick: f32| (keys + stick).clamp(-1.0, 1.0) * speed;
+        camera.translate(
+            combine(axis(Action::MoveForward, Action::MoveBack), -push),
+            combine(axis(Action::MoveRight, Action::MoveLeft), strafe),
+            combine(axis(Action::MoveUp, Action::MoveDown), gamepad.triggers),
+        );
+    }
+
+    fn set_looking(&mut self, looking: bool, window: &Window) {
+        if self.looking == looking {
+            return;
+        }
+        self.looking = looking;
+        let mode = match looking {
+            true => CursorGrabMode::Confined,
+            false => CursorGrabMode::None,
+        };
+        // Some platforms only support one of the grab modes.
+        if window.set_cursor_grab(mode).is_err() && looking {
+            let _ = window.set_cursor_grab(CursorGrabMode::Locked);
+        }
+        window.set_cursor_visible(!looking);
+    }
+}
@@ -0,0 +1,211 @@
+use std::f32::consts::{PI, TAU};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::bookmarks::Bookmark;
+use crate::camera::Camera;
+
+/// Seconds between keyframes while recording.
+const SAMPLE_INTERVAL: f32 = 0.1;
+/// Factor Shift+`,` and Shift+`.` change the playback speed by, and its
+/// limits.
+pub const SPEED_STEP: f32 = 1.5;
+const MIN_SPEED: f32 = 0.1;
+const MAX_SPEED: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Keyframe {
+    /// Seconds since recording started.
+    time: f32,
+    camera: Bookmark,
+}
+
+/// A recorded camera move, R to record and Shift+P to play back. Saved as
+/// JSON next to the model, e.g. `teapot-3.obj.path.json`, and loaded with
+/// it.
+pub struct CameraPath {
+    file: PathBuf,
+    keyframes: Vec<Keyframe>,
+    /// Seconds recorded so far, and since the last keyframe.
+    recording: Option<(f32, f32)>,
+    /// Seconds into the path being played.
+    playing: Option<f32>,
+    pub speed: f32,
+}
+
+impl CameraPath {
+    /// Reads the path saved for `model`, if any.
+    pub fn load(model: &Path) -> Self {
+        let mut file = model.as_os_str().to_owned();
+        file.push(".path.json");
+        let file = PathBuf::from(file);
+        let keyframes = match fs::read_to_string(&file) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|error| {
+                eprintln!("warning: ignoring {}: {}", file.display(), error);
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        CameraPath {
+            file,
+            keyframes,
+            recording: None,
+            playing: None,
+            speed: 1.0,
+        }
+    }
+
+    /// Starts recording over the old path from `camera`, or stops and saves
+    /// the file.
+    pub fn toggle_recording(&mut self, camera: &Camera) {
+        if self.recording.take().is_some() {
+            self.save();
+            return;
+        }
+        self.playing = None;
+        self.keyframes.clear();
+        self.record(0.0, camera);
+        self.recording = Some((0.0, 0.0));
+        println!("recording camera path, R again to stop");
+    }
+
+    /// Plays the path from the start, or stops it.
+    pub fn toggle_playback(&mut self) {
+        if self.playing.take().is_some() {
+            return;
+        }
+        match self.keyframes.len() {
+            0 | 1 => println!("no camera path yet, R records one"),
+            _ => {
+                self.recording = None;
+                self.playing = Some(0.0);
+            }
+        }
+    }
+
+    pub fn change_speed(&mut self, factor: f32) {
+        self.speed = (self.speed * factor).clamp(MIN_SPEED, MAX_SPEED);
+        println!("camera path speed: {:.2}x", self.speed);
+    }
+
+    /// Samples `camera` while recording, or moves it along the path while
+    /// playing, `dt` seconds on from the last call.
+    pub fn update(&mut self, camera: &mut Camera, dt: f32) {
+        if let Some((elapsed, since_sample)) = &mut self.recording {
+            *elapsed += dt;
+            *since_sample += dt;
+            if *since_sample >= SAMPLE_INTERVAL {
+                *since_sample = 0.0;
+                let time = *elapsed;
+                self.record(time, camera);
+            }
+        }
+        if let Some(time) = &mut self.playing {
+            *time += dt * self.speed;
+            let time = *time;
+            let end = self.keyframes.last().map_or(0.0, |keyframe| keyframe.time);
+            self.sample(time.min(end)).apply(camera);
+            if time >= end {
+                self.playing = None;
+            }
+        }
+    }
+
+    /// Adds a keyframe, unwrapping the yaw so it never jumps by a full turn
+    /// between neighbours.
+    fn record(&mut self, time: f32, camera: &Camera) {
+        let mut state = Bookmark::from_camera(camera);
+        if let Some(previous) = self.keyframes.last() {
+            state.yaw =
+                previous.camera.yaw + (state.yaw - previous.camera.yaw + PI).rem_euclid(TAU) - PI;
+        }
+        self.keyframes.push(Keyframe {
+            time,
+            camera: state,
+        });
+    }
+
+    fn save(&self) {
+        let saved = serde_json::to_string(&self.keyframes)
+            .map_err(|error| error.to_string())
+            .and_then(|text| fs::write(&self.file, text).map_err(|error| error.to_string()));
+        match saved {
+            Ok(()) => println!(
+                "saved {} keyframes to {}",
+                self.keyframes.len(),
+                self.file.display()
+            ),
+            Err(error) => eprintln!("failed to save {}: {}", self.file.display(), error),
+        }
+    }
+
+    /// The camera `time` seconds in, on a Catmull-Rom spline through the
+    /// keyframes. There must be at least two.
+    fn sample(&self, time: f32) -> Bookmark {
+        let last = self.keyframes.len() - 1;
+        let segment = self.keyframes[1..]
+            .iter()
+            .position(|keyframe| keyframe.time > time)
+            .unwrap_or(last - 1);
+        let (start, end) = (&self.keyframes[segment], &self.keyframes[segment + 1]);
+        let u = ((time - start.time) / (end.time - start.time).max(f32::EPSILON)).clamp(0.0, 1.0);
+        // The end keyframes stand in for the missing neighbours.
+        let points = [
+            &self.keyframes[segment.saturating_sub(1)].camera,
+            &start.camera,
+            &end.camera,
+            &self.keyframes[(segment + 2).min(last)].camera,
+        ];
+        let curve = |value: fn(&Bookmark) -> f32| {
+            let [p0, p1, p2, p3] = points.map(value);
+            0.5 * (2.0 * p1
+                + (p2 - p0) * u
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * u * u
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * u * u * u)
+        };
+        Bookmark {
+            target: [
+                curve(|state| state.target[0]),
+                curve(|state| state.target[1]),
+                curve(|state| state.target[2]),
+            ],
+            yaw: curve(|state| state.yaw),
+            pitch: curve(|state| state.pitch),
+            // The spline can overshoot past zero where the distance drops
+            // quickly.
+            distance: curve(|state| state.distance).max(f32::EPSILON),
+            fov: curve(|state| state.fov),
+            orthographic: start.camera.orthographic,
+            ortho_scale: curve(|state| state.ortho_scale).max(f32::EPSILON),
+        }
+    }
+}
+
+/// Orbits the camera once around its target for `--turntable`.
+pub struct Turntable {
+    start: Bookmark,
+    seconds: f32,
+    elapsed: f32,
+}
+
+impl Turntable {
+    pub fn new(camera: &Camera, seconds: f32) -> Self {
+        Turntable {
+            start: Bookmark::from_camera(camera),
+            seconds,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Moves `camera` `dt` seconds further round, returning false once the
+    /// full turn is done.
+    pub fn update(&mut self, camera: &mut Camera, dt: f32) -> bool {
+        self.elapsed = (self.elapsed + dt).min(self.seconds);
+        let mut state = self.start;
+        state.yaw += TAU * self.elapsed / self.seconds;
+        state.apply(camera);
+        self.elapsed < self.seconds
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::renderer::{CullMode, Fog};
+
+/// Read from the working directory unless `--config` names another file.
+pub const DEFAULT_PATH: &str = "viewer.toml";
+
+/// The section rebinding keys, whose keys are action names checked when
+/// the keymap is built.
+const KEYS_SECTION: &str = "keys";
+/// Every key each other section accepts, for warning about the ones it
+/// doesn't.
+const KNOWN_KEYS: [(&str, &[&str]); 6] = [
+    ("window", &["width", "height", "title", "vsync", "msaa"]),
+    ("camera", &["position", "target", "fov"]),
+    ("lighting", &["direction", "color", "intensity"]),
+    (
+        "rendering",
+        &["clear_color", "wireframe", "culling", "line_color"],
+    ),
+    ("fog", &["mode", "density", "start", "end", "color"]),
+    (
+        "gamepad",
+        &[
+            "enabled",
+            "dead_zone",
+            "trigger_dead_zone",
+            "response_exponent",
+        ],
+    ),
+];
+
+/// Startup settings from `viewer.toml`. Anything the file leaves out keeps
+/// the viewer's usual value, and command line options win over the file.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub window: WindowConfig,
+    pub camera: CameraConfig,
+    pub lighting: LightingConfig,
+    pub rendering: RenderingConfig,
+    /// Off unless the file turns it on; Shift+F cycles it at runtime.
+    pub fog: Fog,
+    pub gamepad: GamepadConfig,
+    /// Action names and the keys that replace their usual ones.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, KeyNames>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    pub title: String,
+    pub vsync: bool,
+    pub msaa: u16,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            width: 1024,
+            height: 768,
+            title: "opengl_rust".to_string(),
+            vsync: true,
+            msaa: 4,
+        }
+    }
+}
+
+/// Without a position the camera frames the models from the usual angle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<[f32; 3]>,
+    /// Looked at from `position`; the origin if left out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<[f32; 3]>,
+    /// Vertical field of view in degrees.
+    pub fov: f32,
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        CameraConfig {
+            position: None,
+            target: None,
+            fov: 60.0,
+        }
+    }
+}
+
+/// The directional light the scene starts with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LightingConfig {
+    /// Towards the light, in world space.
+    pub direction: [f32; 3],
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl Default for LightingConfig {
+    fn default() -> Self {
+        LightingConfig {
+            direction: [-1.0, 0.7, -0.6],
+            color: [1.0, 1.0, 1.0],
+            intensity: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderingConfig {
+    /// sRGB, like material colors; shows wherever the sky doesn't.
+    pub clear_color: [f32; 3],
+    pub wireframe: bool,
+    pub culling: CullMode,
+    /// sRGB too; what OBJ line elements are drawn in.
+    pub line_color: [f32; 3],
+}
+
+impl Default for RenderingConfig {
+    fn default() -> Self {
+        RenderingConfig {
+            clear_color: [0.12, 0.12, 0.12],
+            wireframe: false,
+            culling: CullMode::Back,
+            line_color: [0.9, 0.9, 0.9],
+        }
+    }
+}
+
+/// How a controller's sticks and triggers respond.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    pub enabled: bool,
+    /// How far a stick has to be pushed, from 0 to 1, before it does
+    /// anything, so a worn stick doesn't drift the camera.
+    pub dead_zone: f32,
+    pub trigger_dead_zone: f32,
+    /// Past the dead zone, input is raised to this power: above 1 gives
+    /// fine control near the center and full speed at the edge.
+    pub response_exponent: f32,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        GamepadConfig {
+            enabled: true,
+            dead_zone: 0.15,
+            trigger_dead_zone: 0.05,
+            response_exponent: 2.0,
+        }
+    }
+}
+
+/// One key name like `"Z"`, `"LShift"` or `"Ctrl+F12"`, or a list of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KeyNames {
+    One(String),
+    Several(Vec<String>),
+}
+
+impl KeyNames {
+    pub fn names(&self) -> &[String] {
+        match self {
+            KeyNames::One(name) => std::slice::from_ref(name),
+            KeyNames::Several(names) => names,
+        }
+    }
+}
+
+impl Config {
+    /// Reads `path`, warning about keys it doesn't know. A missing file is
+    /// only an error when it was asked for by name; otherwise the defaults
+    /// are used.
+    pub fn load(path: &Path, required: bool) -> Result<Config, String> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound && !required => {
+                return Ok(Config::default());
+            }
+            Err(error) => return Err(format!("{}: {}", path.display(), error)),
+        };
+        let value = text
+            .parse::<toml::Value>()
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+        for warning in unknown_keys(&value) {
+            eprintln!("warning: {}: {}", path.display(), warning);
+        }
+        value
+            .try_into()
+            .map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string(self).map_err(|error| error.to_string())?;
+        fs::write(path, text).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
+
+/// Describes each section or key in `value` that `Config` would ignore.
+fn unknown_keys(value: &toml::Value) -> Vec<String> {
+    let mut sections = KNOWN_KEYS.map(|(section, _)| section).to_vec();
+    sections.push(KEYS_SECTION);
+    let mut warnings = Vec::new();
+    let table = match value.as_table() {
+        Some(table) => table,
+        None => return warnings,
+    };
+    for (name, section) in table {
+        if name == KEYS_SECTION {
+            continue;
+        }
+        let known = match KNOWN_KEYS.iter().find(|(known, _)| known == name) {
+            Some((_, keys)) => keys,
+            None => {
+                warnings.push(format!(
+                    "unknown section [{}], expected one of {}",
+                    name,
+                    sections.join(", ")
+                ));
+                continue;
+            }
+        };
+        let keys = section
+            .as_table()
+            .into_iter()
+            .flat_map(|table| table.keys());
+        for key in keys.filter(|key| !known.contains(&key.as_str())) {
+            warnings.push(format!(
+                "unknown key '{}' in [{}], expected one of {}",
+                key,
+                name,
+                known.join(", ")
+            ));
+        }
+    }
+    warnings
+}
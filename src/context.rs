@@ -0,0 +1,211 @@
+use glium::glutin;
+use glium::glutin::event_loop::EventLoop;
+use glium::{Api, Version};
+
+use crate::args::Args;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod surfaceless;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub use surfaceless::create_surfaceless;
+
+/// Elsewhere there's always a window system to make a headless context
+/// with.
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn create_surfaceless(_args: &Args) -> Result<std::rc::Rc<glium::backend::Context>, String> {
+    Err("surfaceless contexts are only made on Linux and the BSDs".to_string())
+}
+
+/// The oldest GLSL the shaders are written for, and the GLSL ES they're
+/// rewritten to on OpenGL ES.
+const REQUIRED_GLSL: Version = Version(Api::Gl, 1, 50);
+const REQUIRED_GLSL_ES: Version = Version(Api::GlEs, 3, 0);
+
+/// One set of context settings to ask for.
+struct Attempt {
+    samples: u16,
+    srgb: bool,
+    gl: glutin::GlRequest,
+}
+
+impl Attempt {
+    fn describe(&self) -> String {
+        let samples = match self.samples {
+            0 => "no MSAA".to_string(),
+            samples => format!("{}x MSAA", samples),
+        };
+        let srgb = match self.srgb {
+            true => "sRGB",
+            false => "no sRGB",
+        };
+        let gl = match self.gl {
+            glutin::GlRequest::Specific(glutin::Api::OpenGl, (major, minor)) => {
+                format!("OpenGL {}.{}", major, minor)
+            }
+            glutin::GlRequest::Specific(glutin::Api::OpenGlEs, (major, minor)) => {
+                format!("OpenGL ES {}.{}", major, minor)
+            }
+            _ => "the newest OpenGL".to_string(),
+        };
+        format!("{}, {}, {}", samples, srgb, gl)
+    }
+}
+
+/// OpenGL ES 3.0, the oldest the ES shaders run on.
+const GLES: glutin::GlRequest = glutin::GlRequest::Specific(glutin::Api::OpenGlEs, (3, 0));
+
+/// What to try, best first: the requested multisampling, then fewer
+/// samples down to none, then no sRGB framebuffer, then older and embedded
+/// versions of OpenGL. The final pass encodes its output itself, so the
+/// picture doesn't need an sRGB framebuffer. With `gles` every attempt is
+/// for OpenGL ES.
+fn attempts(msaa: u16, gles: bool) -> Vec<Attempt> {
+    let latest = match gles {
+        true => GLES,
+        false => glutin::GlRequest::Latest,
+    };
+    let mut attempts = Vec::new();
+    let mut samples = msaa;
+    loop {
+        attempts.push(Attempt {
+            samples,
+            srgb: true,
+            gl: latest,
+        });
+        if samples == 0 {
+            break;
+        }
+        samples = match samples / 2 {
+            1 => 0,
+            half => half,
+        };
+    }
+    let gl = match gles {
+        true => vec![GLES],
+        false => vec![
+            latest,
+            glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 1)),
+            GLES,
+        ],
+    };
+    attempts.extend(gl.into_iter().map(|gl| Attempt {
+        samples: 0,
+        srgb: false,
+        gl,
+    }));
+    attempts
+}
+
+/// Creating the event loop without a display server to talk to panics
+/// deep in the X11 bindings, so it's checked for first.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn check_display_server() -> Result<(), String> {
+    let set = |name: &str| std::env::var_os(name).is_some_and(|value| !value.is_empty());
+    match set("DISPLAY") || set("WAYLAND_DISPLAY") {
+        true => Ok(()),
+        false => Err("no display server: neither DISPLAY nor WAYLAND_DISPLAY is set".to_string()),
+    }
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn check_display_server() -> Result<(), String> {
+    Ok(())
+}
+
+/// A context with no window at all, for `--headless`.
+pub fn create_headless(
+    args: &Args,
+    event_loop: &EventLoop<()>,
+) -> Result<glium::HeadlessRenderer, String> {
+    let gl = match args.gles {
+        true => GLES,
+        false => glutin::GlRequest::Latest,
+    };
+    let context = glutin::ContextBuilder::new()
+        .with_gl(gl)
+        .with_depth_buffer(24)
+        .with_stencil_buffer(8)
+        .build_headless(
+            event_loop,
+            glutin::dpi::PhysicalSize::new(args.width, args.height),
+        )
+        .map_err(|error| error.to_string())?;
+    glium::HeadlessRenderer::new(context).map_err(|error| error.to_string())
+}
+
+/// Opens the window, relaxing the context settings each time creation
+/// fails and saying so. If nothing works, the error lists every attempt
+/// and why it failed.
+pub fn create_display(
+    args: &Args,
+    event_loop: &EventLoop<()>,
+    visible: bool,
+) -> Result<glium::Display, String> {
+    let window_builder = glutin::window::WindowBuilder::new()
+        .with_title(&args.title)
+        .with_inner_size(glutin::dpi::LogicalSize::new(args.width, args.height))
+        .with_min_inner_size(glutin::dpi::PhysicalSize::new(1, 1))
+        .with_visible(visible);
+    let mut failures = Vec::new();
+    for attempt in attempts(args.msaa, args.gles) {
+        let context_builder = glutin::ContextBuilder::new()
+            .with_gl(attempt.gl)
+            .with_depth_buffer(24)
+            .with_stencil_buffer(8)
+            .with_multisampling(attempt.samples)
+            .with_srgb(attempt.srgb)
+            .with_vsync(args.vsync);
+        match glium::Display::new(window_builder.clone(), context_builder, event_loop) {
+            Ok(display) => return Ok(display),
+            Err(error) => {
+                eprintln!("warning: no context with {}: {}", attempt.describe(), error);
+                failures.push(format!("    {}: {}", attempt.describe(), error));
+            }
+        }
+    }
+    Err(format!(
+        "no OpenGL context could be created. Tried:\n{}",
+        failures.join("\n")
+    ))
+}
+
+/// The context's version, renderer and framebuffer, for the log.
+pub fn describe(display: &glium::Display) -> String {
+    let format = display.gl_window().get_pixel_format();
+    format!(
+        "{} on {} ({})\nframebuffer: {} color, {} alpha, {} depth, {} stencil bits, \
+         {}x multisampling, {}",
+        display.get_opengl_version_string(),
+        display.get_opengl_renderer_string(),
+        display.get_opengl_vendor_string(),
+        format.color_bits,
+        format.alpha_bits,
+        format.depth_bits,
+        format.stencil_bits,
+        format.multisampling.unwrap_or(0),
+        match format.srgb {
+            true => "sRGB",
+            false => "not sRGB",
+        },
+    )
+}
+
+/// Fails with an explanation if `facade`'s context can't compile the
+/// shaders, rather than leaving it to the first shader that doesn't.
+pub fn check_features(facade: &impl glium::backend::Facade) -> Result<(), String> {
+    let glsl = facade.get_context().get_supported_glsl_version();
+    let Version(api, major, minor) = glsl;
+    let (name, required) = match api {
+        Api::Gl => ("GLSL", REQUIRED_GLSL),
+        Api::GlEs => ("GLSL ES", REQUIRED_GLSL_ES),
+    };
+    if glsl < required {
+        let Version(_, required_major, required_minor) = required;
+        return Err(format!(
+            "the context only supports {} {}.{:02}, and the shaders need {} {}.{:02}",
+            name, major, minor, name, required_major, required_minor
+        ));
+    }
+    Ok(())
+}
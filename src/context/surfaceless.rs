@@ -0,0 +1,121 @@
+use std::ffi::c_void;
+use std::rc::Rc;
+
+use glium::backend::{Backend, Context};
+use glium::debug::DebugCallbackBehavior;
+use glium::SwapBuffersError;
+use khronos_egl as egl;
+
+use crate::args::Args;
+
+/// `EGL_PLATFORM_SURFACELESS_MESA`, which `khronos_egl` has no name for.
+const PLATFORM_SURFACELESS: egl::Enum = 0x31DD;
+
+type Egl = egl::DynamicInstance<egl::EGL1_5>;
+
+/// An EGL context with no surface to draw on, so everything it draws goes
+/// into framebuffers of its own. It has no display server to talk to.
+struct Surfaceless {
+    egl: Egl,
+    display: egl::Display,
+    context: egl::Context,
+    size: (u32, u32),
+}
+
+unsafe impl Backend for Surfaceless {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        match self.egl.get_proc_address(symbol) {
+            Some(function) => function as *const c_void,
+            None => std::ptr::null(),
+        }
+    }
+
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn is_current(&self) -> bool {
+        self.egl.get_current_context() == Some(self.context)
+    }
+
+    unsafe fn make_current(&self) {
+        self.egl
+            .make_current(self.display, None, None, Some(self.context))
+            .expect("could not make the surfaceless context current");
+    }
+}
+
+impl Drop for Surfaceless {
+    fn drop(&mut self) {
+        let _ = self.egl.make_current(self.display, None, None, None);
+        let _ = self.egl.destroy_context(self.display, self.context);
+        let _ = self.egl.terminate(self.display);
+    }
+}
+
+/// A context from EGL's surfaceless platform, which Mesa provides on the
+/// GPU or, without one, in software, for `--headless` where there's no
+/// display server to open even a hidden window on. glium takes the default
+/// framebuffer to be the window size, though there is none. OpenGL 3.2
+/// core, or OpenGL ES 3.0 with `--gles`.
+pub fn create_surfaceless(args: &Args) -> Result<Rc<Context>, String> {
+    let egl = unsafe { Egl::load_required() }
+        .map_err(|error| format!("could not load libEGL: {}", error))?;
+    let display = unsafe {
+        egl.get_platform_display(
+            PLATFORM_SURFACELESS,
+            egl::DEFAULT_DISPLAY,
+            &[egl::ATTRIB_NONE],
+        )
+    }
+    .map_err(|error| format!("no surfaceless EGL display: {}", error))?;
+    egl.initialize(display)
+        .map_err(|error| format!("could not initialize EGL: {}", error))?;
+
+    let (api, renderable, name) = match args.gles {
+        true => (egl::OPENGL_ES_API, egl::OPENGL_ES3_BIT, "OpenGL ES 3.0"),
+        false => (egl::OPENGL_API, egl::OPENGL_BIT, "OpenGL 3.2"),
+    };
+    let attributes = [
+        egl::SURFACE_TYPE,
+        egl::PBUFFER_BIT,
+        egl::RENDERABLE_TYPE,
+        renderable,
+        egl::NONE,
+    ];
+    let config = egl
+        .choose_first_config(display, &attributes)
+        .map_err(|error| error.to_string())
+        .and_then(|config| config.ok_or_else(|| format!("no {} config", name)))
+        .map_err(|error| format!("could not choose an EGL config: {}", error))?;
+    egl.bind_api(api)
+        .map_err(|error| format!("no {} through EGL: {}", name, error))?;
+    let attributes = match args.gles {
+        true => vec![egl::CONTEXT_MAJOR_VERSION, 3, egl::NONE],
+        false => vec![
+            egl::CONTEXT_MAJOR_VERSION,
+            3,
+            egl::CONTEXT_MINOR_VERSION,
+            2,
+            egl::CONTEXT_OPENGL_PROFILE_MASK,
+            egl::CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            egl::NONE,
+        ],
+    };
+    let context = egl
+        .create_context(display, config, None, &attributes)
+        .map_err(|error| format!("could not create an {} context: {}", name, error))?;
+
+    let backend = Surfaceless {
+        egl,
+        display,
+        context,
+        size: (args.width, args.height),
+    };
+    unsafe { Context::new(backend, true, DebugCallbackBehavior::default()) }
+        .map_err(|error| error.to_string())
+}
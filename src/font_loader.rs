@@ -1,33 +1,152 @@
-use std::io::Cursor;
-
 use glium::texture::RawImage2d;
-extern crate image;
 
-pub struct SpriteSheet {
-    width: u8,
-    height: u8,
-    border: u8,
-    start_x: u8,
-    start_y: u8,
-    rows: u8,
-    columns: u8,
+/// Size of each character cell, in pixels.
+pub const GLYPH_WIDTH: u32 = 8;
+pub const GLYPH_HEIGHT: u32 = 8;
+/// Cells in each row of the atlas.
+const ATLAS_COLUMNS: u32 = 16;
+/// The character of the first glyph in `GLYPHS`.
+const FIRST_CHARACTER: u8 = b' ';
+/// Drawn for characters the font doesn't have.
+const FALLBACK: char = '?';
+
+/// Printable ASCII from space to tilde, each glyph 8 rows of 8 pixels. The
+/// top row is the most significant byte, and the leftmost pixel in a row its
+/// most significant bit.
+const GLYPHS: [u64; 95] = [
+    0x0000000000000000, // space
+    0x1010101010001000, // !
+    0x2828280000000000, // "
+    0x28287c287c282800, // #
+    0x103c503814781000, // $
+    0x60640810204c0c00, // %
+    0x3048502054483400, // &
+    0x1010200000000000, // '
+    0x0810202020100800, // (
+    0x2010080808102000, // )
+    0x0010543854100000, // *
+    0x0010107c10100000, // +
+    0x0000000000301020, // ,
+    0x0000007c00000000, // -
+    0x0000000000303000, // .
+    0x0004081020400000, // /
+    0x38444c5464443800, // 0
+    0x1030101010103800, // 1
+    0x3844040810207c00, // 2
+    0x7c08100804443800, // 3
+    0x081828487c080800, // 4
+    0x7c40780404443800, // 5
+    0x1820407844443800, // 6
+    0x7c04081020202000, // 7
+    0x3844443844443800, // 8
+    0x3844443c04083000, // 9
+    0x0030300030300000, // :
+    0x0030300030102000, // ;
+    0x0810204020100800, // <
+    0x00007c007c000000, // =
+    0x2010080408102000, // >
+    0x3844040810001000, // ?
+    0x3844043454543800, // @
+    0x384444447c444400, // A
+    0x7844447844447800, // B
+    0x3844404040443800, // C
+    0x7048444444487000, // D
+    0x7c40407840407c00, // E
+    0x7c40407840404000, // F
+    0x3844405c44443c00, // G
+    0x4444447c44444400, // H
+    0x3810101010103800, // I
+    0x1c08080808483000, // J
+    0x4448506050484400, // K
+    0x4040404040407c00, // L
+    0x446c545444444400, // M
+    0x444464544c444400, // N
+    0x3844444444443800, // O
+    0x7844447840404000, // P
+    0x3844444454483400, // Q
+    0x7844447850484400, // R
+    0x3c40403804047800, // S
+    0x7c10101010101000, // T
+    0x4444444444443800, // U
+    0x4444444444281000, // V
+    0x4444445454542800, // W
+    0x4444281028444400, // X
+    0x4444442810101000, // Y
+    0x7c04081020407c00, // Z
+    0x3820202020203800, // [
+    0x0040201008040000, // \
+    0x3808080808083800, // ]
+    0x1028440000000000, // ^
+    0x0000000000007c00, // _
+    0x2010080000000000, // `
+    0x000038043c443c00, // a
+    0x4040586444447800, // b
+    0x0000384040443800, // c
+    0x0404344c44443c00, // d
+    0x000038447c403800, // e
+    0x1824207020202000, // f
+    0x00003c44443c0438, // g
+    0x4040586444444400, // h
+    0x1000301010103800, // i
+    0x0800180808084830, // j
+    0x4040485060504800, // k
+    0x3010101010103800, // l
+    0x0000685454444400, // m
+    0x0000586444444400, // n
+    0x0000384444443800, // o
+    0x0000784444784040, // p
+    0x00003c44443c0404, // q
+    0x0000586440404000, // r
+    0x00003c4038047800, // s
+    0x2020702020241800, // t
+    0x00004444444c3400, // u
+    0x0000444444281000, // v
+    0x0000444454542800, // w
+    0x0000442810284400, // x
+    0x00004444443c0438, // y
+    0x00007c0810207c00, // z
+    0x0810102010100800, // {
+    0x1010101010101000, // |
+    0x2010100810102000, // }
+    0x0000205408000000, // ~
+];
+
+/// The atlas cell after the last glyph, left solid for filling rectangles.
+pub const SOLID_CELL: (u32, u32) = (
+    GLYPHS.len() as u32 % ATLAS_COLUMNS,
+    GLYPHS.len() as u32 / ATLAS_COLUMNS,
+);
+
+/// The cell of the atlas holding `character`'s glyph, as a column and a row
+/// counted from the top.
+pub fn glyph_cell(character: char) -> (u32, u32) {
+    let index = match character {
+        ' '..='~' => character as u32 - FIRST_CHARACTER as u32,
+        _ => FALLBACK as u32 - FIRST_CHARACTER as u32,
+    };
+    (index % ATLAS_COLUMNS, index / ATLAS_COLUMNS)
 }
 
-impl SpriteSheet {
-    fn nth(&self, number: u8) -> (u8, u8, u8, u8) {
-        return (0, 0, 0, 0)
+/// Every glyph in white on transparent, laid out in rows of `ATLAS_COLUMNS`
+/// cells and followed by `SOLID_CELL`. Reversed for OpenGL, so the top row
+/// ends up at the top of the texture.
+pub fn load_font() -> RawImage2d<'static, u8> {
+    let rows = (GLYPHS.len() as u32 + 1).div_ceil(ATLAS_COLUMNS);
+    let (width, height) = (ATLAS_COLUMNS * GLYPH_WIDTH, rows * GLYPH_HEIGHT);
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    for (index, glyph) in GLYPHS.iter().chain([&u64::MAX]).enumerate() {
+        let (column, row) = (index as u32 % ATLAS_COLUMNS, index as u32 / ATLAS_COLUMNS);
+        for y in 0..GLYPH_HEIGHT {
+            let bits = (glyph >> ((GLYPH_HEIGHT - 1 - y) * 8)) as u8;
+            for x in 0..GLYPH_WIDTH {
+                if bits & (0x80 >> x) == 0 {
+                    continue;
+                }
+                let pixel = (row * GLYPH_HEIGHT + y) * width + column * GLYPH_WIDTH + x;
+                let start = pixel as usize * 4;
+                pixels[start..start + 4].copy_from_slice(&[255; 4]);
+            }
+        }
     }
+    RawImage2d::from_raw_rgba_reversed(&pixels, (width, height))
 }
-
-pub fn load_font(font_file: &str) -> RawImage2d<u8> {
-    let image = image::load(
-        Cursor::new(&include_bytes!("./profont.png")),
-        image::ImageFormat::Png,
-    )
-    .unwrap()
-    .to_rgba8();
-
-    let image_dimensions = image.dimensions();
-    let image = glium::texture::RawImage2d::from_raw_rgba(image.into_raw(), image_dimensions);
-    image
-} 
@@ -0,0 +1,89 @@
+use std::time::{Duration, Instant};
+
+use glium::glutin::event_loop::ControlFlow;
+
+/// Longest step a single frame may advance animations by, so a stall (a
+/// window drag, a breakpoint) doesn't make everything jump.
+const MAX_DT: f32 = 0.25;
+
+/// Paces redraws to a target frame rate and measures the time between them.
+/// In the background it slows to the background rate, or with none stops
+/// asking for frames and holds time still.
+pub struct FrameClock {
+    last_frame: Instant,
+    /// `None` renders as fast as possible.
+    frame_duration: Option<Duration>,
+    /// `None` only draws when the window system asks, without time moving.
+    background_duration: Option<Duration>,
+    background: bool,
+}
+
+impl FrameClock {
+    /// A `fps` of 0 leaves the frame rate uncapped, a `background_fps` of 0
+    /// pauses in the background.
+    pub fn new(fps: u32, background_fps: u32) -> Self {
+        FrameClock {
+            last_frame: Instant::now(),
+            frame_duration: frame_duration(fps),
+            background_duration: frame_duration(background_fps),
+            background: false,
+        }
+    }
+
+    /// Switches to or from the background rate. Coming back starts time
+    /// from now, so the pause doesn't count towards the next frame.
+    pub fn set_background(&mut self, background: bool) {
+        if self.background && !background {
+            self.last_frame = Instant::now();
+        }
+        self.background = background;
+    }
+
+    fn paused(&self) -> bool {
+        self.background && self.background_duration.is_none()
+    }
+
+    /// The time between frames now, `None` when uncapped or paused.
+    fn current_duration(&self) -> Option<Duration> {
+        match self.background {
+            true => self.background_duration,
+            false => self.frame_duration,
+        }
+    }
+
+    /// Starts a frame, returning the seconds since the previous one, or 0
+    /// while paused so animations hold still.
+    pub fn tick(&mut self) -> f32 {
+        let now = Instant::now();
+        let dt = (now - self.last_frame).as_secs_f32();
+        self.last_frame = now;
+        match self.paused() {
+            true => 0.0,
+            false => dt.min(MAX_DT),
+        }
+    }
+
+    pub fn frame_due(&self) -> bool {
+        match self.current_duration() {
+            Some(duration) => Instant::now() >= self.last_frame + duration,
+            None => !self.paused(),
+        }
+    }
+
+    /// Sleeps until the next frame is due, keeps polling when uncapped, or
+    /// waits for events while paused.
+    pub fn control_flow(&self) -> ControlFlow {
+        match self.current_duration() {
+            Some(duration) => ControlFlow::WaitUntil(self.last_frame + duration),
+            None if self.paused() => ControlFlow::Wait,
+            None => ControlFlow::Poll,
+        }
+    }
+}
+
+fn frame_duration(fps: u32) -> Option<Duration> {
+    match fps {
+        0 => None,
+        fps => Some(Duration::from_secs_f64(1.0 / fps as f64)),
+    }
+}
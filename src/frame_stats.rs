@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Collects frame times and per-frame draw counts, summarising them once per
+/// report interval.
+pub struct FrameStats {
+    frame_times: Vec<f32>,
+    last_frame: Instant,
+    last_report: Instant,
+    draw_calls: usize,
+    triangles: usize,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        FrameStats::new()
+    }
+}
+
+impl FrameStats {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        FrameStats {
+            frame_times: Vec::new(),
+            last_frame: now,
+            last_report: now,
+            draw_calls: 0,
+            triangles: 0,
+        }
+    }
+
+    /// Forgets the frames so far, so a pause doesn't show up as one slow
+    /// frame in the next summary.
+    pub fn restart(&mut self) {
+        *self = FrameStats::new();
+    }
+
+    /// Records a finished frame. Returns a summary when the interval is up,
+    /// e.g. "60 fps, 16.67 ms avg, 17.02 ms p99, 3 draws, 6320 triangles".
+    pub fn frame(&mut self, draw_calls: usize, triangles: usize) -> Option<String> {
+        let now = Instant::now();
+        self.frame_times
+            .push((now - self.last_frame).as_secs_f32() * 1000.0);
+        self.last_frame = now;
+        self.draw_calls = draw_calls;
+        self.triangles = triangles;
+
+        let elapsed = now - self.last_report;
+        if elapsed < REPORT_INTERVAL {
+            return None;
+        }
+        let summary = self.summary(elapsed);
+        self.frame_times.clear();
+        self.last_report = now;
+        Some(summary)
+    }
+
+    fn summary(&mut self, elapsed: Duration) -> String {
+        let fps = self.frame_times.len() as f32 / elapsed.as_secs_f32();
+        let average = self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32;
+        self.frame_times.sort_by(|a, b| a.total_cmp(b));
+        let p99_index = ((self.frame_times.len() as f32 * 0.99).ceil() as usize)
+            .clamp(1, self.frame_times.len())
+            - 1;
+        format!(
+            "{:.0} fps, {:.2} ms avg, {:.2} ms p99, {} draws, {} triangles",
+            fps, average, self.frame_times[p99_index], self.draw_calls, self.triangles
+        )
+    }
+}
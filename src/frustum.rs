@@ -0,0 +1,143 @@
+use crate::math::{Mat4, Vec3};
+
+/// The six planes bounding what a camera sees, each as a unit normal
+/// pointing inwards and a distance, so `normal · p + distance` is how far
+/// inside the plane a point `p` is.
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    planes: [(Vec3, f32); 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a projection times view matrix, in the
+    /// space that matrix transforms out of: left, right, bottom, top, near,
+    /// then far.
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let columns = matrix.to_array();
+        let row = |r: usize| [0, 1, 2, 3].map(|c| columns[c][r]);
+        let w = row(3);
+        let plane = |axis: usize, sign: f32| {
+            let other = row(axis);
+            let [a, b, c, d] = [0, 1, 2, 3].map(|i| w[i] + sign * other[i]);
+            let normal = Vec3::new(a, b, c);
+            let length = normal.length();
+            (normal * (1.0 / length), d / length)
+        };
+        Frustum {
+            planes: [
+                plane(0, 1.0),
+                plane(0, -1.0),
+                plane(1, 1.0),
+                plane(1, -1.0),
+                plane(2, 1.0),
+                plane(2, -1.0),
+            ],
+        }
+    }
+
+    /// Whether any of the sphere could be seen: false only once it's
+    /// entirely outside one of the planes.
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|&(normal, distance)| normal.dot(center) + distance >= -radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_planes(frustum: &Frustum, expected: [([f32; 3], f32); 6]) {
+        for (&(normal, distance), (expected_normal, expected_distance)) in
+            frustum.planes.iter().zip(expected)
+        {
+            let close = (normal - Vec3::from(expected_normal)).length() < 1e-5
+                && (distance - expected_distance).abs() < 1e-5;
+            assert!(close, "{:?}", frustum.planes);
+        }
+    }
+
+    /// 90° across both ways, seeing from 1 to 10 along +Z.
+    fn square_perspective() -> Frustum {
+        Frustum::from_matrix(Mat4::perspective(
+            std::f32::consts::FRAC_PI_2,
+            1.0,
+            1.0,
+            10.0,
+        ))
+    }
+
+    #[test]
+    fn orthographic_planes_are_the_box() {
+        let frustum = Frustum::from_matrix(Mat4::orthographic(-1.0, 1.0, -2.0, 2.0, 1.0, 3.0));
+        assert_planes(
+            &frustum,
+            [
+                ([1.0, 0.0, 0.0], 1.0),
+                ([-1.0, 0.0, 0.0], 1.0),
+                ([0.0, 1.0, 0.0], 2.0),
+                ([0.0, -1.0, 0.0], 2.0),
+                ([0.0, 0.0, 1.0], -1.0),
+                ([0.0, 0.0, -1.0], 3.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn perspective_sides_are_at_45_degrees() {
+        let side = std::f32::consts::FRAC_1_SQRT_2;
+        assert_planes(
+            &square_perspective(),
+            [
+                ([side, 0.0, side], 0.0),
+                ([-side, 0.0, side], 0.0),
+                ([0.0, side, side], 0.0),
+                ([0.0, -side, side], 0.0),
+                ([0.0, 0.0, 1.0], -1.0),
+                ([0.0, 0.0, -1.0], 10.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn spheres_against_the_planes() {
+        let frustum = square_perspective();
+        let visible =
+            |x: f32, z: f32, radius: f32| frustum.intersects_sphere(Vec3::new(x, 0.0, z), radius);
+        assert!(visible(0.0, 5.0, 0.5));
+        assert!(!visible(0.0, -5.0, 1.0));
+        // 0.1 more than its radius beyond the far plane, then 0.1 less.
+        assert!(!visible(0.0, 10.6, 0.5));
+        assert!(visible(0.0, 10.4, 0.5));
+        // The right plane is 1/√2 from (6, 0, 5).
+        assert!(!visible(6.0, 5.0, 0.7));
+        assert!(visible(6.0, 5.0, 0.71));
+    }
+
+    #[test]
+    fn sphere_straddling_the_near_plane_is_visible() {
+        let frustum = square_perspective();
+        let visible = |z: f32| frustum.intersects_sphere(Vec3::new(0.0, 0.0, z), 0.5);
+        assert!(visible(1.0));
+        assert!(visible(0.6));
+        // Entirely between the eye and the near plane.
+        assert!(!visible(0.4));
+    }
+
+    #[test]
+    fn planes_follow_the_view() {
+        // From -5 on Z towards the origin, so the view moves everything
+        // 5 along +Z.
+        let view = Mat4::look_at(
+            Vec3::new(0.0, 0.0, -5.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let projection = Mat4::perspective(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 10.0);
+        let frustum = Frustum::from_matrix(projection * view);
+        assert!(frustum.intersects_sphere(Vec3::new(0.0, 0.0, 0.0), 0.5));
+        assert!(!frustum.intersects_sphere(Vec3::new(0.0, 0.0, -6.0), 0.5));
+        assert!(!frustum.intersects_sphere(Vec3::new(0.0, 0.0, 5.6), 0.5));
+    }
+}
@@ -0,0 +1,35 @@
+use glium::glutin::dpi::{PhysicalPosition, PhysicalSize};
+use glium::glutin::window::{Fullscreen, Window};
+
+/// Switches between windowed and borderless fullscreen, remembering where
+/// the window was so leaving fullscreen puts it back.
+#[derive(Default)]
+pub struct FullscreenToggle {
+    windowed: Option<(Option<PhysicalPosition<i32>>, PhysicalSize<u32>)>,
+}
+
+impl FullscreenToggle {
+    pub fn is_fullscreen(&self) -> bool {
+        self.windowed.is_some()
+    }
+
+    pub fn toggle(&mut self, window: &Window) {
+        if self.is_fullscreen() {
+            self.leave(window);
+        } else {
+            self.windowed = Some((window.outer_position().ok(), window.inner_size()));
+            // `None` picks the monitor the window is currently on.
+            window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+        }
+    }
+
+    pub fn leave(&mut self, window: &Window) {
+        if let Some((position, size)) = self.windowed.take() {
+            window.set_fullscreen(None);
+            window.set_inner_size(size);
+            if let Some(position) = position {
+                window.set_outer_position(position);
+            }
+        }
+    }
+}
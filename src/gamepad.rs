@@ -0,0 +1,206 @@
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use crate::config::GamepadConfig;
+
+/// A controller's sticks and triggers as read from it: stick axes from -1
+/// to 1, right and down positive, and triggers from 0 released to 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GamepadState {
+    pub left_stick: [f32; 2],
+    pub right_stick: [f32; 2],
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+}
+
+/// Where controller input comes from. Runs on its own thread, so reading
+/// may block.
+pub trait Backend: Send + 'static {
+    /// Waits for the controller's state to change and returns it, or
+    /// `None` once it's unplugged. With no controller connected, waits a
+    /// while for one and returns `None` if none turns up.
+    fn next(&mut self) -> Option<GamepadState>;
+}
+
+/// The sticks and triggers after the dead zones and response curve, as the
+/// camera controls use them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Input {
+    pub left_stick: [f32; 2],
+    pub right_stick: [f32; 2],
+    /// The right trigger less the left one.
+    pub triggers: f32,
+}
+
+/// The latest input from whichever controller is connected. A backend
+/// thread does the reading, so the render loop only checks for news.
+pub struct Gamepad {
+    updates: Option<Receiver<Option<GamepadState>>>,
+    state: Option<GamepadState>,
+    config: GamepadConfig,
+}
+
+impl Gamepad {
+    /// Starts reading the platform's controllers, unless the config turns
+    /// them off or the platform has no backend.
+    pub fn new(config: GamepadConfig) -> Self {
+        let backend = match config.enabled {
+            true => default_backend(),
+            false => None,
+        };
+        Gamepad {
+            updates: backend.map(spawn),
+            state: None,
+            config,
+        }
+    }
+
+    /// Takes in what the controller sent since the last call and returns
+    /// the input it adds up to, zero without a controller.
+    pub fn poll(&mut self) -> Input {
+        if let Some(updates) = &self.updates {
+            while let Ok(state) = updates.try_recv() {
+                if state.is_some() != self.state.is_some() {
+                    let change = match state {
+                        Some(_) => "connected",
+                        None => "disconnected",
+                    };
+                    println!("gamepad {}", change);
+                }
+                self.state = state;
+            }
+        }
+        let Some(state) = self.state else {
+            return Input::default();
+        };
+        let config = &self.config;
+        let trigger = |value: f32| config.respond(value, config.trigger_dead_zone);
+        Input {
+            left_stick: config.respond_stick(state.left_stick),
+            right_stick: config.respond_stick(state.right_stick),
+            triggers: trigger(state.right_trigger) - trigger(state.left_trigger),
+        }
+    }
+}
+
+impl GamepadConfig {
+    /// Zero inside the dead zone, then rising from zero at its edge to one
+    /// at full deflection along the response curve.
+    fn respond(&self, value: f32, dead_zone: f32) -> f32 {
+        let live = (value.abs() - dead_zone) / (1.0 - dead_zone).max(f32::EPSILON);
+        live.clamp(0.0, 1.0).powf(self.response_exponent) * value.signum()
+    }
+
+    /// Applies the dead zone to how far the stick is pushed, keeping its
+    /// direction, so diagonals aren't cut off.
+    fn respond_stick(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        let length = (x * x + y * y).sqrt();
+        if length <= self.dead_zone {
+            return [0.0, 0.0];
+        }
+        let scale = self.respond(length.min(1.0), self.dead_zone) / length;
+        [x * scale, y * scale]
+    }
+}
+
+/// Forwards every state the backend reads until the gamepad is dropped.
+fn spawn(mut backend: Box<dyn Backend>) -> Receiver<Option<GamepadState>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || while sender.send(backend.next()).is_ok() {});
+    receiver
+}
+
+#[cfg(target_os = "linux")]
+fn default_backend() -> Option<Box<dyn Backend>> {
+    Some(Box::new(joystick::Joystick::default()))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn default_backend() -> Option<Box<dyn Backend>> {
+    None
+}
+
+/// Controllers through the Linux joystick interface, `/dev/input/js*`.
+#[cfg(target_os = "linux")]
+mod joystick {
+    use std::fs::File;
+    use std::io::Read;
+    use std::time::Duration;
+
+    use super::{Backend, GamepadState};
+
+    const DEVICE_DIRECTORY: &str = "/dev/input";
+    /// How long to wait between looks for a controller while none is
+    /// connected.
+    const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+    /// `struct js_event`: a timestamp, the value, the event type and the
+    /// axis or button number.
+    const EVENT_SIZE: usize = 8;
+    const EVENT_AXIS: u8 = 0x02;
+    /// Set on the events describing the state when the device is opened.
+    const EVENT_INIT: u8 = 0x80;
+    const AXIS_MAX: f32 = 32767.0;
+    /// The axes the xpad driver gives an Xbox controller.
+    const LEFT_X: u8 = 0;
+    const LEFT_Y: u8 = 1;
+    const LEFT_TRIGGER: u8 = 2;
+    const RIGHT_X: u8 = 3;
+    const RIGHT_Y: u8 = 4;
+    const RIGHT_TRIGGER: u8 = 5;
+
+    #[derive(Default)]
+    pub struct Joystick {
+        device: Option<File>,
+        state: GamepadState,
+    }
+
+    impl Backend for Joystick {
+        fn next(&mut self) -> Option<GamepadState> {
+            let Some(device) = &mut self.device else {
+                self.device = open_first();
+                self.state = GamepadState::default();
+                if self.device.is_none() {
+                    std::thread::sleep(SCAN_INTERVAL);
+                }
+                return None;
+            };
+            let mut event = [0; EVENT_SIZE];
+            if device.read_exact(&mut event).is_err() {
+                self.device = None;
+                return None;
+            }
+            let value = i16::from_le_bytes([event[4], event[5]]) as f32 / AXIS_MAX;
+            let (kind, number) = (event[6] & !EVENT_INIT, event[7]);
+            if kind == EVENT_AXIS {
+                // Triggers rest at -1 and go to 1 when pulled all the way.
+                let trigger = (value + 1.0) / 2.0;
+                match number {
+                    LEFT_X => self.state.left_stick[0] = value,
+                    LEFT_Y => self.state.left_stick[1] = value,
+                    RIGHT_X => self.state.right_stick[0] = value,
+                    RIGHT_Y => self.state.right_stick[1] = value,
+                    LEFT_TRIGGER => self.state.left_trigger = trigger,
+                    RIGHT_TRIGGER => self.state.right_trigger = trigger,
+                    _ => (),
+                }
+            }
+            Some(self.state)
+        }
+    }
+
+    /// The first joystick by name that opens.
+    fn open_first() -> Option<File> {
+        let mut paths: Vec<_> = std::fs::read_dir(DEVICE_DIRECTORY)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("js"))
+            })
+            .collect();
+        paths.sort();
+        paths.into_iter().find_map(|path| File::open(path).ok())
+    }
+}
@@ -0,0 +1,122 @@
+use crate::camera::{Camera, Projection};
+use crate::math::Vec3;
+use crate::picking::Ray;
+
+/// How long the arrows are, as a fraction of the view's height.
+const SCREEN_FRACTION: f32 = 0.15;
+/// How close to an arrow the ray has to pass to grab it, as a fraction of
+/// the arrow's length.
+const GRAB_RADIUS: f32 = 0.08;
+/// Shift-dragging keeps the moved coordinate a multiple of this.
+const SNAP_STEP: f32 = 0.5;
+/// Rays closer than this to parallel with an axis can't drag along it.
+const PARALLEL_EPSILON: f32 = 1e-6;
+
+/// The world axes the arrows point along, in the order they're indexed.
+pub const AXES: [Vec3; 3] = [
+    Vec3::new(1.0, 0.0, 0.0),
+    Vec3::new(0.0, 1.0, 0.0),
+    Vec3::new(0.0, 0.0, 1.0),
+];
+
+/// Three arrows at the selected object's origin for moving it along the
+/// world axes, scaled with the view so they stay the same size on screen.
+#[derive(Debug, Clone, Copy)]
+pub struct Gizmo {
+    pub position: Vec3,
+    /// Each arrow's length in world units.
+    pub length: f32,
+}
+
+impl Gizmo {
+    pub fn new(position: Vec3, camera: &Camera) -> Self {
+        let half_height = match camera.projection {
+            Projection::Orthographic => {
+                camera.distance * (camera.fov / 2.0).tan() * camera.ortho_scale
+            }
+            Projection::Perspective => {
+                let depth = (position - Vec3::from(camera.eye())).dot(camera.direction().into());
+                depth.max(0.0) * (camera.fov / 2.0).tan()
+            }
+        };
+        Gizmo {
+            position,
+            length: half_height * 2.0 * SCREEN_FRACTION,
+        }
+    }
+
+    /// The index into `AXES` of the arrow `ray` passes over, the nearest
+    /// along the ray if it passes over more than one.
+    pub fn hit(&self, ray: &Ray) -> Option<usize> {
+        let mut closest: Option<(usize, f32)> = None;
+        for (index, &axis) in AXES.iter().enumerate() {
+            let Some((along_ray, along_axis)) = closest_points(ray, self.position, axis) else {
+                continue;
+            };
+            let gap = ray.origin + ray.direction * along_ray - (self.position + axis * along_axis);
+            let grabbed = along_ray >= 0.0
+                && (0.0..=self.length).contains(&along_axis)
+                && gap.length() <= self.length * GRAB_RADIUS;
+            if grabbed && closest.is_none_or(|(_, nearest)| along_ray < nearest) {
+                closest = Some((index, along_ray));
+            }
+        }
+        closest.map(|(index, _)| index)
+    }
+}
+
+/// An arrow held down, moving an object along its axis.
+#[derive(Debug, Clone, Copy)]
+pub struct Drag {
+    /// Index into the scene's objects.
+    pub object: usize,
+    axis: Vec3,
+    /// The object's translation when the arrow was grabbed.
+    start: Vec3,
+    /// How far along the axis from `start` the arrow was grabbed.
+    grab: f32,
+}
+
+impl Drag {
+    /// Starts dragging `gizmo`'s arrow `axis` under `ray`. The gizmo sits at
+    /// the object's translation, so that's where it starts from.
+    pub fn new(object: usize, axis: usize, gizmo: &Gizmo, ray: &Ray) -> Option<Self> {
+        let axis = AXES[axis];
+        let (_, grab) = closest_points(ray, gizmo.position, axis)?;
+        Some(Drag {
+            object,
+            axis,
+            start: gizmo.position,
+            grab,
+        })
+    }
+
+    /// The object's translation once the arrow follows the cursor to `ray`:
+    /// the grabbed point moves to where the axis passes closest to the ray.
+    /// With `snap` the coordinate along the axis is rounded to a multiple of
+    /// `SNAP_STEP`. `None` while the ray is parallel to the axis.
+    pub fn translation(&self, ray: &Ray, snap: bool) -> Option<Vec3> {
+        let (_, along_axis) = closest_points(ray, self.start, self.axis)?;
+        let moved = self.start + self.axis * (along_axis - self.grab);
+        if !snap {
+            return Some(moved);
+        }
+        let coordinate = moved.dot(self.axis);
+        let snapped = (coordinate / SNAP_STEP).round() * SNAP_STEP;
+        Some(moved + self.axis * (snapped - coordinate))
+    }
+}
+
+/// Where `ray` and the line through `point` along the unit vector `axis`
+/// come closest, as distances along each, or `None` if they're parallel.
+fn closest_points(ray: &Ray, point: Vec3, axis: Vec3) -> Option<(f32, f32)> {
+    let offset = ray.origin - point;
+    let alignment = ray.direction.dot(axis);
+    let length_squared = ray.direction.dot(ray.direction);
+    let denominator = length_squared - alignment * alignment;
+    if denominator <= PARALLEL_EPSILON * length_squared {
+        return None;
+    }
+    let along_ray = (alignment * offset.dot(axis) - ray.direction.dot(offset)) / denominator;
+    Some((along_ray, offset.dot(axis) + along_ray * alignment))
+}
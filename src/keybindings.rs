@@ -0,0 +1,534 @@
+use std::collections::{BTreeMap, HashSet};
+
+use glium::glutin::event::{ModifiersState, VirtualKeyCode};
+
+use crate::camera::CameraMode;
+use crate::config::KeyNames;
+
+/// Something a key does. Most happen once per press; the fly movement ones
+/// last while their key is held, see `is_held`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    ToggleHelp,
+    /// Closes the help, releases the cursor and leaves fullscreen.
+    Release,
+    ToggleFullscreen,
+    SaveSettings,
+    Screenshot,
+    ToggleOverlay,
+    ToggleCameraMode,
+    ToggleProjection,
+    NarrowFov,
+    WidenFov,
+    FrameScene,
+    RestoreBookmark(usize),
+    StoreBookmark(usize),
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    MoveFaster,
+    ToggleCameraRecording,
+    ToggleCameraPlayback,
+    SlowerPlayback,
+    FasterPlayback,
+    CycleWireframe,
+    CycleCulling,
+    ToggleNormals,
+    ToggleLines,
+    CycleRenderMode,
+    CycleShadingModel,
+    ToggleGrid,
+    ToggleShadows,
+    ToggleShadowMap,
+    ToggleReflections,
+    LessEnvironmentReflectivity,
+    MoreEnvironmentReflectivity,
+    SmootherMaterials,
+    RougherMaterials,
+    ToggleGrayscale,
+    ToggleInvert,
+    ToggleVignette,
+    ToggleGammaSplit,
+    ToggleClipping,
+    CycleToneMap,
+    ToggleBloom,
+    LessBloom,
+    MoreBloom,
+    SmallerBloom,
+    LargerBloom,
+    LowerExposure,
+    RaiseExposure,
+    LowerOpacity,
+    RaiseOpacity,
+    CycleFog,
+    ThinnerFog,
+    ThickerFog,
+    ToggleAmbientOcclusion,
+    ToggleOcclusionBuffer,
+    SmallerOcclusion,
+    LargerOcclusion,
+    WeakerOcclusion,
+    StrongerOcclusion,
+    ToggleBakedOcclusion,
+    SmallerPoints,
+    LargerPoints,
+    SelectNext,
+    AddLight,
+    AddCopies,
+    ToggleRipple,
+    FreezeLod,
+    ToggleLodColors,
+    PauseSpin,
+    SpinFaster,
+    SpinSlower,
+    LightLeft,
+    LightRight,
+    LightUp,
+    LightDown,
+    LightBrighter,
+    LightDimmer,
+}
+
+impl Action {
+    /// Whether the action lasts while its key is held rather than happening
+    /// when it's pressed.
+    pub fn is_held(self) -> bool {
+        matches!(
+            self,
+            Action::MoveForward
+                | Action::MoveBack
+                | Action::MoveLeft
+                | Action::MoveRight
+                | Action::MoveUp
+                | Action::MoveDown
+                | Action::MoveFaster
+        )
+    }
+
+    /// What the config's `[keys]` section calls the action: its variant in
+    /// snake case, with bookmarks numbered from 1 like their keys.
+    pub fn name(self) -> String {
+        match self {
+            Action::RestoreBookmark(slot) => format!("restore_bookmark_{}", slot + 1),
+            Action::StoreBookmark(slot) => format!("store_bookmark_{}", slot + 1),
+            _ => {
+                let mut name = String::new();
+                for character in format!("{:?}", self).chars() {
+                    if character.is_ascii_uppercase() && !name.is_empty() {
+                        name.push('_');
+                    }
+                    name.push(character.to_ascii_lowercase());
+                }
+                name
+            }
+        }
+    }
+}
+
+/// A modifier that has to be held for a binding to apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    None,
+    Shift,
+    Ctrl,
+    Alt,
+}
+
+impl Modifier {
+    fn held(self, modifiers: ModifiersState) -> bool {
+        match self {
+            Modifier::None => true,
+            Modifier::Shift => modifiers.shift(),
+            Modifier::Ctrl => modifiers.ctrl(),
+            Modifier::Alt => modifiers.alt(),
+        }
+    }
+}
+
+/// A key, what it does, and the line describing it in the help.
+#[derive(Debug, Clone, Copy)]
+pub struct Keybinding {
+    pub key: VirtualKeyCode,
+    pub modifier: Modifier,
+    /// The camera mode the binding applies in, or `None` for both.
+    pub mode: Option<CameraMode>,
+    pub action: Action,
+    pub description: &'static str,
+}
+
+const fn bind(key: VirtualKeyCode, action: Action, description: &'static str) -> Keybinding {
+    Keybinding {
+        key,
+        modifier: Modifier::None,
+        mode: None,
+        action,
+        description,
+    }
+}
+
+const fn with(
+    modifier: Modifier,
+    key: VirtualKeyCode,
+    action: Action,
+    description: &'static str,
+) -> Keybinding {
+    Keybinding {
+        modifier,
+        ..bind(key, action, description)
+    }
+}
+
+const fn in_mode(
+    mode: CameraMode,
+    key: VirtualKeyCode,
+    action: Action,
+    description: &'static str,
+) -> Keybinding {
+    Keybinding {
+        mode: Some(mode),
+        ..bind(key, action, description)
+    }
+}
+
+/// The keys the viewer responds to unless the config rebinds them, in the
+/// order the help lists them. Every action has at least one. Bindings for
+/// the same action with the same description are listed on one line.
+#[rustfmt::skip]
+pub const KEYBINDINGS: &[Keybinding] = {
+    use Action::*;
+    use CameraMode::{Fly, Orbit};
+    use Modifier::{Alt, Ctrl, Shift};
+    use VirtualKeyCode as Key;
+    &[
+        bind(Key::F1, ToggleHelp, "show or hide this help"),
+        bind(Key::Escape, Release, "close the help, release the cursor, leave fullscreen"),
+        bind(Key::F11, ToggleFullscreen, "toggle fullscreen"),
+        with(Alt, Key::Return, ToggleFullscreen, "toggle fullscreen"),
+        bind(Key::F2, SaveSettings, "save the settings to the config file"),
+        bind(Key::F12, Screenshot, "save a screenshot"),
+        bind(Key::O, ToggleOverlay, "show or hide the statistics overlay"),
+
+        bind(Key::F, ToggleCameraMode, "switch between orbit and fly mode"),
+        bind(Key::P, ToggleProjection, "switch between perspective and orthographic"),
+        bind(Key::Z, NarrowFov, "narrow the field of view"),
+        bind(Key::X, WidenFov, "widen the field of view"),
+        bind(Key::Home, FrameScene, "frame the whole scene"),
+        bind(Key::Key1, RestoreBookmark(0), "restore a camera bookmark"),
+        bind(Key::Key2, RestoreBookmark(1), "restore a camera bookmark"),
+        bind(Key::Key3, RestoreBookmark(2), "restore a camera bookmark"),
+        bind(Key::Key4, RestoreBookmark(3), "restore a camera bookmark"),
+        bind(Key::Key5, RestoreBookmark(4), "restore a camera bookmark"),
+        bind(Key::Key6, RestoreBookmark(5), "restore a camera bookmark"),
+        bind(Key::Key7, RestoreBookmark(6), "restore a camera bookmark"),
+        bind(Key::Key8, RestoreBookmark(7), "restore a camera bookmark"),
+        bind(Key::Key9, RestoreBookmark(8), "restore a camera bookmark"),
+        with(Ctrl, Key::Key1, StoreBookmark(0), "store a camera bookmark"),
+        with(Ctrl, Key::Key2, StoreBookmark(1), "store a camera bookmark"),
+        with(Ctrl, Key::Key3, StoreBookmark(2), "store a camera bookmark"),
+        with(Ctrl, Key::Key4, StoreBookmark(3), "store a camera bookmark"),
+        with(Ctrl, Key::Key5, StoreBookmark(4), "store a camera bookmark"),
+        with(Ctrl, Key::Key6, StoreBookmark(5), "store a camera bookmark"),
+        with(Ctrl, Key::Key7, StoreBookmark(6), "store a camera bookmark"),
+        with(Ctrl, Key::Key8, StoreBookmark(7), "store a camera bookmark"),
+        with(Ctrl, Key::Key9, StoreBookmark(8), "store a camera bookmark"),
+        in_mode(Fly, Key::W, MoveForward, "fly forward"),
+        in_mode(Fly, Key::S, MoveBack, "fly back"),
+        in_mode(Fly, Key::A, MoveLeft, "fly left"),
+        in_mode(Fly, Key::D, MoveRight, "fly right"),
+        in_mode(Fly, Key::E, MoveUp, "fly up"),
+        in_mode(Fly, Key::Space, MoveUp, "fly up"),
+        in_mode(Fly, Key::Q, MoveDown, "fly down"),
+        in_mode(Fly, Key::LControl, MoveDown, "fly down"),
+        in_mode(Fly, Key::RControl, MoveDown, "fly down"),
+        in_mode(Fly, Key::LShift, MoveFaster, "fly faster"),
+        in_mode(Fly, Key::RShift, MoveFaster, "fly faster"),
+        bind(Key::R, ToggleCameraRecording, "start or stop recording a camera path"),
+        with(Shift, Key::P, ToggleCameraPlayback, "play or stop the camera path"),
+        with(Shift, Key::Comma, SlowerPlayback, "slow the camera path down"),
+        with(Shift, Key::Period, FasterPlayback, "speed the camera path up"),
+
+        in_mode(Orbit, Key::W, CycleWireframe, "cycle wireframe modes, in orbit mode"),
+        bind(Key::C, CycleCulling, "cycle face culling"),
+        bind(Key::N, ToggleNormals, "show or hide vertex normals"),
+        with(Shift, Key::L, ToggleLines, "show or hide the line elements of OBJ files"),
+        bind(Key::M, CycleRenderMode, "cycle render modes"),
+        bind(Key::V, CycleShadingModel, "cycle shading models"),
+        bind(Key::G, ToggleGrid, "show or hide the grid"),
+        bind(Key::K, ToggleShadows, "turn shadows on or off"),
+        bind(Key::F10, ToggleShadowMap, "show or hide the shadow map"),
+        bind(Key::J, ToggleReflections, "turn ground reflections on or off"),
+        bind(Key::U, LessEnvironmentReflectivity, "less sky reflection"),
+        bind(Key::I, MoreEnvironmentReflectivity, "more sky reflection"),
+        with(Shift, Key::U, SmootherMaterials, "smoother materials, in PBR shading"),
+        with(Shift, Key::I, RougherMaterials, "rougher materials, in PBR shading"),
+        bind(Key::F5, ToggleGrayscale, "toggle the grayscale effect"),
+        bind(Key::F6, ToggleInvert, "toggle the invert effect"),
+        bind(Key::F7, ToggleVignette, "toggle the vignette effect"),
+        bind(Key::F8, ToggleGammaSplit, "compare with and without gamma correction"),
+        bind(Key::F9, ToggleClipping, "show clipped highlights"),
+        bind(Key::T, CycleToneMap, "cycle tone mapping"),
+        bind(Key::B, ToggleBloom, "turn bloom on or off"),
+        bind(Key::Comma, LessBloom, "weaker bloom"),
+        bind(Key::Period, MoreBloom, "stronger bloom"),
+        bind(Key::Semicolon, SmallerBloom, "smaller bloom"),
+        bind(Key::Apostrophe, LargerBloom, "larger bloom"),
+        bind(Key::LBracket, LowerExposure, "lower the exposure"),
+        bind(Key::RBracket, RaiseExposure, "raise the exposure"),
+        with(Shift, Key::LBracket, LowerOpacity, "fade the selected object, or all of them"),
+        with(Shift, Key::RBracket, RaiseOpacity, "make the selected object, or all, more opaque"),
+        with(Shift, Key::F, CycleFog, "cycle fog modes"),
+        with(Shift, Key::Semicolon, ThinnerFog, "thinner fog"),
+        with(Shift, Key::Apostrophe, ThickerFog, "thicker fog"),
+        with(Shift, Key::K, ToggleAmbientOcclusion, "turn ambient occlusion on or off"),
+        with(Shift, Key::F10, ToggleOcclusionBuffer, "show or hide the raw ambient occlusion"),
+        with(Ctrl, Key::LBracket, SmallerOcclusion, "narrower ambient occlusion"),
+        with(Ctrl, Key::RBracket, LargerOcclusion, "wider ambient occlusion"),
+        with(Ctrl, Key::Semicolon, WeakerOcclusion, "weaker ambient occlusion"),
+        with(Ctrl, Key::Apostrophe, StrongerOcclusion, "stronger ambient occlusion"),
+        with(Ctrl, Key::K, ToggleBakedOcclusion, "compare with and without --bake-ao"),
+        with(Alt, Key::LBracket, SmallerPoints, "smaller points, for point clouds"),
+        with(Alt, Key::RBracket, LargerPoints, "larger points, for point clouds"),
+
+        bind(Key::Tab, SelectNext, "select the next object"),
+        bind(Key::L, AddLight, "add a point light at the camera"),
+        bind(Key::Insert, AddCopies, "add another row of copies"),
+        bind(Key::H, ToggleRipple, "ripple the models or stop them rippling"),
+        bind(Key::Y, FreezeLod, "freeze or unfreeze the level of detail"),
+        with(Shift, Key::Y, ToggleLodColors, "color the teapot by level of detail"),
+        in_mode(Orbit, Key::Space, PauseSpin, "pause or resume the spin, in orbit mode"),
+        bind(Key::Plus, SpinFaster, "spin faster"),
+        bind(Key::Equals, SpinFaster, "spin faster"),
+        bind(Key::NumpadAdd, SpinFaster, "spin faster"),
+        bind(Key::Minus, SpinSlower, "spin slower"),
+        bind(Key::NumpadSubtract, SpinSlower, "spin slower"),
+        bind(Key::Left, LightLeft, "swing the light left"),
+        bind(Key::Right, LightRight, "swing the light right"),
+        bind(Key::Up, LightUp, "raise the light"),
+        bind(Key::Down, LightDown, "lower the light"),
+        bind(Key::PageUp, LightBrighter, "brighten the light"),
+        bind(Key::PageDown, LightDimmer, "dim the light"),
+    ]
+};
+
+/// What the mouse does, listed after the keys in the help.
+pub const MOUSE_HELP: &[(&str, &str)] = &[
+    (
+        "left drag",
+        "orbit, or move the selected object by an arrow",
+    ),
+    ("left click", "select an object, or clear the selection"),
+    ("middle drag", "pan"),
+    ("right drag", "look around in fly mode"),
+    ("wheel", "zoom; with Ctrl, change the field of view"),
+];
+
+/// Every key a binding can name, for looking names up and listing them.
+#[rustfmt::skip]
+const KEYS: &[VirtualKeyCode] = {
+    use VirtualKeyCode::*;
+    &[
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+        F13, F14, F15, F16, F17, F18, F19, F20, F21, F22, F23, F24,
+        Escape, Tab, Space, Return, Back, Insert, Delete, Home, End, PageUp, PageDown,
+        Left, Right, Up, Down,
+        LShift, RShift, LControl, RControl, LAlt, RAlt, LWin, RWin,
+        Snapshot, Scroll, Pause, Capital, Numlock, Compose, Apps,
+        Numpad0, Numpad1, Numpad2, Numpad3, Numpad4,
+        Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+        NumpadAdd, NumpadSubtract, NumpadMultiply, NumpadDivide,
+        NumpadDecimal, NumpadComma, NumpadEnter, NumpadEquals,
+        Apostrophe, Asterisk, At, Backslash, Caret, Colon, Comma, Equals, Grave,
+        LBracket, RBracket, Minus, Period, Plus, Semicolon, Slash, Underline,
+        OEM102, AbntC1, AbntC2, Ax, Yen, Kana, Kanji, Convert, NoConvert,
+    ]
+};
+
+/// The keybindings in effect: the defaults, with the actions the config
+/// rebinds moved to the keys it names.
+pub struct Keymap {
+    bindings: Vec<Keybinding>,
+    /// Actions the config rebound. Their keys win over default bindings of
+    /// the same keys, so taking a key for one action needs no other change.
+    rebound: HashSet<Action>,
+}
+
+impl Keymap {
+    /// Applies the config's `[keys]` section to the defaults. Each named
+    /// action loses its default keys for the ones listed, keeping their
+    /// camera mode and description. Returns a warning for each name that
+    /// isn't an action or a key; an action none of whose keys are known
+    /// keeps its defaults.
+    pub fn new(overrides: &BTreeMap<String, KeyNames>) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let mut actions: Vec<Action> = Vec::new();
+        for binding in KEYBINDINGS {
+            if !actions.contains(&binding.action) {
+                actions.push(binding.action);
+            }
+        }
+        let mut keymap = Keymap {
+            bindings: KEYBINDINGS.to_vec(),
+            rebound: HashSet::new(),
+        };
+        for (name, keys) in overrides {
+            let Some(&action) = actions.iter().find(|action| action.name() == *name) else {
+                let names: Vec<String> = actions.iter().map(|action| action.name()).collect();
+                warnings.push(format!(
+                    "unknown action '{}' in [keys], expected one of {}",
+                    name,
+                    names.join(", ")
+                ));
+                continue;
+            };
+            let mut parsed = Vec::new();
+            for key in keys.names() {
+                match parse_key(key) {
+                    Some(key) => parsed.push(key),
+                    None => {
+                        let names: Vec<String> = KEYS.iter().map(|&key| key_name(key)).collect();
+                        warnings.push(format!(
+                            "unknown key '{}' for {} in [keys], expected one of {}, \
+                             optionally after Shift+, Ctrl+ or Alt+",
+                            key,
+                            name,
+                            names.join(", ")
+                        ));
+                    }
+                }
+            }
+            if parsed.is_empty() && !keys.names().is_empty() {
+                continue;
+            }
+            keymap.rebind(action, &parsed);
+        }
+        (keymap, warnings)
+    }
+
+    /// Replaces `action`'s bindings with `keys`, where its first one was.
+    fn rebind(&mut self, action: Action, keys: &[(Modifier, VirtualKeyCode)]) {
+        let index = self
+            .bindings
+            .iter()
+            .position(|binding| binding.action == action)
+            .unwrap();
+        let default = self.bindings[index];
+        self.bindings.retain(|binding| binding.action != action);
+        let replacements = keys.iter().map(|&(modifier, key)| Keybinding {
+            key,
+            modifier,
+            ..default
+        });
+        self.bindings.splice(index..index, replacements);
+        self.rebound.insert(action);
+    }
+
+    /// The action `key` does with `modifiers` held in camera `mode`. A
+    /// binding that needs one of the held modifiers wins over one that
+    /// needs none, then a rebound action over a default one.
+    pub fn action(
+        &self,
+        key: VirtualKeyCode,
+        modifiers: ModifiersState,
+        mode: CameraMode,
+    ) -> Option<Action> {
+        self.bindings
+            .iter()
+            .filter(|binding| {
+                binding.key == key
+                    && binding.mode.is_none_or(|m| m == mode)
+                    && binding.modifier.held(modifiers)
+            })
+            .min_by_key(|binding| {
+                (
+                    binding.modifier == Modifier::None,
+                    !self.rebound.contains(&binding.action),
+                )
+            })
+            .map(|binding| binding.action)
+    }
+
+    /// The help's lines as the keys and what they do, one line per run of
+    /// bindings sharing a description. Long runs of keys counting up, like
+    /// the bookmarks', are shown as their first and last key.
+    pub fn help_lines(&self) -> Vec<(String, &'static str)> {
+        let mut lines: Vec<(Vec<String>, &'static str)> = Vec::new();
+        for binding in &self.bindings {
+            let label = binding_label(binding);
+            match lines.last_mut() {
+                Some((labels, description)) if *description == binding.description => {
+                    labels.push(label);
+                }
+                _ => lines.push((vec![label], binding.description)),
+            }
+        }
+        lines
+            .into_iter()
+            .map(|(labels, description)| {
+                let keys = if labels.len() > 3 && counts_up(&labels) {
+                    format!("{}..{}", labels[0], labels[labels.len() - 1])
+                } else {
+                    labels.join(", ")
+                };
+                (keys, description)
+            })
+            .collect()
+    }
+}
+
+/// Whether the labels differ only in a last digit going up by one each.
+fn counts_up(labels: &[String]) -> bool {
+    labels.windows(2).all(|pair| {
+        let (previous, next) = (pair[0].as_bytes(), pair[1].as_bytes());
+        let split = previous.len().saturating_sub(1);
+        previous.len() == next.len()
+            && previous[..split] == next[..split]
+            && previous[split].is_ascii_digit()
+            && next[split] == previous[split] + 1
+    })
+}
+
+/// Reads a key name like `Z`, `LShift`, `1` or `Key1`, ignoring case, after
+/// at most one of `Shift+`, `Ctrl+` or `Alt+`.
+fn parse_key(name: &str) -> Option<(Modifier, VirtualKeyCode)> {
+    let (modifier, key) = match name.split_once('+') {
+        Some((modifier, key)) => {
+            let modifier = match modifier.to_ascii_lowercase().as_str() {
+                "shift" => Modifier::Shift,
+                "ctrl" => Modifier::Ctrl,
+                "alt" => Modifier::Alt,
+                _ => return None,
+            };
+            (modifier, key)
+        }
+        None => (Modifier::None, name),
+    };
+    let key = KEYS.iter().copied().find(|&candidate| {
+        key.eq_ignore_ascii_case(&key_name(candidate))
+            || key.eq_ignore_ascii_case(&format!("{:?}", candidate))
+    })?;
+    Some((modifier, key))
+}
+
+fn binding_label(binding: &Keybinding) -> String {
+    let modifier = match binding.modifier {
+        Modifier::None => "",
+        Modifier::Shift => "Shift+",
+        Modifier::Ctrl => "Ctrl+",
+        Modifier::Alt => "Alt+",
+    };
+    format!("{}{}", modifier, key_name(binding.key))
+}
+
+/// The key as glutin names it, without the `Key` in front of digits.
+pub fn key_name(key: VirtualKeyCode) -> String {
+    let name = format!("{:?}", key);
+    match name.strip_prefix("Key") {
+        Some(digit) if digit.len() == 1 => digit.to_string(),
+        _ => name,
+    }
+}
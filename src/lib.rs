@@ -0,0 +1,35 @@
+//! The viewer's loaders, renderer and scene, shared by the binary and the
+//! benchmarks.
+
+#[macro_use]
+extern crate glium;
+
+pub mod args;
+pub mod bookmarks;
+pub mod bvh;
+pub mod camera;
+pub mod camera_path;
+pub mod config;
+pub mod context;
+pub mod font_loader;
+pub mod frame_clock;
+pub mod frame_stats;
+pub mod frustum;
+pub mod fullscreen;
+pub mod gamepad;
+pub mod gizmo;
+pub mod keybindings;
+pub mod loading;
+pub mod math;
+pub mod model_loader;
+pub mod picking;
+pub mod primitives;
+pub mod recording;
+pub mod renderer;
+pub mod scene;
+pub mod screenshot;
+#[allow(dead_code)]
+pub mod teapot;
+pub mod terrain;
+pub mod texture_loader;
+pub mod watch;
@@ -0,0 +1,122 @@
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use glium::Surface;
+
+use crate::model_loader::{LoadProgress, ModelLoadError, Scene};
+
+/// The progress bar's width as a fraction of the window's, and its height
+/// as a fraction of the height.
+const BAR_WIDTH: f32 = 0.4;
+const BAR_HEIGHT: f32 = 0.015;
+const BAR_TRACK: (f32, f32, f32, f32) = (0.25, 0.25, 0.25, 1.0);
+const BAR_FILL: (f32, f32, f32, f32) = (0.85, 0.85, 0.85, 1.0);
+
+type Loaded = Result<Vec<Scene>, String>;
+
+/// Model files being read on their own thread, so the window opens straight
+/// away and keeps responding. Dropping it calls the load off.
+pub struct BackgroundLoad {
+    pub paths: Vec<String>,
+    /// Size of all the files together, for the progress bar.
+    total_bytes: u64,
+    progress: Arc<LoadProgress>,
+    receiver: Receiver<Loaded>,
+}
+
+impl BackgroundLoad {
+    /// Starts calling `load` on each of `paths` in turn. The first failure
+    /// ends the load.
+    pub fn start<F>(paths: Vec<String>, load: F) -> Self
+    where
+        F: Fn(&str, &LoadProgress) -> Result<Scene, ModelLoadError> + Send + 'static,
+    {
+        let total_bytes = paths
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        let progress = Arc::new(LoadProgress::default());
+        let (sender, receiver) = mpsc::channel();
+        let thread_paths = paths.clone();
+        let thread_progress = Arc::clone(&progress);
+        thread::spawn(move || {
+            let mut scenes = Vec::new();
+            for path in &thread_paths {
+                match load(path, &thread_progress) {
+                    Ok(scene) => scenes.push(scene),
+                    // Nobody is waiting for it any more.
+                    Err(_) if thread_progress.is_cancelled() => return,
+                    Err(error) => {
+                        let _ = sender.send(Err(format!("failed to load {}: {}", path, error)));
+                        return;
+                    }
+                }
+            }
+            let _ = sender.send(Ok(scenes));
+        });
+        BackgroundLoad {
+            paths,
+            total_bytes,
+            progress,
+            receiver,
+        }
+    }
+
+    /// The models, or why they couldn't be loaded, once the thread is done.
+    pub fn poll(&self) -> Option<Loaded> {
+        match self.receiver.try_recv() {
+            Ok(loaded) => Some(loaded),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(format!(
+                "failed to load {}: the loading thread stopped",
+                self.paths.join(", ")
+            ))),
+        }
+    }
+
+    /// How much of the files has been read, from 0 to 1.
+    pub fn fraction(&self) -> f32 {
+        match self.total_bytes {
+            0 => 0.0,
+            total => (self.progress.bytes_read() as f32 / total as f32).min(1.0),
+        }
+    }
+
+    /// Shown in the window title while the load runs.
+    pub fn label(&self) -> String {
+        format!(
+            "loading {} ({:.0}%)",
+            self.paths.join(", "),
+            self.fraction() * 100.0
+        )
+    }
+
+    /// Draws a bar across the middle of `frame`, filled as far as the load
+    /// has got.
+    pub fn draw_progress(&self, frame: &mut impl Surface) {
+        let (width, height) = frame.get_dimensions();
+        let bar_width = (width as f32 * BAR_WIDTH) as u32;
+        let track = glium::Rect {
+            left: (width - bar_width) / 2,
+            bottom: height / 2,
+            width: bar_width,
+            height: ((height as f32 * BAR_HEIGHT) as u32).max(2),
+        };
+        let fill = glium::Rect {
+            width: (bar_width as f32 * self.fraction()) as u32,
+            ..track
+        };
+        frame.clear(Some(&track), Some(BAR_TRACK), true, None, None);
+        if fill.width > 0 {
+            frame.clear(Some(&fill), Some(BAR_FILL), true, None, None);
+        }
+    }
+}
+
+impl Drop for BackgroundLoad {
+    fn drop(&mut self) {
+        self.progress.cancel();
+    }
+}
@@ -1,193 +1,1312 @@
 use glium::Surface;
-mod teapot;
-mod font_loader;
-mod model_loader;
+use keybindings::Action;
+use math::Vec3;
+use opengl_rust::{
+    args, bookmarks, camera, camera_path, config, context, font_loader, frame_clock, frame_stats,
+    fullscreen, gamepad, gizmo, keybindings, loading, math, model_loader, picking, primitives,
+    recording, renderer, scene, screenshot, terrain, texture_loader, watch,
+};
+use scene::Transform;
 
-#[macro_use]
-extern crate glium;
+/// Loads a model file, builds a `--primitive` shape or a `--terrain`, fixing
+/// its winding, subdividing it and regenerating its normals if requested,
+/// then welds duplicate vertices, reorders the triangles for the vertex
+/// cache and simplifies the big meshes into levels of detail. Bakes the
+/// ambient occlusion last, with `--bake-ao`. Prints each mesh's statistics.
+fn load_scene(
+    path: &str,
+    args: &args::Args,
+    progress: &model_loader::LoadProgress,
+) -> Result<model_loader::Scene, model_loader::ModelLoadError> {
+    // A terrain grid has no duplicates to weld, and its rows are already
+    // about as cache-friendly as a reordering would make them.
+    let terrain = terrain::is_heightmap(path);
+    let mut scene = match path.parse::<primitives::Primitive>() {
+        Ok(primitive) => primitive.scene(),
+        Err(_) if terrain => terrain::load(path, args.height_scale, progress)?,
+        // Primitives and terrain are made at the viewer's own scale already.
+        Err(_) => {
+            let baking = args.bake_ao.is_some();
+            let mut scene = model_loader::load_cached(path, progress, args.cache, baking)?;
+            if args.normalize {
+                if let Some(([x, y, z], factor)) = model_loader::fit_unit_box(&mut scene) {
+                    println!(
+                        "{}: moved by ({:.3}, {:.3}, {:.3}) and scaled by {} to fit a unit box \
+                         (--keep-units leaves it as it is)",
+                        path, -x, -y, -z, factor
+                    );
+                }
+            }
+            scene
+        }
+    };
+    let (mut before, mut welded) = (0, 0);
+    for mesh in &mut scene.meshes {
+        validate(mesh, args);
+        if args.flip_winding {
+            model_loader::flip_winding(&mut mesh.model);
+        }
+        if args.subdivide > 0 {
+            match model_loader::subdivide(&mut mesh.model, args.subdivide) {
+                Ok(()) => println!(
+                    "{}: {} triangles after subdividing",
+                    mesh.name,
+                    mesh.model.indexes.len() / 3
+                ),
+                Err(error) => eprintln!("warning: not subdividing {}: {}", mesh.name, error),
+            }
+        }
+        if let Some(mode) = args.normal_mode {
+            model_loader::generate_normals(&mut mesh.model, mode);
+        }
+        before += mesh.model.vertices.len();
+        if !terrain {
+            welded += model_loader::weld_vertices(&mut mesh.model, model_loader::WELD_EPSILON);
+        }
+        if args.optimize && !terrain {
+            model_loader::optimize_vertex_cache(&mut mesh.model);
+        }
+        if args.lod {
+            model_loader::build_lods(&mut mesh.model);
+        }
+    }
+    println!("{}: {} vertices, {} after welding", path, before, before - welded);
+    if let Some(settings) = args.bake_ao {
+        let report = |name: &str, percent: usize| {
+            println!("{}: baking ambient occlusion for {}, {}%", path, name, percent)
+        };
+        let cache = args.cache;
+        model_loader::bake_occlusion_cached(path, &mut scene, settings, cache, progress, report)?;
+    }
+    for mesh in &scene.meshes {
+        println!("    {}:", mesh.name);
+        for line in mesh.model.stats().to_string().lines() {
+            println!("        {}", line);
+        }
+        if !mesh.model.lods.is_empty() {
+            let triangles = mesh
+                .model
+                .lods
+                .iter()
+                .map(|lod| (lod.indexes.len() / 3).to_string())
+                .collect::<Vec<String>>();
+            println!("        levels of detail: {} triangles", triangles.join(", "));
+        }
+    }
+    Ok(scene)
+}
+
+/// Loads every model before going on, for the modes that exit once they've
+/// used them.
+fn load_all(args: &args::Args) -> Vec<model_loader::Scene> {
+    let progress = model_loader::LoadProgress::default();
+    let mut scenes: Vec<model_loader::Scene> = Vec::new();
+    for path in &args.paths {
+        match load_scene(path, args, &progress) {
+            Ok(scene) => scenes.push(scene),
+            Err(error) => {
+                eprintln!("failed to load {}: {}", path, error);
+                std::process::exit(1);
+            }
+        }
+    }
+    scenes
+}
 
-#[derive(Copy, Clone)]
-struct Vertex {
-    position: [f32; 2],
-    tex_coords: [f32; 2],
+/// Reports what's wrong with `mesh` for `--validate`, then makes the repairs
+/// asked for, saying how much each changed.
+fn validate(mesh: &mut model_loader::Mesh, args: &args::Args) {
+    let model = &mut mesh.model;
+    if args.validate {
+        println!("{}: {}", mesh.name, model_loader::validate(model));
+    }
+    // Degenerate triangles would get in the way of the winding, and what
+    // they leave unused the last repair strips.
+    if args.drop_degenerate {
+        let dropped = model_loader::drop_degenerate(model);
+        println!("{}: dropped {} degenerate triangles", mesh.name, dropped);
+    }
+    if args.fix_winding {
+        let turned = model_loader::fix_winding(model);
+        println!("{}: turned {} triangles to face like their neighbours", mesh.name, turned);
+        // Normals worked out from the old winding point the old way.
+        if turned > 0 && !model.has_normals {
+            model_loader::compute_normals(model);
+        }
+    }
+    if args.strip_unreferenced {
+        let removed = model_loader::strip_unreferenced(model);
+        println!("{}: removed {} unreferenced vertices", mesh.name, removed);
+    }
 }
 
-implement_vertex!(Vertex, position, tex_coords);
+/// Starts reading `paths` on a background thread.
+fn load_in_background(paths: Vec<String>, args: &args::Args) -> loading::BackgroundLoad {
+    let args = args.clone();
+    loading::BackgroundLoad::start(paths, move |path, progress| {
+        load_scene(path, &args, progress)
+    })
+}
+
+/// Space left between neighbouring models, as a fraction of the widest one.
+const MODEL_GAP: f32 = 0.1;
+
+/// Spin speed at startup in radians per second, and the factor `+`/`-`
+/// change it by.
+const SPIN_SPEED: f32 = 0.5;
+const SPIN_SPEED_STEP: f32 = 1.5;
+
+/// How far the arrow keys swing the light, in radians, and the factor
+/// PageUp/PageDown change its intensity by.
+const LIGHT_ROTATE_STEP: f32 = 0.1;
+const LIGHT_INTENSITY_STEP: f32 = 1.25;
+
+/// The factor `[` and `]` change the exposure by.
+const EXPOSURE_STEP: f32 = 1.25;
+
+/// The factor `,` and `.` change the bloom intensity by, and the pixels `;`
+/// and `'` change its radius by, up to a limit that keeps the blur cheap.
+const BLOOM_INTENSITY_STEP: f32 = 1.25;
+const BLOOM_RADIUS_STEP: u32 = 2;
+const MAX_BLOOM_RADIUS: u32 = 32;
+
+/// How much U and I change the sky's reflection on materials without their
+/// own reflectivity.
+const ENVIRONMENT_REFLECTIVITY_STEP: f32 = 0.05;
+
+/// How much Shift+U and Shift+I change the PBR shading's roughness.
+const ROUGHNESS_STEP: f32 = 0.05;
+
+/// How much Shift+[ and Shift+] change objects' opacity.
+const OPACITY_STEP: f32 = 0.1;
+
+/// What Shift+' multiplies the fog's density by and divides its distances
+/// by, and Shift+; undoes.
+const FOG_STEP: f32 = 1.25;
+
+/// What Ctrl+] multiplies the ambient occlusion's radius by, and Ctrl+[
+/// divides it by.
+const OCCLUSION_RADIUS_STEP: f32 = 1.25;
+
+/// How much Ctrl+; and Ctrl+' change the ambient occlusion's intensity.
+const OCCLUSION_INTENSITY_STEP: f32 = 0.25;
+
+/// What Alt+] multiplies the point size by, and Alt+[ divides it by.
+const POINT_SIZE_STEP: f32 = 1.25;
+
+/// How many pixels the cursor can move between pressing and releasing the
+/// left button for it to count as a click rather than a drag.
+const CLICK_SLOP: f64 = 3.0;
+
+/// Pixels between the window's top left corner and the overlay text.
+const OVERLAY_MARGIN: u32 = 8;
+/// sRGB.
+const OVERLAY_COLOR: [f32; 3] = [0.9, 0.9, 0.9];
 
-const VERTEX_SHADER_SRC: &str = r#"
-        #version 150
-        in vec3 position;
-        
-        in vec3 normal;
-        out vec3 v_normal;
-        out vec3 v_position;
-        
-        uniform mat4 perspective; 
-        uniform mat4 view;
-        uniform mat4 model;
+/// sRGB, dimming the view behind the help.
+const HELP_BACKDROP: [f32; 4] = [0.0, 0.0, 0.0, 0.75];
 
-        void main() {
-            mat4 modelview = view * model;
-            v_normal = transpose(inverse(mat3(modelview))) * normal;
-            gl_Position = perspective * modelview * vec4(position, 1.0);
-            v_position = gl_Position.xyz / gl_Position.w;
+/// Queues the keys and what they do over a darkened frame of `size`, in as
+/// many columns as it takes to fit.
+fn print_help(
+    overlay: &mut renderer::TextOverlay,
+    keymap: &keybindings::Keymap,
+    (width, height): (u32, u32),
+) {
+    overlay.fill(0, 0, width, height, HELP_BACKDROP);
+    let keys = keymap.help_lines();
+    let mouse = keybindings::MOUSE_HELP
+        .iter()
+        .map(|&(buttons, description)| (buttons.to_string(), description))
+        .collect::<Vec<(String, &str)>>();
+    let label_width = keys.iter().chain(&mouse).map(|(label, _)| label.len()).max().unwrap_or(0);
+    let entry = |(label, description): &(String, &str)| {
+        format!("{:<width$}  {}", label, description, width = label_width)
+    };
+    let mut lines = vec!["Keys (F1 or Escape closes this)".to_string(), String::new()];
+    lines.extend(keys.iter().map(entry));
+    lines.push(String::new());
+    lines.extend(mouse.iter().map(entry));
+
+    let longest = lines.iter().map(|line| line.len()).max().unwrap_or(0) as u32;
+    let column_width = longest * font_loader::GLYPH_WIDTH + 2 * OVERLAY_MARGIN;
+    let rows = (height.saturating_sub(2 * OVERLAY_MARGIN) / renderer::LINE_HEIGHT).max(1);
+    for (index, line) in lines.iter().enumerate() {
+        let (column, row) = (index as u32 / rows, index as u32 % rows);
+        let x = OVERLAY_MARGIN + column * column_width;
+        let y = OVERLAY_MARGIN + row * renderer::LINE_HEIGHT;
+        overlay.print(x, y, line, OVERLAY_COLOR);
+    }
+}
+
+/// Prints what a click landed on.
+fn print_pick(hit: Option<picking::Hit>) {
+    match hit {
+        Some(hit) => {
+            let [x, y, z] = hit.position.to_array();
+            let [a, b, c] = hit.barycentric;
+            println!(
+                "picked object {}, triangle {} (nearest vertex {}) at [{:.3}, {:.3}, {:.3}], \
+                 barycentric [{:.3}, {:.3}, {:.3}]",
+                hit.object, hit.triangle, hit.vertex, x, y, z, a, b, c
+            );
+        }
+        None => println!("nothing under the cursor"),
+    }
+}
+
+/// Rotation of the models about their Y axis, advanced by wall-clock time so
+/// the speed doesn't depend on the frame rate.
+struct Spin {
+    angle: f32,
+    speed: f32,
+    paused: bool,
+}
+
+impl Spin {
+    fn new() -> Self {
+        Spin {
+            angle: 0.0,
+            speed: SPIN_SPEED,
+            paused: false,
+        }
+    }
+
+    fn update(&mut self, dt: f32) {
+        if !self.paused {
+            self.angle = (self.angle + self.speed * dt) % std::f32::consts::TAU;
+        }
+    }
+
+    fn handle_action(&mut self, action: Action) {
+        match action {
+            Action::PauseSpin => self.paused = !self.paused,
+            Action::SpinFaster => self.speed *= SPIN_SPEED_STEP,
+            Action::SpinSlower => self.speed /= SPIN_SPEED_STEP,
+            _ => (),
         }
-"#;
+    }
+}
+
+/// Swings the light around the model or changes its intensity. Returns
+/// whether `action` was one that does.
+fn handle_light_action(light: &mut scene::Light, action: Action) -> bool {
+    match action {
+        Action::LightLeft => light.rotate(-LIGHT_ROTATE_STEP, 0.0),
+        Action::LightRight => light.rotate(LIGHT_ROTATE_STEP, 0.0),
+        Action::LightUp => light.rotate(0.0, LIGHT_ROTATE_STEP),
+        Action::LightDown => light.rotate(0.0, -LIGHT_ROTATE_STEP),
+        Action::LightBrighter => light.intensity *= LIGHT_INTENSITY_STEP,
+        Action::LightDimmer => light.intensity /= LIGHT_INTENSITY_STEP,
+        _ => return false,
+    }
+    true
+}
+
+/// Bounds of every mesh in a file together.
+fn scene_bounds(scene: &model_loader::Scene) -> model_loader::Aabb {
+    let mut meshes = scene.meshes.iter().map(|mesh| mesh.model.bounds);
+    let first = meshes.next().unwrap_or_default();
+    meshes.fold(first, |bounds, other| bounds.union(&other))
+}
+
+/// Returns the X position of each file's center so they sit next to each
+/// other without overlapping, with the whole row centered on the origin, and
+/// the width of the row including the gap after it.
+fn layout_along_x(bounds: &[model_loader::Aabb]) -> (Vec<f32>, f32) {
+    let widths = bounds
+        .iter()
+        .map(|bounds| bounds.max[0] - bounds.min[0])
+        .collect::<Vec<f32>>();
+    let gap = widths.iter().fold(0.0f32, |a, b| a.max(*b)) * MODEL_GAP;
+
+    let mut offsets: Vec<f32> = Vec::new();
+    let mut cursor = 0.0;
+    for width in &widths {
+        offsets.push(cursor + width / 2.0);
+        cursor += width + gap;
+    }
+    let center = (cursor - gap) / 2.0;
+    let offsets = offsets.iter().map(|offset| offset - center).collect();
+    (offsets, cursor)
+}
+
+/// Replaces the uploaded meshes and the scene with the loaded files laid out
+/// along X, each standing on the grid with its bounding box centered over
+/// the Z axis, returning the width of the row.
+fn populate_scene(
+    renderer: &mut renderer::Renderer,
+    scene: &mut scene::Scene,
+    loaded: Vec<model_loader::Scene>,
+) -> f32 {
+    renderer.clear_meshes();
+    renderer.selected = None;
+    scene.clear();
+    let bounds = loaded.iter().map(scene_bounds).collect::<Vec<model_loader::Aabb>>();
+    let (offsets, width) = layout_along_x(&bounds);
+    for ((file, bounds), offset) in loaded.into_iter().zip(bounds).zip(offsets) {
+        for mesh in file.meshes {
+            let mesh_id = renderer.add_mesh(mesh.model);
+            let transform = Transform {
+                origin: bounds.center().into(),
+                translation: Vec3::new(offset, (bounds.max[1] - bounds.min[1]) / 2.0, 0.0),
+                ..Default::default()
+            };
+            scene.add(mesh_id, transform);
+        }
+    }
+    width
+}
+
+/// Whether any loaded material has a roughness of its own, as glTF's do,
+/// so it's meant for the PBR shading.
+fn prefers_pbr(loaded: &[model_loader::Scene]) -> bool {
+    loaded
+        .iter()
+        .flat_map(|file| &file.meshes)
+        .flat_map(|mesh| &mesh.model.materials)
+        .any(|material| material.roughness.is_some())
+}
+
+/// Replaces the scene with `count` tinted copies of its objects in a square
+/// grid on the XZ plane, `spacing` apart, starting where the objects are and
+/// growing away from the camera.
+fn fill_stress_grid(scene: &mut scene::Scene, count: usize, spacing: f32) {
+    let template = std::mem::take(&mut scene.objects);
+    let side = (count as f32).sqrt().ceil() as usize;
+    let half_width = (side as f32 - 1.0) * spacing / 2.0;
+    for i in 0..count {
+        let column = (i % side) as f32 * spacing - half_width;
+        let row = (i / side) as f32 * spacing;
+        let hue = i as f32 * 0.7;
+        let tint = [
+            0.6 + 0.4 * hue.sin(),
+            0.6 + 0.4 * (hue + 2.1).sin(),
+            0.6 + 0.4 * (hue + 4.2).sin(),
+        ];
+        for object in &template {
+            let mut transform = object.transform;
+            transform.translation = transform.translation + Vec3::new(column, 0.0, row);
+            scene.add_tinted(object.mesh_id, transform, tint);
+        }
+    }
+}
 
-const FRAGMENT_SHADER_SRC: &str = r#"
-    #version 150
+/// Points the camera at everything in the scene from the framing view.
+fn frame_scene(camera: &mut camera::Camera, renderer: &renderer::Renderer, scene: &scene::Scene) {
+    let (center, radius) = renderer.bounding_sphere(scene);
+    camera.frame(center.into(), radius, renderer.aspect_ratio());
+}
 
-    in vec3 v_normal;
-    in vec3 v_position;
-    out vec4 color;
-    uniform vec3 u_light;
+/// Starts the camera where the config says, or framing the scene from the
+/// usual angle.
+fn place_camera(
+    camera: &mut camera::Camera,
+    renderer: &renderer::Renderer,
+    scene: &scene::Scene,
+    config: &config::CameraConfig,
+) {
+    if let Some(position) = config.position {
+        *camera = camera::Camera::looking_at(position, config.target.unwrap_or_default());
+    }
+    camera.set_fov(config.fov.to_radians());
+    if config.position.is_none() {
+        frame_scene(camera, renderer, scene);
+    }
+}
 
-    
-    const vec3 ambient_color = vec3(0.2, 0.0, 0.0);
-    const vec3 diffuse_color = vec3(0.6, 0.0, 0.0);
-    const vec3 specular_color = vec3(1.0, 1.0, 1.0);
+/// The directional light the config starts the scene with.
+fn configured_light(lighting: &config::LightingConfig) -> scene::Light {
+    scene::Light {
+        kind: scene::LightKind::Directional {
+            direction: lighting.direction.into(),
+        },
+        color: lighting.color,
+        intensity: lighting.intensity,
+    }
+}
 
+/// The settings as they are now, for F2 to save over the config file.
+fn current_config(
+    args: &args::Args,
+    window: &glium::glutin::window::Window,
+    camera: &camera::Camera,
+    scene: &scene::Scene,
+    renderer: &renderer::Renderer,
+) -> config::Config {
+    let size = window.inner_size();
+    let lighting = match scene.lights.first() {
+        Some(scene::Light {
+            kind: scene::LightKind::Directional { direction },
+            color,
+            intensity,
+        }) => config::LightingConfig {
+            direction: direction.to_array(),
+            color: *color,
+            intensity: *intensity,
+        },
+        _ => args.config.lighting.clone(),
+    };
+    config::Config {
+        window: config::WindowConfig {
+            width: size.width,
+            height: size.height,
+            title: args.title.clone(),
+            vsync: args.vsync,
+            msaa: args.msaa,
+        },
+        camera: config::CameraConfig {
+            position: Some(camera.eye()),
+            target: Some(camera.target),
+            fov: camera.fov.to_degrees(),
+        },
+        lighting,
+        rendering: config::RenderingConfig {
+            clear_color: renderer.background,
+            wireframe: !matches!(renderer.wireframe, renderer::WireframeMode::Shaded),
+            culling: renderer.cull_mode,
+            line_color: renderer.line_color,
+        },
+        fog: renderer.fog,
+        gamepad: args.config.gamepad.clone(),
+        keys: args.config.keys.clone(),
+    }
+}
 
-    void main() {
-        float diffuse = max(dot(normalize(v_normal), normalize(u_light)), 0.0);
+/// Renders the models once into a `--width` by `--height` offscreen target
+/// and saves it to `path`. Nothing depends on the clock: the models aren't
+/// spun and the camera and light are the startup ones, so the same
+/// arguments always give the same image.
+fn render_to_file(
+    facade: &impl glium::backend::Facade,
+    args: &args::Args,
+    loaded: Vec<model_loader::Scene>,
+    path: &str,
+) {
+    let mut renderer = renderer::Renderer::new(facade).unwrap();
+    let mut scene = scene::Scene::default();
+    scene.lights.push(configured_light(&args.config.lighting));
+    if prefers_pbr(&loaded) {
+        renderer.shading_model = renderer::ShadingModel::Pbr;
+    }
+    let row_width = populate_scene(&mut renderer, &mut scene, loaded);
+    if args.stress > 0 {
+        fill_stress_grid(&mut scene, args.stress, row_width);
+    }
+    if args.wireframe {
+        renderer.wireframe = renderer::WireframeMode::Wireframe;
+    }
+    renderer.toon_bands = args.toon_bands;
+    renderer.output.srgb = args.srgb;
+    renderer.reflectivity = args.reflectivity;
+    renderer.ripple_on_gpu = args.gpu_deform;
+    renderer.points_only = args.points;
+    renderer.background = args.config.rendering.clear_color;
+    renderer.line_color = args.config.rendering.line_color;
+    renderer.cull_mode = args.config.rendering.culling;
+    renderer.fog = args.config.fog;
+    if let Some(path) = &args.skybox {
+        load_skybox(facade, &mut renderer, path);
+    }
 
-        vec3 camera_dir = normalize(-v_position);
-        vec3 half_direction = normalize(normalize(u_light) + camera_dir);
-        float specular = pow(max(dot(half_direction, normalize(v_normal)), 0.0), 16.0);
+    let size = (args.width, args.height);
+    renderer.resize(size.into());
+    let mut camera = camera::Camera::default();
+    place_camera(&mut camera, &renderer, &scene, &args.config.camera);
+    let image = screenshot::render_offscreen(facade, size, |target| {
+        renderer.render(target, &scene, &camera);
+    });
+    screenshot::save(image, path.into()).join().unwrap();
+}
 
-        color = vec4(ambient_color + diffuse * diffuse_color + specular * specular_color, 1.0);
+/// Keeps the gradient sky if the images can't be loaded.
+fn load_skybox(
+    facade: &impl glium::backend::Facade,
+    renderer: &mut renderer::Renderer,
+    path: &str,
+) {
+    match texture_loader::load_cubemap(facade, std::path::Path::new(path)) {
+        Ok(cubemap) => renderer.set_skybox(cubemap),
+        Err(error) => eprintln!("failed to load skybox, using the gradient: {}", error),
     }
-"#;
+}
 
 fn main() {
-    let image = font_loader::load_font("");
-    println!("Starting window...");
+    let args = match args::Args::parse() {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("error: {}\n\n{}", error, args::USAGE);
+            std::process::exit(1);
+        }
+    };
+    if args.help {
+        println!("{}", args::USAGE);
+        return;
+    }
+    if let Some(threads) = args.threads {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads);
+        if let Err(error) = pool.build_global() {
+            eprintln!("warning: ignoring --threads: {}", error);
+        }
+    }
+
+    if args.stats_only {
+        load_all(&args);
+        return;
+    }
+
+    if args.list_objects {
+        let scenes = load_all(&args);
+        for (path, scene) in args.paths.iter().zip(&scenes) {
+            println!("{}:", path);
+            for mesh in &scene.meshes {
+                println!("    {}: {} triangles", mesh.name, mesh.model.indexes.len() / 3);
+            }
+        }
+        return;
+    }
+
+    if let Some(path) = &args.export {
+        let meshes = load_all(&args).into_iter().flat_map(|scene| scene.meshes);
+        let scene = model_loader::Scene {
+            meshes: meshes.collect(),
+        };
+        match model_loader::export_obj(&scene, std::path::Path::new(path)) {
+            Ok(()) => println!("saved {}", path),
+            Err(error) => {
+                eprintln!("failed to save {}: {}", path, error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
     use glium::glutin;
 
+    let display_server = context::check_display_server();
+    // With no display server there's no event loop for glutin to make a
+    // context with, but EGL can still make one to render offscreen.
+    if let (Err(error), Some(path)) = (&display_server, &args.output) {
+        match context::create_surfaceless(&args) {
+            Ok(surfaceless) => {
+                render_to_file(&surfaceless, &args, load_all(&args), path);
+                return;
+            }
+            Err(surfaceless) => {
+                eprintln!("error: {}, and no surfaceless context: {}", error, surfaceless);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Err(error) = display_server {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
     let event_loop = glutin::event_loop::EventLoop::new();
-    let window_builder = glutin::window::WindowBuilder::new();
-    let context_builder = glutin::ContextBuilder::new().with_depth_buffer(24);
-    let display = glium::Display::new(window_builder, context_builder, &event_loop)
-        .expect("Error creating window");
-
-    let model = model_loader::load_file("./teapot-3.obj");
-    let positions = glium::VertexBuffer::new(&display, &model.vertices).unwrap();
-    let normals = glium::VertexBuffer::new(&display, &model.normals).unwrap();
-    let indices = glium::IndexBuffer::new(
-        &display,
-        glium::index::PrimitiveType::TrianglesList,
-        &model.indexes,
-    )
-    .unwrap();
-    let program =
-        glium::Program::from_source(&display, VERTEX_SHADER_SRC, FRAGMENT_SHADER_SRC, None)
-            .unwrap();
-
-    let mut t: f32 = -0.5;
-    event_loop.run(move |ev, _, control_flow| {
-        let mut target = display.draw();
-        target.clear_color_and_depth((0.12, 0.12, 0.12, 1.0), 1.0);
-        let perspective = {
-            let (width, height) = target.get_dimensions();
-            let aspect_ratio = height as f32 / width as f32;
-
-            let fov: f32 = 3.141592 / 3.0;
-            let zfar = 1024.0;
-            let znear = 0.1;
-
-            let f = 1.0 / (fov / 2.0).tan();
-
-            [
-                [f * aspect_ratio, 0.0, 0.0, 0.0],
-                [0.0, f, 0.0, 0.0],
-                [0.0, 0.0, (zfar + znear) / (zfar - znear), 1.0],
-                [0.0, 0.0, -(2.0 * zfar * znear) / (zfar - znear), 0.0],
-            ]
-        };
-        let view = view_matrix(&[2.0, -1.0, 1.0], &[-2.0, 1.0, 1.0], &[0.0, 1.0, 0.0]);
-        let uniforms = uniform! {
-            model: [
-                [0.1, 0.0, 0.0, 0.0],
-                [0.0, 0.1, 0.0, 0.0],
-                [0.0, 0.0, 0.1, 0.0],
-                [0.0, 0.0, 2.0, 1.0f32],
-            ],
-            view: view,
-            u_light: [-1.0, 0.4, 0.9f32],
-            perspective: perspective,
-        };
-        let params = glium::DrawParameters {
-            depth: glium::Depth {
-                test: glium::draw_parameters::DepthTest::IfLess,
-                write: true,
-                ..Default::default()
-            },
-            ..Default::default()
-        };
-        target
-            .draw(
-                (&positions, &normals),
-                &indices,
-                &program,
-                &uniforms,
-                &params,
-            )
-            .unwrap();
-        target.finish().unwrap();
-
-        t += 0.0002;
-        if t > 0.5 {
-            t = -0.5;
-        }
-
-        let next_frame_time =
-            std::time::Instant::now() + std::time::Duration::from_nanos(16_666_667);
-        *control_flow = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
-        match ev {
-            glutin::event::Event::WindowEvent { event, .. } => match event {
-                glutin::event::WindowEvent::CloseRequested => {
-                    *control_flow = glutin::event_loop::ControlFlow::Exit;
-                    return;
-                }
-                _ => return,
-            },
-            _ => (),
+    if let Some(path) = &args.output {
+        match context::create_headless(&args, &event_loop) {
+            Ok(headless) => {
+                render_to_file(&headless, &args, load_all(&args), path);
+                return;
+            }
+            // Falls back to the hidden window --screenshot uses.
+            Err(error) => {
+                eprintln!("warning: no headless context, using a hidden window: {}", error)
+            }
         }
-    });
-}
+    }
+    let offscreen = args.output.as_ref().or(args.screenshot.as_ref());
 
-fn view_matrix(position: &[f32; 3], direction: &[f32; 3], up: &[f32; 3]) -> [[f32; 4]; 4] {
-    let f = {
-        let f = direction;
-        let len = f[0] * f[0] + f[1] * f[1] + f[2] * f[2];
-        let len = len.sqrt();
-        [f[0] / len, f[1] / len, f[2] / len]
+    println!("Starting window...");
+    let display = match context::create_display(&args, &event_loop, offscreen.is_none()) {
+        Ok(display) => display,
+        Err(error) => {
+            eprintln!("failed to create window: {}", error);
+            std::process::exit(1);
+        }
     };
+    println!("{}", context::describe(&display));
+    if let Err(error) = context::check_features(&display) {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+    let samples = display.gl_window().get_pixel_format().multisampling;
 
-    let s = [up[1] * f[2] - up[2] * f[1],
-             up[2] * f[0] - up[0] * f[2],
-             up[0] * f[1] - up[1] * f[0]];
+    if let Some(path) = offscreen {
+        render_to_file(&display, &args, load_all(&args), path);
+        return;
+    }
+
+    let mut renderer = renderer::Renderer::new(&display).unwrap();
+    renderer.resize(display.gl_window().window().inner_size());
+    renderer.set_samples(samples.unwrap_or(0) as u32);
+    let mut scene = scene::Scene::default();
+    scene.lights.push(configured_light(&args.config.lighting));
+    // The models arrive in the event loop, where the scene is filled in.
+    let mut loading = Some(load_in_background(args.paths.clone(), &args));
+    // Whether the first load has finished, successfully or not.
+    let mut started = false;
+    // Shown in the title instead of the frame statistics until a load works.
+    let mut load_error: Option<String> = None;
+    // With --watch, the files the models on screen came from, and whether
+    // the load running is a reload of them.
+    let mut watched = args.watch.then(|| watch::WatchedFiles::new(&args.paths));
+    let mut reloading = false;
+    let mut row_width = 0.0;
+    // How many copies of the loaded row Insert has made, counting the first.
+    let mut copies = 1;
+
+    let mut camera = camera::Camera::default();
+    if args.wireframe {
+        renderer.wireframe = renderer::WireframeMode::Wireframe;
+    }
+    renderer.toon_bands = args.toon_bands;
+    renderer.output.srgb = args.srgb;
+    renderer.reflectivity = args.reflectivity;
+    renderer.ripple_on_gpu = args.gpu_deform;
+    renderer.points_only = args.points;
+    renderer.background = args.config.rendering.clear_color;
+    renderer.line_color = args.config.rendering.line_color;
+    renderer.cull_mode = args.config.rendering.culling;
+    renderer.fog = args.config.fog;
+    if let Some(path) = &args.skybox {
+        load_skybox(&display, &mut renderer, path);
+    }
 
-    let s_norm = {
-        let len = s[0] * s[0] + s[1] * s[1] + s[2] * s[2];
-        let len = len.sqrt();
-        [s[0] / len, s[1] / len, s[2] / len]
+    let mut camera_controls = camera::CameraControls::default();
+    let mut gamepad = gamepad::Gamepad::new(args.config.gamepad.clone());
+    let (keymap, warnings) = keybindings::Keymap::new(&args.config.keys);
+    for warning in warnings {
+        eprintln!("warning: {}: {}", args.config_path.display(), warning);
+    }
+    let mut bookmarks = bookmarks::Bookmarks::load(std::path::Path::new(&args.paths[0]));
+    let mut camera_path = camera_path::CameraPath::load(std::path::Path::new(&args.paths[0]));
+    let mut spin = Spin::new();
+    // Starts once the models are in place.
+    let mut turntable: Option<camera_path::Turntable> = None;
+    // With vsync the buffer swap already waits for the display, so the clock
+    // doesn't throttle on top of it.
+    let mut clock =
+        frame_clock::FrameClock::new(if args.vsync { 0 } else { args.fps }, args.background_fps);
+    let sync_label = match (args.vsync, args.fps) {
+        (true, _) => "vsync".to_string(),
+        (false, 0) => "uncapped".to_string(),
+        (false, fps) => format!("{} fps cap", fps),
     };
+    let mut stats = frame_stats::FrameStats::new();
+    let mut overlay = renderer::TextOverlay::new(&display).unwrap();
+    // Statistics and view settings in the corner, toggled with O, and the
+    // last frame summary they show.
+    let mut show_overlay = true;
+    // The list of keys, toggled with F1.
+    let mut show_help = false;
+    let mut frame_summary = String::new();
+    let mut recorder = args.record.as_ref().map(|directory| {
+        let size = display.get_framebuffer_dimensions();
+        let directory = std::path::Path::new(directory);
+        match recording::FrameRecorder::new(&display, directory, args.frames, size) {
+            Ok(recorder) => recorder,
+            Err(error) => {
+                eprintln!("failed to start recording: {}", error);
+                std::process::exit(1);
+            }
+        }
+    });
+    let mut fullscreen = fullscreen::FullscreenToggle::default();
+    let mut modifiers = glutin::event::ModifiersState::empty();
+    // In physical pixels, while it's over the window, and where the left
+    // button went down.
+    let mut cursor: Option<(f64, f64)> = None;
+    let mut clicked_at: Option<(f64, f64)> = None;
+    let mut dragging: Option<gizmo::Drag> = None;
+    // Out of sight or out of focus, drawing drops to --background-fps.
+    let (mut focused, mut minimized, mut occluded) = (true, false, false);
+    let mut in_background = false;
+    event_loop.run(move |ev, _, control_flow| {
+        match ev {
+            glutin::event::Event::MainEventsCleared => {
+                // Watched files are edited in another window, and recordings
+                // and turntables run to the end whatever the window does.
+                let hidden = minimized || occluded || (!focused && !args.watch);
+                let background = hidden && recorder.is_none() && args.turntable.is_none();
+                if background != in_background {
+                    in_background = background;
+                    clock.set_background(background);
+                    if !background {
+                        stats.restart();
+                    }
+                }
+                if clock.frame_due() {
+                    display.gl_window().window().request_redraw();
+                }
+                *control_flow = clock.control_flow();
+            }
+            glutin::event::Event::RedrawRequested(_) => {
+                if let Some(files) = &mut watched {
+                    if loading.is_none() && files.changed() {
+                        println!("{} changed, reloading", files.paths().join(", "));
+                        loading = Some(load_in_background(files.paths().to_vec(), &args));
+                        // Until a load works there's no view to keep.
+                        reloading = !scene.objects.is_empty();
+                    }
+                }
+                if let Some(loaded) = loading.as_ref().and_then(|load| load.poll()) {
+                    let load = loading.take().unwrap();
+                    match loaded {
+                        Ok(loaded) => {
+                            if !reloading && prefers_pbr(&loaded) {
+                                renderer.shading_model = renderer::ShadingModel::Pbr;
+                            }
+                            row_width = populate_scene(&mut renderer, &mut scene, loaded);
+                            copies = 1;
+                            load_error = None;
+                            if reloading {
+                                // The camera stays put, to compare against the last version.
+                                if args.stress > 0 {
+                                    fill_stress_grid(&mut scene, args.stress, row_width);
+                                }
+                            } else if started {
+                                frame_scene(&mut camera, &renderer, &scene);
+                            } else {
+                                if args.stress > 0 {
+                                    fill_stress_grid(&mut scene, args.stress, row_width);
+                                    println!("stress test: {} instances", args.stress);
+                                }
+                                place_camera(&mut camera, &renderer, &scene, &args.config.camera);
+                                // The models hold still so the orbit shows each side once.
+                                turntable = args.turntable.map(|seconds| {
+                                    spin.paused = true;
+                                    camera_path::Turntable::new(&camera, seconds)
+                                });
+                            }
+                            println!("loaded {}", load.paths.join(", "));
+                            if !reloading {
+                                let path = std::path::Path::new(&load.paths[0]);
+                                bookmarks = bookmarks::Bookmarks::load(path);
+                                camera_path = camera_path::CameraPath::load(path);
+                            }
+                        }
+                        // The previous models, if any, stay on screen.
+                        Err(error) => {
+                            eprintln!("{}", error);
+                            display.gl_window().window().set_title(&format!(
+                                "{} - {}",
+                                args.title, error
+                            ));
+                            load_error = Some(error);
+                            // Recordings and turntables have nothing to show.
+                            if !started && (recorder.is_some() || args.turntable.is_some()) {
+                                *control_flow = glutin::event_loop::ControlFlow::Exit;
+                            }
+                        }
+                    }
+                    started = true;
+                    reloading = false;
+                }
 
-    let u = [f[1] * s_norm[2] - f[2] * s_norm[1],
-             f[2] * s_norm[0] - f[0] * s_norm[2],
-             f[0] * s_norm[1] - f[1] * s_norm[0]];
+                let dt = clock.tick();
+                // Recordings step by a fixed time so every frame is reproducible.
+                let dt = match recorder {
+                    Some(_) => recording::FRAME_TIME,
+                    None => dt,
+                };
+                renderer.reload_changed_shaders();
+                camera_controls.set_gamepad(gamepad.poll());
+                camera_controls.update(&mut camera, dt);
+                bookmarks.update(&mut camera, dt);
+                camera_path.update(&mut camera, dt);
+                if let Some(turntable) = &mut turntable {
+                    if !turntable.update(&mut camera, dt) {
+                        *control_flow = glutin::event_loop::ControlFlow::Exit;
+                    }
+                }
+                spin.update(dt);
+                renderer.model_rotation = spin.angle;
+                // Recordings show the models as they are, without the cursor.
+                renderer.hovered = match recorder {
+                    Some(_) => None,
+                    None => cursor.and_then(|cursor| renderer.pick(&scene, &camera, cursor)),
+                };
 
-    let p = [-position[0] * s_norm[0] - position[1] * s_norm[1] - position[2] * s_norm[2],
-             -position[0] * u[0] - position[1] * u[1] - position[2] * u[2],
-             -position[0] * f[0] - position[1] * f[1] - position[2] * f[2]];
+                let mut target = display.draw();
+                let mut drawn = renderer::RenderStats::default();
+                let recording = match &mut recorder {
+                    // Nothing is captured until the models are there.
+                    Some(_) if !started => true,
+                    Some(recorder) => {
+                        renderer.resize(recorder.size().into());
+                        recorder.capture(&display, &mut target, |offscreen| {
+                            drawn = renderer.render(offscreen, &scene, &camera);
+                        })
+                    }
+                    None if scene.objects.is_empty() => {
+                        let [r, g, b] = renderer.background;
+                        target.clear_color_srgb_and_depth((r, g, b, 1.0), 1.0);
+                        false
+                    }
+                    None => {
+                        drawn = renderer.render(&mut target, &scene, &camera);
+                        false
+                    }
+                };
+                // Like the hover highlight, kept out of recordings.
+                if show_overlay && recorder.is_none() && !scene.objects.is_empty() {
+                    let [x, y, z] = camera.eye();
+                    let text = format!(
+                        "{}\ncamera [{:.2}, {:.2}, {:.2}], {:.0} deg FOV\n\
+                         {:?} render mode, {}, {:?}",
+                        frame_summary,
+                        x,
+                        y,
+                        z,
+                        camera.fov.to_degrees(),
+                        renderer.render_mode,
+                        renderer.wireframe.label(),
+                        renderer.shading_model
+                    );
+                    overlay.print(OVERLAY_MARGIN, OVERLAY_MARGIN, &text, OVERLAY_COLOR);
+                }
+                if show_help && recorder.is_none() {
+                    print_help(&mut overlay, &keymap, target.get_dimensions());
+                }
+                overlay.draw(&display, &mut target);
+                if let Some(load) = &loading {
+                    load.draw_progress(&mut target);
+                    display.gl_window().window().set_title(&format!(
+                        "{} - {}",
+                        args.title,
+                        load.label()
+                    ));
+                }
+                target.finish().unwrap();
+                if recorder.is_some() && !recording {
+                    recorder = None;
+                    *control_flow = glutin::event_loop::ControlFlow::Exit;
+                }
 
-    [
-        [s_norm[0], u[0], f[0], 0.0],
-        [s_norm[1], u[1], f[1], 0.0],
-        [s_norm[2], u[2], f[2], 0.0],
-        [p[0], p[1], p[2], 1.0],
-    ]
+                if let Some(mut summary) = stats.frame(drawn.draw_calls, drawn.triangles) {
+                    summary += &format!(", {}/{} objects", drawn.objects_drawn, drawn.objects);
+                    if !drawn.objects_per_lod.is_empty() {
+                        let counts = drawn
+                            .objects_per_lod
+                            .iter()
+                            .map(|count| count.to_string())
+                            .collect::<Vec<String>>();
+                        summary += &format!(" (LOD {})", counts.join("/"));
+                    }
+                    if let Some(milliseconds) = drawn.bloom_gpu_time {
+                        summary += &format!(", bloom {:.2} ms GPU", milliseconds);
+                    }
+                    if let Some(milliseconds) = drawn.ripple_upload_time {
+                        summary += &format!(", ripple upload {:.2} ms", milliseconds);
+                    }
+                    if !args.quiet && loading.is_none() && load_error.is_none() {
+                        let title = format!(
+                            "{} - {} - {}, {}, {:.0}° FOV",
+                            args.title,
+                            summary,
+                            sync_label,
+                            renderer.wireframe.label(),
+                            camera.fov.to_degrees()
+                        );
+                        display.gl_window().window().set_title(&title);
+                    }
+                    frame_summary = summary;
+                }
+            }
+            glutin::event::Event::DeviceEvent { event, .. } => {
+                camera_controls.handle_device_event(&event, &mut camera);
+            }
+            glutin::event::Event::WindowEvent { event, .. } => {
+                // Pressing on one of the gizmo's arrows starts moving the
+                // selected object instead of the camera.
+                let mut grabbed = false;
+                if let glutin::event::WindowEvent::MouseInput {
+                    state: glutin::event::ElementState::Pressed,
+                    button: glutin::event::MouseButton::Left,
+                    ..
+                } = event
+                {
+                    dragging = cursor.and_then(|cursor| {
+                        let ray = renderer.ray(&camera, cursor)?;
+                        let gizmo = renderer.gizmo(&scene, &camera)?;
+                        gizmo::Drag::new(renderer.selected?, gizmo.hit(&ray)?, &gizmo, &ray)
+                    });
+                    grabbed = dragging.is_some();
+                }
+                if !grabbed {
+                    camera_controls.handle_event(&event, &mut camera, display.gl_window().window());
+                }
+                // Keys do what the keymap says, the movement ones for as
+                // long as they're held.
+                let pressed = match event {
+                    glutin::event::WindowEvent::KeyboardInput {
+                        input:
+                            glutin::event::KeyboardInput {
+                                virtual_keycode: Some(key),
+                                state,
+                                ..
+                            },
+                        ..
+                    } => match state {
+                        glutin::event::ElementState::Pressed => {
+                            keymap.action(key, modifiers, camera.mode)
+                                .map(|action| (key, action))
+                        }
+                        glutin::event::ElementState::Released => {
+                            camera_controls.release(key);
+                            None
+                        }
+                    },
+                    _ => None,
+                };
+                if let Some((key, action)) = pressed {
+                    let gl_window = display.gl_window();
+                    let window = gl_window.window();
+                    camera_controls.handle_action(action, &mut camera, window);
+                    match action {
+                        _ if action.is_held() => camera_controls.hold(key, action),
+                        Action::ToggleHelp => show_help = !show_help,
+                        Action::Release if show_help => show_help = false,
+                        Action::Release => fullscreen.leave(window),
+                        Action::ToggleFullscreen => fullscreen.toggle(window),
+                        Action::RestoreBookmark(slot) => {
+                            if !bookmarks.restore(slot, &camera) {
+                                println!("no bookmark {}, Ctrl+{0} saves one", slot + 1);
+                            }
+                        }
+                        Action::StoreBookmark(slot) => bookmarks.store(slot, &camera),
+                        Action::SaveSettings => {
+                            let settings =
+                                current_config(&args, window, &camera, &scene, &renderer);
+                            match settings.save(&args.config_path) {
+                                Ok(()) => println!("saved {}", args.config_path.display()),
+                                Err(error) => eprintln!("failed to save settings: {}", error),
+                            }
+                        }
+                        // The front buffer is the last frame shown, at the
+                        // framebuffer's real size on HiDPI screens too.
+                        Action::Screenshot => match display.read_front_buffer() {
+                            Ok(image) => {
+                                screenshot::save(image, screenshot::timestamped_path());
+                            }
+                            Err(error) => eprintln!("failed to take screenshot: {:?}", error),
+                        },
+                        Action::ToggleOverlay => show_overlay = !show_overlay,
+                        Action::CycleWireframe => {
+                            renderer.wireframe = renderer.wireframe.next();
+                            println!("{}", renderer.wireframe.label());
+                        }
+                        Action::CycleCulling => {
+                            renderer.cull_mode = renderer.cull_mode.next();
+                            println!("culling: {:?}", renderer.cull_mode);
+                        }
+                        Action::ToggleNormals => renderer.show_normals = !renderer.show_normals,
+                        Action::ToggleLines => {
+                            renderer.show_lines = !renderer.show_lines;
+                            println!("line elements: {}", renderer.show_lines);
+                        }
+                        Action::CycleRenderMode => {
+                            renderer.render_mode = renderer.render_mode.next();
+                            println!("render mode: {:?}", renderer.render_mode);
+                        }
+                        Action::CycleShadingModel => {
+                            renderer.shading_model = renderer.shading_model.next();
+                            println!("shading model: {:?}", renderer.shading_model);
+                        }
+                        Action::ToggleGrid => renderer.show_grid = !renderer.show_grid,
+                        Action::SelectNext if !scene.objects.is_empty() => {
+                            let next = renderer.selected.map_or(0, |selected| selected + 1)
+                                % scene.objects.len();
+                            renderer.selected = Some(next);
+                            println!("selected object {}", next);
+                        }
+                        Action::ToggleGrayscale | Action::ToggleInvert | Action::ToggleVignette => {
+                            let effect = match action {
+                                Action::ToggleGrayscale => renderer::Effect::Grayscale,
+                                Action::ToggleInvert => renderer::Effect::Invert,
+                                _ => renderer::Effect::Vignette,
+                            };
+                            renderer.output.toggle_effect(effect);
+                            println!("post effects: {}", renderer.output.effects_label());
+                        }
+                        Action::ToggleGammaSplit => {
+                            renderer.output.gamma_split = !renderer.output.gamma_split;
+                        }
+                        Action::ToggleShadows => renderer.shadows = !renderer.shadows,
+                        Action::ToggleReflections => renderer.reflections = !renderer.reflections,
+                        Action::ToggleShadowMap => {
+                            renderer.show_shadow_map = !renderer.show_shadow_map;
+                        }
+                        Action::ToggleClipping => {
+                            renderer.output.show_clipping = !renderer.output.show_clipping;
+                        }
+                        Action::CycleToneMap => {
+                            renderer.output.tone_map = renderer.output.tone_map.next();
+                            println!("tone mapping: {:?}", renderer.output.tone_map);
+                        }
+                        Action::ToggleBloom => {
+                            renderer.output.bloom = !renderer.output.bloom;
+                            println!("bloom: {}", renderer.output.bloom);
+                        }
+                        Action::ToggleCameraRecording => camera_path.toggle_recording(&camera),
+                        Action::ToggleCameraPlayback => camera_path.toggle_playback(),
+                        Action::SlowerPlayback => {
+                            camera_path.change_speed(1.0 / camera_path::SPEED_STEP);
+                        }
+                        Action::FasterPlayback => camera_path.change_speed(camera_path::SPEED_STEP),
+                        Action::LessBloom => {
+                            renderer.output.bloom_intensity /= BLOOM_INTENSITY_STEP;
+                            println!("bloom intensity: {:.2}", renderer.output.bloom_intensity);
+                        }
+                        Action::MoreBloom => {
+                            renderer.output.bloom_intensity *= BLOOM_INTENSITY_STEP;
+                            println!("bloom intensity: {:.2}", renderer.output.bloom_intensity);
+                        }
+                        Action::SmallerBloom => {
+                            let radius = &mut renderer.output.bloom_radius;
+                            *radius = radius.saturating_sub(BLOOM_RADIUS_STEP).max(1);
+                            println!("bloom radius: {}", radius);
+                        }
+                        Action::LargerBloom => {
+                            let radius = &mut renderer.output.bloom_radius;
+                            *radius = (*radius + BLOOM_RADIUS_STEP).min(MAX_BLOOM_RADIUS);
+                            println!("bloom radius: {}", radius);
+                        }
+                        Action::LessEnvironmentReflectivity
+                        | Action::MoreEnvironmentReflectivity => {
+                            let step = match action {
+                                Action::LessEnvironmentReflectivity => {
+                                    -ENVIRONMENT_REFLECTIVITY_STEP
+                                }
+                                _ => ENVIRONMENT_REFLECTIVITY_STEP,
+                            };
+                            let reflectivity = &mut renderer.environment_reflectivity;
+                            *reflectivity = (*reflectivity + step).clamp(0.0, 1.0);
+                            println!("environment reflectivity: {:.2}", reflectivity);
+                        }
+                        Action::SmootherMaterials | Action::RougherMaterials => {
+                            let step = match action {
+                                Action::SmootherMaterials => -ROUGHNESS_STEP,
+                                _ => ROUGHNESS_STEP,
+                            };
+                            let offset = &mut renderer.roughness_offset;
+                            *offset = (*offset + step).clamp(-1.0, 1.0);
+                            println!("roughness offset: {:+.2}", offset);
+                        }
+                        Action::CycleFog => {
+                            renderer.fog.mode = renderer.fog.mode.next();
+                            println!("fog: {:?}", renderer.fog.mode);
+                        }
+                        Action::ThinnerFog | Action::ThickerFog => {
+                            let factor = match action {
+                                Action::ThickerFog => FOG_STEP,
+                                _ => 1.0 / FOG_STEP,
+                            };
+                            let fog = &mut renderer.fog;
+                            fog.density *= factor;
+                            fog.start /= factor;
+                            fog.end /= factor;
+                            match fog.mode {
+                                renderer::FogMode::Linear => {
+                                    println!("fog from {:.1} to {:.1}", fog.start, fog.end)
+                                }
+                                _ => println!("fog density: {:.4}", fog.density),
+                            }
+                        }
+                        Action::ToggleAmbientOcclusion => {
+                            renderer.ambient_occlusion = !renderer.ambient_occlusion;
+                            println!("ambient occlusion: {}", renderer.ambient_occlusion);
+                        }
+                        Action::ToggleOcclusionBuffer => {
+                            renderer.show_ambient_occlusion = !renderer.show_ambient_occlusion;
+                            if !renderer.ambient_occlusion {
+                                println!("turn ambient occlusion on with Shift+K to see it");
+                            }
+                        }
+                        Action::SmallerPoints | Action::LargerPoints => {
+                            let factor = match action {
+                                Action::LargerPoints => POINT_SIZE_STEP,
+                                _ => 1.0 / POINT_SIZE_STEP,
+                            };
+                            renderer.point_size = (renderer.point_size * factor).clamp(0.01, 100.0);
+                            println!("point size: {:.2}", renderer.point_size);
+                        }
+                        Action::ToggleBakedOcclusion => {
+                            renderer.baked_occlusion = !renderer.baked_occlusion;
+                            println!("baked ambient occlusion: {}", renderer.baked_occlusion);
+                            if args.bake_ao.is_none() {
+                                println!("start with --bake-ao <rays> to bake some");
+                            }
+                        }
+                        Action::SmallerOcclusion | Action::LargerOcclusion => {
+                            let factor = match action {
+                                Action::LargerOcclusion => OCCLUSION_RADIUS_STEP,
+                                _ => 1.0 / OCCLUSION_RADIUS_STEP,
+                            };
+                            renderer.ao_radius = (renderer.ao_radius * factor).clamp(0.01, 100.0);
+                            println!("ambient occlusion radius: {:.3}", renderer.ao_radius);
+                        }
+                        Action::WeakerOcclusion | Action::StrongerOcclusion => {
+                            let step = match action {
+                                Action::StrongerOcclusion => OCCLUSION_INTENSITY_STEP,
+                                _ => -OCCLUSION_INTENSITY_STEP,
+                            };
+                            let intensity = &mut renderer.ao_intensity;
+                            *intensity = (*intensity + step).clamp(0.0, 8.0);
+                            println!("ambient occlusion intensity: {:.2}", intensity);
+                        }
+                        Action::LowerOpacity | Action::RaiseOpacity => {
+                            let step = match action {
+                                Action::LowerOpacity => -OPACITY_STEP,
+                                _ => OPACITY_STEP,
+                            };
+                            let objects = match renderer.selected {
+                                Some(index) => {
+                                    scene.objects.get_mut(index..=index).unwrap_or_default()
+                                }
+                                None => &mut scene.objects[..],
+                            };
+                            for object in objects.iter_mut() {
+                                // Kept on the steps, so stepping back up reaches
+                                // exactly opaque.
+                                let steps = ((object.opacity + step) / OPACITY_STEP).round();
+                                object.opacity = (steps * OPACITY_STEP).clamp(0.0, 1.0);
+                            }
+                            if let Some(object) = objects.first() {
+                                println!("opacity: {:.0}%", object.opacity * 100.0);
+                            }
+                        }
+                        Action::LowerExposure => {
+                            renderer.output.exposure /= EXPOSURE_STEP;
+                            println!("exposure: {:.2}", renderer.output.exposure);
+                        }
+                        Action::RaiseExposure => {
+                            renderer.output.exposure *= EXPOSURE_STEP;
+                            println!("exposure: {:.2}", renderer.output.exposure);
+                        }
+                        Action::AddLight if scene.lights.len() < renderer::MAX_LIGHTS => {
+                            // Half as bright at the model as at the camera.
+                            let attenuation = 1.0 / (camera.distance * camera.distance);
+                            let light = scene::Light::point(camera.eye().into(), attenuation);
+                            println!("added {}", light);
+                            scene.lights.push(light);
+                        }
+                        Action::AddLight => {
+                            println!("at most {} lights are supported", renderer::MAX_LIGHTS);
+                        }
+                        Action::FrameScene => frame_scene(&mut camera, &renderer, &scene),
+                        Action::AddCopies => {
+                            let originals = scene.objects.len() / copies;
+                            let shift = row_width * copies as f32;
+                            for i in 0..originals {
+                                let mut copy = scene.objects[i];
+                                copy.transform.translation.x += shift;
+                                scene.objects.push(copy);
+                            }
+                            copies += 1;
+                        }
+                        Action::ToggleRipple => renderer.ripple = !renderer.ripple,
+                        Action::FreezeLod => {
+                            renderer.freeze_lod = !renderer.freeze_lod;
+                            match renderer.freeze_lod {
+                                true => println!("level of detail frozen"),
+                                false => println!("level of detail follows the camera"),
+                            }
+                        }
+                        Action::ToggleLodColors => {
+                            renderer.show_tessellation_levels = !renderer.show_tessellation_levels
+                        }
+                        _ => {
+                            spin.handle_action(action);
+                            // The keys adjust the first light.
+                            if let Some(light) = scene.lights.first_mut() {
+                                if handle_light_action(light, action) {
+                                    println!("{}", light);
+                                }
+                            }
+                        }
+                    }
+                    // Don't wait for the resize event after switching,
+                    // or the next frame is drawn with the old aspect ratio.
+                    renderer.resize(window.inner_size());
+                }
+                match event {
+                    glutin::event::WindowEvent::ModifiersChanged(state) => modifiers = state,
+                    glutin::event::WindowEvent::CursorMoved { position, .. } => {
+                        cursor = Some((position.x, position.y));
+                        let moved = dragging.and_then(|drag| {
+                            let ray = renderer.ray(&camera, (position.x, position.y))?;
+                            Some((drag.object, drag.translation(&ray, modifiers.shift())?))
+                        });
+                        if let Some((index, translation)) = moved {
+                            if let Some(object) = scene.objects.get_mut(index) {
+                                object.transform.translation = translation;
+                            }
+                        }
+                    }
+                    glutin::event::WindowEvent::CursorLeft { .. } => cursor = None,
+                    glutin::event::WindowEvent::MouseInput {
+                        state,
+                        button: glutin::event::MouseButton::Left,
+                        ..
+                    } => match state {
+                        glutin::event::ElementState::Pressed => clicked_at = cursor,
+                        glutin::event::ElementState::Released => {
+                            if let Some(drag) = dragging.take() {
+                                clicked_at = None;
+                                if let Some(object) = scene.objects.get(drag.object) {
+                                    let [x, y, z] = object.transform.translation.to_array();
+                                    println!(
+                                        "moved object {} to [{:.3}, {:.3}, {:.3}]",
+                                        drag.object, x, y, z
+                                    );
+                                }
+                            } else if let (Some((x, y)), Some(start)) = (cursor, clicked_at.take())
+                            {
+                                if (x - start.0).hypot(y - start.1) <= CLICK_SLOP {
+                                    // Clicking empty space clears the selection.
+                                    let hit = renderer.pick(&scene, &camera, (x, y));
+                                    renderer.selected = hit.map(|hit| hit.object);
+                                    print_pick(hit);
+                                }
+                            }
+                        }
+                    },
+                    glutin::event::WindowEvent::Focused(gained) => focused = gained,
+                    glutin::event::WindowEvent::Occluded(hidden) => occluded = hidden,
+                    glutin::event::WindowEvent::Resized(size) => {
+                        // Some platforms only show minimizing as a resize to nothing.
+                        minimized = size.width == 0 || size.height == 0;
+                        display.gl_window().resize(size);
+                        renderer.resize(size);
+                        display.gl_window().window().request_redraw();
+                    }
+                    glutin::event::WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        display.gl_window().resize(*new_inner_size);
+                        renderer.resize(*new_inner_size);
+                    }
+                    glutin::event::WindowEvent::CloseRequested => {
+                        *control_flow = glutin::event_loop::ControlFlow::Exit;
+                    }
+                    glutin::event::WindowEvent::DroppedFile(path) => {
+                        let path = path.to_string_lossy().to_string();
+                        if let Err(error) = args::check_model_path(&path) {
+                            println!("ignoring dropped file: {}", error);
+                            return;
+                        }
+                        // Replacing a load in progress calls it off.
+                        let paths = vec![path];
+                        if args.watch {
+                            watched = Some(watch::WatchedFiles::new(&paths));
+                        }
+                        loading = Some(load_in_background(paths, &args));
+                        reloading = false;
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+    });
 }
-
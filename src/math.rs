@@ -0,0 +1,559 @@
+use std::ops::{Add, Mul, Neg, Sub};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vec3 {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    pub fn dot(self, other: Vec3) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    /// Returns the zero vector unchanged instead of dividing by zero.
+    pub fn normalize(self) -> Vec3 {
+        let length = self.length();
+        if length <= f32::EPSILON {
+            return self;
+        }
+        self * (1.0 / length)
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        [self.x, self.y, self.z]
+    }
+}
+
+impl From<[f32; 3]> for Vec3 {
+    fn from(v: [f32; 3]) -> Self {
+        Vec3::new(v[0], v[1], v[2])
+    }
+}
+
+impl From<Vec3> for [f32; 3] {
+    fn from(v: Vec3) -> Self {
+        v.to_array()
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+
+    fn add(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, other: Vec3) -> Vec3 {
+        Vec3::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl Mul<f32> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, scale: f32) -> Vec3 {
+        Vec3::new(self.x * scale, self.y * scale, self.z * scale)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Vec3 {
+        Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+/// A 3x3 matrix stored column by column, like `Mat4`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat3 {
+    pub columns: [[f32; 3]; 3],
+}
+
+impl Mat3 {
+    pub fn transpose(&self) -> Mat3 {
+        Mat3 {
+            columns: [0, 1, 2].map(|c| [0, 1, 2].map(|r| self.columns[r][c])),
+        }
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let [a, b, c] = self.columns.map(Vec3::from);
+        a.dot(b.cross(c))
+    }
+
+    /// Inverse from the cross products of the columns, `None` for singular
+    /// matrices.
+    pub fn inverse(&self) -> Option<Mat3> {
+        let determinant = self.determinant();
+        if determinant.abs() <= f32::EPSILON {
+            return None;
+        }
+        // The rows of the inverse are the cross products of pairs of
+        // columns over the determinant.
+        let [a, b, c] = self.columns.map(Vec3::from);
+        let rows =
+            [b.cross(c), c.cross(a), a.cross(b)].map(|row| (row * (1.0 / determinant)).into());
+        Some(Mat3 { columns: rows }.transpose())
+    }
+
+    /// The square of the scale if this is a rotation, possibly mirrored,
+    /// scaled the same along every axis: columns of equal length at right
+    /// angles to each other.
+    fn uniform_scale_squared(&self) -> Option<f32> {
+        const TOLERANCE: f32 = 1e-5;
+        let [a, b, c] = self.columns.map(Vec3::from);
+        let squared = a.dot(a);
+        let tolerance = squared * TOLERANCE;
+        let uniform = squared > 0.0
+            && (b.dot(b) - squared).abs() <= tolerance
+            && (c.dot(c) - squared).abs() <= tolerance
+            && [a.dot(b), b.dot(c), c.dot(a)]
+                .iter()
+                .all(|dot| dot.abs() <= tolerance);
+        uniform.then_some(squared)
+    }
+
+    pub fn to_array(self) -> [[f32; 3]; 3] {
+        self.columns
+    }
+}
+
+/// A 4x4 matrix stored column by column, the layout glium's `uniform!`
+/// expects for `mat4`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub columns: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        columns: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn translation(offset: Vec3) -> Mat4 {
+        let mut m = Mat4::IDENTITY;
+        m.columns[3] = [offset.x, offset.y, offset.z, 1.0];
+        m
+    }
+
+    pub fn scale(scale: Vec3) -> Mat4 {
+        let mut m = Mat4::IDENTITY;
+        m.columns[0][0] = scale.x;
+        m.columns[1][1] = scale.y;
+        m.columns[2][2] = scale.z;
+        m
+    }
+
+    pub fn uniform_scale(scale: f32) -> Mat4 {
+        Mat4::scale(Vec3::new(scale, scale, scale))
+    }
+
+    pub fn rotation_x(angle: f32) -> Mat4 {
+        let (s, c) = angle.sin_cos();
+        Mat4 {
+            columns: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, c, s, 0.0],
+                [0.0, -s, c, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn rotation_y(angle: f32) -> Mat4 {
+        let (s, c) = angle.sin_cos();
+        Mat4 {
+            columns: [
+                [c, 0.0, -s, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [s, 0.0, c, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn rotation_z(angle: f32) -> Mat4 {
+        let (s, c) = angle.sin_cos();
+        Mat4 {
+            columns: [
+                [c, s, 0.0, 0.0],
+                [-s, c, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Perspective projection looking down +Z, with `aspect` being
+    /// width / height.
+    pub fn perspective(fov: f32, aspect: f32, znear: f32, zfar: f32) -> Mat4 {
+        let f = 1.0 / (fov / 2.0).tan();
+        Mat4 {
+            columns: [
+                [f / aspect, 0.0, 0.0, 0.0],
+                [0.0, f, 0.0, 0.0],
+                [0.0, 0.0, (zfar + znear) / (zfar - znear), 1.0],
+                [0.0, 0.0, -(2.0 * zfar * znear) / (zfar - znear), 0.0],
+            ],
+        }
+    }
+
+    /// Orthographic projection looking down +Z, showing `left` to `right`
+    /// and `bottom` to `top`. Depth maps to -1 to 1 like `perspective`.
+    pub fn orthographic(
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        znear: f32,
+        zfar: f32,
+    ) -> Mat4 {
+        Mat4 {
+            columns: [
+                [2.0 / (right - left), 0.0, 0.0, 0.0],
+                [0.0, 2.0 / (top - bottom), 0.0, 0.0],
+                [0.0, 0.0, 2.0 / (zfar - znear), 0.0],
+                [
+                    -(right + left) / (right - left),
+                    -(top + bottom) / (top - bottom),
+                    -(zfar + znear) / (zfar - znear),
+                    1.0,
+                ],
+            ],
+        }
+    }
+
+    /// View matrix for an eye at `eye` looking towards `target`.
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Mat4 {
+        let f = (target - eye).normalize();
+        let s = up.cross(f).normalize();
+        let u = f.cross(s);
+        Mat4 {
+            columns: [
+                [s.x, u.x, f.x, 0.0],
+                [s.y, u.y, f.y, 0.0],
+                [s.z, u.z, f.z, 0.0],
+                [-eye.dot(s), -eye.dot(u), -eye.dot(f), 1.0],
+            ],
+        }
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = Mat4::IDENTITY;
+        for (c, column) in self.columns.iter().enumerate() {
+            for (r, value) in column.iter().enumerate() {
+                m.columns[r][c] = *value;
+            }
+        }
+        m
+    }
+
+    /// Inverse by cofactor expansion, `None` for singular matrices.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let m = &self.columns;
+        // Element at row r, column c.
+        let a = |r: usize, c: usize| m[c][r];
+
+        let s0 = a(0, 0) * a(1, 1) - a(1, 0) * a(0, 1);
+        let s1 = a(0, 0) * a(1, 2) - a(1, 0) * a(0, 2);
+        let s2 = a(0, 0) * a(1, 3) - a(1, 0) * a(0, 3);
+        let s3 = a(0, 1) * a(1, 2) - a(1, 1) * a(0, 2);
+        let s4 = a(0, 1) * a(1, 3) - a(1, 1) * a(0, 3);
+        let s5 = a(0, 2) * a(1, 3) - a(1, 2) * a(0, 3);
+        let c5 = a(2, 2) * a(3, 3) - a(3, 2) * a(2, 3);
+        let c4 = a(2, 1) * a(3, 3) - a(3, 1) * a(2, 3);
+        let c3 = a(2, 1) * a(3, 2) - a(3, 1) * a(2, 2);
+        let c2 = a(2, 0) * a(3, 3) - a(3, 0) * a(2, 3);
+        let c1 = a(2, 0) * a(3, 2) - a(3, 0) * a(2, 2);
+        let c0 = a(2, 0) * a(3, 1) - a(3, 0) * a(2, 1);
+
+        let determinant = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if determinant.abs() <= f32::EPSILON {
+            return None;
+        }
+        let d = 1.0 / determinant;
+
+        let rows = [
+            [
+                (a(1, 1) * c5 - a(1, 2) * c4 + a(1, 3) * c3) * d,
+                (-a(0, 1) * c5 + a(0, 2) * c4 - a(0, 3) * c3) * d,
+                (a(3, 1) * s5 - a(3, 2) * s4 + a(3, 3) * s3) * d,
+                (-a(2, 1) * s5 + a(2, 2) * s4 - a(2, 3) * s3) * d,
+            ],
+            [
+                (-a(1, 0) * c5 + a(1, 2) * c2 - a(1, 3) * c1) * d,
+                (a(0, 0) * c5 - a(0, 2) * c2 + a(0, 3) * c1) * d,
+                (-a(3, 0) * s5 + a(3, 2) * s2 - a(3, 3) * s1) * d,
+                (a(2, 0) * s5 - a(2, 2) * s2 + a(2, 3) * s1) * d,
+            ],
+            [
+                (a(1, 0) * c4 - a(1, 1) * c2 + a(1, 3) * c0) * d,
+                (-a(0, 0) * c4 + a(0, 1) * c2 - a(0, 3) * c0) * d,
+                (a(3, 0) * s4 - a(3, 1) * s2 + a(3, 3) * s0) * d,
+                (-a(2, 0) * s4 + a(2, 1) * s2 - a(2, 3) * s0) * d,
+            ],
+            [
+                (-a(1, 0) * c3 + a(1, 1) * c1 - a(1, 2) * c0) * d,
+                (a(0, 0) * c3 - a(0, 1) * c1 + a(0, 2) * c0) * d,
+                (-a(3, 0) * s3 + a(3, 1) * s1 - a(3, 2) * s0) * d,
+                (a(2, 0) * s3 - a(2, 1) * s1 + a(2, 2) * s0) * d,
+            ],
+        ];
+        // `rows` holds the inverse row by row; store it column by column.
+        Some(Mat4 { columns: rows }.transpose())
+    }
+
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let m = &self.columns;
+        let w = m[0][3] * p.x + m[1][3] * p.y + m[2][3] * p.z + m[3][3];
+        let w = if w.abs() <= f32::EPSILON { 1.0 } else { w };
+        Vec3::new(
+            (m[0][0] * p.x + m[1][0] * p.y + m[2][0] * p.z + m[3][0]) / w,
+            (m[0][1] * p.x + m[1][1] * p.y + m[2][1] * p.z + m[3][1]) / w,
+            (m[0][2] * p.x + m[1][2] * p.y + m[2][2] * p.z + m[3][2]) / w,
+        )
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.columns;
+        Vec3::new(
+            m[0][0] * v.x + m[1][0] * v.y + m[2][0] * v.z,
+            m[0][1] * v.x + m[1][1] * v.y + m[2][1] * v.z,
+            m[0][2] * v.x + m[1][2] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    pub fn to_array(self) -> [[f32; 4]; 4] {
+        self.columns
+    }
+
+    /// The upper-left 3x3, i.e. the rotation and scale without translation.
+    pub fn to_mat3_array(self) -> [[f32; 3]; 3] {
+        [0, 1, 2].map(|c| [0, 1, 2].map(|r| self.columns[c][r]))
+    }
+
+    /// Transforms normals the way this transforms points: the inverse
+    /// transpose of the upper-left 3x3, which keeps them perpendicular to
+    /// surfaces under non-uniform scale. Singular matrices flatten
+    /// everything, so their normals are left as they are.
+    pub fn normal_matrix(&self) -> Mat3 {
+        let upper = Mat3 {
+            columns: self.to_mat3_array(),
+        };
+        // Most models are only moved, turned and scaled evenly. The inverse
+        // transpose of a rotation scaled by `s` is the rotation over `s`, so
+        // nothing needs inverting, however small `s` is.
+        if let Some(squared) = upper.uniform_scale_squared() {
+            return Mat3 {
+                columns: upper
+                    .columns
+                    .map(|column| column.map(|value| value / squared)),
+            };
+        }
+        match upper.inverse() {
+            Some(inverse) => inverse.transpose(),
+            None => Mat3 {
+                columns: Mat4::IDENTITY.to_mat3_array(),
+            },
+        }
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut m = [[0.0f32; 4]; 4];
+        for (c, column) in m.iter_mut().enumerate() {
+            for (r, value) in column.iter_mut().enumerate() {
+                *value = (0..4)
+                    .map(|k| self.columns[k][r] * other.columns[c][k])
+                    .sum();
+            }
+        }
+        Mat4 { columns: m }
+    }
+}
+
+impl From<Mat4> for [[f32; 4]; 4] {
+    fn from(m: Mat4) -> Self {
+        m.columns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Mat4, b: Mat4) {
+        for (column_a, column_b) in a.columns.iter().zip(&b.columns) {
+            for (x, y) in column_a.iter().zip(column_b) {
+                assert!((x - y).abs() < 1e-5, "{:?}\n!=\n{:?}", a, b);
+            }
+        }
+    }
+
+    /// The free function `main` built its view matrix with before this
+    /// module, from the eye position and the direction it looks in.
+    fn view_matrix(position: &[f32; 3], direction: &[f32; 3], up: &[f32; 3]) -> [[f32; 4]; 4] {
+        let f = {
+            let f = direction;
+            let len = f[0] * f[0] + f[1] * f[1] + f[2] * f[2];
+            let len = len.sqrt();
+            [f[0] / len, f[1] / len, f[2] / len]
+        };
+        let s = [
+            up[1] * f[2] - up[2] * f[1],
+            up[2] * f[0] - up[0] * f[2],
+            up[0] * f[1] - up[1] * f[0],
+        ];
+        let s_norm = {
+            let len = s[0] * s[0] + s[1] * s[1] + s[2] * s[2];
+            let len = len.sqrt();
+            [s[0] / len, s[1] / len, s[2] / len]
+        };
+        let u = [
+            f[1] * s_norm[2] - f[2] * s_norm[1],
+            f[2] * s_norm[0] - f[0] * s_norm[2],
+            f[0] * s_norm[1] - f[1] * s_norm[0],
+        ];
+        let p = [
+            -position[0] * s_norm[0] - position[1] * s_norm[1] - position[2] * s_norm[2],
+            -position[0] * u[0] - position[1] * u[1] - position[2] * u[2],
+            -position[0] * f[0] - position[1] * f[1] - position[2] * f[2],
+        ];
+        [
+            [s_norm[0], u[0], f[0], 0.0],
+            [s_norm[1], u[1], f[1], 0.0],
+            [s_norm[2], u[2], f[2], 0.0],
+            [p[0], p[1], p[2], 1.0],
+        ]
+    }
+
+    #[test]
+    fn look_at_matches_view_matrix() {
+        let views = [
+            ([2.0, -1.0, 1.0], [-2.0, 1.0, 1.0], [0.0, 1.0, 0.0]),
+            ([0.0, 0.0, -3.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+            ([5.0, 4.0, -2.5], [-1.0, -0.8, 0.3], [0.0, 0.0, 1.0]),
+        ];
+        for (eye, direction, up) in views {
+            let target = Vec3::from(eye) + Vec3::from(direction);
+            let view = Mat4::look_at(eye.into(), target, up.into());
+            let expected = Mat4 {
+                columns: view_matrix(&eye, &direction, &up),
+            };
+            assert_close(view, expected);
+        }
+    }
+
+    #[test]
+    fn matrix_times_inverse_is_identity() {
+        let matrices = [
+            Mat4::translation(Vec3::new(1.0, -2.0, 3.5))
+                * Mat4::rotation_y(0.7)
+                * Mat4::rotation_x(-1.2)
+                * Mat4::scale(Vec3::new(2.0, 0.5, 3.0)),
+            Mat4::perspective(1.0, 1.5, 0.1, 100.0),
+            Mat4::look_at(
+                Vec3::new(3.0, 2.0, 1.0),
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            ),
+        ];
+        for m in matrices {
+            let inverse = m.inverse().unwrap();
+            assert_close(m * inverse, Mat4::IDENTITY);
+            assert_close(inverse * m, Mat4::IDENTITY);
+        }
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        assert!(Mat4::scale(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    /// `matrix` times `v`, with the columns as `Mat3` stores them.
+    fn times(matrix: Mat3, v: Vec3) -> Vec3 {
+        let [a, b, c] = matrix.columns.map(Vec3::from);
+        a * v.x + b * v.y + c * v.z
+    }
+
+    #[test]
+    fn normal_matrix_is_the_inverse_transpose() {
+        let models = [
+            Mat4::scale(Vec3::new(2.0, 0.5, 3.0)),
+            Mat4::translation(Vec3::new(1.0, -2.0, 3.5))
+                * Mat4::rotation_y(0.7)
+                * Mat4::rotation_x(-1.2)
+                * Mat4::scale(Vec3::new(2.0, 0.5, 3.0)),
+            Mat4::rotation_z(0.3) * Mat4::scale(Vec3::new(-1.0, 4.0, 0.25)),
+            // Scaled evenly, which skips the inverse.
+            Mat4::translation(Vec3::new(0.0, 5.0, 0.0))
+                * Mat4::rotation_x(0.4)
+                * Mat4::scale(Vec3::new(3.0, 3.0, 3.0)),
+        ];
+        // A surface along (1, -1, 0) and (0, 1, -1), and its normal.
+        let tangents = [Vec3::new(1.0, -1.0, 0.0), Vec3::new(0.0, 1.0, -1.0)];
+        let normal = Vec3::new(1.0, 1.0, 1.0);
+        for model in models {
+            // The upper-left 3x3 of the 4x4 inverse transpose, the long way
+            // round.
+            let inverse = model.inverse().unwrap().transpose().columns;
+            let expected = Mat3 {
+                columns: [0, 1, 2].map(|c| [0, 1, 2].map(|r| inverse[c][r])),
+            };
+            let normal_matrix = model.normal_matrix();
+            for (a, b) in normal_matrix.columns.iter().zip(&expected.columns) {
+                for (x, y) in a.iter().zip(b) {
+                    assert!(
+                        (x - y).abs() < 1e-5,
+                        "{:?}\n!=\n{:?}",
+                        normal_matrix,
+                        expected
+                    );
+                }
+            }
+            let normal = times(normal_matrix, normal).normalize();
+            for tangent in tangents {
+                let tangent = model.transform_vector(tangent).normalize();
+                assert!(tangent.dot(normal).abs() < 1e-5, "{:?}", model);
+            }
+        }
+        // Transforming normals like points would tilt them off the surface.
+        let model = models[0];
+        let upper = Mat3 {
+            columns: model.to_mat3_array(),
+        };
+        let tangent = model.transform_vector(tangents[0]).normalize();
+        assert!(tangent.dot(times(upper, normal).normalize()).abs() > 0.1);
+    }
+}
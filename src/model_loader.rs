@@ -1,136 +1,2155 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
-use std::io::{self, BufRead};
+use std::hash::{BuildHasherDefault, Hasher};
+use std::io::{self, BufRead, Read};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
-#[derive(Debug, Clone, Copy)]
-pub struct Vertex {
-    position: (f32, f32, f32),
+use rayon::prelude::*;
+
+mod cache;
+mod export;
+mod gltf;
+mod occlusion;
+mod optimize;
+mod ply;
+mod scan;
+mod simplify;
+mod stats;
+mod stl;
+mod subdivide;
+mod tangents;
+mod validate;
+
+pub use cache::CacheMode;
+pub use export::export_obj;
+pub use gltf::load_gltf;
+pub use occlusion::{bake_occlusion, BakeSettings, Occlusion};
+pub use optimize::{optimize_vertex_cache, weld_vertices, WELD_EPSILON};
+pub use ply::load_ply;
+pub use simplify::{build_lods, Lod};
+pub use stats::Aabb;
+pub use stl::load_stl;
+pub use subdivide::subdivide;
+pub use tangents::{compute_tangents, Tangent};
+pub use validate::{drop_degenerate, fix_winding, strip_unreferenced, validate};
+
+/// Position, normal and texture coordinates interleaved in one buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vertex3D {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub tex_coords: [f32; 2],
 }
 
-implement_vertex!(Vertex, position);
+implement_vertex!(Vertex3D, position, normal, tex_coords);
 
 #[derive(Copy, Clone)]
-pub struct Normal {
-    normal: (f32, f32, f32),
+pub struct Color {
+    pub color: (f32, f32, f32),
+}
+
+implement_vertex!(Color, color);
+
+impl Color {
+    pub const WHITE: Color = Color {
+        color: (1.0, 1.0, 1.0),
+    };
+}
+
+#[derive(Debug, Clone)]
+pub struct Material {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub shininess: f32,
+    /// Texture from `map_Kd`, already resolved against the OBJ's directory.
+    pub diffuse_map: Option<PathBuf>,
+    /// Tangent-space normal map from `norm`, `map_Bump` or `bump`, resolved
+    /// the same way.
+    pub normal_map: Option<PathBuf>,
+    /// How much the surroundings are mirrored, from 0 to 1, read from the
+    /// `Pm` (metallic) extension. `None` uses the viewer's adjustable value.
+    /// The PBR shading takes it as the metalness, with `None` as 0.
+    pub reflectivity: Option<f32>,
+    /// How rough the surface is from 0 to 1 for the PBR shading, from the
+    /// `Pr` extension or glTF's roughness. `None` works it out from
+    /// `shininess`, and means the material wasn't made for PBR.
+    pub roughness: Option<f32>,
+    /// Roughness in green and metalness in blue, scaling `roughness` and
+    /// `reflectivity`, from glTF's metallic-roughness texture.
+    pub metallic_roughness_map: Option<PathBuf>,
+    /// From 0 for invisible to 1 for opaque, read from `d` or `Tr`, its
+    /// complement. Anything less is drawn blended after the opaque faces.
+    pub opacity: f32,
+}
+
+impl Material {
+    /// `roughness`, or the roughness whose GGX highlight is about as wide
+    /// as a Blinn-Phong highlight with this `shininess`.
+    pub fn pbr_roughness(&self) -> f32 {
+        match self.roughness {
+            Some(roughness) => roughness,
+            None => (2.0 / (self.shininess.max(0.0) + 2.0)).powf(0.25),
+        }
+    }
 }
 
-implement_vertex!(Normal, normal);
+impl Default for Material {
+    /// The red material the viewer has always drawn models with.
+    fn default() -> Self {
+        Material {
+            name: "default".to_string(),
+            ambient: [0.2, 0.0, 0.0],
+            diffuse: [0.6, 0.0, 0.0],
+            specular: [1.0, 1.0, 1.0],
+            shininess: 16.0,
+            diffuse_map: None,
+            normal_map: None,
+            reflectivity: None,
+            roughness: None,
+            metallic_roughness_map: None,
+            opacity: 1.0,
+        }
+    }
+}
+
+/// A run of triangles in `Model::indexes` drawn with a single material.
+#[derive(Debug, Clone)]
+pub struct SubMesh {
+    pub material: usize,
+    pub range: Range<usize>,
+}
 
 pub struct Model {
-    pub vertices: Vec<Vertex>,
-    pub normals: Vec<Normal>,
-    pub indexes: Vec<u16>,
-}
-
-pub fn load_file(path: &str) -> Model {
-    let mut vertices: Vec<Vertex> = Vec::new();
-    let mut normals: Vec<Normal> = Vec::new();
-    let mut vertex_indexes: Vec<u16> = Vec::new();
-    let mut normal_indexes: Vec<u16> = Vec::new();
-    // This still does not read UVs from files
-    let mut uv_indexes: Vec<u16> = Vec::new();
-
-    let file = File::open(path).unwrap();
-    let lines = io::BufReader::new(file).lines();
-    for line in lines {
-        match line {
-            Ok(line) => {
-                let split = line.split_whitespace().collect::<Vec<&str>>();
-                match split.first() {
-                    Some(&"v") => vertices.push(Vertex {
-                        position: (
-                            split.get(1).unwrap().parse().unwrap(),
-                            split.get(2).unwrap().parse().unwrap(),
-                            split.get(3).unwrap().parse().unwrap(),
-                        ),
-                    }),
-                    Some(&"vn") => normals.push(Normal {
-                        normal: (
-                            split.get(1).unwrap().parse().unwrap(),
-                            split.get(2).unwrap().parse().unwrap(),
-                            split.get(3).unwrap().parse().unwrap(),
-                        ),
-                    }),
-                    Some(&"f") => {
-                        let index_1 = split.get(1).unwrap().split('/').collect::<Vec<&str>>();
-                        let index_2 = split.get(2).unwrap().split('/').collect::<Vec<&str>>();
-                        let index_3 = split.get(3).unwrap().split('/').collect::<Vec<&str>>();
-
-                        if let Some(index) = index_1.get(0) {
-                            if let Ok(number) = index.parse::<u16>() {
-                                vertex_indexes.push(number);
-                            }
-                        }
+    pub vertices: Vec<Vertex3D>,
+    /// Per-vertex colors, empty when the file has none.
+    pub colors: Vec<Color>,
+    pub indexes: Vec<u32>,
+    /// Line segments over the same vertices, two indexes each, from OBJ
+    /// `l` statements. Empty for everything else.
+    pub lines: Vec<u32>,
+    pub materials: Vec<Material>,
+    pub sub_meshes: Vec<SubMesh>,
+    /// Bounds of the vertex positions as loaded.
+    pub bounds: Aabb,
+    /// Whether the file supplied normals, rather than them being computed.
+    pub has_normals: bool,
+    pub has_tex_coords: bool,
+    /// Bicubic Bézier patches the triangles were evaluated from, sixteen
+    /// control points each in rows of four, for tessellating on the GPU.
+    /// Empty for everything but the teapot.
+    pub patches: Vec<[[f32; 3]; 16]>,
+    /// Coarser versions of the mesh over the same vertices, finest first,
+    /// for drawing it when it's small on the screen. Empty unless
+    /// `build_lods` found it worth simplifying.
+    pub lods: Vec<Lod>,
+    /// How open each vertex is to the ambient light, from 0 to 1. Empty
+    /// unless `bake_occlusion` was run on the model as it is now.
+    pub occlusion: Vec<f32>,
+}
 
-                        if let Some(index) = index_2.get(0) {
-                            if let Ok(number) = index.parse::<u16>() {
-                                vertex_indexes.push(number);
-                            }
-                        }
+/// The most vertices a mesh can have, as many as a `u32` index reaches.
+pub const MAX_VERTICES: usize = u32::MAX as usize + 1;
 
-                        if let Some(index) = index_3.get(0) {
-                            if let Ok(number) = index.parse::<u16>() {
-                                vertex_indexes.push(number);
-                            }
-                        }
+/// A model's indexes in the type they're uploaded as.
+pub enum GpuIndexes<'a> {
+    U16(Vec<u16>),
+    U32(&'a [u32]),
+}
 
-                        if let Some(index) = index_1.get(1) {
-                            if let Ok(number) = index.parse::<u16>() {
-                                uv_indexes.push(number);
-                            }
-                        }
-                        if let Some(index) = index_2.get(1) {
-                            if let Ok(number) = index.parse::<u16>() {
-                                uv_indexes.push(number);
-                            }
-                        }
-                        if let Some(index) = index_3.get(1) {
-                            if let Ok(number) = index.parse::<u16>() {
-                                uv_indexes.push(number);
-                            }
-                        }
+impl Model {
+    /// `indexes` into the model's vertices, its own or a level of detail's,
+    /// narrowed to `u16` when every vertex fits, which halves the index
+    /// buffer for all but the biggest meshes.
+    pub fn gpu_indexes<'a>(&self, indexes: &'a [u32]) -> GpuIndexes<'a> {
+        match self.vertices.len() <= u16::MAX as usize + 1 {
+            true => GpuIndexes::U16(indexes.iter().map(|&index| index as u16).collect()),
+            false => GpuIndexes::U32(indexes),
+        }
+    }
 
+    /// Whether the model is vertices alone, with no faces or lines between
+    /// them, which the renderer draws as points.
+    pub fn is_point_cloud(&self) -> bool {
+        self.indexes.is_empty() && self.lines.is_empty() && !self.vertices.is_empty()
+    }
 
-                        if let Some(index) = index_1.get(2) {
-                            if let Ok(number) = index.parse::<u16>() {
-                                normal_indexes.push(number);
-                            }
-                        }
-                        if let Some(index) = index_2.get(2) {
-                            if let Ok(number) = index.parse::<u16>() {
-                                normal_indexes.push(number);
-                            }
-                        }
-                        if let Some(index) = index_3.get(2) {
-                            if let Ok(number) = index.parse::<u16>() {
-                                normal_indexes.push(number);
-                            }
-                        }
+    /// Fails if any face or line refers to a vertex past the ones that were
+    /// read, which the renderer would otherwise draw from out of bounds.
+    fn check_indexes(&self) -> Result<(), ModelLoadError> {
+        match self
+            .indexes
+            .iter()
+            .chain(&self.lines)
+            .find(|&&index| index as usize >= self.vertices.len())
+        {
+            Some(index) => Err(ModelLoadError::InvalidData(format!(
+                "face index {} out of range (mesh has {} vertices)",
+                index,
+                self.vertices.len()
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ModelLoadError {
+    Io(io::Error),
+    InvalidFloat {
+        line: usize,
+        value: String,
+    },
+    InvalidIndex {
+        line: usize,
+        value: String,
+    },
+    IndexOutOfRange {
+        line: usize,
+        index: i64,
+        count: usize,
+        kind: &'static str,
+    },
+    MalformedStatement {
+        line: usize,
+        statement: String,
+    },
+    UnsupportedStatement {
+        line: usize,
+        statement: String,
+    },
+    /// More than `MAX_VERTICES`.
+    TooManyVertices {
+        count: usize,
+    },
+    UnsupportedFormat {
+        extension: String,
+    },
+    InvalidData(String),
+}
+
+impl fmt::Display for ModelLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ModelLoadError::Io(error) => write!(f, "{}", error),
+            ModelLoadError::InvalidFloat { line, value } => {
+                write!(f, "line {}: could not parse '{}' as a number", line, value)
+            }
+            ModelLoadError::InvalidIndex { line, value } => {
+                write!(
+                    f,
+                    "line {}: could not parse '{}' as a face index",
+                    line, value
+                )
+            }
+            ModelLoadError::IndexOutOfRange {
+                line,
+                index,
+                count,
+                kind,
+            } => write!(
+                f,
+                "line {}: face index {} out of range (mesh has {} {})",
+                line, index, count, kind
+            ),
+            ModelLoadError::MalformedStatement { line, statement } => {
+                write!(f, "line {}: malformed '{}' statement", line, statement)
+            }
+            ModelLoadError::UnsupportedStatement { line, statement } => {
+                write!(f, "line {}: unsupported statement '{}'", line, statement)
+            }
+            ModelLoadError::TooManyVertices { count } => {
+                write!(
+                    f,
+                    "mesh has {} vertices, at most {} are supported",
+                    count, MAX_VERTICES
+                )
+            }
+            ModelLoadError::UnsupportedFormat { extension } => {
+                write!(f, "unsupported model format '{}'", extension)
+            }
+            ModelLoadError::InvalidData(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ModelLoadError {}
+
+impl From<io::Error> for ModelLoadError {
+    fn from(error: io::Error) -> Self {
+        ModelLoadError::Io(error)
+    }
+}
+
+/// A face corner as written in the file, kept with its line number so index
+/// errors can still point at the offending face once the whole file is read.
+#[derive(Clone, Copy)]
+struct Corner {
+    line: usize,
+    vertex: i64,
+    texcoord: Option<i64>,
+    normal: Option<i64>,
+}
+
+/// Hashes face corners for `build_model`, which spent most of its time in the
+/// default SipHash. The keys are small integers from a file the user chose,
+/// so there's nothing to protect against.
+#[derive(Default)]
+struct CornerHasher(u64);
+
+impl CornerHasher {
+    fn add(&mut self, value: u64) {
+        self.0 = (self.0.rotate_left(5) ^ value).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+impl Hasher for CornerHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.add(u64::from(byte));
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.add(value);
+    }
+
+    fn write_i64(&mut self, value: i64) {
+        self.add(value as u64);
+    }
+
+    fn write_isize(&mut self, value: isize) {
+        self.add(value as u64);
+    }
+}
+
+type CornerHashing = BuildHasherDefault<CornerHasher>;
+
+/// A named object or group from the file, with its own geometry.
+pub struct Mesh {
+    pub name: String,
+    pub model: Model,
+}
+
+pub struct Scene {
+    pub meshes: Vec<Mesh>,
+}
+
+impl Scene {
+    pub fn single(name: &str, model: Model) -> Self {
+        Scene {
+            meshes: vec![Mesh {
+                name: name.to_string(),
+                model,
+            }],
+        }
+    }
+}
+
+/// Faces collected for one `o`/`g` section while the file is being read.
+struct Group {
+    name: String,
+    corners: Vec<Corner>,
+    /// Two corners to each segment of the group's polylines.
+    line_corners: Vec<Corner>,
+    materials: Vec<Material>,
+    face_materials: Vec<usize>,
+    /// Slot of the active `usemtl` material in this group's list, filled in
+    /// lazily by the first face that uses it.
+    material_slot: Option<usize>,
+}
+
+impl Group {
+    fn new(name: &str) -> Self {
+        Group {
+            name: name.to_string(),
+            corners: Vec::new(),
+            line_corners: Vec::new(),
+            materials: Vec::new(),
+            face_materials: Vec::new(),
+            material_slot: None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.corners.is_empty() && self.line_corners.is_empty()
+    }
+}
+
+pub const SUPPORTED_EXTENSIONS: [&str; 5] = ["obj", "stl", "ply", "gltf", "glb"];
+
+/// Bytes read at a time by the formats that parse a whole file at once, so
+/// their progress still moves.
+const READ_CHUNK: usize = 1 << 20;
+
+/// How far through its model files a load is, shared with whoever waits for
+/// it. They can also call the load off, which fails its next read.
+#[derive(Debug, Default)]
+pub struct LoadProgress {
+    read: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl LoadProgress {
+    /// Bytes of model files read so far. Material libraries and external
+    /// buffers aren't counted.
+    pub fn bytes_read(&self) -> u64 {
+        self.read.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn advance(&self, bytes: usize) -> io::Result<()> {
+        if self.is_cancelled() {
+            return Err(io::Error::other("load cancelled"));
+        }
+        self.read.fetch_add(bytes as u64, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Like `fs::read`, counting the bytes as they come in.
+    pub fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let mut file = File::open(path)?;
+        let mut chunk = vec![0; READ_CHUNK];
+        loop {
+            match file.read(&mut chunk)? {
+                0 => return Ok(bytes),
+                count => {
+                    self.advance(count)?;
+                    bytes.extend_from_slice(&chunk[..count]);
+                }
+            }
+        }
+    }
+}
+
+/// Loads any supported model format, picked by file extension, reporting
+/// the bytes read to `progress`.
+pub fn load_file(path: &str, progress: &LoadProgress) -> Result<Scene, ModelLoadError> {
+    let extension = Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let scene = match extension.as_str() {
+        "obj" => load_obj(path, progress)?,
+        "stl" => Scene::single("default", load_stl(path, progress)?),
+        "ply" => Scene::single("default", load_ply(path, progress)?),
+        "gltf" | "glb" => Scene::single("default", load_gltf(path, progress)?),
+        _ => return Err(ModelLoadError::UnsupportedFormat { extension }),
+    };
+    for mesh in &scene.meshes {
+        mesh.model.check_indexes()?;
+    }
+    Ok(scene)
+}
+
+/// Files smaller than this parse about as fast as their cache would load,
+/// so they don't get one unless it's asked for.
+const MIN_CACHED_SIZE: u64 = 1 << 20;
+
+/// Like `load_file`, but skips parsing when `path` has an up-to-date
+/// `.meshcache` next to it, and writes one after parsing a big file, or any
+/// file if `baking`, so `bake_occlusion_cached` has somewhere to keep what
+/// it bakes. A cache that can't be read is parsed over and replaced.
+pub fn load_cached(
+    path: &str,
+    progress: &LoadProgress,
+    mode: CacheMode,
+    baking: bool,
+) -> Result<Scene, ModelLoadError> {
+    let source = Path::new(path);
+    if mode == CacheMode::Use {
+        if let Some(scene) = cache::read(source) {
+            let size = std::fs::metadata(source).map_or(0, |metadata| metadata.len());
+            progress.advance(size as usize)?;
+            return Ok(scene);
+        }
+    }
+    let scene = load_file(path, progress)?;
+    let size = std::fs::metadata(source).map_or(0, |metadata| metadata.len());
+    let wanted = match mode {
+        CacheMode::Use => size >= MIN_CACHED_SIZE || baking,
+        CacheMode::Rebuild => true,
+        CacheMode::Off => false,
+    };
+    if wanted {
+        if let Err(error) = cache::write(source, &scene) {
+            eprintln!(
+                "warning: could not write {}: {}",
+                cache::cache_path(source).display(),
+                error
+            );
+        }
+    }
+    Ok(scene)
+}
+
+/// Bakes the ambient occlusion of each of `scene`'s meshes, as they are
+/// after loading `path`, with `bake_occlusion`, calling `report` with the
+/// mesh's name and how far it's got. Unless `mode` is off, it's kept in
+/// `path`'s cache, if it has one, and taken from there next time the
+/// meshes and settings are the same.
+pub fn bake_occlusion_cached(
+    path: &str,
+    scene: &mut Scene,
+    settings: BakeSettings,
+    mode: CacheMode,
+    progress: &LoadProgress,
+    report: impl Fn(&str, usize) + Sync,
+) -> Result<(), ModelLoadError> {
+    let source = Path::new(path);
+    let key = cache::occlusion_key(scene, settings);
+    let cached = match mode {
+        CacheMode::Use => cache::read_occlusion(source, key),
+        CacheMode::Rebuild | CacheMode::Off => None,
+    };
+    let fits = |occlusion: &[Vec<f32>]| {
+        occlusion.len() == scene.meshes.len()
+            && occlusion
+                .iter()
+                .zip(&scene.meshes)
+                .all(|(values, mesh)| values.len() == mesh.model.vertices.len())
+    };
+    if let Some(occlusion) = cached.filter(|occlusion| fits(occlusion)) {
+        for (mesh, values) in scene.meshes.iter_mut().zip(occlusion) {
+            mesh.model.occlusion = values;
+        }
+        return Ok(());
+    }
+    for mesh in &mut scene.meshes {
+        let name = mesh.name.as_str();
+        bake_occlusion(&mut mesh.model, settings, progress, |percent| {
+            report(name, percent)
+        })?;
+    }
+    if mode != CacheMode::Off {
+        let occlusion = scene
+            .meshes
+            .iter()
+            .map(|mesh| mesh.model.occlusion.clone())
+            .collect::<Vec<Vec<f32>>>();
+        if let Err(error) = cache::write_occlusion(source, key, &occlusion) {
+            eprintln!(
+                "warning: could not write {}: {}",
+                cache::cache_path(source).display(),
+                error
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Bytes parsed between reports to the load's progress.
+const PROGRESS_STEP: usize = 1 << 20;
+
+pub fn load_obj(path: &str, progress: &LoadProgress) -> Result<Scene, ModelLoadError> {
+    let bytes = std::fs::read(path)?;
+    let directory = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+    parse_obj(&bytes, directory, progress)
+}
+
+/// Parses OBJ data, resolving `mtllib` paths against `directory` and
+/// reporting the bytes parsed to `progress`. Every `o` or `g` section becomes
+/// its own mesh; files without any come back as a single mesh named
+/// "default".
+///
+/// Big files are split at line boundaries and the pieces parsed in parallel
+/// on rayon's thread pool, then put back together in file order.
+pub fn parse_obj(
+    bytes: &[u8],
+    directory: &Path,
+    progress: &LoadProgress,
+) -> Result<Scene, ModelLoadError> {
+    let count = (bytes.len() / MIN_CHUNK_SIZE).clamp(1, rayon::current_num_threads());
+    parse_pieces(bytes, directory, progress, count)
+}
+
+/// Parses `bytes` as `parse_obj` does, cut into `count` pieces however big
+/// they come out.
+fn parse_pieces(
+    bytes: &[u8],
+    directory: &Path,
+    progress: &LoadProgress,
+    count: usize,
+) -> Result<Scene, ModelLoadError> {
+    let pieces = split_lines(bytes, count);
+    let newlines = pieces
+        .par_iter()
+        .map(|piece| piece.iter().filter(|&&byte| byte == b'\n').count())
+        .collect::<Vec<usize>>();
+    let first_lines = newlines.iter().scan(1, |line, count| {
+        let first = *line;
+        *line += count;
+        Some(first)
+    });
+    let chunks = pieces
+        .par_iter()
+        .zip(first_lines.collect::<Vec<usize>>())
+        .map(|(piece, first_line)| parse_chunk(piece, first_line, progress))
+        .collect::<Vec<Chunk>>();
+
+    let mut builder = ObjBuilder::new(directory);
+    for chunk in chunks {
+        builder.add_chunk(chunk)?;
+    }
+    builder.finish()
+}
+
+/// Pieces smaller than this aren't worth a thread of their own.
+const MIN_CHUNK_SIZE: usize = 1 << 20;
+
+/// Cuts `bytes` into `count` pieces of about the same size, each ending
+/// with a whole line.
+fn split_lines(bytes: &[u8], count: usize) -> Vec<&[u8]> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for piece in 1..=count {
+        let target = (bytes.len() * piece / count).max(start);
+        let end = match bytes[target..].iter().position(|&byte| byte == b'\n') {
+            Some(newline) if piece < count => target + newline + 1,
+            _ => bytes.len(),
+        };
+        if end > start {
+            pieces.push(&bytes[start..end]);
+        }
+        start = end;
+    }
+    pieces
+}
+
+/// A statement whose meaning depends on everything before it in the file,
+/// kept in order while a chunk is parsed and applied once the chunks before
+/// it have been.
+enum Statement {
+    /// The chunk's `corners` in `range`, with how many positions, texture
+    /// coordinates and normals the chunk had read by then, for the negative
+    /// indices that count back from there.
+    Face {
+        range: Range<usize>,
+        counts: (usize, usize, usize),
+    },
+    /// A polyline through the chunk's `corners` in `range`, counted as for
+    /// a face.
+    Line {
+        range: Range<usize>,
+        counts: (usize, usize, usize),
+    },
+    Library(Vec<String>),
+    UseMaterial {
+        line: usize,
+        name: String,
+    },
+    Group(String),
+}
+
+/// One piece of an OBJ file, parsed without knowing what came before it.
+#[derive(Default)]
+struct Chunk {
+    positions: Vec<[f32; 3]>,
+    /// Empty until a `v` with a color, then one for each position up to the
+    /// last that had one, white for those that didn't.
+    colors: Vec<Color>,
+    normals: Vec<[f32; 3]>,
+    texcoords: Vec<[f32; 2]>,
+    /// As written, negative indices included.
+    corners: Vec<Corner>,
+    statements: Vec<Statement>,
+    /// What stopped the parse, to report after the statements before it.
+    error: Option<ModelLoadError>,
+}
+
+/// Parses the lines of `bytes`, numbering them from `first_line`.
+fn parse_chunk(bytes: &[u8], first_line: usize, progress: &LoadProgress) -> Chunk {
+    let mut chunk = Chunk::default();
+    if let Err(error) = read_chunk(&mut chunk, bytes, first_line, progress) {
+        chunk.error = Some(error);
+    }
+    chunk
+}
+
+fn read_chunk(
+    chunk: &mut Chunk,
+    bytes: &[u8],
+    first_line: usize,
+    progress: &LoadProgress,
+) -> Result<(), ModelLoadError> {
+    let mut unreported = 0;
+    for (number, line) in scan::lines(bytes).enumerate() {
+        unreported += line.len() + 1;
+        if unreported >= PROGRESS_STEP {
+            progress.advance(unreported)?;
+            unreported = 0;
+        }
+        let number = first_line + number;
+        let mut tokens = scan::Tokens::new(line);
+        let statement = match tokens.next() {
+            Some(statement) => statement,
+            None => continue,
+        };
+        match statement {
+            b"v" => {
+                let (position, color) = read_vertex(tokens, number)?;
+                chunk.positions.push(position);
+                if let Some(color) = color {
+                    chunk.colors.resize(chunk.positions.len() - 1, Color::WHITE);
+                    chunk.colors.push(color);
+                }
+            }
+            b"vn" => chunk.normals.push(read_triple(tokens, "vn", number)?),
+            b"vt" => chunk.texcoords.push(read_pair(tokens, number)?),
+            b"f" => {
+                let start = chunk.corners.len();
+                for corner in tokens {
+                    chunk.corners.push(read_corner(corner, number)?);
+                }
+                if chunk.corners.len() - start < 3 {
+                    return Err(ModelLoadError::MalformedStatement {
+                        line: number,
+                        statement: "f".to_string(),
+                    });
+                }
+                chunk.statements.push(Statement::Face {
+                    range: start..chunk.corners.len(),
+                    counts: (
+                        chunk.positions.len(),
+                        chunk.texcoords.len(),
+                        chunk.normals.len(),
+                    ),
+                });
+            }
+            b"l" => {
+                let start = chunk.corners.len();
+                for corner in tokens {
+                    chunk.corners.push(read_corner(corner, number)?);
+                }
+                if chunk.corners.len() - start < 2 {
+                    return Err(ModelLoadError::MalformedStatement {
+                        line: number,
+                        statement: "l".to_string(),
+                    });
+                }
+                chunk.statements.push(Statement::Line {
+                    range: start..chunk.corners.len(),
+                    counts: (
+                        chunk.positions.len(),
+                        chunk.texcoords.len(),
+                        chunk.normals.len(),
+                    ),
+                });
+            }
+            b"mtllib" => chunk.statements.push(Statement::Library(
+                tokens
+                    .map(|file| String::from_utf8_lossy(file).to_string())
+                    .collect(),
+            )),
+            b"usemtl" => chunk.statements.push(Statement::UseMaterial {
+                line: number,
+                name: join_tokens(tokens),
+            }),
+            b"o" | b"g" => chunk
+                .statements
+                .push(Statement::Group(match tokens.clone().next() {
+                    Some(_) => join_tokens(tokens),
+                    None => "default".to_string(),
+                })),
+            b"s" => (),
+            statement if statement.starts_with(b"#") => (),
+            statement => {
+                return Err(ModelLoadError::UnsupportedStatement {
+                    line: number,
+                    statement: String::from_utf8_lossy(statement).to_string(),
+                })
+            }
+        }
+    }
+    progress.advance(unreported)?;
+    Ok(())
+}
+
+/// Puts parsed chunks back together in file order.
+struct ObjBuilder<'a> {
+    directory: &'a Path,
+    positions: Vec<[f32; 3]>,
+    /// As in a chunk, white for positions without a color of their own
+    /// once any has one.
+    colors: Vec<Color>,
+    normals: Vec<[f32; 3]>,
+    texcoords: Vec<[f32; 2]>,
+    library: Vec<Material>,
+    current_material: Material,
+    groups: Vec<Group>,
+    /// Reused for every face and line rather than allocated each time.
+    face: Vec<Corner>,
+}
+
+impl<'a> ObjBuilder<'a> {
+    fn new(directory: &'a Path) -> Self {
+        ObjBuilder {
+            directory,
+            positions: Vec::new(),
+            colors: Vec::new(),
+            normals: Vec::new(),
+            texcoords: Vec::new(),
+            library: Vec::new(),
+            current_material: Material::default(),
+            groups: vec![Group::new("default")],
+            face: Vec::new(),
+        }
+    }
+
+    fn add_chunk(&mut self, mut chunk: Chunk) -> Result<(), ModelLoadError> {
+        let offsets = (
+            self.positions.len(),
+            self.texcoords.len(),
+            self.normals.len(),
+        );
+        if !self.colors.is_empty() || !chunk.colors.is_empty() {
+            self.colors.resize(offsets.0, Color::WHITE);
+            chunk.colors.resize(chunk.positions.len(), Color::WHITE);
+            self.colors.append(&mut chunk.colors);
+        }
+        self.positions.append(&mut chunk.positions);
+        self.normals.append(&mut chunk.normals);
+        self.texcoords.append(&mut chunk.texcoords);
+        for statement in chunk.statements {
+            match statement {
+                Statement::Face { range, counts } => {
+                    let counts = (
+                        offsets.0 + counts.0,
+                        offsets.1 + counts.1,
+                        offsets.2 + counts.2,
+                    );
+                    self.face.clear();
+                    for corner in &chunk.corners[range] {
+                        self.face.push(corner.resolve(counts)?);
+                    }
+                    self.add_face();
+                }
+                Statement::Line { range, counts } => {
+                    let counts = (
+                        offsets.0 + counts.0,
+                        offsets.1 + counts.1,
+                        offsets.2 + counts.2,
+                    );
+                    self.face.clear();
+                    for corner in &chunk.corners[range] {
+                        self.face.push(corner.resolve(counts)?);
+                    }
+                    // Each pair of neighbouring corners is a segment.
+                    let group = self.groups.last_mut().unwrap();
+                    for segment in self.face.windows(2) {
+                        group.line_corners.extend_from_slice(segment);
+                    }
+                }
+                Statement::Library(files) => self.load_library(&files),
+                Statement::UseMaterial { line, name } => self.use_material(line, &name),
+                Statement::Group(name) => {
+                    let group = self.groups.last_mut().unwrap();
+                    if group.is_empty() {
+                        group.name = name;
+                    } else {
+                        self.groups.push(Group::new(&name));
                     }
-                    _ => (),
                 }
             }
-            Err(_) => (),
+        }
+        match chunk.error {
+            Some(error) => Err(error),
+            None => Ok(()),
         }
     }
 
-    let mut out_vertices: Vec<Vertex> = Vec::new();
-    let mut out_normals: Vec<Normal> = Vec::new();
-    let mut out_index: Vec<u16> = Vec::new();
+    fn add_face(&mut self) {
+        let group = self.groups.last_mut().unwrap();
+        let material = match group.material_slot {
+            Some(material) => material,
+            None => {
+                let slot = material_slot(&mut group.materials, self.current_material.clone());
+                group.material_slot = Some(slot);
+                slot
+            }
+        };
+        // Fan out from the first corner so quads and n-gons keep the
+        // winding order they were written with.
+        let face = &self.face;
+        for i in 1..face.len() - 1 {
+            group.corners.push(face[0]);
+            group.corners.push(face[i]);
+            group.corners.push(face[i + 1]);
+            group.face_materials.push(material);
+        }
+    }
 
-    for index in &vertex_indexes {
-        let a: usize = (index - 1).into();
-        out_vertices.push(*vertices.get(a).unwrap());
+    fn load_library(&mut self, files: &[String]) {
+        for file in files {
+            let path = self.directory.join(file);
+            match load_mtl(&path, self.directory) {
+                Ok(loaded) => self.library.extend(loaded),
+                Err(error) => eprintln!(
+                    "warning: could not load material library {}: {}",
+                    path.display(),
+                    error
+                ),
+            }
+        }
     }
 
-    for index in &normal_indexes {
-        let a: usize = (index - 1).into();
-        out_normals.push(*normals.get(a).unwrap());
-        out_index.push((out_index.len()).try_into().unwrap());
+    fn use_material(&mut self, line: usize, name: &str) {
+        self.current_material = match self.library.iter().find(|material| material.name == name) {
+            Some(material) => material.clone(),
+            None => {
+                eprintln!(
+                    "warning: line {}: unknown material '{}', using the default",
+                    line, name
+                );
+                Material::default()
+            }
+        };
+        self.groups.last_mut().unwrap().material_slot = None;
     }
 
-    return Model {
+    /// Builds each group's mesh, in parallel too.
+    fn finish(mut self) -> Result<Scene, ModelLoadError> {
+        // A trailing `o`/`g` with no faces or lines after it would otherwise
+        // show up as an empty mesh.
+        if self.groups.len() > 1 && self.groups.last().unwrap().is_empty() {
+            self.groups.pop();
+        }
+        // A scan of `v` lines without any faces is a point cloud, all of it
+        // one mesh whatever groups it was in.
+        let faceless = self.groups.iter().all(Group::is_empty);
+        if faceless && !self.positions.is_empty() {
+            let model = point_cloud(&self.positions, &self.colors)?;
+            return Ok(Scene::single("default", model));
+        }
+        let (positions, normals, texcoords) = (&self.positions, &self.normals, &self.texcoords);
+        let colors = &self.colors;
+        let meshes = self
+            .groups
+            .into_par_iter()
+            .map(|group| {
+                let mut model = build_model(&group, positions, colors, normals, texcoords)?;
+                if normals.is_empty() {
+                    compute_normals(&mut model);
+                }
+                Ok(Mesh {
+                    name: group.name,
+                    model,
+                })
+            })
+            .collect::<Vec<Result<Mesh, ModelLoadError>>>();
+        // The first group's error, as when they're built one by one.
+        let meshes = meshes
+            .into_iter()
+            .collect::<Result<Vec<Mesh>, ModelLoadError>>()?;
+        Ok(Scene { meshes })
+    }
+}
+
+/// A model of `positions` alone, with a default material for the points.
+/// `colors` is empty or one for each position.
+fn point_cloud(positions: &[[f32; 3]], colors: &[Color]) -> Result<Model, ModelLoadError> {
+    if positions.len() > MAX_VERTICES {
+        return Err(ModelLoadError::TooManyVertices {
+            count: positions.len(),
+        });
+    }
+    let vertices = positions
+        .iter()
+        .map(|&position| Vertex3D {
+            position,
+            ..Default::default()
+        })
+        .collect::<Vec<Vertex3D>>();
+    Ok(Model {
+        bounds: Aabb::from_vertices(&vertices),
+        vertices,
+        colors: colors.to_vec(),
+        indexes: Vec::new(),
+        lines: Vec::new(),
+        materials: vec![Material::default()],
+        sub_meshes: vec![SubMesh {
+            material: 0,
+            range: 0..0,
+        }],
+        has_normals: false,
+        has_tex_coords: false,
+        patches: Vec::new(),
+        lods: Vec::new(),
+        occlusion: Vec::new(),
+    })
+}
+
+/// Flattens a group's face corners into glium-ready buffers.
+fn build_model(
+    group: &Group,
+    positions: &[[f32; 3]],
+    colors: &[Color],
+    normals: &[[f32; 3]],
+    texcoords: &[[f32; 2]],
+) -> Result<Model, ModelLoadError> {
+    let mut out_vertices: Vec<Vertex3D> = Vec::new();
+    let mut out_colors: Vec<Color> = Vec::new();
+    let mut out_index: Vec<u32> = Vec::new();
+    // OBJ indexes positions, texcoords and normals separately, glium wants a
+    // single index per vertex, so every distinct triple becomes one vertex.
+    // Lines index the same lists, so their corners share the vertices too.
+    let mut emitted: HashMap<(i64, Option<i64>, Option<i64>), u32, CornerHashing> =
+        HashMap::default();
+
+    for corner in group.corners.iter().chain(&group.line_corners) {
+        let key = (corner.vertex, corner.texcoord, corner.normal);
+        if let Some(index) = emitted.get(&key) {
+            out_index.push(*index);
+            continue;
+        }
+
+        let a = resolve_index(corner.vertex, positions.len(), corner.line, "vertices")?;
+        // Missing components stay zeroed so the shader can always read them.
+        let mut vertex = Vertex3D {
+            position: positions[a],
+            ..Default::default()
+        };
+        if let Some(normal) = corner.normal {
+            let a = resolve_index(normal, normals.len(), corner.line, "normals")?;
+            vertex.normal = normals[a];
+        }
+        if let Some(texcoord) = corner.texcoord {
+            let a = resolve_index(
+                texcoord,
+                texcoords.len(),
+                corner.line,
+                "texture coordinates",
+            )?;
+            vertex.tex_coords = texcoords[a];
+        }
+        out_vertices.push(vertex);
+        // The color goes with the position, whatever it's paired with.
+        if !colors.is_empty() {
+            out_colors.push(colors[a]);
+        }
+
+        let index: u32 =
+            (out_vertices.len() - 1)
+                .try_into()
+                .map_err(|_| ModelLoadError::TooManyVertices {
+                    count: out_vertices.len(),
+                })?;
+        emitted.insert(key, index);
+        out_index.push(index);
+    }
+
+    let lines = out_index.split_off(group.corners.len());
+    let (indexes, sub_meshes) = group_by_material(&out_index, &group.face_materials);
+    Ok(Model {
+        bounds: Aabb::from_vertices(&out_vertices),
         vertices: out_vertices,
-        normals: out_normals,
-        indexes: out_index,
+        colors: out_colors,
+        indexes,
+        lines,
+        materials: group.materials.clone(),
+        sub_meshes,
+        has_normals: !normals.is_empty(),
+        has_tex_coords: group.corners.iter().any(|corner| corner.texcoord.is_some()),
+        patches: Vec::new(),
+        lods: Vec::new(),
+        occlusion: Vec::new(),
+    })
+}
+
+/// Reorders triangles so each material's faces are contiguous, keeping the
+/// materials in the order they first appear in the file.
+fn group_by_material(indexes: &[u32], face_materials: &[usize]) -> (Vec<u32>, Vec<SubMesh>) {
+    let mut order: Vec<usize> = Vec::new();
+    for material in face_materials {
+        if !order.contains(material) {
+            order.push(*material);
+        }
+    }
+
+    let mut grouped: Vec<u32> = Vec::with_capacity(indexes.len());
+    let mut sub_meshes: Vec<SubMesh> = Vec::new();
+    for material in order {
+        let start = grouped.len();
+        for (triangle, face_material) in indexes.chunks_exact(3).zip(face_materials) {
+            if *face_material == material {
+                grouped.extend_from_slice(triangle);
+            }
+        }
+        sub_meshes.push(SubMesh {
+            material,
+            range: start..grouped.len(),
+        });
+    }
+    (grouped, sub_meshes)
+}
+
+/// Returns the index of `material` in the model's list, adding it if needed.
+fn material_slot(materials: &mut Vec<Material>, material: Material) -> usize {
+    if let Some(index) = materials.iter().position(|m| m.name == material.name) {
+        return index;
+    }
+    materials.push(material);
+    materials.len() - 1
+}
+
+/// Reads the materials in an MTL file. Texture paths are resolved against
+/// `directory`, the folder holding the OBJ that referenced the library.
+pub fn load_mtl(path: &Path, directory: &Path) -> Result<Vec<Material>, ModelLoadError> {
+    let file = File::open(path)?;
+    let mut materials: Vec<Material> = Vec::new();
+
+    for (number, line) in io::BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        let number = number + 1;
+        let split = line.split_whitespace().collect::<Vec<&str>>();
+        let statement = match split.first() {
+            Some(statement) => *statement,
+            None => continue,
+        };
+        if statement == "newmtl" {
+            materials.push(Material {
+                name: split[1..].join(" "),
+                ..Material::default()
+            });
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => continue,
+        };
+        match statement {
+            "Ka" => material.ambient = parse_color(&split, number)?,
+            "Kd" => material.diffuse = parse_color(&split, number)?,
+            "Ks" => material.specular = parse_color(&split, number)?,
+            "Ns" => {
+                let value = split.get(1).ok_or(ModelLoadError::MalformedStatement {
+                    line: number,
+                    statement: "Ns".to_string(),
+                })?;
+                material.shininess = parse_float(value, number)?;
+            }
+            "Pm" => {
+                let value = split.get(1).ok_or(ModelLoadError::MalformedStatement {
+                    line: number,
+                    statement: "Pm".to_string(),
+                })?;
+                material.reflectivity = Some(parse_float(value, number)?.clamp(0.0, 1.0));
+            }
+            "Pr" => {
+                let value = split.get(1).ok_or(ModelLoadError::MalformedStatement {
+                    line: number,
+                    statement: "Pr".to_string(),
+                })?;
+                material.roughness = Some(parse_float(value, number)?.clamp(0.0, 1.0));
+            }
+            "d" | "Tr" => {
+                // `-halo` may come before the value, which is always last.
+                let value = match split.last() {
+                    Some(value) if split.len() > 1 => parse_float(value, number)?,
+                    _ => {
+                        return Err(ModelLoadError::MalformedStatement {
+                            line: number,
+                            statement: statement.to_string(),
+                        })
+                    }
+                };
+                material.opacity = match statement {
+                    "d" => value,
+                    _ => 1.0 - value,
+                }
+                .clamp(0.0, 1.0);
+            }
+            // Options such as `-s 1 1 1` or `-bm 1` may precede the file
+            // name, which is always the last token.
+            "map_Kd" | "norm" | "map_Bump" | "bump" => {
+                let file = match split.last() {
+                    Some(file) if split.len() > 1 => directory.join(file),
+                    _ => {
+                        return Err(ModelLoadError::MalformedStatement {
+                            line: number,
+                            statement: statement.to_string(),
+                        })
+                    }
+                };
+                match statement {
+                    "map_Kd" => material.diffuse_map = Some(file),
+                    _ => material.normal_map = Some(file),
+                }
+            }
+            // Everything else in an MTL file is optional for us.
+            _ => (),
+        }
+    }
+
+    Ok(materials)
+}
+
+fn parse_color(split: &[&str], line: usize) -> Result<[f32; 3], ModelLoadError> {
+    parse_triple(split, line)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalMode {
+    Flat,
+    /// Faces meeting at more than `crease_angle_deg` keep separate normals.
+    Smooth {
+        crease_angle_deg: f32,
+    },
+}
+
+impl std::str::FromStr for NormalMode {
+    type Err = String;
+
+    /// Accepts `flat`, `smooth` (no creases) or `smooth:<degrees>`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.split_once(':') {
+            None if value == "flat" => Ok(NormalMode::Flat),
+            None if value == "smooth" => Ok(NormalMode::Smooth {
+                crease_angle_deg: 180.0,
+            }),
+            Some(("smooth", angle)) => match angle.parse() {
+                Ok(crease_angle_deg) => Ok(NormalMode::Smooth { crease_angle_deg }),
+                Err(_) => Err(format!("invalid crease angle '{}'", angle)),
+            },
+            _ => Err(format!("unknown normal mode '{}'", value)),
+        }
+    }
+}
+
+/// Rebuilds the model's normals, duplicating vertices along hard edges so
+/// faces on either side of a crease can be lit independently.
+pub fn generate_normals(model: &mut Model, mode: NormalMode) {
+    let face_normals = model
+        .indexes
+        .chunks_exact(3)
+        .map(|triangle| face_normal(model, triangle))
+        .collect::<Vec<[f32; 3]>>();
+
+    // Group faces by vertex position rather than vertex index, otherwise a
+    // texture seam would also split the shading.
+    let mut faces_at: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (face, triangle) in model.indexes.chunks_exact(3).enumerate() {
+        for &index in triangle {
+            let key = position_key(model.vertices[index as usize].position);
+            faces_at.entry(key).or_default().push(face);
+        }
+    }
+
+    let min_cos = match mode {
+        NormalMode::Flat => 1.0,
+        NormalMode::Smooth { crease_angle_deg } => crease_angle_deg.to_radians().cos(),
+    };
+
+    let mut vertices: Vec<Vertex3D> = Vec::new();
+    let mut colors: Vec<Color> = Vec::new();
+    let mut indexes: Vec<u32> = Vec::new();
+    let mut emitted: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+
+    for (face, triangle) in model.indexes.chunks_exact(3).enumerate() {
+        let own = normalize(face_normals[face]);
+        for &index in triangle {
+            let normal = match mode {
+                NormalMode::Flat => own,
+                NormalMode::Smooth { .. } => {
+                    let key = position_key(model.vertices[index as usize].position);
+                    let mut sum = [0.0f32; 3];
+                    for &other in &faces_at[&key] {
+                        let candidate = face_normals[other];
+                        // Faces are always allowed to contribute to their own
+                        // corners, degenerate or not.
+                        if other == face || dot(normalize(candidate), own) >= min_cos - 1e-6 {
+                            sum = [
+                                sum[0] + candidate[0],
+                                sum[1] + candidate[1],
+                                sum[2] + candidate[2],
+                            ];
+                        }
+                    }
+                    normalize(sum)
+                }
+            };
+
+            let key = (index, normal.map(f32::to_bits));
+            if let Some(existing) = emitted.get(&key) {
+                indexes.push(*existing);
+                continue;
+            }
+            vertices.push(Vertex3D {
+                normal,
+                ..model.vertices[index as usize]
+            });
+            if !model.colors.is_empty() {
+                colors.push(model.colors[index as usize]);
+            }
+            let new_index: u32 = (vertices.len() - 1).try_into().unwrap();
+            emitted.insert(key, new_index);
+            indexes.push(new_index);
+        }
+    }
+    // Lines are unlit, so they keep whatever normals their vertices had.
+    for index in &mut model.lines {
+        let vertex = model.vertices[*index as usize];
+        let key = (*index, vertex.normal.map(f32::to_bits));
+        *index = *emitted.entry(key).or_insert_with(|| {
+            vertices.push(vertex);
+            if !model.colors.is_empty() {
+                colors.push(model.colors[*index as usize]);
+            }
+            (vertices.len() - 1).try_into().unwrap()
+        });
+    }
+
+    // Triangles keep their order, so the sub-mesh ranges stay valid.
+    model.vertices = vertices;
+    model.colors = colors;
+    model.indexes = indexes;
+}
+
+fn face_normal(model: &Model, triangle: &[u32]) -> [f32; 3] {
+    let a = model.vertices[triangle[0] as usize].position;
+    let b = model.vertices[triangle[1] as usize].position;
+    let c = model.vertices[triangle[2] as usize].position;
+    let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    [
+        ab[1] * ac[2] - ab[2] * ac[1],
+        ab[2] * ac[0] - ab[0] * ac[2],
+        ab[0] * ac[1] - ab[1] * ac[0],
+    ]
+}
+
+fn position_key(position: [f32; 3]) -> [u32; 3] {
+    position.map(f32::to_bits)
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// Normalizes a vector, leaving zero-length vectors at zero instead of NaN.
+fn normalize(vector: [f32; 3]) -> [f32; 3] {
+    let length = dot(vector, vector).sqrt();
+    if length <= f32::EPSILON {
+        return [0.0, 0.0, 0.0];
+    }
+    [vector[0] / length, vector[1] / length, vector[2] / length]
+}
+
+/// Replaces the model's normals with smooth per-vertex normals. Each face adds
+/// its unnormalized cross product, so larger faces weigh more.
+pub fn compute_normals(model: &mut Model) {
+    let mut sums = vec![[0.0f32; 3]; model.vertices.len()];
+    for triangle in model.indexes.chunks_exact(3) {
+        let cross = face_normal(model, triangle);
+        // Zero-area triangles have no direction to contribute.
+        if dot(cross, cross).sqrt() <= f32::EPSILON {
+            continue;
+        }
+        for &index in triangle {
+            let sum = &mut sums[index as usize];
+            sum[0] += cross[0];
+            sum[1] += cross[1];
+            sum[2] += cross[2];
+        }
+    }
+
+    for (vertex, sum) in model.vertices.iter_mut().zip(sums) {
+        vertex.normal = normalize(sum);
+    }
+}
+
+/// Reverses the winding of every triangle, for models exported with the
+/// opposite handedness. Normals are left alone; regenerate them with
+/// `generate_normals` if they were derived from the old winding. Patches
+/// have their rows reversed to match, which turns their normals too.
+pub fn flip_winding(model: &mut Model) {
+    for triangle in model.indexes.chunks_exact_mut(3) {
+        triangle.swap(1, 2);
+    }
+    for patch in &mut model.patches {
+        for row in patch.chunks_exact_mut(4) {
+            row.reverse();
+        }
+    }
+}
+
+/// Moves the scene so the middle of its bounding box is at the origin and
+/// scales it so the box's longest side is 1, all of its meshes together so
+/// they stay where they were relative to each other. Returns the center it
+/// was moved from and the factor it was scaled by, or `None`, leaving it
+/// alone, if it has no size to scale.
+pub fn fit_unit_box(scene: &mut Scene) -> Option<([f32; 3], f32)> {
+    let mut meshes = scene
+        .meshes
+        .iter()
+        .filter(|mesh| !mesh.model.vertices.is_empty())
+        .map(|mesh| mesh.model.bounds);
+    let first = meshes.next()?;
+    let bounds = meshes.fold(first, |bounds, other| bounds.union(&other));
+    let size = (0..3)
+        .map(|axis| bounds.max[axis] - bounds.min[axis])
+        .fold(0.0f32, f32::max);
+    if !size.is_normal() {
+        return None;
+    }
+    let center = bounds.center();
+    let factor = 1.0 / size;
+    let apply = |point: &mut [f32; 3]| {
+        for axis in 0..3 {
+            point[axis] = (point[axis] - center[axis]) * factor;
+        }
     };
+    for mesh in &mut scene.meshes {
+        let model = &mut mesh.model;
+        for vertex in &mut model.vertices {
+            apply(&mut vertex.position);
+        }
+        for point in model.patches.iter_mut().flatten() {
+            apply(point);
+        }
+        // A uniform scale leaves the normals as they were.
+        apply(&mut model.bounds.min);
+        apply(&mut model.bounds.max);
+    }
+    Some((center, factor))
+}
+
+fn parse_triple(split: &[&str], line: usize) -> Result<[f32; 3], ModelLoadError> {
+    if split.len() < 4 {
+        return Err(ModelLoadError::MalformedStatement {
+            line,
+            statement: split[0].to_string(),
+        });
+    }
+    Ok([
+        parse_float(split[1], line)?,
+        parse_float(split[2], line)?,
+        parse_float(split[3], line)?,
+    ])
+}
+
+fn parse_float(value: &str, line: usize) -> Result<f32, ModelLoadError> {
+    value.parse().map_err(|_| ModelLoadError::InvalidFloat {
+        line,
+        value: value.to_string(),
+    })
+}
+
+/// The words of an OBJ line after the statement, separated by single
+/// spaces.
+fn join_tokens(tokens: scan::Tokens) -> String {
+    tokens
+        .map(String::from_utf8_lossy)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The three numbers after a `v` or `vn`; any more are ignored.
+fn read_triple(
+    mut tokens: scan::Tokens,
+    statement: &str,
+    line: usize,
+) -> Result<[f32; 3], ModelLoadError> {
+    match [tokens.next(), tokens.next(), tokens.next()] {
+        [Some(x), Some(y), Some(z)] => Ok([
+            read_float(x, line)?,
+            read_float(y, line)?,
+            read_float(z, line)?,
+        ]),
+        _ => Err(ModelLoadError::MalformedStatement {
+            line,
+            statement: statement.to_string(),
+        }),
+    }
+}
+
+/// A `v`'s position, and its color if it has one. MeshLab and scanners
+/// write vertex colors as three more values after the position, some with
+/// an alpha after those, which is ignored; a lone fourth value is a weight,
+/// ignored as well.
+fn read_vertex(
+    tokens: scan::Tokens,
+    line: usize,
+) -> Result<([f32; 3], Option<Color>), ModelLoadError> {
+    let position = read_triple(tokens.clone(), "v", line)?;
+    let mut rest = tokens.skip(3);
+    let color = match [rest.next(), rest.next(), rest.next()] {
+        [Some(r), Some(g), Some(b)] => Some(Color {
+            color: (
+                read_float(r, line)?,
+                read_float(g, line)?,
+                read_float(b, line)?,
+            ),
+        }),
+        _ => None,
+    };
+    Ok((position, color))
+}
+
+/// A `vt`'s coordinates, with v defaulting to zero.
+fn read_pair(mut tokens: scan::Tokens, line: usize) -> Result<[f32; 2], ModelLoadError> {
+    let u = tokens.next().ok_or(ModelLoadError::MalformedStatement {
+        line,
+        statement: "vt".to_string(),
+    })?;
+    let v = match tokens.next() {
+        Some(value) => read_float(value, line)?,
+        None => 0.0,
+    };
+    Ok([read_float(u, line)?, v])
+}
+
+fn read_float(value: &[u8], line: usize) -> Result<f32, ModelLoadError> {
+    scan::parse_f32(value).ok_or_else(|| ModelLoadError::InvalidFloat {
+        line,
+        value: String::from_utf8_lossy(value).to_string(),
+    })
+}
+
+fn read_index(value: &[u8], line: usize) -> Result<i64, ModelLoadError> {
+    scan::parse_i64(value).ok_or_else(|| ModelLoadError::InvalidIndex {
+        line,
+        value: String::from_utf8_lossy(value).to_string(),
+    })
+}
+
+/// A face corner's indices as written; `Corner::resolve` deals with the
+/// negative ones.
+fn read_corner(corner: &[u8], line: usize) -> Result<Corner, ModelLoadError> {
+    let mut parts = corner.split(|&byte| byte == b'/');
+    let vertex = read_index(parts.next().unwrap_or_default(), line)?;
+    let mut optional = || match parts.next() {
+        Some(index) if !index.is_empty() => read_index(index, line).map(Some),
+        _ => Ok(None),
+    };
+    let texcoord = optional()?;
+    let normal = optional()?;
+    Ok(Corner {
+        line,
+        vertex,
+        texcoord,
+        normal,
+    })
+}
+
+impl Corner {
+    /// Turns negative indices into positive ones, given how many
+    /// positions, texture coordinates and normals came before the face.
+    fn resolve(
+        self,
+        (vertex_count, texcoord_count, normal_count): (usize, usize, usize),
+    ) -> Result<Corner, ModelLoadError> {
+        let line = self.line;
+        let vertex = make_absolute(self.vertex, vertex_count, line, "vertices")?;
+        let texcoord = match self.texcoord {
+            Some(index) => Some(make_absolute(
+                index,
+                texcoord_count,
+                line,
+                "texture coordinates",
+            )?),
+            None => None,
+        };
+        let normal = match self.normal {
+            Some(index) => Some(make_absolute(index, normal_count, line, "normals")?),
+            None => None,
+        };
+        Ok(Corner {
+            line,
+            vertex,
+            texcoord,
+            normal,
+        })
+    }
+}
+
+/// Negative indices count back from the end of the list as it stands when the
+/// face is read, so they have to be resolved before any later `v`/`vn` lines.
+fn make_absolute(
+    index: i64,
+    count: usize,
+    line: usize,
+    kind: &'static str,
+) -> Result<i64, ModelLoadError> {
+    if index >= 0 {
+        return Ok(index);
+    }
+    let absolute = count as i64 + 1 + index;
+    if absolute < 1 {
+        return Err(ModelLoadError::IndexOutOfRange {
+            line,
+            index,
+            count,
+            kind,
+        });
+    }
+    Ok(absolute)
+}
+
+/// Turns a 1-based OBJ index into a position in the parsed list.
+fn resolve_index(
+    index: i64,
+    count: usize,
+    line: usize,
+    kind: &'static str,
+) -> Result<usize, ModelLoadError> {
+    if index < 1 || index as usize > count {
+        return Err(ModelLoadError::IndexOutOfRange {
+            line,
+            index,
+            count,
+            kind,
+        });
+    }
+    Ok(index as usize - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Scene, ModelLoadError> {
+        parse_obj(text.as_bytes(), Path::new("."), &LoadProgress::default())
+    }
+
+    fn parse_error(text: &str) -> ModelLoadError {
+        match parse(text) {
+            Ok(_) => panic!("parsed without an error:\n{}", text),
+            Err(error) => error,
+        }
+    }
+
+    /// Writes `contents` to a file in the temporary directory, named for
+    /// the test so tests running at the same time don't share one.
+    pub(super) fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("opengl_rust-{}-{}", std::process::id(), name));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    const TRIANGLE: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+
+    #[test]
+    fn bad_float_is_invalid_float() {
+        let error = parse_error("v 0 0 0\nv 1 zero 0\n");
+        assert!(
+            matches!(&error, ModelLoadError::InvalidFloat { line: 2, value } if value == "zero"),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn bad_index_is_invalid_index() {
+        let error = parse_error(&format!("{}f 1 two 3\n", TRIANGLE));
+        assert!(
+            matches!(&error, ModelLoadError::InvalidIndex { line: 4, value } if value == "two"),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn index_past_the_vertices_is_out_of_range() {
+        let error = parse_error(&format!("{}f 1 2 9\n", TRIANGLE));
+        assert!(
+            matches!(
+                error,
+                ModelLoadError::IndexOutOfRange {
+                    line: 4,
+                    index: 9,
+                    count: 3,
+                    kind: "vertices"
+                }
+            ),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn unknown_statement_is_unsupported() {
+        let error = parse_error(&format!("{}curv 0 1 1 2\n", TRIANGLE));
+        assert!(
+            matches!(&error, ModelLoadError::UnsupportedStatement { line: 4, statement } if statement == "curv"),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn face_with_two_corners_is_malformed() {
+        let error = parse_error(&format!("{}f 1 2\n", TRIANGLE));
+        assert!(
+            matches!(&error, ModelLoadError::MalformedStatement { line: 4, statement } if statement == "f"),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn missing_file_is_io() {
+        let path = std::env::temp_dir().join("opengl_rust-no-such-model.obj");
+        match load_file(path.to_str().unwrap(), &LoadProgress::default()) {
+            Err(ModelLoadError::Io(error)) => assert_eq!(error.kind(), io::ErrorKind::NotFound),
+            Err(error) => panic!("expected an IO error, got {:?}", error),
+            Ok(_) => panic!("loaded a file that doesn't exist"),
+        }
+    }
+
+    /// No OBJ small enough for a test has more vertices than a `u32` index
+    /// reaches, but a PLY header can say it does.
+    #[test]
+    fn too_many_vertices() {
+        let header = format!(
+            "ply\nformat ascii 1.0\nelement vertex {}\nproperty float x\nend_header\n",
+            MAX_VERTICES + 1
+        );
+        let path = temp_file("too-many-vertices.ply", header.as_bytes());
+        let result = load_file(path.to_str().unwrap(), &LoadProgress::default());
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Err(ModelLoadError::TooManyVertices { count }) => assert_eq!(count, MAX_VERTICES + 1),
+            Err(error) => panic!("expected TooManyVertices, got {:?}", error),
+            Ok(_) => panic!("loaded {} vertices", MAX_VERTICES + 1),
+        }
+    }
+
+    /// A unit cube around the origin, its faces written as quads.
+    const QUAD_CUBE: &str = "\
+v -0.5 -0.5 -0.5
+v 0.5 -0.5 -0.5
+v 0.5 0.5 -0.5
+v -0.5 0.5 -0.5
+v -0.5 -0.5 0.5
+v 0.5 -0.5 0.5
+v 0.5 0.5 0.5
+v -0.5 0.5 0.5
+f 1 4 3 2
+f 5 6 7 8
+f 1 2 6 5
+f 4 8 7 3
+f 1 5 8 4
+f 2 3 7 6
+";
+
+    #[test]
+    fn quads_are_split_into_triangles() {
+        let scene = parse(QUAD_CUBE).unwrap();
+        let model = &scene.meshes[0].model;
+        assert_eq!(model.vertices.len(), 8);
+        assert_eq!(model.indexes.len(), 36);
+        // Every triangle still faces out of the cube, as its quad did.
+        for triangle in model.indexes.chunks_exact(3) {
+            let normal = face_normal(model, triangle);
+            let corner = model.vertices[triangle[0] as usize].position;
+            assert!(dot(normal, corner) > 0.0, "{:?} faces in", triangle);
+        }
+    }
+
+    #[test]
+    fn negative_indexes_count_back_from_the_face() {
+        // The `v` after the face mustn't change what its negative indexes
+        // were relative to.
+        let scene = parse(&format!("{}f 1 -2 -1\nv 5 5 5\nf -1 -2 2\n", TRIANGLE)).unwrap();
+        let model = &scene.meshes[0].model;
+        let positions = model
+            .indexes
+            .iter()
+            .map(|&index| model.vertices[index as usize].position)
+            .collect::<Vec<[f32; 3]>>();
+        assert_eq!(
+            positions,
+            [
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [5.0, 5.0, 5.0],
+                [0.0, 1.0, 0.0],
+                [1.0, 0.0, 0.0],
+            ]
+        );
+    }
+
+    #[test]
+    fn negative_index_before_the_first_vertex_is_out_of_range() {
+        let error = parse_error(&format!("{}f 1 2 -4\n", TRIANGLE));
+        assert!(
+            matches!(
+                error,
+                ModelLoadError::IndexOutOfRange {
+                    line: 4,
+                    index: -4,
+                    count: 3,
+                    kind: "vertices"
+                }
+            ),
+            "{:?}",
+            error
+        );
+    }
+
+    /// Writes a face corner from its position, its side of the cube and
+    /// which corner of the side it is.
+    type CornerFormat = fn(&str, usize, usize) -> String;
+
+    /// `QUAD_CUBE` with normals and texture coordinates, its corners
+    /// written by `format`.
+    fn cube_with(format: CornerFormat) -> String {
+        let mut text = QUAD_CUBE
+            .lines()
+            .filter(|line| line.starts_with("v "))
+            .collect::<Vec<&str>>()
+            .join("\n");
+        text.push_str("\nvn 0 0 -1\nvn 0 0 1\nvn 0 -1 0\nvn 0 1 0\nvn -1 0 0\nvn 1 0 0\n");
+        text.push_str("vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n");
+        let faces = QUAD_CUBE.lines().filter(|line| line.starts_with("f "));
+        for (side, face) in faces.enumerate() {
+            let corners = face.split_whitespace().skip(1).enumerate();
+            let corners = corners.map(|(corner, position)| format(position, side + 1, corner + 1));
+            text.push_str(&format!(
+                "f {}\n",
+                corners.collect::<Vec<String>>().join(" ")
+            ));
+        }
+        text
+    }
+
+    #[test]
+    fn every_face_index_format() {
+        let formats: [(&str, CornerFormat, usize); 4] = [
+            ("v", |position, _, _| position.to_string(), 8),
+            (
+                "v//vn",
+                |position, side, _| format!("{}//{}", position, side),
+                24,
+            ),
+            // The corners where two sides agree on the texture coordinates
+            // as well as the position share a vertex.
+            (
+                "v/vt",
+                |position, _, corner| format!("{}/{}", position, corner),
+                20,
+            ),
+            (
+                "v/vt/vn",
+                |position, side, corner| format!("{}/{}/{}", position, corner, side),
+                24,
+            ),
+        ];
+        for (name, format, vertices) in formats {
+            let scene = parse(&cube_with(format)).unwrap();
+            let model = &scene.meshes[0].model;
+            assert_eq!(model.vertices.len(), vertices, "{}", name);
+            assert_eq!(model.indexes.len(), 36, "{}", name);
+            assert_eq!(model.has_tex_coords, name.contains("vt"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn crease_angle_splits_the_cube_along_its_edges() {
+        let mut scene = parse(QUAD_CUBE).unwrap();
+        let model = &mut scene.meshes[0].model;
+        generate_normals(
+            model,
+            NormalMode::Smooth {
+                crease_angle_deg: 30.0,
+            },
+        );
+        assert_eq!(model.vertices.len(), 24);
+        assert_eq!(model.indexes.len(), 36);
+        for triangle in model.indexes.chunks_exact(3) {
+            let face = normalize(face_normal(model, triangle));
+            for &index in triangle {
+                let normal = model.vertices[index as usize].normal;
+                // One component is ±1, the others zero.
+                let axes = normal.iter().filter(|value| value.abs() == 1.0).count();
+                let zeros = normal.iter().filter(|&&value| value == 0.0).count();
+                assert_eq!((axes, zeros), (1, 2), "{:?} isn't along an axis", normal);
+                assert_eq!(normal, face);
+            }
+        }
+    }
+
+    #[test]
+    fn two_materials_make_two_sub_meshes() {
+        let library = temp_file(
+            "two-materials.mtl",
+            b"newmtl red\nKd 1 0 0\nnewmtl blue\nKd 0 0 1\n",
+        );
+        let obj = format!(
+            "mtllib {}\n{}v 1 1 0\nusemtl red\nf 1 2 3\nusemtl blue\nf 2 4 3\nusemtl red\nf 1 3 4\n",
+            library.file_name().unwrap().to_str().unwrap(),
+            TRIANGLE
+        );
+        let directory = library.parent().unwrap();
+        let scene = parse_obj(obj.as_bytes(), directory, &LoadProgress::default());
+        std::fs::remove_file(&library).unwrap();
+        let model = &scene.unwrap().meshes[0].model;
+
+        assert_eq!(model.sub_meshes.len(), 2);
+        let names = model
+            .sub_meshes
+            .iter()
+            .map(|sub_mesh| model.materials[sub_mesh.material].name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, ["red", "blue"]);
+        // The ranges follow each other from the start of the buffer to its
+        // end, so together they cover it once.
+        let mut end = 0;
+        for sub_mesh in &model.sub_meshes {
+            assert_eq!(sub_mesh.range.start, end);
+            assert!(sub_mesh.range.end > sub_mesh.range.start);
+            end = sub_mesh.range.end;
+        }
+        assert_eq!(end, model.indexes.len());
+        assert_eq!(model.sub_meshes[0].range.len(), 6);
+    }
+
+    /// Two triangles over a vertex without a color, one with an alpha after
+    /// its color and two with just the color.
+    const COLORED: &str = "\
+v 0 1 0
+v 0 0 0 1 0 0
+v 1 0 0 0 1 0 0.5
+v 1 1 0 0 0 0.25
+f 2 3 1
+f 3 4 1
+";
+
+    #[test]
+    fn colored_vertices_fill_the_color_buffer() {
+        let progress = LoadProgress::default();
+        for count in 1..=COLORED.len() {
+            let scene = parse_pieces(COLORED.as_bytes(), Path::new("."), &progress, count).unwrap();
+            let model = &scene.meshes[0].model;
+            assert_eq!(model.vertices.len(), 4);
+            assert_eq!(model.colors.len(), model.vertices.len());
+            let buffer = model
+                .vertices
+                .iter()
+                .zip(&model.colors)
+                .map(|(vertex, color)| (vertex.position, color.color))
+                .collect::<Vec<([f32; 3], (f32, f32, f32))>>();
+            // In the order the faces first use them, the uncolored one white.
+            let expected = [
+                ([0.0, 0.0, 0.0], (1.0, 0.0, 0.0)),
+                ([1.0, 0.0, 0.0], (0.0, 1.0, 0.0)),
+                ([0.0, 1.0, 0.0], (1.0, 1.0, 1.0)),
+                ([1.0, 1.0, 0.0], (0.0, 0.0, 0.25)),
+            ];
+            assert_eq!(buffer, expected, "in {} pieces", count);
+        }
+    }
+
+    #[test]
+    fn weights_are_not_colors() {
+        let scene = parse("v 0 0 0 1\nv 1 0 0 0.5\nv 0 1 0\nf 1 2 3\n").unwrap();
+        let model = &scene.meshes[0].model;
+        assert_eq!(model.vertices.len(), 3);
+        assert!(model.colors.is_empty());
+    }
+
+    /// The statements the teapots use, read the way `parse_obj` did before
+    /// the byte scanner, with `str::split_whitespace` and `str::parse`.
+    fn str_chunk(text: &str) -> Chunk {
+        let mut chunk = Chunk::default();
+        for (number, line) in text.lines().enumerate() {
+            let number = number + 1;
+            let split = line.split_whitespace().collect::<Vec<&str>>();
+            match split.first() {
+                Some(&"v") => chunk.positions.push(parse_triple(&split, number).unwrap()),
+                Some(&"vn") => chunk.normals.push(parse_triple(&split, number).unwrap()),
+                Some(&"vt") => chunk.texcoords.push([
+                    parse_float(split[1], number).unwrap(),
+                    parse_float(split.get(2).unwrap_or(&"0"), number).unwrap(),
+                ]),
+                Some(&"f") => {
+                    let start = chunk.corners.len();
+                    for corner in &split[1..] {
+                        let mut parts = corner.split('/').map(|part| part.parse::<i64>().ok());
+                        chunk.corners.push(Corner {
+                            line: number,
+                            vertex: parts.next().flatten().unwrap(),
+                            texcoord: parts.next().flatten(),
+                            normal: parts.next().flatten(),
+                        });
+                    }
+                    chunk.statements.push(Statement::Face {
+                        range: start..chunk.corners.len(),
+                        counts: (
+                            chunk.positions.len(),
+                            chunk.texcoords.len(),
+                            chunk.normals.len(),
+                        ),
+                    });
+                }
+                Some(&"o") | Some(&"g") => chunk
+                    .statements
+                    .push(Statement::Group(split[1..].join(" "))),
+                _ => (),
+            }
+        }
+        chunk
+    }
+
+    /// Checks that two scenes have the same meshes, with bit for bit the
+    /// same vertices.
+    pub(super) fn assert_same_scene(scene: &Scene, expected: &Scene) {
+        let bits = |model: &Model| {
+            model
+                .vertices
+                .iter()
+                .flat_map(|vertex| {
+                    vertex
+                        .position
+                        .iter()
+                        .chain(&vertex.normal)
+                        .chain(&vertex.tex_coords)
+                        .map(|value| value.to_bits())
+                        .collect::<Vec<u32>>()
+                })
+                .collect::<Vec<u32>>()
+        };
+        assert_eq!(scene.meshes.len(), expected.meshes.len());
+        for (mesh, expected) in scene.meshes.iter().zip(&expected.meshes) {
+            assert_eq!(mesh.name, expected.name);
+            assert!(bits(&mesh.model) == bits(&expected.model), "{}", mesh.name);
+            assert_eq!(mesh.model.indexes, expected.model.indexes);
+            let ranges = |model: &Model| {
+                model
+                    .sub_meshes
+                    .iter()
+                    .map(|sub_mesh| (sub_mesh.material, sub_mesh.range.clone()))
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(ranges(&mesh.model), ranges(&expected.model));
+        }
+    }
+
+    #[test]
+    fn scanner_matches_str_parsing_on_the_teapots() {
+        let teapots = [
+            include_str!("teapot.obj"),
+            include_str!("teapot-2.obj"),
+            include_str!("teapot-3.obj"),
+        ];
+        for text in teapots {
+            let scanned = parse(text).unwrap();
+            let mut builder = ObjBuilder::new(Path::new("."));
+            builder.add_chunk(str_chunk(text)).unwrap();
+            let reference = builder.finish().unwrap();
+
+            assert_same_scene(&scanned, &reference);
+        }
+    }
+
+    #[test]
+    fn any_split_parses_the_same_as_one_piece() {
+        // Negative indexes reach back into whichever piece the vertices
+        // ended up in, and the second object's faces use the first's.
+        let text = "\
+o first
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 1
+vn 0 0 1
+f -4/1/1 -3/2/1 -2/2/1 -1/1/1
+f 1//1 3//1 4//1
+o second
+v 0 0 1
+v 1 0 1
+f -2 -1 -4
+f 5/-1/-1 6/-2/-1 3/-1/-1
+l 1 -1
+";
+        let progress = LoadProgress::default();
+        let whole = parse_pieces(text.as_bytes(), Path::new("."), &progress, 1).unwrap();
+        assert_eq!(whole.meshes.len(), 2);
+        // Enough pieces for a cut to land at every byte, so inside faces
+        // and between a face and the vertices it counts back to.
+        for count in 2..=text.len() {
+            let split = parse_pieces(text.as_bytes(), Path::new("."), &progress, count).unwrap();
+            assert_same_scene(&split, &whole);
+        }
+    }
+
+    #[test]
+    fn cuts_fall_between_lines() {
+        let text = b"v 0 0 0\nv 1 0 0\nf 1 2 3";
+        for count in 1..=text.len() {
+            let pieces = split_lines(text, count);
+            assert_eq!(pieces.concat(), text);
+            for piece in &pieces[..pieces.len() - 1] {
+                assert_eq!(piece.last(), Some(&b'\n'));
+            }
+        }
+    }
+
+    /// An OBJ fan of `count` vertices, every one of them in a face, and the
+    /// indexes it should load as.
+    fn fan(count: usize) -> (String, Vec<u32>) {
+        let mut text = String::new();
+        for vertex in 0..count {
+            text += &format!("v {} {} 0\n", vertex % 300, vertex / 300);
+        }
+        let mut indexes = Vec::new();
+        for vertex in 2..count {
+            text += &format!("f 1 {} {}\n", vertex, vertex + 1);
+            indexes.extend([0, vertex as u32 - 1, vertex as u32]);
+        }
+        (text, indexes)
+    }
+
+    #[test]
+    fn indexes_past_u16_survive_to_the_gpu_and_the_cache() {
+        let (text, expected) = fan(70_000);
+        let scene = parse(&text).unwrap();
+        let model = &scene.meshes[0].model;
+        assert_eq!(model.vertices.len(), 70_000);
+        assert!(model.indexes == expected);
+        match model.gpu_indexes(&model.indexes) {
+            GpuIndexes::U32(indexes) => assert!(indexes.contains(&69_999)),
+            GpuIndexes::U16(_) => panic!("70000 vertices narrowed to u16"),
+        }
+
+        let source = temp_file("wide-indexes.obj", text.as_bytes());
+        cache::write(&source, &scene).unwrap();
+        let cached = cache::read(&source).expect("the cache was rejected");
+        std::fs::remove_file(cache::cache_path(&source)).unwrap();
+        std::fs::remove_file(&source).unwrap();
+        assert!(cached.meshes[0].model.indexes == expected);
+    }
+
+    #[test]
+    fn indexes_that_fit_are_narrowed() {
+        let (text, expected) = fan(u16::MAX as usize + 1);
+        let model = &parse(&text).unwrap().meshes[0].model;
+        match model.gpu_indexes(&model.indexes) {
+            GpuIndexes::U16(indexes) => {
+                assert!(indexes.iter().map(|&index| index as u32).eq(expected))
+            }
+            GpuIndexes::U32(_) => panic!("65536 vertices kept u32 indexes"),
+        }
+    }
 }
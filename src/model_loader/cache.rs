@@ -0,0 +1,510 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use super::{Aabb, BakeSettings, Color, Material, Mesh, Model, Scene, SubMesh, Vertex3D};
+
+const MAGIC: &[u8; 8] = b"MESHCACH";
+/// Bumped whenever the layout below or what the loaders produce changes, so
+/// old caches are parsed again rather than misread.
+const VERSION: u32 = 8;
+
+/// Whether `load_cached` reads and writes `<model>.meshcache` files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Use a cache that matches the model, writing one when there isn't.
+    Use,
+    /// Parse the model and overwrite its cache.
+    Rebuild,
+    Off,
+}
+
+/// Where the cache for `source` goes: next to it, e.g.
+/// `teapot.obj.meshcache`.
+pub fn cache_path(source: &Path) -> PathBuf {
+    let mut path = source.as_os_str().to_owned();
+    path.push(".meshcache");
+    PathBuf::from(path)
+}
+
+/// The source file's size and modification time, which a cache has to match.
+/// Material libraries and textures aren't checked.
+fn stamp(source: &Path) -> io::Result<(u64, u64, u32)> {
+    let metadata = fs::metadata(source)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok((metadata.len(), modified.as_secs(), modified.subsec_nanos()))
+}
+
+/// The scene cached for `source`, or `None` if there's no cache or it's
+/// stale, from another version or corrupt.
+pub fn read(source: &Path) -> Option<Scene> {
+    let bytes = fs::read(cache_path(source)).ok()?;
+    let (meshes, mut reader) = read_meshes(source, &bytes)?;
+    read_occlusion_section(&mut reader)?;
+    match reader.bytes.is_empty() {
+        true => Some(Scene { meshes }),
+        false => None,
+    }
+}
+
+/// The meshes of a cache's `bytes` for `source`, and a reader at what
+/// follows them, if it's up to date.
+fn read_meshes<'a>(source: &Path, bytes: &'a [u8]) -> Option<(Vec<Mesh>, Reader<'a>)> {
+    let directory = source.parent().unwrap_or_else(|| Path::new("."));
+    let mut reader = Reader { bytes };
+    if reader.take(MAGIC.len())? != MAGIC || reader.u32()? != VERSION {
+        return None;
+    }
+    let (size, seconds, nanos) = stamp(source).ok()?;
+    if (reader.u64()?, reader.u64()?, reader.u32()?) != (size, seconds, nanos) {
+        return None;
+    }
+    let meshes = (0..reader.len(1)?)
+        .map(|_| read_mesh(&mut reader, directory))
+        .collect::<Option<Vec<Mesh>>>()?;
+    Some((meshes, reader))
+}
+
+/// The ambient occlusion baked for each mesh of the cache for `source`, if
+/// it has some baked with `key`. The meshes it was baked for are the cached
+/// ones after whatever was done to them once loaded, so `key` has to stand
+/// for that too; see `occlusion_key`.
+pub fn read_occlusion(source: &Path, key: u64) -> Option<Vec<Vec<f32>>> {
+    let bytes = fs::read(cache_path(source)).ok()?;
+    let (_, mut reader) = read_meshes(source, &bytes)?;
+    match read_occlusion_section(&mut reader)? {
+        Some((stored, occlusion)) if stored == key && reader.bytes.is_empty() => Some(occlusion),
+        _ => None,
+    }
+}
+
+/// Adds `occlusion`, one list per mesh, to the cache for `source` in place
+/// of any baked before. Does nothing if there's no up-to-date cache to add
+/// it to.
+pub fn write_occlusion(source: &Path, key: u64, occlusion: &[Vec<f32>]) -> io::Result<()> {
+    let path = cache_path(source);
+    let Ok(bytes) = fs::read(&path) else {
+        return Ok(());
+    };
+    let Some((_, reader)) = read_meshes(source, &bytes) else {
+        return Ok(());
+    };
+    let mut writer = Writer(bytes[..bytes.len() - reader.bytes.len()].to_vec());
+    writer.flag(true);
+    writer.u64(key);
+    writer.len(occlusion.len());
+    for values in occlusion {
+        writer.len(values.len());
+        writer.floats(values);
+    }
+    write_whole(&path, &writer.0)
+}
+
+/// A hash of `settings` and the positions, normals and triangles of
+/// `scene`, which is all the occlusion baked for it depends on.
+pub fn occlusion_key(scene: &Scene, settings: BakeSettings) -> u64 {
+    // FNV-1a, which stays the same from one build to the next.
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let mut add = |value: u32| {
+        for byte in value.to_le_bytes() {
+            hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    add(settings.rays);
+    add(settings.bias.to_bits());
+    for mesh in &scene.meshes {
+        add(mesh.model.vertices.len() as u32);
+        for vertex in &mesh.model.vertices {
+            for value in vertex.position.iter().chain(&vertex.normal) {
+                add(value.to_bits());
+            }
+        }
+        add(mesh.model.indexes.len() as u32);
+        for &index in &mesh.model.indexes {
+            add(index);
+        }
+    }
+    hash
+}
+
+/// The key some occlusion was baked with, and the occlusion for each mesh.
+type Baked = (u64, Vec<Vec<f32>>);
+
+/// The optional section after the meshes: `Some(None)` if there is none,
+/// `None` if it's corrupt.
+fn read_occlusion_section(reader: &mut Reader) -> Option<Option<Baked>> {
+    if !reader.flag()? {
+        return Some(None);
+    }
+    let key = reader.u64()?;
+    let occlusion = (0..reader.len(8)?)
+        .map(|_| {
+            (0..reader.len(4)?)
+                .map(|_| reader.f32())
+                .collect::<Option<Vec<f32>>>()
+        })
+        .collect::<Option<Vec<Vec<f32>>>>()?;
+    Some(Some((key, occlusion)))
+}
+
+/// Saves `scene` as the cache for `source`.
+pub fn write(source: &Path, scene: &Scene) -> io::Result<()> {
+    let directory = source.parent().unwrap_or_else(|| Path::new("."));
+    let (size, seconds, nanos) = stamp(source)?;
+    let mut writer = Writer(Vec::new());
+    writer.0.extend_from_slice(MAGIC);
+    writer.u32(VERSION);
+    writer.u64(size);
+    writer.u64(seconds);
+    writer.u32(nanos);
+    writer.len(scene.meshes.len());
+    for mesh in &scene.meshes {
+        writer.str(&mesh.name);
+        write_model(&mut writer, &mesh.model, directory);
+    }
+    // No occlusion until `write_occlusion` adds some.
+    writer.flag(false);
+    write_whole(&cache_path(source), &writer.0)
+}
+
+/// Written whole, then renamed, so a reader never sees half a cache.
+fn write_whole(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".tmp");
+    fs::write(&partial, bytes)?;
+    fs::rename(&partial, path)
+}
+
+fn write_model(writer: &mut Writer, model: &Model, directory: &Path) {
+    writer.len(model.vertices.len());
+    for vertex in &model.vertices {
+        writer.floats(&vertex.position);
+        writer.floats(&vertex.normal);
+        writer.floats(&vertex.tex_coords);
+    }
+    writer.len(model.colors.len());
+    for color in &model.colors {
+        let (r, g, b) = color.color;
+        writer.floats(&[r, g, b]);
+    }
+    writer.len(model.indexes.len());
+    for &index in &model.indexes {
+        writer.0.extend_from_slice(&index.to_le_bytes());
+    }
+    writer.len(model.lines.len());
+    for &index in &model.lines {
+        writer.0.extend_from_slice(&index.to_le_bytes());
+    }
+    writer.len(model.materials.len());
+    for material in &model.materials {
+        writer.str(&material.name);
+        writer.floats(&material.ambient);
+        writer.floats(&material.diffuse);
+        writer.floats(&material.specular);
+        writer.floats(&[material.shininess, material.opacity]);
+        // Kept relative to the model, which may be opened from elsewhere.
+        let maps = [
+            &material.diffuse_map,
+            &material.normal_map,
+            &material.metallic_roughness_map,
+        ];
+        for map in maps {
+            let map = map.as_ref().map(|map| {
+                map.strip_prefix(directory)
+                    .unwrap_or(map)
+                    .to_string_lossy()
+                    .to_string()
+            });
+            writer.flag(map.is_some());
+            if let Some(map) = map {
+                writer.str(&map);
+            }
+        }
+        for value in [material.reflectivity, material.roughness] {
+            writer.flag(value.is_some());
+            if let Some(value) = value {
+                writer.floats(&[value]);
+            }
+        }
+    }
+    writer.len(model.sub_meshes.len());
+    for sub_mesh in &model.sub_meshes {
+        writer.usize(sub_mesh.material);
+        writer.usize(sub_mesh.range.start);
+        writer.usize(sub_mesh.range.end);
+    }
+    writer.floats(&model.bounds.min);
+    writer.floats(&model.bounds.max);
+    writer.flag(model.has_normals);
+    writer.flag(model.has_tex_coords);
+}
+
+fn read_mesh(reader: &mut Reader, directory: &Path) -> Option<Mesh> {
+    let name = reader.str()?;
+    let vertices = (0..reader.len(32)?)
+        .map(|_| {
+            Some(Vertex3D {
+                position: reader.floats()?,
+                normal: reader.floats()?,
+                tex_coords: reader.floats()?,
+            })
+        })
+        .collect::<Option<Vec<Vertex3D>>>()?;
+    let colors = (0..reader.len(12)?)
+        .map(|_| {
+            let [r, g, b] = reader.floats()?;
+            Some(Color { color: (r, g, b) })
+        })
+        .collect::<Option<Vec<Color>>>()?;
+    let indexes = (0..reader.len(4)?)
+        .map(|_| Some(u32::from_le_bytes(reader.array()?)))
+        .collect::<Option<Vec<u32>>>()?;
+    let lines = (0..reader.len(4)?)
+        .map(|_| Some(u32::from_le_bytes(reader.array()?)))
+        .collect::<Option<Vec<u32>>>()?;
+    let materials = (0..reader.len(1)?)
+        .map(|_| {
+            Some(Material {
+                name: reader.str()?,
+                ambient: reader.floats()?,
+                diffuse: reader.floats()?,
+                specular: reader.floats()?,
+                shininess: reader.f32()?,
+                opacity: reader.f32()?,
+                diffuse_map: match reader.flag()? {
+                    true => Some(directory.join(reader.str()?)),
+                    false => None,
+                },
+                normal_map: match reader.flag()? {
+                    true => Some(directory.join(reader.str()?)),
+                    false => None,
+                },
+                metallic_roughness_map: match reader.flag()? {
+                    true => Some(directory.join(reader.str()?)),
+                    false => None,
+                },
+                reflectivity: match reader.flag()? {
+                    true => Some(reader.f32()?),
+                    false => None,
+                },
+                roughness: match reader.flag()? {
+                    true => Some(reader.f32()?),
+                    false => None,
+                },
+            })
+        })
+        .collect::<Option<Vec<Material>>>()?;
+    let sub_meshes = (0..reader.len(1)?)
+        .map(|_| {
+            Some(SubMesh {
+                material: reader.usize()?,
+                range: reader.usize()?..reader.usize()?,
+            })
+        })
+        .collect::<Option<Vec<SubMesh>>>()?;
+    let bounds = Aabb {
+        min: reader.floats()?,
+        max: reader.floats()?,
+    };
+    let model = Model {
+        vertices,
+        colors,
+        indexes,
+        lines,
+        materials,
+        sub_meshes,
+        bounds,
+        has_normals: reader.flag()?,
+        has_tex_coords: reader.flag()?,
+        patches: Vec::new(),
+        lods: Vec::new(),
+        occlusion: Vec::new(),
+    };
+    // A cache that would send the renderer out of bounds counts as corrupt.
+    let valid_indexes = model
+        .indexes
+        .iter()
+        .chain(&model.lines)
+        .all(|&index| (index as usize) < model.vertices.len())
+        && model.lines.len().is_multiple_of(2);
+    let valid_colors = model.colors.is_empty() || model.colors.len() == model.vertices.len();
+    let valid_sub_meshes = model.sub_meshes.iter().all(|sub_mesh| {
+        sub_mesh.material < model.materials.len()
+            && sub_mesh.range.start <= sub_mesh.range.end
+            && sub_mesh.range.end <= model.indexes.len()
+    });
+    match valid_indexes && valid_colors && valid_sub_meshes {
+        true => Some(Mesh { name, model }),
+        false => None,
+    }
+}
+
+/// Little-endian fields appended to a buffer.
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.u64(value as u64);
+    }
+
+    fn len(&mut self, len: usize) {
+        self.usize(len);
+    }
+
+    fn flag(&mut self, flag: bool) {
+        self.0.push(flag as u8);
+    }
+
+    fn floats(&mut self, values: &[f32]) {
+        for value in values {
+            self.0.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn str(&mut self, value: &str) {
+        self.len(value.len());
+        self.0.extend_from_slice(value.as_bytes());
+    }
+}
+
+/// Reads fields back in the order `Writer` wrote them, giving `None` once
+/// the data runs out or doesn't make sense.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        if count > self.bytes.len() {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.take(N)?.try_into().ok()
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.array()?))
+    }
+
+    fn usize(&mut self) -> Option<usize> {
+        usize::try_from(self.u64()?).ok()
+    }
+
+    /// A count of items that take at least `item_size` bytes each, checked
+    /// against what's left so a corrupt count can't ask for huge buffers.
+    fn len(&mut self, item_size: usize) -> Option<usize> {
+        let len = self.usize()?;
+        match len.checked_mul(item_size)? <= self.bytes.len() {
+            true => Some(len),
+            false => None,
+        }
+    }
+
+    fn flag(&mut self) -> Option<bool> {
+        match self.take(1)? {
+            [0] => Some(false),
+            [1] => Some(true),
+            _ => None,
+        }
+    }
+
+    fn floats<const N: usize>(&mut self) -> Option<[f32; N]> {
+        let mut values = [0.0; N];
+        for value in &mut values {
+            *value = self.f32()?;
+        }
+        Some(values)
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = self.len(1)?;
+        String::from_utf8(self.take(len)?.to_vec()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_loader::tests::{assert_same_scene, temp_file};
+    use crate::model_loader::{load_obj, LoadProgress};
+
+    /// A copy of the teapot in the temporary directory, for its cache to go
+    /// beside, with the written cache's bytes.
+    fn cached_teapot(name: &str) -> (PathBuf, Scene, Vec<u8>) {
+        let source = temp_file(name, include_bytes!("../teapot-3.obj"));
+        let scene = load_obj(source.to_str().unwrap(), &LoadProgress::default()).unwrap();
+        write(&source, &scene).unwrap();
+        let bytes = fs::read(cache_path(&source)).unwrap();
+        (source, scene, bytes)
+    }
+
+    fn remove(source: &Path) {
+        fs::remove_file(cache_path(source)).unwrap();
+        fs::remove_file(source).unwrap();
+    }
+
+    #[test]
+    fn teapot_round_trips_exactly() {
+        let (source, scene, _) = cached_teapot("cache-round-trip.obj");
+        let cached = read(&source).expect("the cache was rejected");
+        assert_same_scene(&cached, &scene);
+        let model = &cached.meshes[0].model;
+        assert_eq!(model.has_normals, scene.meshes[0].model.has_normals);
+        assert_eq!(model.bounds, scene.meshes[0].model.bounds);
+        remove(&source);
+    }
+
+    #[test]
+    fn truncated_cache_is_rejected() {
+        let (source, _, bytes) = cached_teapot("cache-truncated.obj");
+        let lengths = (0..bytes.len())
+            .step_by(bytes.len() / 100)
+            .chain([bytes.len() - 1]);
+        for length in lengths {
+            fs::write(cache_path(&source), &bytes[..length]).unwrap();
+            assert!(
+                read(&source).is_none(),
+                "read {} of {} bytes",
+                length,
+                bytes.len()
+            );
+        }
+        remove(&source);
+    }
+
+    #[test]
+    fn other_version_is_rejected() {
+        let (source, _, mut bytes) = cached_teapot("cache-version.obj");
+        for version in [VERSION - 1, VERSION + 1] {
+            bytes[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&version.to_le_bytes());
+            fs::write(cache_path(&source), &bytes).unwrap();
+            assert!(read(&source).is_none(), "read version {}", version);
+        }
+        remove(&source);
+    }
+}
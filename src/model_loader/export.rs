@@ -0,0 +1,269 @@
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use super::{Material, Model, Scene};
+
+/// Writes every mesh of `scene` to an OBJ file at `path`, each as an `o`
+/// object, and their materials to an MTL library of the same name beside
+/// it, e.g. `fixed.obj` and `fixed.mtl`, for `load_obj` to read back. Each
+/// vertex gets its own position, normal and texture coordinates, with the
+/// color after the position if the model has vertex colors, and the numbers
+/// are written with as many digits as it takes to read back exactly the
+/// same. Materials with the same name as an earlier one are only written
+/// once. Patches and levels of detail aren't kept, nor are
+/// metallic-roughness maps, which MTL has no statement for.
+pub fn export_obj(scene: &Scene, path: &Path) -> io::Result<()> {
+    let library = path.with_extension("mtl");
+    let mut out = BufWriter::new(File::create(path)?);
+    if let Some(name) = library.file_name() {
+        writeln!(out, "mtllib {}", name.to_string_lossy())?;
+    }
+    // OBJ indexes count from 1 across the whole file.
+    let mut first = 1;
+    for mesh in &scene.meshes {
+        write_model(&mut out, &mesh.name, &mesh.model, first)?;
+        first += mesh.model.vertices.len();
+    }
+    out.flush()?;
+
+    let materials = scene.meshes.iter().flat_map(|mesh| &mesh.model.materials);
+    write_library(&library, materials)
+}
+
+fn write_model(out: &mut impl Write, name: &str, model: &Model, first: usize) -> io::Result<()> {
+    writeln!(out, "o {}", name)?;
+    for (index, vertex) in model.vertices.iter().enumerate() {
+        let [x, y, z] = vertex.position;
+        match model.colors.get(index) {
+            Some(color) => {
+                let (r, g, b) = color.color;
+                writeln!(out, "v {} {} {} {} {} {}", x, y, z, r, g, b)?;
+            }
+            None => writeln!(out, "v {} {} {}", x, y, z)?,
+        }
+    }
+    for vertex in &model.vertices {
+        let [x, y, z] = vertex.normal;
+        writeln!(out, "vn {} {} {}", x, y, z)?;
+    }
+    if model.has_tex_coords {
+        for vertex in &model.vertices {
+            let [u, v] = vertex.tex_coords;
+            writeln!(out, "vt {} {}", u, v)?;
+        }
+    }
+
+    let corner = |index: u32| {
+        let index = first + index as usize;
+        match model.has_tex_coords {
+            true => format!("{}/{}/{}", index, index, index),
+            false => format!("{}//{}", index, index),
+        }
+    };
+    for sub_mesh in &model.sub_meshes {
+        writeln!(out, "usemtl {}", model.materials[sub_mesh.material].name)?;
+        for triangle in model.indexes[sub_mesh.range.clone()].chunks_exact(3) {
+            writeln!(
+                out,
+                "f {} {} {}",
+                corner(triangle[0]),
+                corner(triangle[1]),
+                corner(triangle[2])
+            )?;
+        }
+    }
+    for segment in model.lines.chunks_exact(2) {
+        let (a, b) = (first + segment[0] as usize, first + segment[1] as usize);
+        writeln!(out, "l {} {}", a, b)?;
+    }
+    Ok(())
+}
+
+fn write_library<'a>(path: &Path, materials: impl Iterator<Item = &'a Material>) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let mut written = HashSet::new();
+    for material in materials {
+        if !written.insert(material.name.as_str()) {
+            continue;
+        }
+        writeln!(out, "newmtl {}", material.name)?;
+        let colors = [
+            ("Ka", material.ambient),
+            ("Kd", material.diffuse),
+            ("Ks", material.specular),
+        ];
+        for (statement, [r, g, b]) in colors {
+            writeln!(out, "{} {} {} {}", statement, r, g, b)?;
+        }
+        writeln!(out, "Ns {}", material.shininess)?;
+        writeln!(out, "d {}", material.opacity)?;
+        if let Some(reflectivity) = material.reflectivity {
+            writeln!(out, "Pm {}", reflectivity)?;
+        }
+        if let Some(roughness) = material.roughness {
+            writeln!(out, "Pr {}", roughness)?;
+        }
+        // Absolute, since the export may be somewhere else than the model.
+        let maps = [
+            ("map_Kd", &material.diffuse_map),
+            ("norm", &material.normal_map),
+        ];
+        for (statement, map) in maps {
+            if let Some(map) = map {
+                let map = fs::canonicalize(map).unwrap_or_else(|_| map.clone());
+                writeln!(out, "{} {}", statement, map.display())?;
+            }
+        }
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_loader::tests::temp_file;
+    use crate::model_loader::{load_obj, Color, LoadProgress, Mesh, SubMesh, Vertex3D};
+    use crate::primitives;
+    use std::collections::HashMap;
+
+    /// Equal as far as writing them out in decimal and reading them back
+    /// can be trusted to keep.
+    fn assert_close(values: &[f32], expected: &[f32]) {
+        assert_eq!(values.len(), expected.len());
+        for (value, expected) in values.iter().zip(expected) {
+            let tolerance = 1e-6 * expected.abs().max(1.0);
+            assert!(
+                (value - expected).abs() <= tolerance,
+                "{:?} != {:?}",
+                values,
+                expected
+            );
+        }
+    }
+
+    fn attributes(vertex: &Vertex3D) -> Vec<f32> {
+        let Vertex3D {
+            position,
+            normal,
+            tex_coords,
+        } = vertex;
+        [&position[..], normal, tex_coords].concat()
+    }
+
+    /// A sphere colored by where its vertices are, in two materials, then a
+    /// plain cube with an edge drawn, so the second object's indexes start
+    /// after the first's vertices.
+    fn scene() -> Scene {
+        let mut sphere = primitives::uv_sphere(1.0, 6, 12);
+        sphere.colors = sphere
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let [x, y, z] = vertex.position.map(|value| value * 0.5 + 0.5);
+                Color { color: (x, y, z) }
+            })
+            .collect();
+        let half = sphere.indexes.len() / 6 * 3;
+        sphere.materials = ["top", "bottom"]
+            .map(|name| Material {
+                name: name.to_string(),
+                diffuse: [0.25, 0.5, 0.75],
+                ..Default::default()
+            })
+            .to_vec();
+        sphere.sub_meshes = vec![
+            SubMesh {
+                material: 0,
+                range: 0..half,
+            },
+            SubMesh {
+                material: 1,
+                range: half..sphere.indexes.len(),
+            },
+        ];
+        let mut cube = primitives::cube(2.0);
+        cube.lines = vec![0, 1];
+        let meshes = [("sphere", sphere), ("cube", cube)];
+        Scene {
+            meshes: meshes
+                .into_iter()
+                .map(|(name, model)| Mesh {
+                    name: name.to_string(),
+                    model,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn export_loads_back_the_same() {
+        let scene = scene();
+        let path = temp_file("export.obj", b"");
+        export_obj(&scene, &path).unwrap();
+        let loaded = load_obj(path.to_str().unwrap(), &LoadProgress::default());
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("mtl")).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.meshes.len(), scene.meshes.len());
+        for (mesh, expected) in loaded.meshes.iter().zip(&scene.meshes) {
+            let (model, expected_model) = (&mesh.model, &expected.model);
+            assert_eq!(mesh.name, expected.name);
+            assert_eq!(model.indexes.len(), expected_model.indexes.len());
+            // Loading numbers the vertices in the order the faces use them,
+            // leaving out any that none do, so the indexes are compared
+            // through the renumbering.
+            let mut renumbered = HashMap::new();
+            for (&index, &expected_index) in model.indexes.iter().zip(&expected_model.indexes) {
+                let earlier = *renumbered.entry(expected_index).or_insert(index);
+                assert_eq!(earlier, index, "vertex {} loaded twice", expected_index);
+                let (vertex, expected_vertex) = (
+                    &model.vertices[index as usize],
+                    &expected_model.vertices[expected_index as usize],
+                );
+                assert_close(&attributes(vertex), &attributes(expected_vertex));
+                // Once any vertex in the file has a color, the rest are white.
+                let color = model.colors[index as usize].color;
+                let expected_color = match expected_model.colors.get(expected_index as usize) {
+                    Some(color) => color.color,
+                    None => Color::WHITE.color,
+                };
+                let rgb = |(r, g, b): (f32, f32, f32)| [r, g, b];
+                assert_close(&rgb(color), &rgb(expected_color));
+            }
+            // `l` has no normals to give, so the ends of lines load as
+            // vertices of their own, at the same places.
+            assert_eq!(model.lines.len(), expected_model.lines.len());
+            for (&index, &expected_index) in model.lines.iter().zip(&expected_model.lines) {
+                let position = model.vertices[index as usize].position;
+                assert_close(
+                    &position,
+                    &expected_model.vertices[expected_index as usize].position,
+                );
+            }
+            let line_ends = model.lines.iter().collect::<HashSet<_>>();
+            assert_eq!(model.vertices.len(), renumbered.len() + line_ends.len());
+
+            let materials = |model: &Model| {
+                model
+                    .sub_meshes
+                    .iter()
+                    .map(|sub_mesh| {
+                        let material = &model.materials[sub_mesh.material];
+                        (
+                            material.name.clone(),
+                            material.diffuse,
+                            sub_mesh.range.clone(),
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(materials(model), materials(expected_model));
+            assert!(model.has_normals);
+            assert_eq!(model.has_tex_coords, expected_model.has_tex_coords);
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use std::fs;
+use std::path::Path;
+
+use serde_json::Value;
+
+use super::{
+    compute_normals, Aabb, LoadProgress, Material, Model, ModelLoadError, SubMesh, Vertex3D,
+    MAX_VERTICES,
+};
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+const MODE_TRIANGLES: u64 = 4;
+
+fn invalid(reason: String) -> ModelLoadError {
+    ModelLoadError::InvalidData(format!("glTF: {}", reason))
+}
+
+/// Loads the first mesh of a `.gltf` (with external or `data:` buffers) or a
+/// self-contained `.glb`. Every triangle primitive becomes one sub-mesh, with
+/// its base color factor standing in for the Phong material colors.
+pub fn load_gltf(path: &str, progress: &LoadProgress) -> Result<Model, ModelLoadError> {
+    let bytes = progress.read_file(Path::new(path))?;
+    let directory = Path::new(path).parent().unwrap_or_else(|| Path::new("."));
+    parse_gltf(bytes, directory)
+}
+
+/// Parses a `.gltf` or `.glb` file's bytes, reading external buffers from
+/// `directory`.
+fn parse_gltf(bytes: Vec<u8>, directory: &Path) -> Result<Model, ModelLoadError> {
+    let (json, glb_buffer) = if read_u32(&bytes, 0) == Some(GLB_MAGIC) {
+        split_glb(&bytes)?
+    } else {
+        (bytes, None)
+    };
+    let document: Value = serde_json::from_slice(&json)
+        .map_err(|error| invalid(format!("invalid JSON: {}", error)))?;
+
+    let mut buffers: Vec<Vec<u8>> = Vec::new();
+    for (index, buffer) in array(&document, "buffers").iter().enumerate() {
+        let data = match buffer["uri"].as_str() {
+            Some(uri) => match uri.strip_prefix("data:") {
+                Some(data) => {
+                    let (_, encoded) = data
+                        .split_once(";base64,")
+                        .ok_or_else(|| invalid(format!("buffer {} is not base64", index)))?;
+                    decode_base64(encoded)?
+                }
+                None => fs::read(directory.join(uri))?,
+            },
+            // Only a GLB's first buffer may leave the URI out.
+            None => glb_buffer
+                .clone()
+                .ok_or_else(|| invalid(format!("buffer {} has no data", index)))?,
+        };
+        buffers.push(data);
+    }
+
+    let mesh = array(&document, "meshes")
+        .first()
+        .ok_or_else(|| invalid("file has no meshes".to_string()))?;
+
+    let mut model = Model {
+        vertices: Vec::new(),
+        colors: Vec::new(),
+        indexes: Vec::new(),
+        lines: Vec::new(),
+        materials: Vec::new(),
+        sub_meshes: Vec::new(),
+        bounds: Aabb::default(),
+        has_normals: true,
+        has_tex_coords: false,
+        patches: Vec::new(),
+        lods: Vec::new(),
+        occlusion: Vec::new(),
+    };
+    let mut missing_normals = false;
+
+    for primitive in array(mesh, "primitives") {
+        if primitive["mode"].as_u64().unwrap_or(MODE_TRIANGLES) != MODE_TRIANGLES {
+            eprintln!("warning: skipping a glTF primitive that is not a triangle list");
+            continue;
+        }
+        let attributes = &primitive["attributes"];
+        let position_accessor = attributes["POSITION"]
+            .as_u64()
+            .ok_or_else(|| invalid("primitive has no POSITION".to_string()))?;
+        let positions = read_accessor(&document, &buffers, position_accessor, "VEC3")?;
+        let count = positions.len();
+        let normals = match attributes["NORMAL"].as_u64() {
+            Some(accessor) => read_accessor(&document, &buffers, accessor, "VEC3")?,
+            None => Vec::new(),
+        };
+        let texcoords = match attributes["TEXCOORD_0"].as_u64() {
+            Some(accessor) => read_accessor(&document, &buffers, accessor, "VEC2")?,
+            None => Vec::new(),
+        };
+        let indexes = match primitive["indices"].as_u64() {
+            Some(accessor) => read_accessor(&document, &buffers, accessor, "SCALAR")?
+                .iter()
+                .map(|index| index[0] as usize)
+                .collect::<Vec<usize>>(),
+            None => (0..count).collect(),
+        };
+
+        let base = model.vertices.len();
+        if base + count > MAX_VERTICES {
+            return Err(ModelLoadError::TooManyVertices {
+                count: base + count,
+            });
+        }
+        for (i, position) in positions.iter().enumerate() {
+            let normal = normals.get(i).map(|n| [n[0], n[1], n[2]]);
+            // glTF puts the UV origin at the top left, OBJ and our textures
+            // at the bottom left.
+            let texcoord = texcoords.get(i).map(|t| [t[0], 1.0 - t[1]]);
+            model.vertices.push(Vertex3D {
+                position: [position[0], position[1], position[2]],
+                normal: normal.unwrap_or_default(),
+                tex_coords: texcoord.unwrap_or_default(),
+            });
+        }
+
+        let start = model.indexes.len();
+        for index in indexes {
+            if index >= count {
+                return Err(invalid(format!(
+                    "index {} out of range (primitive has {} vertices)",
+                    index, count
+                )));
+            }
+            model.indexes.push((base + index) as u32);
+        }
+        model
+            .materials
+            .push(read_material(&document, primitive, directory));
+        model.sub_meshes.push(SubMesh {
+            material: model.materials.len() - 1,
+            range: start..model.indexes.len(),
+        });
+        missing_normals |= normals.is_empty();
+        model.has_tex_coords |= !texcoords.is_empty();
+    }
+
+    model.bounds = Aabb::from_vertices(&model.vertices);
+    model.has_normals = !missing_normals;
+    if missing_normals {
+        compute_normals(&mut model);
+    }
+    Ok(model)
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value[key].as_array().map(Vec::as_slice).unwrap_or(&[])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let data = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+}
+
+/// Splits a GLB container into its JSON chunk and optional BIN chunk.
+fn split_glb(bytes: &[u8]) -> Result<(Vec<u8>, Option<Vec<u8>>), ModelLoadError> {
+    let length = read_u32(bytes, 8).ok_or_else(|| invalid("truncated GLB header".to_string()))?;
+    let end = (length as usize).min(bytes.len());
+    let mut offset = 12;
+    let mut json: Option<Vec<u8>> = None;
+    let mut bin: Option<Vec<u8>> = None;
+    while offset + 8 <= end {
+        let chunk_length = read_u32(bytes, offset).unwrap() as usize;
+        let chunk_type = read_u32(bytes, offset + 4).unwrap();
+        let data = bytes
+            .get(offset + 8..offset + 8 + chunk_length)
+            .ok_or_else(|| invalid("truncated GLB chunk".to_string()))?;
+        match chunk_type {
+            CHUNK_JSON => json = Some(data.to_vec()),
+            CHUNK_BIN => bin = Some(data.to_vec()),
+            _ => (),
+        }
+        offset += 8 + chunk_length;
+    }
+    let json = json.ok_or_else(|| invalid("GLB has no JSON chunk".to_string()))?;
+    Ok((json, bin))
+}
+
+/// Reads an accessor as rows of floats, one row per element, converting
+/// integer components (normalized or not) on the way. Fails unless the
+/// accessor's elements are the `expected` type, such as `VEC3`.
+fn read_accessor(
+    document: &Value,
+    buffers: &[Vec<u8>],
+    index: u64,
+    expected: &str,
+) -> Result<Vec<Vec<f32>>, ModelLoadError> {
+    let accessor = &document["accessors"][index as usize];
+    let view_index = accessor["bufferView"]
+        .as_u64()
+        .ok_or_else(|| invalid(format!("accessor {} has no buffer view", index)))?;
+    let view = &document["bufferViews"][view_index as usize];
+    let buffer = buffers
+        .get(view["buffer"].as_u64().unwrap_or(0) as usize)
+        .ok_or_else(|| invalid(format!("buffer view {} has no buffer", view_index)))?;
+
+    let components = match accessor["type"].as_str() {
+        Some(kind) if kind != expected => {
+            return Err(invalid(format!(
+                "accessor {} is {}, it should be {}",
+                index, kind, expected
+            )))
+        }
+        Some("SCALAR") => 1,
+        Some("VEC2") => 2,
+        Some("VEC3") => 3,
+        Some("VEC4") => 4,
+        other => return Err(invalid(format!("unsupported accessor type {:?}", other))),
+    };
+    let component_type = accessor["componentType"].as_u64().unwrap_or(0);
+    let size = match component_type {
+        5120 | 5121 => 1,
+        5122 | 5123 => 2,
+        5125 | 5126 => 4,
+        other => return Err(invalid(format!("unsupported component type {}", other))),
+    };
+    let normalized = accessor["normalized"].as_bool().unwrap_or(false);
+    let count = accessor["count"].as_u64().unwrap_or(0) as usize;
+    let start = view["byteOffset"].as_u64().unwrap_or(0) as usize
+        + accessor["byteOffset"].as_u64().unwrap_or(0) as usize;
+    let stride = view["byteStride"]
+        .as_u64()
+        .map(|stride| stride as usize)
+        .unwrap_or(components * size);
+
+    // `count` comes from the file, so the buffer has to be checked to hold
+    // that many elements before anything is allocated for them.
+    let end = match count {
+        0 => start,
+        count => (count - 1)
+            .checked_mul(stride)
+            .and_then(|last| last.checked_add(start + components * size))
+            .unwrap_or(usize::MAX),
+    };
+    if end > buffer.len() {
+        return Err(invalid(format!("accessor {} reads past its buffer", index)));
+    }
+
+    let mut rows: Vec<Vec<f32>> = Vec::with_capacity(count);
+    for element in 0..count {
+        let mut row: Vec<f32> = Vec::with_capacity(components);
+        for component in 0..components {
+            let offset = start + element * stride + component * size;
+            let data = &buffer[offset..offset + size];
+            let value = match component_type {
+                5120 => {
+                    let value = data[0] as i8 as f32;
+                    if normalized {
+                        (value / 127.0).max(-1.0)
+                    } else {
+                        value
+                    }
+                }
+                5121 => {
+                    let value = data[0] as f32;
+                    if normalized {
+                        value / 255.0
+                    } else {
+                        value
+                    }
+                }
+                5122 => {
+                    let value = i16::from_le_bytes([data[0], data[1]]) as f32;
+                    if normalized {
+                        (value / 32767.0).max(-1.0)
+                    } else {
+                        value
+                    }
+                }
+                5123 => {
+                    let value = u16::from_le_bytes([data[0], data[1]]) as f32;
+                    if normalized {
+                        value / 65535.0
+                    } else {
+                        value
+                    }
+                }
+                5125 => u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as f32,
+                _ => f32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+            };
+            row.push(value);
+        }
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+/// Keeps a glTF material's metallic-roughness terms for the PBR shading,
+/// and maps them onto the viewer's Phong terms for the others: the base
+/// color drives ambient and diffuse, roughness dims and widens the
+/// highlight, and an explicit metallic factor sets how much the
+/// surroundings are mirrored. A blended base color's alpha is the opacity.
+fn read_material(document: &Value, primitive: &Value, directory: &Path) -> Material {
+    let index = match primitive["material"].as_u64() {
+        Some(index) => index as usize,
+        None => return Material::default(),
+    };
+    let material = &document["materials"][index];
+    let pbr = &material["pbrMetallicRoughness"];
+    let [red, green, blue, alpha] = pbr["baseColorFactor"]
+        .as_array()
+        .map(|factor| {
+            let channel = |i: usize| factor.get(i).and_then(Value::as_f64).unwrap_or(1.0) as f32;
+            [channel(0), channel(1), channel(2), channel(3)]
+        })
+        .unwrap_or([1.0; 4]);
+    let base = [red, green, blue];
+    // Alpha is ignored unless the material asks to blend; masking isn't
+    // supported, so masked materials stay opaque.
+    let opacity = match material["alphaMode"].as_str() {
+        Some("BLEND") => alpha.clamp(0.0, 1.0),
+        _ => 1.0,
+    };
+    let roughness = pbr["roughnessFactor"].as_f64().unwrap_or(1.0) as f32;
+    // The spec defaults to fully metallic, which would turn every material
+    // without a factor into a mirror.
+    let reflectivity = pbr["metallicFactor"]
+        .as_f64()
+        .map(|metallic| (metallic as f32).clamp(0.0, 1.0));
+
+    // Only images in files of their own; embedded ones aren't decoded.
+    let image_file = |texture: &Value| {
+        texture["index"]
+            .as_u64()
+            .and_then(|texture| document["textures"][texture as usize]["source"].as_u64())
+            .and_then(|image| document["images"][image as usize]["uri"].as_str())
+            .filter(|uri| !uri.starts_with("data:"))
+            .map(|uri| directory.join(uri))
+    };
+
+    Material {
+        name: material["name"]
+            .as_str()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("material {}", index)),
+        ambient: base.map(|channel| channel / 3.0),
+        diffuse: base,
+        specular: [1.0 - roughness; 3],
+        shininess: 2.0 + (1.0 - roughness) * 126.0,
+        diffuse_map: image_file(&pbr["baseColorTexture"]),
+        normal_map: image_file(&material["normalTexture"]),
+        reflectivity,
+        roughness: Some(roughness.clamp(0.0, 1.0)),
+        metallic_roughness_map: image_file(&pbr["metallicRoughnessTexture"]),
+        opacity,
+    }
+}
+
+fn decode_base64(encoded: &str) -> Result<Vec<u8>, ModelLoadError> {
+    let mut output: Vec<u8> = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut accumulator: u32 = 0;
+    let mut bits = 0;
+    for byte in encoded.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            b'\r' | b'\n' | b' ' => continue,
+            _ => return Err(invalid("invalid base64 data".to_string())),
+        };
+        accumulator = (accumulator << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((accumulator >> bits) as u8);
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One triangle: three float positions, then three `u16` indexes.
+    const TRIANGLE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "buffers": [{
+            "byteLength": 44,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA="
+        }],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3" },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "indices": 1 }] }]
+    }"#;
+
+    fn parse(json: &str) -> Result<Model, ModelLoadError> {
+        parse_gltf(json.as_bytes().to_vec(), Path::new("."))
+    }
+
+    fn parse_error(json: &str) -> String {
+        match parse(json) {
+            Err(ModelLoadError::InvalidData(reason)) => reason,
+            Err(error) => panic!("expected InvalidData, got {:?}", error),
+            Ok(_) => panic!("loaded a broken file"),
+        }
+    }
+
+    #[test]
+    fn embedded_triangle() {
+        let model = parse(TRIANGLE).unwrap();
+        assert_eq!(model.vertices.len(), 3);
+        assert_eq!(model.indexes, [0, 1, 2]);
+        let positions = model.vertices.iter().map(|vertex| vertex.position);
+        assert_eq!(
+            positions.collect::<Vec<[f32; 3]>>(),
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn positions_must_be_vec3() {
+        let scalar = TRIANGLE.replacen(r#""type": "VEC3""#, r#""type": "SCALAR""#, 1);
+        assert!(parse_error(&scalar).contains("should be VEC3"));
+    }
+
+    #[test]
+    fn count_past_the_buffer_fails_before_reading() {
+        let huge = TRIANGLE.replacen(r#""count": 3"#, r#""count": 100000000000000"#, 1);
+        assert!(parse_error(&huge).contains("reads past its buffer"));
+    }
+}
@@ -0,0 +1,111 @@
+use std::f32::consts::TAU;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rayon::prelude::*;
+
+use super::{tangents, LoadProgress, Model, ModelLoadError};
+use crate::bvh::Bvh;
+use crate::math::Vec3;
+use crate::picking::Ray;
+
+/// Vertices baked between checks for the load being called off.
+const CHUNK_SIZE: usize = 1024;
+/// Turns each ray from the one before by this much around the normal, so
+/// however many there are they spread evenly.
+const GOLDEN_ANGLE: f32 = 2.399_963;
+
+/// How `bake_occlusion` samples each vertex's hemisphere.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BakeSettings {
+    /// Rays cast from each vertex.
+    pub rays: u32,
+    /// How far off its vertex each ray starts, along the normal, as a
+    /// fraction of the model's bounding radius, so it doesn't hit the
+    /// triangles around the vertex itself.
+    pub bias: f32,
+}
+
+/// A vertex's baked ambient occlusion as the renderer uploads it.
+#[derive(Copy, Clone)]
+pub struct Occlusion {
+    pub occlusion: f32,
+}
+
+implement_vertex!(Occlusion, occlusion);
+
+impl Occlusion {
+    /// For models without any baked, which leaves their lighting as it was.
+    pub const OPEN: Occlusion = Occlusion { occlusion: 1.0 };
+}
+
+/// Fills `model.occlusion` with the fraction of the rays from each vertex
+/// that get away without hitting the model. They're spread over the
+/// hemisphere around its normal weighted toward the normal, as the light
+/// reaching a surface is.
+///
+/// The vertices are shared out over rayon's thread pool, and `report` is
+/// called with the percentage done each time another tenth is, from
+/// whichever thread finished it. Fails if the load is called off partway,
+/// leaving the model as it was.
+pub fn bake_occlusion(
+    model: &mut Model,
+    settings: BakeSettings,
+    progress: &LoadProgress,
+    report: impl Fn(usize) + Sync,
+) -> Result<(), ModelLoadError> {
+    let bvh = Bvh::build(model);
+    let bias = settings.bias * model.stats().radius;
+    let total = model.vertices.len();
+    let done = AtomicUsize::new(0);
+    let mut occlusion = vec![1.0; total];
+    occlusion
+        .par_chunks_mut(CHUNK_SIZE)
+        .enumerate()
+        .try_for_each(|(chunk, values)| {
+            if progress.is_cancelled() {
+                return Err(io::Error::other("load cancelled"));
+            }
+            for (offset, value) in values.iter_mut().enumerate() {
+                let vertex = chunk * CHUNK_SIZE + offset;
+                *value = vertex_occlusion(model, &bvh, vertex, settings.rays, bias);
+            }
+            let before = done.fetch_add(values.len(), Ordering::Relaxed);
+            let after = before + values.len();
+            if before * 10 / total != after * 10 / total {
+                report(after * 100 / total);
+            }
+            Ok(())
+        })?;
+    model.occlusion = occlusion;
+    Ok(())
+}
+
+/// The fraction of `rays` rays from `vertex`, `bias` above it, that miss
+/// the model, or 1 if it has no normal to face them along.
+fn vertex_occlusion(model: &Model, bvh: &Bvh, vertex: usize, rays: u32, bias: f32) -> f32 {
+    let position = Vec3::from(model.vertices[vertex].position);
+    let normal = Vec3::from(model.vertices[vertex].normal).normalize();
+    if rays == 0 || normal.length() == 0.0 {
+        return 1.0;
+    }
+    let tangent = Vec3::from(tangents::perpendicular(normal.to_array()));
+    let bitangent = normal.cross(tangent);
+    let origin = position + normal * bias;
+    // Each vertex starts its spiral somewhere else, so neighbours don't all
+    // miss the same gaps between the rays.
+    let turn = (vertex as u32).wrapping_mul(2_654_435_769) as f32 / 4_294_967_296.0 * TAU;
+    let open = (0..rays)
+        .filter(|&ray| {
+            // Even over the disk under the hemisphere, then lifted onto it,
+            // which puts them evenly by the cosine to the normal.
+            let t = (ray as f32 + 0.5) / rays as f32;
+            let (sin, cos) = (ray as f32 * GOLDEN_ANGLE + turn).sin_cos();
+            let across = t.sqrt();
+            let direction =
+                tangent * (cos * across) + bitangent * (sin * across) + normal * (1.0 - t).sqrt();
+            !bvh.hits_any(model, &Ray { origin, direction })
+        })
+        .count();
+    open as f32 / rays as f32
+}
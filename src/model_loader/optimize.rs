@@ -0,0 +1,336 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::Model;
+
+/// Cache slots the Forsyth scoring simulates; real GPUs have at least this
+/// many, so the order also works well on larger caches.
+const CACHE_SIZE: usize = 32;
+const CACHE_DECAY_POWER: f32 = 1.5;
+/// Vertices of the triangle just emitted score a bit lower, so the next
+/// triangle prefers sharing an edge with it over sharing only one corner.
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+/// Cache size used to check the result is an improvement.
+const FIFO_SIZE: usize = 16;
+
+/// Positions, normals and texture coordinates closer than this are welded.
+pub const WELD_EPSILON: f32 = 1e-6;
+
+/// Merges vertices whose position, normal, texture coordinates and color all
+/// match to within `epsilon`, returning how many vertices were removed.
+/// Loaders that emit one vertex per face corner (STL, unindexed glTF) shrink
+/// the most.
+pub fn weld_vertices(model: &mut Model, epsilon: f32) -> usize {
+    let quantize = |value: f32| (value / epsilon).round() as i64;
+    let before = model.vertices.len();
+    let mut welded: HashMap<[i64; 11], u32> = HashMap::new();
+    let mut remap: Vec<u32> = Vec::with_capacity(before);
+    let mut vertices = Vec::new();
+    let mut colors = Vec::new();
+
+    for (index, vertex) in model.vertices.iter().enumerate() {
+        let color = model.colors.get(index).map(|c| c.color).unwrap_or_default();
+        let key = [
+            quantize(vertex.position[0]),
+            quantize(vertex.position[1]),
+            quantize(vertex.position[2]),
+            quantize(vertex.normal[0]),
+            quantize(vertex.normal[1]),
+            quantize(vertex.normal[2]),
+            quantize(vertex.tex_coords[0]),
+            quantize(vertex.tex_coords[1]),
+            quantize(color.0),
+            quantize(color.1),
+            quantize(color.2),
+        ];
+        let new_index = *welded.entry(key).or_insert_with(|| {
+            vertices.push(*vertex);
+            if !model.colors.is_empty() {
+                colors.push(model.colors[index]);
+            }
+            // Welding never adds vertices, so this always fits.
+            (vertices.len() - 1) as u32
+        });
+        remap.push(new_index);
+    }
+
+    for index in model.indexes.iter_mut().chain(&mut model.lines) {
+        *index = remap[*index as usize];
+    }
+    model.vertices = vertices;
+    model.colors = colors;
+    before - model.vertices.len()
+}
+
+/// Reorders each sub-mesh's triangles for the post-transform vertex cache
+/// with Tom Forsyth's linear-speed algorithm, then renumbers vertices in the
+/// order they are first used. Sub-mesh ranges are unchanged. Point clouds
+/// are left alone, since every vertex would go unused.
+pub fn optimize_vertex_cache(model: &mut Model) {
+    if model.is_point_cloud() {
+        return;
+    }
+    for sub_mesh in &model.sub_meshes {
+        let range = sub_mesh.range.clone();
+        let reordered = reorder_triangles(&model.indexes[range.clone()], model.vertices.len());
+        // Some exporters already write a good order; never make it worse.
+        if cache_misses(&reordered) < cache_misses(&model.indexes[range.clone()]) {
+            model.indexes[range].copy_from_slice(&reordered);
+        }
+    }
+
+    // Fetch order now matches the index order as closely as it can.
+    let mut remap: Vec<Option<u32>> = vec![None; model.vertices.len()];
+    let mut order: Vec<usize> = Vec::with_capacity(model.vertices.len());
+    for index in model.indexes.iter_mut().chain(&mut model.lines) {
+        let new_index = *remap[*index as usize].get_or_insert_with(|| {
+            order.push(*index as usize);
+            (order.len() - 1) as u32
+        });
+        *index = new_index;
+    }
+    // Vertices no triangle or line uses are dropped.
+    model.vertices = order.iter().map(|&old| model.vertices[old]).collect();
+    if !model.colors.is_empty() {
+        model.colors = order.iter().map(|&old| model.colors[old]).collect();
+    }
+}
+
+/// Counts misses in a small FIFO cache, the kind older GPUs actually have.
+fn cache_misses(indexes: &[u32]) -> usize {
+    let mut cache: VecDeque<u32> = VecDeque::with_capacity(FIFO_SIZE);
+    let mut misses = 0;
+    for index in indexes {
+        if !cache.contains(index) {
+            misses += 1;
+            if cache.len() == FIFO_SIZE {
+                cache.pop_front();
+            }
+            cache.push_back(*index);
+        }
+    }
+    misses
+}
+
+fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scaled = (position - 3) as f32 / (CACHE_SIZE - 3) as f32;
+            (1.0 - scaled).powf(CACHE_DECAY_POWER)
+        }
+    };
+    cache_score + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+fn reorder_triangles(indexes: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indexes.len() / 3;
+    let mut triangles_of: Vec<Vec<usize>> = vec![Vec::new(); vertex_count];
+    for (triangle, corners) in indexes.chunks_exact(3).enumerate() {
+        for &vertex in corners {
+            triangles_of[vertex as usize].push(triangle);
+        }
+    }
+
+    let mut score: Vec<f32> = (0..vertex_count)
+        .map(|vertex| vertex_score(None, triangles_of[vertex].len()))
+        .collect();
+    let triangle_score = |score: &[f32], triangle: usize| -> f32 {
+        indexes[triangle * 3..triangle * 3 + 3]
+            .iter()
+            .map(|&vertex| score[vertex as usize])
+            .sum()
+    };
+    let mut emitted = vec![false; triangle_count];
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut output: Vec<u32> = Vec::with_capacity(indexes.len());
+    let mut best: Option<usize> = None;
+    let mut next_unused = 0;
+
+    for _ in 0..triangle_count {
+        // When nothing in the cache has triangles left, continue with the
+        // first unused triangle rather than scanning for the best one, which
+        // would be quadratic on meshes made of many small islands.
+        let triangle = match best {
+            Some(triangle) => triangle,
+            None => {
+                while emitted[next_unused] {
+                    next_unused += 1;
+                }
+                next_unused
+            }
+        };
+        emitted[triangle] = true;
+        let corners = &indexes[triangle * 3..triangle * 3 + 3];
+        output.extend_from_slice(corners);
+        for &vertex in corners {
+            triangles_of[vertex as usize].retain(|&other| other != triangle);
+        }
+
+        // Move the triangle's corners to the front of the cache.
+        let mut new_cache: Vec<u32> = corners.to_vec();
+        new_cache.extend(cache.iter().filter(|vertex| !corners.contains(vertex)));
+        for &evicted in new_cache.iter().skip(CACHE_SIZE) {
+            score[evicted as usize] = vertex_score(None, triangles_of[evicted as usize].len());
+        }
+        new_cache.truncate(CACHE_SIZE);
+        for (position, &vertex) in new_cache.iter().enumerate() {
+            score[vertex as usize] =
+                vertex_score(Some(position), triangles_of[vertex as usize].len());
+        }
+        cache = new_cache;
+
+        best = None;
+        let mut best_score = f32::MIN;
+        for &vertex in &cache {
+            for &candidate in &triangles_of[vertex as usize] {
+                let candidate_score = triangle_score(&score, candidate);
+                if candidate_score > best_score {
+                    best_score = candidate_score;
+                    best = Some(candidate);
+                }
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+    use std::path::Path;
+
+    use super::*;
+    use crate::model_loader::{parse_obj, Color, LoadProgress};
+    use crate::primitives;
+
+    /// Each triangle in `range` as its corners' position, normal, texture
+    /// coordinates and color, however the vertices are numbered.
+    fn triangles(model: &Model, range: Range<usize>) -> Vec<[[f32; 11]; 3]> {
+        let corner = |index: u32| {
+            let vertex = &model.vertices[index as usize];
+            let color = model
+                .colors
+                .get(index as usize)
+                .map(|c| c.color)
+                .unwrap_or_default();
+            let [x, y, z] = vertex.position;
+            let [nx, ny, nz] = vertex.normal;
+            let [u, v] = vertex.tex_coords;
+            [x, y, z, nx, ny, nz, u, v, color.0, color.1, color.2]
+        };
+        model.indexes[range]
+            .chunks_exact(3)
+            .map(|triangle| {
+                [
+                    corner(triangle[0]),
+                    corner(triangle[1]),
+                    corner(triangle[2]),
+                ]
+            })
+            .collect()
+    }
+
+    fn sorted(mut triangles: Vec<[[f32; 11]; 3]>) -> Vec<[[f32; 11]; 3]> {
+        let key = |triangle: &[[f32; 11]; 3]| triangle.concat();
+        triangles.sort_by(|a, b| {
+            let (a, b) = (key(a), key(b));
+            a.iter()
+                .zip(&b)
+                .map(|(a, b)| a.total_cmp(b))
+                .find(|order| order.is_ne())
+                .unwrap()
+        });
+        triangles
+    }
+
+    fn assert_close(actual: &[[[f32; 11]; 3]], expected: &[[[f32; 11]; 3]]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            let close = actual
+                .iter()
+                .flatten()
+                .zip(expected.iter().flatten())
+                .all(|(a, b)| (a - b).abs() <= WELD_EPSILON);
+            assert!(close, "{:?} became {:?}", expected, actual);
+        }
+    }
+
+    /// Welds and reorders `model`, checking each sub-mesh keeps the same
+    /// triangles, if not in the same order, each with its corners in the
+    /// same order.
+    fn assert_same_triangles(mut model: Model) -> Model {
+        let before: Vec<_> = model
+            .sub_meshes
+            .iter()
+            .map(|sub_mesh| triangles(&model, sub_mesh.range.clone()))
+            .collect();
+        weld_vertices(&mut model, WELD_EPSILON);
+        optimize_vertex_cache(&mut model);
+        for (sub_mesh, before) in model.sub_meshes.iter().zip(before) {
+            let after = triangles(&model, sub_mesh.range.clone());
+            assert_close(&sorted(after), &sorted(before));
+        }
+        model
+    }
+
+    /// A cube with three corners of its own for every triangle, each
+    /// colored by its face.
+    fn faceted_cube() -> Model {
+        let mut cube = primitives::cube(1.0);
+        cube.vertices = cube
+            .indexes
+            .iter()
+            .map(|&index| cube.vertices[index as usize])
+            .collect();
+        cube.colors = cube
+            .vertices
+            .iter()
+            .map(|vertex| Color {
+                color: vertex.normal.map(|value| value.abs()).into(),
+            })
+            .collect();
+        cube.indexes = (0..cube.vertices.len() as u32).collect();
+        cube
+    }
+
+    #[test]
+    fn welding_and_reordering_keep_the_cube_faceted() {
+        let cube = assert_same_triangles(faceted_cube());
+        // Each face's two triangles now share two corners.
+        assert_eq!(cube.vertices.len(), 24);
+        assert_eq!(cube.colors.len(), 24);
+    }
+
+    #[test]
+    fn welding_and_reordering_keep_the_teapot() {
+        // The teapot that repeats corners, and isn't in a good order.
+        let text = include_bytes!("../teapot-2.obj");
+        let scene = parse_obj(&text[..], Path::new("."), &LoadProgress::default()).unwrap();
+        for mesh in scene.meshes {
+            let (vertices, indexes) = (mesh.model.vertices.len(), mesh.model.indexes.clone());
+            let model = assert_same_triangles(mesh.model);
+            assert!(model.vertices.len() < vertices);
+            assert_ne!(model.indexes, indexes);
+        }
+    }
+
+    #[test]
+    fn an_order_no_better_for_the_cache_is_kept() {
+        let plane = primitives::plane(1.0, 1.0, 3);
+        let reordered = reorder_triangles(&plane.indexes, plane.vertices.len());
+        assert_ne!(reordered, plane.indexes);
+        assert_eq!(cache_misses(&reordered), cache_misses(&plane.indexes));
+
+        let before = triangles(&plane, 0..plane.indexes.len());
+        let mut optimized = plane;
+        optimize_vertex_cache(&mut optimized);
+        // Renumbered, but the triangles are still in the order they came.
+        assert_close(&triangles(&optimized, 0..optimized.indexes.len()), &before);
+    }
+}
@@ -0,0 +1,375 @@
+use std::path::Path;
+
+use super::{
+    compute_normals, Aabb, Color, LoadProgress, Material, Model, ModelLoadError, SubMesh, Vertex3D,
+    MAX_VERTICES,
+};
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Result<Self, ModelLoadError> {
+        match name {
+            "char" | "int8" => Ok(Scalar::I8),
+            "uchar" | "uint8" => Ok(Scalar::U8),
+            "short" | "int16" => Ok(Scalar::I16),
+            "ushort" | "uint16" => Ok(Scalar::U16),
+            "int" | "int32" => Ok(Scalar::I32),
+            "uint" | "uint32" => Ok(Scalar::U32),
+            "float" | "float32" => Ok(Scalar::F32),
+            "double" | "float64" => Ok(Scalar::F64),
+            _ => Err(ModelLoadError::InvalidData(format!(
+                "unknown PLY property type '{}'",
+                name
+            ))),
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    fn is_integer(self) -> bool {
+        !matches!(self, Scalar::F32 | Scalar::F64)
+    }
+}
+
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Reads values out of the body, whichever encoding the header declared.
+enum Body<'a> {
+    Ascii(std::str::SplitWhitespace<'a>),
+    Binary { bytes: &'a [u8], offset: usize },
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, ModelLoadError> {
+        match self {
+            Body::Ascii(tokens) => {
+                let token = tokens.next().ok_or_else(truncated)?;
+                token.parse().map_err(|_| {
+                    ModelLoadError::InvalidData(format!("invalid PLY value '{}'", token))
+                })
+            }
+            Body::Binary { bytes, offset } => {
+                let end = *offset + scalar.size();
+                let data = bytes.get(*offset..end).ok_or_else(truncated)?;
+                *offset = end;
+                Ok(match scalar {
+                    Scalar::I8 => data[0] as i8 as f64,
+                    Scalar::U8 => data[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([data[0], data[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([data[0], data[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes([data[0], data[1], data[2], data[3]]) as f64,
+                    Scalar::U32 => u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as f64,
+                    Scalar::F32 => f32::from_le_bytes([data[0], data[1], data[2], data[3]]) as f64,
+                    Scalar::F64 => f64::from_le_bytes([
+                        data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7],
+                    ]),
+                })
+            }
+        }
+    }
+}
+
+fn truncated() -> ModelLoadError {
+    ModelLoadError::InvalidData("PLY body ends before all elements were read".to_string())
+}
+
+/// Loads an ASCII or binary little-endian PLY file. Positions are required;
+/// normals, texture coordinates and `red`/`green`/`blue` colors are picked up
+/// when the vertex element declares them.
+pub fn load_ply(path: &str, progress: &LoadProgress) -> Result<Model, ModelLoadError> {
+    parse_ply(&progress.read_file(Path::new(path))?)
+}
+
+fn parse_ply(bytes: &[u8]) -> Result<Model, ModelLoadError> {
+    let (elements, format, body_start) = parse_header(bytes)?;
+    // Checked against the header, before reading a body that big.
+    let count = element_count(&elements, "vertex");
+    if count > MAX_VERTICES {
+        return Err(ModelLoadError::TooManyVertices { count });
+    }
+    let mut body = match format.as_str() {
+        "ascii" => Body::Ascii(
+            std::str::from_utf8(&bytes[body_start..])
+                .map_err(|_| ModelLoadError::InvalidData("PLY body is not text".to_string()))?
+                .split_whitespace(),
+        ),
+        "binary_little_endian" => Body::Binary {
+            bytes: &bytes[body_start..],
+            offset: 0,
+        },
+        _ => {
+            return Err(ModelLoadError::InvalidData(format!(
+                "unsupported PLY format '{}'",
+                format
+            )))
+        }
+    };
+
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut texcoords: Vec<[f32; 2]> = Vec::new();
+    let mut colors: Vec<[f32; 3]> = Vec::new();
+    let mut indexes: Vec<u32> = Vec::new();
+    let mut has_normals = false;
+    let mut has_texcoords = false;
+    let mut has_colors = false;
+
+    for element in &elements {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|property| names.contains(&property.name.as_str()))
+        };
+        if element.name == "vertex" {
+            has_normals = find(&["nx"]).is_some();
+            has_texcoords = find(&["u", "s", "texture_u"]).is_some();
+            has_colors = find(&["red"]).is_some();
+        }
+
+        for _ in 0..element.count {
+            let mut values: Vec<(f64, Scalar)> = Vec::with_capacity(element.properties.len());
+            let mut list: Vec<f64> = Vec::new();
+            for property in &element.properties {
+                match property.kind {
+                    PropertyKind::Scalar(scalar) => values.push((body.read(scalar)?, scalar)),
+                    PropertyKind::List { count, item } => {
+                        let count = body.read(count)? as usize;
+                        // Other lists, like the texture coordinates some
+                        // exporters give each face, are read past.
+                        let corners =
+                            matches!(property.name.as_str(), "vertex_indices" | "vertex_index");
+                        for _ in 0..count {
+                            let value = body.read(item)?;
+                            if corners {
+                                list.push(value);
+                            }
+                        }
+                        values.push((0.0, item));
+                    }
+                }
+            }
+
+            let get = |names: &[&str]| {
+                element
+                    .properties
+                    .iter()
+                    .position(|property| names.contains(&property.name.as_str()))
+                    .map(|index| values[index])
+            };
+            match element.name.as_str() {
+                "vertex" => {
+                    let coordinate = |name: &str| get(&[name]).map(|v| v.0 as f32).unwrap_or(0.0);
+                    positions.push([coordinate("x"), coordinate("y"), coordinate("z")]);
+                    if has_normals {
+                        normals.push([coordinate("nx"), coordinate("ny"), coordinate("nz")]);
+                    }
+                    if has_texcoords {
+                        let u = get(&["u", "s", "texture_u"]).map(|v| v.0).unwrap_or(0.0);
+                        let v = get(&["v", "t", "texture_v"]).map(|v| v.0).unwrap_or(0.0);
+                        texcoords.push([u as f32, v as f32]);
+                    }
+                    if has_colors {
+                        // Integer channels are 0-255, float channels 0-1.
+                        let channel = |name: &str| match get(&[name]) {
+                            Some((value, scalar)) if scalar.is_integer() => value as f32 / 255.0,
+                            Some((value, _)) => value as f32,
+                            None => 1.0,
+                        };
+                        colors.push([channel("red"), channel("green"), channel("blue")]);
+                    }
+                }
+                "face" => {
+                    if list.len() < 3 {
+                        continue;
+                    }
+                    let mut face: Vec<u32> = Vec::with_capacity(list.len());
+                    for index in &list {
+                        let index = *index as usize;
+                        if index >= element_count(&elements, "vertex") {
+                            return Err(ModelLoadError::InvalidData(format!(
+                                "PLY face index {} out of range (mesh has {} vertices)",
+                                index,
+                                element_count(&elements, "vertex")
+                            )));
+                        }
+                        face.push(index as u32);
+                    }
+                    for i in 1..face.len() - 1 {
+                        indexes.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    let mut model = Model {
+        vertices: positions
+            .iter()
+            .enumerate()
+            .map(|(i, position)| Vertex3D {
+                position: *position,
+                normal: normals.get(i).copied().unwrap_or_default(),
+                tex_coords: texcoords.get(i).copied().unwrap_or_default(),
+            })
+            .collect(),
+        colors: colors
+            .iter()
+            .map(|c| Color {
+                color: (c[0], c[1], c[2]),
+            })
+            .collect(),
+        sub_meshes: vec![SubMesh {
+            material: 0,
+            range: 0..indexes.len(),
+        }],
+        indexes,
+        lines: Vec::new(),
+        materials: vec![Material::default()],
+        bounds: Aabb::default(),
+        has_normals,
+        has_tex_coords: has_texcoords,
+        patches: Vec::new(),
+        lods: Vec::new(),
+        occlusion: Vec::new(),
+    };
+    model.bounds = Aabb::from_vertices(&model.vertices);
+    if !has_normals {
+        compute_normals(&mut model);
+    }
+    Ok(model)
+}
+
+fn element_count(elements: &[Element], name: &str) -> usize {
+    elements
+        .iter()
+        .find(|element| element.name == name)
+        .map(|element| element.count)
+        .unwrap_or(0)
+}
+
+/// Returns the declared elements, the format name and where the body starts.
+fn parse_header(bytes: &[u8]) -> Result<(Vec<Element>, String, usize), ModelLoadError> {
+    let invalid = |reason: &str| ModelLoadError::InvalidData(format!("PLY header: {}", reason));
+    let marker = b"end_header";
+    let end = bytes
+        .windows(marker.len())
+        .position(|window| window == marker)
+        .ok_or_else(|| invalid("missing end_header"))?;
+    // The body starts right after the newline that ends the header, or at
+    // the end of the file if the header has no newline after it.
+    let mut body_start = end + marker.len();
+    while body_start < bytes.len() && bytes[body_start] != b'\n' {
+        body_start += 1;
+    }
+    let body_start = (body_start + 1).min(bytes.len());
+
+    let header = String::from_utf8_lossy(&bytes[..end]);
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(invalid("file does not start with 'ply'"));
+    }
+
+    let mut elements: Vec<Element> = Vec::new();
+    let mut format = String::new();
+    for line in lines {
+        let split = line.split_whitespace().collect::<Vec<&str>>();
+        match split.as_slice() {
+            ["format", name, ..] => format = name.to_string(),
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| invalid("bad element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("property before any element"))?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::List {
+                        count: Scalar::parse(count)?,
+                        item: Scalar::parse(item)?,
+                    },
+                }),
+            ["property", scalar, name] => elements
+                .last_mut()
+                .ok_or_else(|| invalid("property before any element"))?
+                .properties
+                .push(Property {
+                    name: name.to_string(),
+                    kind: PropertyKind::Scalar(Scalar::parse(scalar)?),
+                }),
+            ["comment", ..] | ["obj_info", ..] | [] => (),
+            _ => return Err(invalid(&format!("unexpected line '{}'", line))),
+        }
+    }
+    Ok((elements, format, body_start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\n\
+                          property float y\nproperty float z\n";
+
+    #[test]
+    fn only_the_vertex_index_list_makes_faces() {
+        let ply = format!(
+            "{}element face 1\nproperty list uchar int vertex_indices\n\
+             property list uchar float texcoord\nend_header\n\
+             0 0 0\n1 0 0\n0 1 0\n3 0 1 2 6 0 0 1 0 0 1\n",
+            HEADER
+        );
+        let model = parse_ply(ply.as_bytes()).unwrap();
+        assert_eq!(model.indexes, [0, 1, 2]);
+    }
+
+    #[test]
+    fn header_at_the_end_of_the_file() {
+        for format in ["ascii", "binary_little_endian"] {
+            let empty = format!("ply\nformat {} 1.0\nelement vertex 0\nend_header", format);
+            assert!(parse_ply(empty.as_bytes()).unwrap().vertices.is_empty());
+            let missing = format!("{}end_header", HEADER.replace("ascii", format));
+            match parse_ply(missing.as_bytes()) {
+                Err(ModelLoadError::InvalidData(_)) => (),
+                Err(error) => panic!("expected InvalidData, got {:?}", error),
+                Ok(_) => panic!("read vertices from an empty body"),
+            }
+        }
+    }
+}
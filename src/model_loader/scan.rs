@@ -0,0 +1,130 @@
+/// Splits `bytes` into lines, without their `\n`. A `\r` before it is left
+/// for `Tokens` to skip as whitespace.
+pub fn lines(bytes: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let bytes = bytes.strip_suffix(b"\n").unwrap_or(bytes);
+    bytes.split(|&byte| byte == b'\n')
+}
+
+/// The whitespace-separated words of a line.
+#[derive(Clone)]
+pub struct Tokens<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(line: &'a [u8]) -> Self {
+        Tokens { rest: line }
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let start = self
+            .rest
+            .iter()
+            .position(|byte| !byte.is_ascii_whitespace())?;
+        let rest = &self.rest[start..];
+        let end = rest
+            .iter()
+            .position(|byte| byte.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        self.rest = &rest[end..];
+        Some(&rest[..end])
+    }
+}
+
+/// Largest mantissa an `f64` holds exactly, and the powers of ten it does
+/// too. Dividing or multiplying two exact values rounds once, so the `f64`
+/// is the one `str::parse` would give.
+const MAX_EXACT_MANTISSA: u64 = 1 << 53;
+const EXACT_POWERS_OF_TEN: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16,
+    1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+];
+/// The `f64` bits below an `f32`'s mantissa, and their pattern halfway
+/// between two `f32`s.
+const BELOW_F32_MANTISSA: u64 = (1 << 29) - 1;
+const HALFWAY: u64 = 1 << 28;
+
+/// Parses a decimal like `-1.25` or `3e-2` directly when that's exact, and
+/// everything else (long mantissas, big exponents, `inf`) with `str::parse`.
+pub fn parse_f32(token: &[u8]) -> Option<f32> {
+    match parse_f32_fast(token) {
+        Some(value) => Some(value),
+        None => std::str::from_utf8(token).ok()?.parse().ok(),
+    }
+}
+
+fn parse_f32_fast(token: &[u8]) -> Option<f32> {
+    let (negative, mut rest) = match token.split_first()? {
+        (b'-', rest) => (true, rest),
+        (b'+', rest) => (false, rest),
+        _ => (false, token),
+    };
+    let mut mantissa: u64 = 0;
+    let mut exponent: i32 = 0;
+    let mut digits = 0;
+    let mut fraction = false;
+    while let Some((&byte, tail)) = rest.split_first() {
+        match byte {
+            b'0'..=b'9' => {
+                mantissa = mantissa
+                    .checked_mul(10)?
+                    .checked_add(u64::from(byte - b'0'))?;
+                digits += 1;
+                if fraction {
+                    exponent -= 1;
+                }
+            }
+            b'.' if !fraction => fraction = true,
+            _ => break,
+        }
+        rest = tail;
+    }
+    if digits == 0 {
+        return None;
+    }
+    if let Some((b'e' | b'E', tail)) = rest.split_first() {
+        let written = std::str::from_utf8(tail).ok()?.parse::<i32>().ok()?;
+        exponent = exponent.checked_add(written)?;
+        rest = &[];
+    }
+    if !rest.is_empty() || mantissa > MAX_EXACT_MANTISSA {
+        return None;
+    }
+    let power = *EXACT_POWERS_OF_TEN.get(exponent.unsigned_abs() as usize)?;
+    let value = match exponent < 0 {
+        true => mantissa as f64 / power,
+        false => mantissa as f64 * power,
+    };
+    // Rounding on to `f32` only differs from rounding the decimal directly
+    // when the `f64` landed exactly halfway between two `f32`s. Subnormals
+    // and overflow are left to `str::parse` too.
+    let normal = (f32::MIN_POSITIVE as f64..=f32::MAX as f64).contains(&value);
+    if !normal && value != 0.0 || value.to_bits() & BELOW_F32_MANTISSA == HALFWAY {
+        return None;
+    }
+    let value = value as f32;
+    Some(if negative { -value } else { value })
+}
+
+/// Parses a face index, with `str::parse` there for what it can't handle.
+pub fn parse_i64(token: &[u8]) -> Option<i64> {
+    let (negative, digits) = match token.split_first()? {
+        (b'-', rest) => (true, rest),
+        _ => (false, token),
+    };
+    let mut value: i64 = 0;
+    for &byte in digits {
+        if !byte.is_ascii_digit() || value > i64::MAX / 10 - 1 {
+            return std::str::from_utf8(token).ok()?.parse().ok();
+        }
+        value = value * 10 + i64::from(byte - b'0');
+    }
+    if digits.is_empty() {
+        return None;
+    }
+    Some(if negative { -value } else { value })
+}
@@ -0,0 +1,609 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+
+use super::{Aabb, Model, SubMesh};
+
+/// Fractions of the triangles kept by each level of detail after the first,
+/// which is the model itself.
+pub const LOD_FRACTIONS: [f32; 3] = [0.25, 0.06, 0.015];
+/// Meshes with fewer triangles aren't worth simplifying.
+pub const MIN_LOD_TRIANGLES: usize = 4096;
+/// A level left with more than this fraction of the previous level's
+/// triangles, because collapsing stalled, isn't kept.
+const MIN_REDUCTION: f32 = 0.9;
+/// A collapse may turn a triangle by up to about 78 degrees from where it
+/// is, and by less than 90 from where it started.
+const MIN_TURN_COSINE: f64 = 0.2;
+/// Vertices closer than this fraction of the model's size are taken to be
+/// in the same place, closing seams that rounding left slightly open.
+const JOIN_DISTANCE: f32 = 1e-6;
+
+/// A coarser version of a model drawing from its vertex buffer.
+pub struct Lod {
+    pub indexes: Vec<u32>,
+    pub sub_meshes: Vec<SubMesh>,
+}
+
+/// Fills `model.lods` with one simplified mesh for each of `LOD_FRACTIONS`,
+/// if the model is big enough to be worth it.
+///
+/// Edges are collapsed cheapest first by Garland and Heckbert's quadric
+/// error metric, each onto one of its own ends, which keeps every level on
+/// the model's vertices. Vertices are joined by position, so seams in the
+/// normals or texture coordinates don't stop collapses across them. Ends of
+/// boundary edges, non-manifold edges and edges between sub-meshes never
+/// move, and a collapse that would turn a triangle over is skipped.
+pub fn build_lods(model: &mut Model) {
+    let triangles = model.indexes.len() / 3;
+    if triangles < MIN_LOD_TRIANGLES {
+        return;
+    }
+    let mut simplifier = Simplifier::new(model);
+    let mut lods = Vec::new();
+    let mut previous = triangles;
+    for fraction in LOD_FRACTIONS {
+        simplifier.collapse_to((triangles as f32 * fraction) as usize);
+        let lod = simplifier.lod(model);
+        let kept = lod.indexes.len() / 3;
+        if kept as f32 > previous as f32 * MIN_REDUCTION {
+            break;
+        }
+        previous = kept;
+        lods.push(lod);
+    }
+    model.lods = lods;
+}
+
+/// The squared distance to a set of planes, weighted by the areas of the
+/// triangles they came from, as the upper triangle of its symmetric 4x4
+/// matrix.
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn plane([a, b, c]: [f64; 3], d: f64, weight: f64) -> Self {
+        #[rustfmt::skip]
+        let terms = [a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d];
+        Quadric(terms.map(|term| term * weight))
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (term, other) in self.0.iter_mut().zip(other.0) {
+            *term += other;
+        }
+    }
+
+    fn error(&self, [x, y, z]: [f64; 3]) -> f64 {
+        let q = &self.0;
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+/// The cheapest way found to move every corner at one position onto a
+/// neighbouring one.
+struct Collapse {
+    cost: f64,
+    from: usize,
+    /// The position's version when the cost was worked out. If it has
+    /// changed since, the collapse is stale.
+    version: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost.total_cmp(&other.cost)
+    }
+}
+
+struct Simplifier {
+    /// Each triangle's corners as vertex indexes, `None` once it's
+    /// collapsed away.
+    triangles: Vec<Option<[u32; 3]>>,
+    alive: usize,
+    /// The vertices sharing each position, and the position each vertex has.
+    position_of: Vec<usize>,
+    vertices_at: Vec<Vec<u32>>,
+    points: Vec<[f64; 3]>,
+    quadrics: Vec<Quadric>,
+    /// Each triangle's normal before any collapse, scaled by twice its area.
+    original_normals: Vec<[f64; 3]>,
+    /// Triangles with a corner at each position. Includes dead ones, which
+    /// are skipped.
+    triangles_at: Vec<Vec<usize>>,
+    locked: Vec<bool>,
+    removed: Vec<bool>,
+    versions: Vec<u32>,
+    /// What each position's queued collapse costs, infinite for none.
+    queued: Vec<f64>,
+    normals: Vec<[f32; 3]>,
+    heap: BinaryHeap<Reverse<Collapse>>,
+}
+
+impl Simplifier {
+    fn new(model: &Model) -> Self {
+        let mut position_of = Vec::with_capacity(model.vertices.len());
+        let mut vertices_at: Vec<Vec<u32>> = Vec::new();
+        let mut points = Vec::new();
+        let bounds = Aabb::from_vertices(&model.vertices);
+        let size = (0..3)
+            .map(|axis| bounds.max[axis] - bounds.min[axis])
+            .fold(0.0, f32::max);
+        let cell = (size * JOIN_DISTANCE).max(f32::MIN_POSITIVE);
+        let mut by_cell: HashMap<[i64; 3], usize> = HashMap::new();
+        for (index, vertex) in model.vertices.iter().enumerate() {
+            let position = *by_cell
+                .entry(vertex.position.map(|value| (value / cell).round() as i64))
+                .or_insert_with(|| {
+                    vertices_at.push(Vec::new());
+                    points.push(vertex.position.map(f64::from));
+                    points.len() - 1
+                });
+            vertices_at[position].push(index as u32);
+            position_of.push(position);
+        }
+
+        let mut sub_mesh_of = vec![0; model.indexes.len() / 3];
+        for (number, sub_mesh) in model.sub_meshes.iter().enumerate() {
+            sub_mesh_of[sub_mesh.range.start / 3..sub_mesh.range.end / 3].fill(number);
+        }
+        let mut simplifier = Simplifier {
+            triangles: Vec::with_capacity(sub_mesh_of.len()),
+            original_normals: Vec::with_capacity(sub_mesh_of.len()),
+            alive: 0,
+            position_of,
+            quadrics: vec![Quadric::default(); points.len()],
+            triangles_at: vec![Vec::new(); points.len()],
+            locked: vec![false; points.len()],
+            removed: vec![false; points.len()],
+            versions: vec![0; points.len()],
+            queued: vec![f64::INFINITY; points.len()],
+            vertices_at,
+            points,
+            normals: model.vertices.iter().map(|vertex| vertex.normal).collect(),
+            heap: BinaryHeap::new(),
+        };
+
+        // Every triangle's edges as their ends in order, and its sub-mesh.
+        let mut edges: Vec<(usize, usize, usize)> = Vec::with_capacity(model.indexes.len());
+        for (triangle, corners) in model.indexes.chunks_exact(3).enumerate() {
+            let corners = [corners[0], corners[1], corners[2]];
+            let [a, b, c] = corners.map(|vertex| simplifier.position_of[vertex as usize]);
+            let normal = simplifier.face_normal([a, b, c]);
+            simplifier.original_normals.push(normal);
+            // Triangles without three distinct positions cover nothing, so
+            // they're left out from the start.
+            if a == b || b == c || c == a {
+                simplifier.triangles.push(None);
+                continue;
+            }
+            simplifier.triangles.push(Some(corners));
+            simplifier.alive += 1;
+            let sub_mesh = sub_mesh_of[triangle];
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                edges.push((from.min(to), from.max(to), sub_mesh));
+            }
+            let twice_area = length(normal);
+            if twice_area > 0.0 {
+                let unit = normal.map(|component| component / twice_area);
+                let d = -dot(unit, simplifier.points[a]);
+                let quadric = Quadric::plane(unit, d, twice_area / 2.0);
+                for position in [a, b, c] {
+                    simplifier.quadrics[position].add(&quadric);
+                }
+            }
+            for position in [a, b, c] {
+                simplifier.triangles_at[position].push(triangle);
+            }
+        }
+        edges.sort_unstable();
+        for sharing in edges.chunk_by(|first, second| first.0 == second.0 && first.1 == second.1) {
+            let (a, b, sub_mesh) = sharing[0];
+            if sharing.len() != 2 || sharing[1].2 != sub_mesh {
+                simplifier.locked[a] = true;
+                simplifier.locked[b] = true;
+            }
+        }
+        for position in 0..simplifier.points.len() {
+            simplifier.push(position);
+        }
+        simplifier
+    }
+
+    /// What collapsing `from` onto each of its `neighbours` would cost,
+    /// cheapest first.
+    fn costs(&self, from: usize, neighbours: &[usize]) -> Vec<(f64, usize)> {
+        let mut costs = neighbours
+            .iter()
+            .map(|&to| (self.cost(from, to), to))
+            .collect::<Vec<(f64, usize)>>();
+        costs.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+        costs
+    }
+
+    fn cost(&self, from: usize, to: usize) -> f64 {
+        let mut quadric = self.quadrics[from];
+        quadric.add(&self.quadrics[to]);
+        quadric.error(self.points[to])
+    }
+
+    /// Queues `from`'s cheapest collapse. Whether it's allowed is only
+    /// checked once it comes up.
+    fn push(&mut self, from: usize) {
+        match self.costs(from, &self.neighbours(from)).first() {
+            Some(&(cost, _)) => self.queue(from, cost),
+            None => self.queued[from] = f64::INFINITY,
+        }
+    }
+
+    /// Queues collapsing `from` for `cost` in place of what was queued for
+    /// it, unless it can't move.
+    fn queue(&mut self, from: usize, cost: f64) {
+        if self.locked[from] {
+            return;
+        }
+        self.versions[from] += 1;
+        self.queued[from] = cost;
+        self.heap.push(Reverse(Collapse {
+            cost,
+            from,
+            version: self.versions[from],
+        }));
+    }
+
+    /// Collapses edges, cheapest first, until `target` triangles are left
+    /// or nothing more can be collapsed.
+    fn collapse_to(&mut self, target: usize) {
+        while self.alive > target {
+            let Some(Reverse(collapse)) = self.heap.pop() else {
+                return;
+            };
+            let from = collapse.from;
+            if self.removed[from] || collapse.version != self.versions[from] {
+                continue;
+            }
+            // Collapses elsewhere only ever make this one dearer, so the
+            // queued cost may be too low but never too high.
+            let neighbours = self.neighbours(from);
+            let allowed = self.costs(from, &neighbours).into_iter().find(|&(_, to)| {
+                self.keeps_manifold(from, &neighbours, to) && !self.flips(from, to)
+            });
+            match allowed {
+                Some((cost, _)) if cost > collapse.cost => self.queue(from, cost),
+                Some((_, to)) => self.collapse(from, &neighbours, to),
+                // Until a neighbour changes.
+                None => self.queued[from] = f64::INFINITY,
+            }
+        }
+    }
+
+    /// Positions sharing a triangle with `position`, sorted.
+    fn neighbours(&self, position: usize) -> Vec<usize> {
+        let mut neighbours = Vec::new();
+        for &triangle in &self.triangles_at[position] {
+            let Some(positions) = self.positions(triangle) else {
+                continue;
+            };
+            neighbours.extend(positions.into_iter().filter(|&other| other != position));
+        }
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    /// The link condition: the only neighbours the two ends share are the
+    /// far corners of the triangles on their edge. Otherwise the collapse
+    /// would pinch the surface, or fold it onto itself.
+    fn keeps_manifold(&self, from: usize, neighbours: &[usize], to: usize) -> bool {
+        let to_neighbours = self.neighbours(to);
+        let mut shared = neighbours
+            .iter()
+            .filter(|neighbour| to_neighbours.binary_search(neighbour).is_ok())
+            .count();
+        for &triangle in &self.triangles_at[from] {
+            let Some(positions) = self.positions(triangle) else {
+                continue;
+            };
+            if positions.contains(&to) {
+                shared = shared.saturating_sub(1);
+            }
+        }
+        shared == 0
+    }
+
+    /// Whether moving `from` onto `to` turns any triangle around `from` too
+    /// far, or flattens it to nothing.
+    fn flips(&self, from: usize, to: usize) -> bool {
+        self.triangles_at[from].iter().any(|&triangle| {
+            let Some(positions) = self.positions(triangle) else {
+                return false;
+            };
+            if positions.contains(&to) {
+                return false;
+            }
+            let before = self.face_normal(positions);
+            let after = self.face_normal(positions.map(|p| if p == from { to } else { p }));
+            dot(before, after) <= MIN_TURN_COSINE * length(before) * length(after)
+                || dot(self.original_normals[triangle], after) <= 0.0
+        })
+    }
+
+    /// Moves `from`, with `neighbours`, onto `to`.
+    fn collapse(&mut self, from: usize, neighbours: &[usize], to: usize) {
+        let triangles = std::mem::take(&mut self.triangles_at[from]);
+        for &triangle in &triangles {
+            let Some(positions) = self.positions(triangle) else {
+                continue;
+            };
+            if positions.contains(&to) {
+                self.triangles[triangle] = None;
+                self.alive -= 1;
+                continue;
+            }
+            let corners = self.triangles[triangle].as_mut().unwrap();
+            for corner in corners.iter_mut() {
+                if self.position_of[*corner as usize] == from {
+                    *corner = Self::closest_vertex(&self.vertices_at[to], &self.normals, *corner);
+                }
+            }
+            self.triangles_at[to].push(triangle);
+        }
+        let quadric = self.quadrics[from];
+        self.quadrics[to].add(&quadric);
+        self.removed[from] = true;
+        self.triangles_at[to].retain(|&triangle| self.triangles[triangle].is_some());
+        // The positions that were `from`'s neighbours can now collapse onto
+        // `to` instead, which might be cheaper than what's queued for them.
+        // Everything else only got dearer, and is put right when it comes
+        // up.
+        self.push(to);
+        for &position in neighbours {
+            let cost = self.cost(position, to);
+            if position != to && cost < self.queued[position] {
+                self.queue(position, cost);
+            }
+        }
+    }
+
+    /// Of the vertices at a position, the one whose normal is nearest
+    /// `corner`'s, so creases and seams stay where they can.
+    fn closest_vertex(candidates: &[u32], normals: &[[f32; 3]], corner: u32) -> u32 {
+        let normal = normals[corner as usize].map(f64::from);
+        let similarity = |vertex: u32| dot(normals[vertex as usize].map(f64::from), normal);
+        *candidates
+            .iter()
+            .max_by(|&&a, &&b| similarity(a).total_cmp(&similarity(b)))
+            .unwrap()
+    }
+
+    fn positions(&self, triangle: usize) -> Option<[usize; 3]> {
+        let corners = self.triangles[triangle]?;
+        Some(corners.map(|vertex| self.position_of[vertex as usize]))
+    }
+
+    fn face_normal(&self, [a, b, c]: [usize; 3]) -> [f64; 3] {
+        let (a, b, c) = (self.points[a], self.points[b], self.points[c]);
+        cross(sub(b, a), sub(c, a))
+    }
+
+    /// The triangles left, in their original order so each sub-mesh's stay
+    /// together.
+    fn lod(&self, model: &Model) -> Lod {
+        let mut indexes = Vec::with_capacity(self.alive * 3);
+        let mut sub_meshes = Vec::with_capacity(model.sub_meshes.len());
+        for sub_mesh in &model.sub_meshes {
+            let start = indexes.len();
+            for triangle in sub_mesh.range.start / 3..sub_mesh.range.end / 3 {
+                if let Some(corners) = self.triangles[triangle] {
+                    indexes.extend_from_slice(&corners);
+                }
+            }
+            sub_meshes.push(SubMesh {
+                material: sub_mesh.material,
+                range: start..indexes.len(),
+            });
+        }
+        Lod {
+            indexes,
+            sub_meshes,
+        }
+    }
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(a: [f64; 3]) -> f64 {
+    dot(a, a).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vec3;
+    use crate::picking::{intersect_triangle, Ray};
+    use crate::primitives::uv_sphere;
+    use std::collections::HashSet;
+
+    /// A unit sphere of about sixteen thousand triangles, with its levels.
+    fn sphere() -> Model {
+        let mut model = uv_sphere(1.0, 64, 128);
+        build_lods(&mut model);
+        assert_eq!(model.lods.len(), LOD_FRACTIONS.len());
+        model
+    }
+
+    fn corners(model: &Model, triangle: &[u32]) -> [Vec3; 3] {
+        [0, 1, 2].map(|corner| Vec3::from(model.vertices[triangle[corner] as usize].position))
+    }
+
+    /// Directions spread evenly over the sphere, on a Fibonacci spiral.
+    fn directions(count: usize) -> Vec<Vec3> {
+        let golden_angle = std::f32::consts::PI * (3.0 - 5f32.sqrt());
+        (0..count)
+            .map(|i| {
+                let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+                let around = (1.0 - y * y).sqrt();
+                let (sin, cos) = (golden_angle * i as f32).sin_cos();
+                Vec3::new(around * cos, y, around * sin)
+            })
+            .collect()
+    }
+
+    /// How near the middle a level gets, over points spread across each
+    /// triangle. Every corner is on the sphere, so the triangles are inside
+    /// it.
+    fn nearest_point(model: &Model, lod: &Lod) -> f32 {
+        const STEPS: u32 = 8;
+        let mut nearest = f32::INFINITY;
+        for triangle in lod.indexes.chunks_exact(3) {
+            let [a, b, c] = corners(model, triangle);
+            for i in 0..=STEPS {
+                for j in 0..=STEPS - i {
+                    let (u, v) = (i as f32 / STEPS as f32, j as f32 / STEPS as f32);
+                    nearest = nearest.min((a + (b - a) * u + (c - a) * v).length());
+                }
+            }
+        }
+        nearest
+    }
+
+    /// The nearest and furthest a level is from the middle along rays out
+    /// of it, infinitely far where a ray misses.
+    fn ray_distances(model: &Model, lod: &Lod) -> (f32, f32) {
+        let mut range = (f32::INFINITY, 0.0f32);
+        for direction in directions(500) {
+            let ray = Ray {
+                origin: Vec3::new(0.0, 0.0, 0.0),
+                direction,
+            };
+            let hit = lod
+                .indexes
+                .chunks_exact(3)
+                .filter_map(|triangle| intersect_triangle(&ray, corners(model, triangle)))
+                .map(|(distance, _, _)| distance)
+                .fold(f32::INFINITY, f32::min);
+            range = (range.0.min(hit), range.1.max(hit));
+        }
+        range
+    }
+
+    #[test]
+    fn sphere_levels_stay_near_the_sphere() {
+        let model = sphere();
+        let triangles = model.indexes.len() / 3;
+        // How far inside the sphere each level may go, a few times the
+        // sagitta of as many triangles spread evenly over it.
+        let bounds = [0.01, 0.03, 0.1];
+        for ((lod, fraction), bound) in model.lods.iter().zip(LOD_FRACTIONS).zip(bounds) {
+            let kept = lod.indexes.len() / 3;
+            assert!(
+                kept <= (triangles as f32 * fraction) as usize,
+                "{} kept",
+                kept
+            );
+            let inner = nearest_point(&model, lod);
+            let (nearest, furthest) = ray_distances(&model, lod);
+            assert!(inner > 1.0 - bound, "a point {} from the middle", inner);
+            // Every point of the sphere has the level close under it.
+            assert!(nearest > 1.0 - bound && furthest <= 1.0 + 1e-5);
+        }
+    }
+
+    #[test]
+    fn sphere_levels_face_outward() {
+        let model = sphere();
+        for lod in &model.lods {
+            for triangle in lod.indexes.chunks_exact(3) {
+                let [a, b, c] = corners(&model, triangle);
+                let center = (a + b + c) * (1.0 / 3.0);
+                assert!(
+                    (b - a).cross(c - a).dot(center) > 0.0,
+                    "{:?} turned over",
+                    [a, b, c]
+                );
+            }
+        }
+    }
+
+    /// Pairs of positions that only one of the triangles has as an edge.
+    fn boundary(model: &Model, indexes: &[u32]) -> HashSet<[[i64; 3]; 2]> {
+        let key = |vertex: u32| {
+            let position = model.vertices[vertex as usize].position;
+            position.map(|value| (value * 1e5).round() as i64)
+        };
+        let mut edges = HashSet::new();
+        for triangle in indexes.chunks_exact(3) {
+            for (from, to) in [(0, 1), (1, 2), (2, 0)] {
+                let (from, to) = (key(triangle[from]), key(triangle[to]));
+                let edge = [from.min(to), from.max(to)];
+                if !edges.remove(&edge) {
+                    edges.insert(edge);
+                }
+            }
+        }
+        edges
+    }
+
+    #[test]
+    fn cut_sphere_keeps_its_rim() {
+        let mut model = uv_sphere(1.0, 64, 128);
+        // The sphere below a ring a little above the equator, open at the
+        // top.
+        let below = model
+            .indexes
+            .chunks_exact(3)
+            .filter(|triangle| {
+                corners(&model, triangle)
+                    .iter()
+                    .all(|corner| corner.y < 0.3)
+            })
+            .flatten()
+            .copied()
+            .collect::<Vec<u32>>();
+        model.indexes = below;
+        model.sub_meshes[0].range = 0..model.indexes.len();
+        let rim = boundary(&model, &model.indexes);
+        assert_eq!(rim.len(), 128);
+        build_lods(&mut model);
+        assert!(!model.lods.is_empty());
+        for lod in &model.lods {
+            assert_eq!(boundary(&model, &lod.indexes), rim);
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt;
+
+use super::{dot, face_normal, Model, Vertex3D};
+
+/// Axis-aligned bounding box. An empty model gets a zero-sized box at the
+/// origin.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    pub fn from_vertices(vertices: &[Vertex3D]) -> Self {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for vertex in vertices {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(vertex.position[axis]);
+                max[axis] = max[axis].max(vertex.position[axis]);
+            }
+        }
+        if vertices.is_empty() {
+            return Aabb::default();
+        }
+        Aabb { min, max }
+    }
+
+    /// The smallest box holding both boxes.
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: [
+                self.min[0].min(other.min[0]),
+                self.min[1].min(other.min[1]),
+                self.min[2].min(other.min[2]),
+            ],
+            max: [
+                self.max[0].max(other.max[0]),
+                self.max[1].max(other.max[1]),
+                self.max[2].max(other.max[2]),
+            ],
+        }
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        [
+            (self.min[0] + self.max[0]) / 2.0,
+            (self.min[1] + self.max[1]) / 2.0,
+            (self.min[2] + self.max[2]) / 2.0,
+        ]
+    }
+}
+
+/// Summary of a loaded model, see `Model::stats`.
+#[derive(Debug, Clone, Copy)]
+pub struct ModelStats {
+    pub vertices: usize,
+    pub triangles: usize,
+    pub lines: usize,
+    pub has_normals: bool,
+    pub has_tex_coords: bool,
+    pub bounds: Aabb,
+    /// Radius of the sphere around the bounding box's center that holds every
+    /// vertex.
+    pub radius: f32,
+    pub surface_area: f32,
+}
+
+impl Model {
+    pub fn stats(&self) -> ModelStats {
+        let center = self.bounds.center();
+        let radius = self
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let offset = [
+                    vertex.position[0] - center[0],
+                    vertex.position[1] - center[1],
+                    vertex.position[2] - center[2],
+                ];
+                dot(offset, offset)
+            })
+            .fold(0.0f32, f32::max)
+            .sqrt();
+        let surface_area = self
+            .indexes
+            .chunks_exact(3)
+            .map(|triangle| {
+                let cross = face_normal(self, triangle);
+                dot(cross, cross).sqrt() / 2.0
+            })
+            .sum();
+        ModelStats {
+            vertices: self.vertices.len(),
+            triangles: self.indexes.len() / 3,
+            lines: self.lines.len() / 2,
+            has_normals: self.has_normals,
+            has_tex_coords: self.has_tex_coords,
+            bounds: self.bounds,
+            radius,
+            surface_area,
+        }
+    }
+}
+
+impl fmt::Display for ModelStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let [min_x, min_y, min_z] = self.bounds.min;
+        let [max_x, max_y, max_z] = self.bounds.max;
+        write!(
+            f,
+            "{} vertices, {} triangles",
+            self.vertices, self.triangles
+        )?;
+        match self.lines {
+            0 => writeln!(f)?,
+            lines => writeln!(f, ", {} lines", lines)?,
+        }
+        writeln!(
+            f,
+            "normals: {}, texture coordinates: {}",
+            if self.has_normals {
+                "from file"
+            } else {
+                "generated"
+            },
+            if self.has_tex_coords { "yes" } else { "none" }
+        )?;
+        writeln!(
+            f,
+            "bounds: ({:.3}, {:.3}, {:.3}) to ({:.3}, {:.3}, {:.3})",
+            min_x, min_y, min_z, max_x, max_y, max_z
+        )?;
+        write!(
+            f,
+            "bounding sphere radius: {:.3}, surface area: {:.3}",
+            self.radius, self.surface_area
+        )
+    }
+}
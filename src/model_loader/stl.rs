@@ -0,0 +1,184 @@
+use std::path::Path;
+
+use super::{
+    compute_normals, Aabb, LoadProgress, Material, Model, ModelLoadError, SubMesh, Vertex3D,
+    MAX_VERTICES,
+};
+
+const HEADER_SIZE: usize = 84;
+const TRIANGLE_SIZE: usize = 50;
+
+/// Loads a binary or ASCII STL file. Every facet becomes three vertices with
+/// the facet normal, falling back to computed normals when the file leaves
+/// them zeroed.
+pub fn load_stl(path: &str, progress: &LoadProgress) -> Result<Model, ModelLoadError> {
+    let bytes = progress.read_file(Path::new(path))?;
+    let triangles = if is_binary(&bytes) {
+        parse_binary(&bytes)?
+    } else {
+        parse_ascii(&String::from_utf8_lossy(&bytes))?
+    };
+    build_model(&triangles)
+}
+
+/// An STL facet: the normal followed by three corners.
+type Facet = [[f32; 3]; 4];
+
+/// ASCII files start with "solid", but so do plenty of binary exporters'
+/// headers, so a size that matches the triangle count wins over the keyword.
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() >= HEADER_SIZE {
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        if HEADER_SIZE + count * TRIANGLE_SIZE == bytes.len() {
+            return true;
+        }
+    }
+    let start = String::from_utf8_lossy(&bytes[..bytes.len().min(512)]).to_string();
+    !(start.trim_start().starts_with("solid") && start.contains("facet"))
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<Vec<Facet>, ModelLoadError> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(ModelLoadError::InvalidData(format!(
+            "binary STL is only {} bytes long, the header alone needs {}",
+            bytes.len(),
+            HEADER_SIZE
+        )));
+    }
+    let declared = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+    let available = (bytes.len() - HEADER_SIZE) / TRIANGLE_SIZE;
+    // Some exporters write a bogus count; trust whatever is actually there.
+    if declared != available {
+        eprintln!(
+            "warning: STL header declares {} triangles but the file holds {}",
+            declared, available
+        );
+    }
+
+    let read_f32 = |offset: usize| {
+        f32::from_le_bytes([
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ])
+    };
+    let mut facets: Vec<Facet> = Vec::with_capacity(available);
+    for triangle in 0..available {
+        let start = HEADER_SIZE + triangle * TRIANGLE_SIZE;
+        let mut facet = [[0.0f32; 3]; 4];
+        for (i, row) in facet.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = read_f32(start + (i * 3 + j) * 4);
+            }
+        }
+        facets.push(facet);
+    }
+    Ok(facets)
+}
+
+fn parse_ascii(text: &str) -> Result<Vec<Facet>, ModelLoadError> {
+    let mut facets: Vec<Facet> = Vec::new();
+    let mut facet = [[0.0f32; 3]; 4];
+    let mut corner = 0;
+
+    for (number, line) in text.lines().enumerate() {
+        let number = number + 1;
+        let split = line.split_whitespace().collect::<Vec<&str>>();
+        match split.first() {
+            Some(&"facet") => {
+                // "facet normal nx ny nz"
+                if split.len() < 5 {
+                    return Err(ModelLoadError::MalformedStatement {
+                        line: number,
+                        statement: "facet".to_string(),
+                    });
+                }
+                facet[0] = parse_vector(&split[2..], number)?;
+                corner = 0;
+            }
+            Some(&"vertex") => {
+                if corner == 3 || split.len() < 4 {
+                    return Err(ModelLoadError::MalformedStatement {
+                        line: number,
+                        statement: "vertex".to_string(),
+                    });
+                }
+                corner += 1;
+                facet[corner] = parse_vector(&split[1..], number)?;
+            }
+            Some(&"endfacet") => {
+                if corner != 3 {
+                    return Err(ModelLoadError::MalformedStatement {
+                        line: number,
+                        statement: "endfacet".to_string(),
+                    });
+                }
+                facets.push(facet);
+            }
+            Some(&"solid") | Some(&"endsolid") | Some(&"outer") | Some(&"endloop") | None => (),
+            Some(statement) => {
+                return Err(ModelLoadError::UnsupportedStatement {
+                    line: number,
+                    statement: statement.to_string(),
+                })
+            }
+        }
+    }
+    Ok(facets)
+}
+
+fn parse_vector(values: &[&str], line: usize) -> Result<[f32; 3], ModelLoadError> {
+    let mut vector = [0.0f32; 3];
+    for (component, value) in vector.iter_mut().zip(values) {
+        *component = value.parse().map_err(|_| ModelLoadError::InvalidFloat {
+            line,
+            value: value.to_string(),
+        })?;
+    }
+    Ok(vector)
+}
+
+fn build_model(facets: &[Facet]) -> Result<Model, ModelLoadError> {
+    let count = facets.len() * 3;
+    if count > MAX_VERTICES {
+        return Err(ModelLoadError::TooManyVertices { count });
+    }
+
+    let mut vertices: Vec<Vertex3D> = Vec::with_capacity(count);
+    let mut missing_normals = false;
+    for facet in facets {
+        let normal = facet[0];
+        missing_normals |= normal == [0.0, 0.0, 0.0];
+        for position in &facet[1..] {
+            vertices.push(Vertex3D {
+                position: *position,
+                normal,
+                tex_coords: [0.0, 0.0],
+            });
+        }
+    }
+
+    let mut model = Model {
+        bounds: Aabb::from_vertices(&vertices),
+        vertices,
+        colors: Vec::new(),
+        indexes: (0..count as u32).collect(),
+        lines: Vec::new(),
+        materials: vec![Material::default()],
+        sub_meshes: vec![SubMesh {
+            material: 0,
+            range: 0..count,
+        }],
+        has_normals: !missing_normals,
+        has_tex_coords: false,
+        patches: Vec::new(),
+        lods: Vec::new(),
+        occlusion: Vec::new(),
+    };
+    // Each facet owns its three vertices, so computed normals are flat too.
+    if missing_normals {
+        compute_normals(&mut model);
+    }
+    Ok(model)
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::fmt;
+
+use super::{position_key, Color, Model, NormalMode, Vertex3D, MAX_VERTICES};
+
+/// Why a model couldn't be subdivided. It's left as it was, but for the
+/// triangles `subdivide` drops first.
+#[derive(Debug)]
+pub enum SubdivideError {
+    /// Loop's masks need every edge to have one triangle on each side, or
+    /// just one along a boundary.
+    NonManifold { edges: usize },
+    /// More than `MAX_VERTICES` after `iterations` rounds.
+    TooManyVertices { iterations: u32 },
+}
+
+impl fmt::Display for SubdivideError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SubdivideError::NonManifold { edges } => {
+                write!(f, "{} edges are shared by more than two triangles", edges)
+            }
+            SubdivideError::TooManyVertices { iterations } => {
+                write!(
+                    f,
+                    "{} subdivisions would need more than {} vertices",
+                    iterations, MAX_VERTICES
+                )
+            }
+        }
+    }
+}
+
+/// What's known about the edge between two positions.
+#[derive(Default)]
+struct Edge {
+    triangles: usize,
+    /// The positions opposite it in its triangles, as far as two.
+    opposite: [usize; 2],
+}
+
+/// Splits every triangle into four `iterations` times by Loop's scheme,
+/// which takes the surface toward a smooth limit, then gives it smooth
+/// normals. Triangles without three distinct positions are dropped first.
+///
+/// The connectivity comes from the positions, so seams in the texture
+/// coordinates or colors don't open up; those are interpolated along each
+/// split edge instead. Boundary edges follow their own curve, without
+/// pulling in the surface around them. Patches are dropped, since they no
+/// longer describe the triangles, and so are levels of detail.
+pub fn subdivide(model: &mut Model, iterations: u32) -> Result<(), SubdivideError> {
+    drop_degenerate(model);
+    let mut triangles = model.indexes.len() / 3;
+    let mut vertices = model.vertices.len();
+    for _ in 0..iterations {
+        // Every edge is on at least one triangle, so each triangle adds at
+        // most three vertices.
+        vertices += triangles * 3;
+        triangles *= 4;
+    }
+    if vertices > MAX_VERTICES {
+        return Err(SubdivideError::TooManyVertices { iterations });
+    }
+    for _ in 0..iterations {
+        subdivide_once(model)?;
+    }
+    model.patches.clear();
+    model.lods.clear();
+    super::generate_normals(
+        model,
+        NormalMode::Smooth {
+            crease_angle_deg: 180.0,
+        },
+    );
+    model.has_normals = false;
+    model.bounds = super::Aabb::from_vertices(&model.vertices);
+    Ok(())
+}
+
+fn drop_degenerate(model: &mut Model) {
+    let key = |index: u32| position_key(model.vertices[index as usize].position);
+    let mut indexes = Vec::with_capacity(model.indexes.len());
+    for sub_mesh in &mut model.sub_meshes {
+        let start = indexes.len();
+        for corners in model.indexes[sub_mesh.range.clone()].chunks_exact(3) {
+            let [a, b, c] = [key(corners[0]), key(corners[1]), key(corners[2])];
+            if a != b && b != c && c != a {
+                indexes.extend_from_slice(corners);
+            }
+        }
+        sub_mesh.range = start..indexes.len();
+    }
+    model.indexes = indexes;
+}
+
+fn subdivide_once(model: &mut Model) -> Result<(), SubdivideError> {
+    // Vertices at the same place are one point of the surface.
+    let mut points: Vec<[f32; 3]> = Vec::new();
+    let mut by_position: HashMap<[u32; 3], usize> = HashMap::new();
+    let point_of = model
+        .vertices
+        .iter()
+        .map(|vertex| {
+            *by_position
+                .entry(position_key(vertex.position))
+                .or_insert_with(|| {
+                    points.push(vertex.position);
+                    points.len() - 1
+                })
+        })
+        .collect::<Vec<usize>>();
+
+    let mut edges: HashMap<(usize, usize), Edge> = HashMap::new();
+    for corners in model.indexes.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|corner| point_of[corners[corner] as usize]);
+        for (from, to, opposite) in [(a, b, c), (b, c, a), (c, a, b)] {
+            let edge = edges.entry((from.min(to), from.max(to))).or_default();
+            if edge.triangles < 2 {
+                edge.opposite[edge.triangles] = opposite;
+            }
+            edge.triangles += 1;
+        }
+    }
+    let non_manifold = edges.values().filter(|edge| edge.triangles > 2).count();
+    if non_manifold > 0 {
+        return Err(SubdivideError::NonManifold {
+            edges: non_manifold,
+        });
+    }
+
+    // The even rule moves each existing point toward its neighbours, or
+    // along the boundary toward its two neighbours there.
+    let mut neighbour_sums = vec![[0.0f32; 3]; points.len()];
+    let mut valences = vec![0usize; points.len()];
+    let mut boundary_sums = vec![[0.0f32; 3]; points.len()];
+    let mut boundary_valences = vec![0usize; points.len()];
+    for (&(a, b), edge) in &edges {
+        for (from, to) in [(a, b), (b, a)] {
+            add(&mut neighbour_sums[from], points[to], 1.0);
+            valences[from] += 1;
+            if edge.triangles == 1 {
+                add(&mut boundary_sums[from], points[to], 1.0);
+                boundary_valences[from] += 1;
+            }
+        }
+    }
+    let moved = (0..points.len())
+        .map(|point| match boundary_valences[point] {
+            // Not on any triangle, so nothing to move toward.
+            _ if valences[point] == 0 => points[point],
+            0 => {
+                let n = valences[point] as f32;
+                let beta = loop_beta(valences[point]);
+                let mut moved = [0.0; 3];
+                add(&mut moved, points[point], 1.0 - n * beta);
+                add(&mut moved, neighbour_sums[point], beta);
+                moved
+            }
+            2 => {
+                let mut moved = [0.0; 3];
+                add(&mut moved, points[point], 0.75);
+                add(&mut moved, boundary_sums[point], 0.125);
+                moved
+            }
+            // Where boundaries cross, the point is a corner and stays.
+            _ => points[point],
+        })
+        .collect::<Vec<[f32; 3]>>();
+
+    // The odd rule puts a new point on each edge, weighted toward its own
+    // ends over the far corners of its triangles.
+    let edge_point = |a: usize, b: usize| {
+        let edge = &edges[&(a.min(b), a.max(b))];
+        let mut point = [0.0; 3];
+        match edge.triangles {
+            2 => {
+                add(&mut point, points[a], 0.375);
+                add(&mut point, points[b], 0.375);
+                add(&mut point, points[edge.opposite[0]], 0.125);
+                add(&mut point, points[edge.opposite[1]], 0.125);
+            }
+            _ => {
+                add(&mut point, points[a], 0.5);
+                add(&mut point, points[b], 0.5);
+            }
+        }
+        point
+    };
+
+    let mut vertices = model
+        .vertices
+        .iter()
+        .zip(&point_of)
+        .map(|(vertex, &point)| Vertex3D {
+            position: moved[point],
+            ..*vertex
+        })
+        .collect::<Vec<Vertex3D>>();
+    let mut colors = model.colors.clone();
+    let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut midpoint = |a: u32, b: u32| {
+        *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+            let (first, second) = (model.vertices[a as usize], model.vertices[b as usize]);
+            vertices.push(Vertex3D {
+                position: edge_point(point_of[a as usize], point_of[b as usize]),
+                normal: first.normal,
+                tex_coords: [0, 1]
+                    .map(|axis| (first.tex_coords[axis] + second.tex_coords[axis]) / 2.0),
+            });
+            if !model.colors.is_empty() {
+                let (first, second) = (model.colors[a as usize], model.colors[b as usize]);
+                colors.push(Color {
+                    color: (
+                        (first.color.0 + second.color.0) / 2.0,
+                        (first.color.1 + second.color.1) / 2.0,
+                        (first.color.2 + second.color.2) / 2.0,
+                    ),
+                });
+            }
+            (vertices.len() - 1) as u32
+        })
+    };
+
+    // Each triangle becomes four in its place, so the sub-meshes only
+    // need their ranges scaled.
+    let mut indexes = Vec::with_capacity(model.indexes.len() * 4);
+    for corners in model.indexes.chunks_exact(3) {
+        let [a, b, c] = [corners[0], corners[1], corners[2]];
+        let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+        indexes.extend_from_slice(&[a, ab, ca, ab, b, bc, ca, bc, c, ab, bc, ca]);
+    }
+    model.vertices = vertices;
+    model.colors = colors;
+    model.indexes = indexes;
+    for sub_mesh in &mut model.sub_meshes {
+        sub_mesh.range = sub_mesh.range.start * 4..sub_mesh.range.end * 4;
+    }
+    Ok(())
+}
+
+/// Loop's weight for each neighbour of an interior point with `valence`
+/// of them.
+fn loop_beta(valence: usize) -> f32 {
+    let n = valence as f32;
+    let cosine = 0.375 + 0.25 * (TAU / n).cos();
+    (0.625 - cosine * cosine) / n
+}
+
+fn add(sum: &mut [f32; 3], point: [f32; 3], weight: f32) {
+    for axis in 0..3 {
+        sum[axis] += point[axis] * weight;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vec3;
+    use crate::model_loader::{Aabb, Material, SubMesh};
+
+    /// The twelve corners at (0, ±1, ±φ) and their turns, each triangle of
+    /// three corners two apart wound to face out.
+    fn icosahedron() -> Model {
+        let phi = (1.0 + 5f32.sqrt()) / 2.0;
+        let mut corners = Vec::new();
+        for (a, b) in [(1.0, phi), (1.0, -phi), (-1.0, phi), (-1.0, -phi)] {
+            corners.extend([[0.0, a, b], [a, b, 0.0], [b, 0.0, a]].map(Vec3::from));
+        }
+        let neighbours =
+            |i: usize, j: usize| ((corners[i] - corners[j]).length() - 2.0).abs() < 1e-4;
+        let mut indexes = Vec::new();
+        for a in 0..12 {
+            for b in a + 1..12 {
+                for c in b + 1..12 {
+                    if !(neighbours(a, b) && neighbours(b, c) && neighbours(c, a)) {
+                        continue;
+                    }
+                    let normal = (corners[b] - corners[a]).cross(corners[c] - corners[a]);
+                    let [a, b, c] = [a, b, c].map(|corner| corner as u32);
+                    match normal.dot(corners[a as usize]) > 0.0 {
+                        true => indexes.extend([a, b, c]),
+                        false => indexes.extend([a, c, b]),
+                    }
+                }
+            }
+        }
+        assert_eq!(indexes.len(), 60);
+        let vertices = corners
+            .iter()
+            .map(|&corner| Vertex3D {
+                position: corner.into(),
+                normal: corner.normalize().into(),
+                tex_coords: [0.0, 0.0],
+            })
+            .collect::<Vec<Vertex3D>>();
+        Model {
+            bounds: Aabb::from_vertices(&vertices),
+            sub_meshes: vec![SubMesh {
+                material: 0,
+                range: 0..indexes.len(),
+            }],
+            vertices,
+            colors: Vec::new(),
+            indexes,
+            lines: Vec::new(),
+            materials: vec![Material::default()],
+            has_normals: true,
+            has_tex_coords: false,
+            patches: Vec::new(),
+            lods: Vec::new(),
+            occlusion: Vec::new(),
+        }
+    }
+
+    /// How much the surface's distance from the middle varies, relative to
+    /// the square of its mean so the shrinking Loop's scheme does doesn't
+    /// count. Measured over points spread across each triangle, since an
+    /// icosahedron's corners are all as far out as each other and it's the
+    /// flat faces between them that rounding out shows on.
+    fn radius_variance(model: &Model) -> f64 {
+        const STEPS: u32 = 4;
+        let mut radii = Vec::new();
+        for triangle in model.indexes.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2]
+                .map(|corner| Vec3::from(model.vertices[triangle[corner] as usize].position));
+            for i in 0..=STEPS {
+                for j in 0..=STEPS - i {
+                    let (u, v) = (i as f32 / STEPS as f32, j as f32 / STEPS as f32);
+                    radii.push(f64::from((a + (b - a) * u + (c - a) * v).length()));
+                }
+            }
+        }
+        let mean = radii.iter().sum::<f64>() / radii.len() as f64;
+        let variance = radii
+            .iter()
+            .map(|radius| (radius - mean).powi(2))
+            .sum::<f64>();
+        variance / radii.len() as f64 / (mean * mean)
+    }
+
+    #[test]
+    fn icosahedron_rounds_out() {
+        let mut model = icosahedron();
+        let mut variance = radius_variance(&model);
+        // Its limit surface isn't quite a sphere, the radius varying by about
+        // a percent, and after four rounds it's as round as that.
+        for iterations in 1..=4 {
+            subdivide(&mut model, 1).unwrap();
+            assert_eq!(model.indexes.len(), 60 * 4usize.pow(iterations));
+            let next = radius_variance(&model);
+            assert!(
+                next < variance,
+                "{} after {} from {}",
+                next,
+                iterations,
+                variance
+            );
+            variance = next;
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use super::{dot, normalize, Model};
+
+/// A vertex's tangent along increasing u, and in `w` whether the bitangent
+/// along increasing v is the cross product of the normal and the tangent,
+/// 1, or its opposite where the texture is mirrored, -1.
+#[derive(Copy, Clone, Default)]
+pub struct Tangent {
+    pub tangent: [f32; 4],
+}
+
+implement_vertex!(Tangent, tangent);
+
+/// Tangents for each of the model's vertices, for normal mapping, or none
+/// if it has no texture coordinates to map by.
+///
+/// As in MikkTSpace, each triangle's tangent and bitangent are worked out
+/// from how its texture coordinates change across it, then summed at its
+/// corners weighted by the angle there. Each vertex's sum is made
+/// perpendicular to its normal, and the bitangents only decide the sign in
+/// `w`. Vertices on triangles without a usable mapping get some tangent
+/// perpendicular to the normal anyway.
+pub fn compute_tangents(model: &Model) -> Vec<Tangent> {
+    if !model.has_tex_coords {
+        return Vec::new();
+    }
+    let mut tangents = vec![[0.0f32; 3]; model.vertices.len()];
+    let mut bitangents = vec![[0.0f32; 3]; model.vertices.len()];
+    for triangle in model.indexes.chunks_exact(3) {
+        let corners = [0, 1, 2].map(|corner| &model.vertices[triangle[corner] as usize]);
+        let edge_1 = sub(corners[1].position, corners[0].position);
+        let edge_2 = sub(corners[2].position, corners[0].position);
+        let [du_1, dv_1] =
+            [0, 1].map(|axis| corners[1].tex_coords[axis] - corners[0].tex_coords[axis]);
+        let [du_2, dv_2] =
+            [0, 1].map(|axis| corners[2].tex_coords[axis] - corners[0].tex_coords[axis]);
+        let determinant = du_1 * dv_2 - du_2 * dv_1;
+        if determinant == 0.0 {
+            continue;
+        }
+        // Only the directions matter, so the determinant's size doesn't.
+        let sign = determinant.signum();
+        let tangent = normalize(scale_sub(edge_1, dv_2 * sign, edge_2, dv_1 * sign));
+        let bitangent = normalize(scale_sub(edge_2, du_1 * sign, edge_1, du_2 * sign));
+        for corner in 0..3 {
+            let here = corners[corner].position;
+            let next = sub(corners[(corner + 1) % 3].position, here);
+            let previous = sub(corners[(corner + 2) % 3].position, here);
+            let cosine = dot(normalize(next), normalize(previous)).clamp(-1.0, 1.0);
+            let angle = cosine.acos();
+            let index = triangle[corner] as usize;
+            add(&mut tangents[index], tangent, angle);
+            add(&mut bitangents[index], bitangent, angle);
+        }
+    }
+
+    model
+        .vertices
+        .iter()
+        .zip(tangents.iter().zip(&bitangents))
+        .map(|(vertex, (&tangent, &bitangent))| {
+            let normal = normalize(vertex.normal);
+            let mut along = normalize(scale_sub(tangent, 1.0, normal, dot(normal, tangent)));
+            if along == [0.0; 3] {
+                along = perpendicular(normal);
+            }
+            let handedness = match dot(cross(normal, along), bitangent) < 0.0 {
+                true => -1.0,
+                false => 1.0,
+            };
+            Tangent {
+                tangent: [along[0], along[1], along[2], handedness],
+            }
+        })
+        .collect()
+}
+
+/// Some unit vector perpendicular to `normal`, or along x if it's zero.
+pub(super) fn perpendicular(normal: [f32; 3]) -> [f32; 3] {
+    // Crossed with whichever axis it's furthest from.
+    let axis = match normal[0].abs() < 0.9 {
+        true => [1.0, 0.0, 0.0],
+        false => [0.0, 1.0, 0.0],
+    };
+    let perpendicular = normalize(cross(normal, axis));
+    match perpendicular == [0.0; 3] {
+        true => [1.0, 0.0, 0.0],
+        false => perpendicular,
+    }
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+/// `a` times `a_scale` less `b` times `b_scale`.
+fn scale_sub(a: [f32; 3], a_scale: f32, b: [f32; 3], b_scale: f32) -> [f32; 3] {
+    [0, 1, 2].map(|axis| a[axis] * a_scale - b[axis] * b_scale)
+}
+
+fn add(sum: &mut [f32; 3], vector: [f32; 3], weight: f32) {
+    for axis in 0..3 {
+        sum[axis] += vector[axis] * weight;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    #[test]
+    fn tangents_are_unit_length_and_perpendicular_to_the_normals() {
+        let mut unmapped = primitives::uv_sphere(1.0, 8, 16);
+        // Texture coordinates that don't change anywhere, leaving every
+        // tangent to the fallback.
+        for vertex in &mut unmapped.vertices {
+            vertex.tex_coords = [0.5, 0.5];
+        }
+        let models = [
+            primitives::plane(2.0, 2.0, 4),
+            primitives::cube(2.0),
+            primitives::uv_sphere(1.0, 8, 16),
+            primitives::torus(1.0, 0.35, 16, 8),
+            primitives::cylinder(1.0, 2.0, 16),
+            primitives::teapot(4),
+            unmapped,
+        ];
+        for model in &models {
+            let tangents = compute_tangents(model);
+            assert_eq!(tangents.len(), model.vertices.len());
+            for (vertex, tangent) in model.vertices.iter().zip(&tangents) {
+                let [x, y, z, handedness] = tangent.tangent;
+                let along = [x, y, z];
+                assert!((dot(along, along).sqrt() - 1.0).abs() < 1e-5, "{:?}", along);
+                let normal = normalize(vertex.normal);
+                assert!(
+                    dot(along, normal).abs() < 1e-5,
+                    "{:?} at {:?}",
+                    along,
+                    normal
+                );
+                assert!(handedness == 1.0 || handedness == -1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn plane_tangents_follow_its_texture() {
+        // u runs along +X and v along -Z, which is the normal crossed with
+        // the tangent.
+        let plane = primitives::plane(2.0, 2.0, 1);
+        for tangent in compute_tangents(&plane) {
+            assert_eq!(tangent.tangent, [1.0, 0.0, 0.0, 1.0]);
+        }
+    }
+}
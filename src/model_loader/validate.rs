@@ -0,0 +1,411 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use super::{dot, face_normal, position_key, Aabb, Model};
+
+/// An edge between two positions, the lower key first.
+type EdgeKey = ([u32; 3], [u32; 3]);
+
+/// What `validate` found wrong with a model. The edges are between
+/// positions rather than vertex indexes, so a texture seam doesn't count as
+/// a boundary.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Validation {
+    /// Vertices identical to an earlier one in every attribute.
+    pub duplicate_vertices: usize,
+    /// Triangles with no area, or with a corner that isn't finite.
+    pub degenerate_triangles: usize,
+    /// Edges shared by more than two triangles.
+    pub non_manifold_edges: usize,
+    /// Edges whose two triangles both run along them the same way, so one
+    /// of them faces the other way from its neighbour.
+    pub inconsistent_edges: usize,
+    /// Vertices no triangle or line refers to.
+    pub unreferenced_vertices: usize,
+    pub non_finite_vertices: usize,
+}
+
+impl Validation {
+    pub fn is_clean(&self) -> bool {
+        *self == Validation::default()
+    }
+}
+
+impl fmt::Display for Validation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_clean() {
+            return write!(f, "no problems found");
+        }
+        let problems = [
+            (self.duplicate_vertices, "duplicate vertices"),
+            (self.degenerate_triangles, "degenerate triangles"),
+            (self.non_manifold_edges, "non-manifold edges"),
+            (
+                self.inconsistent_edges,
+                "edges between triangles wound opposite ways",
+            ),
+            (self.unreferenced_vertices, "unreferenced vertices"),
+            (
+                self.non_finite_vertices,
+                "vertices with NaN or infinite coordinates",
+            ),
+        ];
+        let found = problems
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, problem)| format!("{} {}", count, problem))
+            .collect::<Vec<String>>();
+        write!(f, "{}", found.join(", "))
+    }
+}
+
+/// Looks for the problems that leave holes in a model or turn patches of it
+/// inside out.
+pub fn validate(model: &Model) -> Validation {
+    let mut seen = HashSet::new();
+    let duplicate_vertices = model
+        .vertices
+        .iter()
+        .enumerate()
+        .filter(|&(index, vertex)| {
+            let color = model.colors.get(index).map(|c| c.color).unwrap_or_default();
+            let key = (
+                position_key(vertex.position),
+                position_key(vertex.normal),
+                vertex.tex_coords.map(f32::to_bits),
+                position_key([color.0, color.1, color.2]),
+            );
+            !seen.insert(key)
+        })
+        .count();
+    let degenerate_triangles = model
+        .indexes
+        .chunks_exact(3)
+        .filter(|triangle| is_degenerate(model, triangle))
+        .count();
+    let edges = edge_map(model);
+    let non_manifold_edges = edges.values().filter(|sides| sides.len() > 2).count();
+    let inconsistent_edges = edges
+        .values()
+        .filter(|sides| sides.len() == 2 && sides[0].1 == sides[1].1)
+        .count();
+    let unreferenced_vertices = match model.is_point_cloud() {
+        true => 0,
+        false => referenced(model).iter().filter(|&&used| !used).count(),
+    };
+    let non_finite_vertices = model
+        .vertices
+        .iter()
+        .filter(|vertex| !vertex.position.iter().all(|value| value.is_finite()))
+        .count();
+    Validation {
+        duplicate_vertices,
+        degenerate_triangles,
+        non_manifold_edges,
+        inconsistent_edges,
+        unreferenced_vertices,
+        non_finite_vertices,
+    }
+}
+
+/// Removes the triangles `validate` counts as degenerate, returning how
+/// many. Each sub-mesh keeps the rest of its own.
+pub fn drop_degenerate(model: &mut Model) -> usize {
+    let before = model.indexes.len() / 3;
+    let degenerate = model
+        .indexes
+        .chunks_exact(3)
+        .map(|triangle| is_degenerate(model, triangle))
+        .collect::<Vec<bool>>();
+    let mut indexes = Vec::with_capacity(model.indexes.len());
+    for sub_mesh in &mut model.sub_meshes {
+        let start = indexes.len();
+        let triangles = model.indexes[sub_mesh.range.clone()].chunks_exact(3);
+        let skip = &degenerate[sub_mesh.range.start / 3..];
+        for (triangle, &skip) in triangles.zip(skip) {
+            if !skip {
+                indexes.extend_from_slice(triangle);
+            }
+        }
+        sub_mesh.range = start..indexes.len();
+    }
+    model.indexes = indexes;
+    before - model.indexes.len() / 3
+}
+
+/// Removes the vertices nothing refers to, returning how many, and shrinks
+/// the bounds to the ones left. Point clouds are left alone, since all they
+/// have is vertices.
+pub fn strip_unreferenced(model: &mut Model) -> usize {
+    if model.is_point_cloud() {
+        return 0;
+    }
+    let used = referenced(model);
+    let mut remap = vec![0u32; model.vertices.len()];
+    let mut kept = 0;
+    for (index, &used) in used.iter().enumerate() {
+        if used {
+            remap[index] = kept;
+            kept += 1;
+        }
+    }
+    let removed = model.vertices.len() - kept as usize;
+    if removed == 0 {
+        return 0;
+    }
+
+    let lods = model.lods.iter_mut().flat_map(|lod| &mut lod.indexes);
+    for index in model.indexes.iter_mut().chain(&mut model.lines).chain(lods) {
+        *index = remap[*index as usize];
+    }
+    let mut keep = used.iter().copied();
+    model.vertices.retain(|_| keep.next().unwrap());
+    if !model.colors.is_empty() {
+        let mut keep = used.iter().copied();
+        model.colors.retain(|_| keep.next().unwrap());
+    }
+    if model.occlusion.len() == used.len() {
+        let mut keep = used.iter().copied();
+        model.occlusion.retain(|_| keep.next().unwrap());
+    }
+    model.bounds = Aabb::from_vertices(&model.vertices);
+    removed
+}
+
+/// Makes neighbouring triangles agree on which way they face, returning
+/// how many were turned over. Starting from each triangle not yet reached,
+/// the fill spreads across the edges with one triangle on either side, and
+/// wherever the two run along their edge the same way the one it reaches is
+/// marked for turning. Whichever way most of each connected piece already
+/// faced is the way it ends up. Where they can't all agree, as on a Möbius
+/// strip, the edges the fill didn't cross are left disagreeing.
+pub fn fix_winding(model: &mut Model) -> usize {
+    let triangles = model.indexes.len() / 3;
+    let mut neighbours: Vec<Vec<(usize, bool)>> = vec![Vec::new(); triangles];
+    for sides in edge_map(model).values() {
+        if let [(a, a_forward), (b, b_forward)] = sides[..] {
+            // Consistent neighbours run along their edge opposite ways.
+            let disagree = a_forward == b_forward;
+            neighbours[a].push((b, disagree));
+            neighbours[b].push((a, disagree));
+        }
+    }
+
+    let mut reached = vec![false; triangles];
+    let mut turn = vec![false; triangles];
+    let mut queue = VecDeque::new();
+    for seed in 0..triangles {
+        if reached[seed] {
+            continue;
+        }
+        reached[seed] = true;
+        queue.push_back(seed);
+        let mut piece = Vec::new();
+        while let Some(triangle) = queue.pop_front() {
+            piece.push(triangle);
+            for &(neighbour, disagree) in &neighbours[triangle] {
+                if !reached[neighbour] {
+                    reached[neighbour] = true;
+                    turn[neighbour] = turn[triangle] != disagree;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+        let turned = piece.iter().filter(|&&triangle| turn[triangle]).count();
+        if turned * 2 > piece.len() {
+            for triangle in piece {
+                turn[triangle] = !turn[triangle];
+            }
+        }
+    }
+
+    for (triangle, corners) in model.indexes.chunks_exact_mut(3).enumerate() {
+        if turn[triangle] {
+            corners.swap(1, 2);
+        }
+    }
+    turn.iter().filter(|&&turned| turned).count()
+}
+
+fn is_degenerate(model: &Model, triangle: &[u32]) -> bool {
+    let cross = face_normal(model, triangle);
+    let area = dot(cross, cross);
+    !(area > 0.0 && area.is_finite())
+}
+
+/// Each edge of the triangles that aren't degenerate, with the triangles
+/// along it and whether each runs from the lower key to the higher.
+fn edge_map(model: &Model) -> HashMap<EdgeKey, Vec<(usize, bool)>> {
+    let mut edges: HashMap<EdgeKey, Vec<(usize, bool)>> = HashMap::new();
+    for (triangle, corners) in model.indexes.chunks_exact(3).enumerate() {
+        if is_degenerate(model, corners) {
+            continue;
+        }
+        let [a, b, c] =
+            [0, 1, 2].map(|corner| position_key(model.vertices[corners[corner] as usize].position));
+        for (from, to) in [(a, b), (b, c), (c, a)] {
+            let forward = from < to;
+            let key = match forward {
+                true => (from, to),
+                false => (to, from),
+            };
+            edges.entry(key).or_default().push((triangle, forward));
+        }
+    }
+    edges
+}
+
+/// Whether each vertex is used by a triangle or line, at any level of
+/// detail.
+fn referenced(model: &Model) -> Vec<bool> {
+    let mut used = vec![false; model.vertices.len()];
+    let lods = model.lods.iter().flat_map(|lod| &lod.indexes);
+    for &index in model.indexes.iter().chain(&model.lines).chain(lods) {
+        used[index as usize] = true;
+    }
+    used
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_loader::Vertex3D;
+    use crate::primitives;
+
+    /// A cube two units across, which has none of the problems. Its faces
+    /// each have their own four vertices, but the edges are between
+    /// positions, so it's closed.
+    fn cube() -> Model {
+        let model = primitives::cube(2.0);
+        assert!(validate(&model).is_clean(), "{}", validate(&model));
+        model
+    }
+
+    /// Whether every triangle faces out of the cube.
+    fn faces_out(model: &Model) -> bool {
+        model.indexes.chunks_exact(3).all(|triangle| {
+            let corner = model.vertices[triangle[0] as usize].position;
+            dot(face_normal(model, triangle), corner) > 0.0
+        })
+    }
+
+    #[test]
+    fn duplicate_vertex() {
+        let mut model = cube();
+        // Used in place of the first vertex at one corner, so it's not also
+        // unreferenced.
+        model.vertices.push(model.vertices[0]);
+        let first = model.indexes.iter().position(|&index| index == 0).unwrap();
+        model.indexes[first] = 24;
+        let expected = Validation {
+            duplicate_vertices: 1,
+            ..Default::default()
+        };
+        assert_eq!(validate(&model), expected);
+    }
+
+    #[test]
+    fn degenerate_triangles_are_dropped() {
+        let mut model = cube();
+        // One with a repeated corner and one with three in a line.
+        let [a, b] = [0, 1].map(|corner| model.indexes[corner]);
+        let [from, to] = [a, b].map(|index| model.vertices[index as usize].position);
+        model.vertices.push(Vertex3D {
+            position: [0, 1, 2].map(|axis| (from[axis] + to[axis]) / 2.0),
+            ..model.vertices[0]
+        });
+        model.indexes.extend([a, a, b, a, 24, b]);
+        model.sub_meshes[0].range.end = model.indexes.len();
+        let expected = Validation {
+            degenerate_triangles: 2,
+            ..Default::default()
+        };
+        assert_eq!(validate(&model), expected);
+        assert_eq!(drop_degenerate(&mut model), 2);
+        assert_eq!(model.sub_meshes[0].range, 0..36);
+        // The vertex on the edge is no longer used.
+        let expected = Validation {
+            unreferenced_vertices: 1,
+            ..Default::default()
+        };
+        assert_eq!(validate(&model), expected);
+    }
+
+    #[test]
+    fn fin_on_an_edge_is_non_manifold() {
+        let mut model = cube();
+        model.vertices.push(Vertex3D {
+            position: [3.0, 3.0, 3.0],
+            ..model.vertices[0]
+        });
+        let [a, b] = [0, 1].map(|corner| model.indexes[corner]);
+        model.indexes.extend([a, b, 24]);
+        model.sub_meshes[0].range.end = model.indexes.len();
+        let expected = Validation {
+            non_manifold_edges: 1,
+            ..Default::default()
+        };
+        assert_eq!(validate(&model), expected);
+    }
+
+    #[test]
+    fn turned_triangle_is_turned_back() {
+        let mut model = cube();
+        model.indexes.swap(4, 5);
+        // Its neighbour on the same face and the two on the faces next to it.
+        let expected = Validation {
+            inconsistent_edges: 3,
+            ..Default::default()
+        };
+        assert_eq!(validate(&model), expected);
+        assert_eq!(fix_winding(&mut model), 1);
+        assert!(validate(&model).is_clean(), "{}", validate(&model));
+        assert!(faces_out(&model));
+    }
+
+    #[test]
+    fn unreferenced_vertices_are_stripped() {
+        let mut model = cube();
+        let far = Vertex3D {
+            position: [9.0, 9.0, 9.0],
+            ..model.vertices[0]
+        };
+        model.vertices.insert(0, far);
+        model.vertices.push(far);
+        for index in &mut model.indexes {
+            *index += 1;
+        }
+        model.bounds = Aabb::from_vertices(&model.vertices);
+        let expected = Validation {
+            duplicate_vertices: 1,
+            unreferenced_vertices: 2,
+            ..Default::default()
+        };
+        assert_eq!(validate(&model), expected);
+        assert_eq!(strip_unreferenced(&mut model), 2);
+        assert!(validate(&model).is_clean(), "{}", validate(&model));
+        assert_eq!(model.vertices.len(), 24);
+        assert_eq!(model.bounds.max, [1.0, 1.0, 1.0]);
+        assert!(faces_out(&model));
+    }
+
+    #[test]
+    fn non_finite_corner_is_repaired_away() {
+        let mut model = cube();
+        model.vertices[0].position = [f32::NAN, f32::INFINITY, 1.0];
+        // Both of its face's triangles have it as a corner.
+        let expected = Validation {
+            degenerate_triangles: 2,
+            non_finite_vertices: 1,
+            ..Default::default()
+        };
+        assert_eq!(validate(&model), expected);
+        assert_eq!(drop_degenerate(&mut model), 2);
+        // Which leaves all four of the face's vertices unused.
+        assert_eq!(strip_unreferenced(&mut model), 4);
+        assert!(validate(&model).is_clean(), "{}", validate(&model));
+        assert_eq!(model.vertices.len(), 20);
+        assert_eq!(model.indexes.len(), 30);
+        assert!(faces_out(&model));
+    }
+}
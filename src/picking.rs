@@ -0,0 +1,156 @@
+use crate::bvh::Bvh;
+use crate::math::{Mat4, Vec3};
+use crate::model_loader::{Aabb, Model};
+
+/// Triangles this close to edge-on to the ray are treated as missed.
+const PARALLEL_EPSILON: f32 = 1e-9;
+
+/// A half-line from `origin`. `direction` isn't necessarily unit length, so
+/// distances along it are in multiples of it.
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// The ray through the pixel at `cursor` in a `size` framebuffer, from
+    /// the near plane into the scene, found by undoing `projection` and then
+    /// `view`. Works for orthographic projections too, where every ray
+    /// points the same way.
+    pub fn from_screen(
+        cursor: (f64, f64),
+        size: (u32, u32),
+        view: Mat4,
+        projection: Mat4,
+    ) -> Option<Ray> {
+        let x = (2.0 * cursor.0 / size.0 as f64 - 1.0) as f32;
+        let y = (1.0 - 2.0 * cursor.1 / size.1 as f64) as f32;
+        let (inverse_projection, inverse_view) = (projection.inverse()?, view.inverse()?);
+        let unproject = |z: f32| {
+            inverse_view.transform_point(inverse_projection.transform_point(Vec3::new(x, y, z)))
+        };
+        let (near, far) = (unproject(-1.0), unproject(1.0));
+        Some(Ray {
+            origin: near,
+            direction: (far - near).normalize(),
+        })
+    }
+
+    /// The same ray in the space `matrix` transforms out of, with distances
+    /// along it unchanged.
+    fn transformed(&self, matrix: &Mat4) -> Ray {
+        Ray {
+            origin: matrix.transform_point(self.origin),
+            direction: matrix.transform_vector(self.direction),
+        }
+    }
+
+    /// How far along the ray it enters `bounds`, or 0 if it starts inside,
+    /// or `None` if it misses or only gets there after `max_distance`.
+    pub fn box_distance(&self, bounds: &Aabb, max_distance: f32) -> Option<f32> {
+        let origin = self.origin.to_array();
+        let direction = self.direction.to_array();
+        let (mut near, mut far) = (0.0f32, max_distance);
+        for axis in 0..3 {
+            // Dividing by a zero component gives infinities, which the
+            // comparisons below handle.
+            let inverse = 1.0 / direction[axis];
+            let mut entry = (bounds.min[axis] - origin[axis]) * inverse;
+            let mut exit = (bounds.max[axis] - origin[axis]) * inverse;
+            if entry > exit {
+                std::mem::swap(&mut entry, &mut exit);
+            }
+            near = near.max(entry);
+            far = far.min(exit);
+            if near > far {
+                return None;
+            }
+        }
+        Some(near)
+    }
+}
+
+/// Where a ray first meets an object's triangles.
+#[derive(Debug, Clone, Copy)]
+pub struct Hit {
+    /// Index into the scene's objects.
+    pub object: usize,
+    /// Index of the triangle in the mesh, a third of its place in `indexes`.
+    pub triangle: usize,
+    /// The triangle's corner closest to the hit, as a vertex index.
+    pub vertex: usize,
+    /// In world space.
+    pub position: Vec3,
+    /// Weights of the triangle's three corners at the hit.
+    pub barycentric: [f32; 3],
+    /// The triangle's corners in world space, for highlighting it.
+    pub corners: [Vec3; 3],
+    distance: f32,
+}
+
+/// The closest hit among `objects`, each given as its index, model matrix,
+/// model and the tree built over that model. Each ray is taken into the
+/// object's own space to search the tree.
+pub fn pick<'a>(
+    ray: &Ray,
+    objects: impl Iterator<Item = (usize, Mat4, &'a Model, &'a Bvh)>,
+) -> Option<Hit> {
+    let mut closest: Option<Hit> = None;
+    for (object, matrix, model, bvh) in objects {
+        let Some(inverse) = matrix.inverse() else {
+            continue;
+        };
+        let Some(hit) = bvh.intersect_ray(model, &ray.transformed(&inverse)) else {
+            continue;
+        };
+        if closest.is_some_and(|closest| closest.distance <= hit.distance) {
+            continue;
+        }
+        let corners = &model.indexes[hit.triangle * 3..hit.triangle * 3 + 3];
+        let points =
+            [0, 1, 2].map(|corner| Vec3::from(model.vertices[corners[corner] as usize].position));
+        let barycentric = [1.0 - hit.u - hit.v, hit.u, hit.v];
+        let nearest_corner = (0..3)
+            .max_by(|&a, &b| barycentric[a].total_cmp(&barycentric[b]))
+            .unwrap_or(0);
+        closest = Some(Hit {
+            object,
+            triangle: hit.triangle,
+            vertex: corners[nearest_corner] as usize,
+            position: ray.origin + ray.direction * hit.distance,
+            barycentric,
+            corners: points.map(|point| matrix.transform_point(point)),
+            distance: hit.distance,
+        });
+    }
+    closest
+}
+
+/// Möller–Trumbore: the distance along `ray` to the triangle, and the
+/// barycentric weights of its second and third corners there. Both sides of
+/// the triangle count.
+pub fn intersect_triangle(ray: &Ray, [a, b, c]: [Vec3; 3]) -> Option<(f32, f32, f32)> {
+    let (edge_1, edge_2) = (b - a, c - a);
+    let p = ray.direction.cross(edge_2);
+    let determinant = edge_1.dot(p);
+    if determinant.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+    let offset = ray.origin - a;
+    let u = offset.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = offset.cross(edge_1);
+    let v = ray.direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = edge_2.dot(q) * inverse_determinant;
+    match distance >= 0.0 {
+        true => Some((distance, u, v)),
+        false => None,
+    }
+}
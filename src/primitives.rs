@@ -0,0 +1,370 @@
+use std::f32::consts::{PI, TAU};
+use std::str::FromStr;
+
+use crate::math::Vec3;
+use crate::model_loader::{Aabb, Material, Model, Scene, SubMesh, Vertex3D};
+
+mod teapot;
+
+pub use teapot::teapot;
+
+/// Detail used when a `--primitive` spec doesn't give one.
+const DEFAULT_PLANE_SUBDIVISIONS: u32 = 1;
+const DEFAULT_SEGMENTS: u32 = 32;
+pub const DEFAULT_TEAPOT_RESOLUTION: u32 = 10;
+/// The most detail any primitive takes, which keeps each well under a
+/// hundred thousand vertices.
+const MAX_DETAIL: u32 = 255;
+const MAX_TEAPOT_RESOLUTION: u32 = 40;
+
+/// A shape `--primitive` builds instead of loading a file, written as
+/// `name` or `name:detail`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Primitive {
+    Plane { subdivisions: u32 },
+    Cube,
+    Sphere { segments: u32 },
+    Torus { segments: u32 },
+    Cylinder { segments: u32 },
+    Teapot { resolution: u32 },
+}
+
+impl FromStr for Primitive {
+    type Err = String;
+
+    /// Accepts `plane[:subdivisions]`, `cube`, `sphere`, `torus` or
+    /// `cylinder` with an optional `:segments`, and `teapot[:resolution]`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, detail) = match value.split_once(':') {
+            Some((name, detail)) => match detail.parse::<u32>() {
+                Ok(detail) => (name, Some(detail)),
+                Err(_) => return Err(format!("invalid detail '{}'", detail)),
+            },
+            None => (value, None),
+        };
+        let (minimum, maximum, default) = match name {
+            "plane" => (1, MAX_DETAIL, DEFAULT_PLANE_SUBDIVISIONS),
+            "cube" if detail.is_none() => return Ok(Primitive::Cube),
+            "cube" => return Err("cube doesn't take a detail".to_string()),
+            "sphere" | "torus" | "cylinder" => (3, MAX_DETAIL, DEFAULT_SEGMENTS),
+            "teapot" => (1, MAX_TEAPOT_RESOLUTION, DEFAULT_TEAPOT_RESOLUTION),
+            _ => {
+                return Err(format!(
+                    "unknown primitive '{}', expected plane, cube, sphere, torus, cylinder \
+                     or teapot",
+                    name
+                ))
+            }
+        };
+        let detail = match detail {
+            Some(detail) if !(minimum..=maximum).contains(&detail) => {
+                return Err(format!(
+                    "{} detail must be {} to {}",
+                    name, minimum, maximum
+                ))
+            }
+            Some(detail) => detail,
+            None => default,
+        };
+        Ok(match name {
+            "plane" => Primitive::Plane {
+                subdivisions: detail,
+            },
+            "sphere" => Primitive::Sphere { segments: detail },
+            "torus" => Primitive::Torus { segments: detail },
+            "cylinder" => Primitive::Cylinder { segments: detail },
+            _ => Primitive::Teapot { resolution: detail },
+        })
+    }
+}
+
+impl Primitive {
+    /// Builds the shape about two units across, the size the viewer frames
+    /// anyway.
+    pub fn model(&self) -> Model {
+        match *self {
+            Primitive::Plane { subdivisions } => plane(2.0, 2.0, subdivisions),
+            Primitive::Cube => cube(2.0),
+            Primitive::Sphere { segments } => uv_sphere(1.0, (segments / 2).max(2), segments),
+            Primitive::Torus { segments } => torus(1.0, 0.35, segments, (segments / 2).max(3)),
+            Primitive::Cylinder { segments } => cylinder(1.0, 2.0, segments),
+            // Left at its usual size, about six units from spout to handle.
+            Primitive::Teapot { resolution } => teapot(resolution),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Primitive::Plane { .. } => "plane",
+            Primitive::Cube => "cube",
+            Primitive::Sphere { .. } => "sphere",
+            Primitive::Torus { .. } => "torus",
+            Primitive::Cylinder { .. } => "cylinder",
+            Primitive::Teapot { .. } => "teapot",
+        }
+    }
+
+    pub fn scene(&self) -> Scene {
+        Scene::single(self.name(), self.model())
+    }
+}
+
+/// A plane on XZ facing up, `width` along X and `depth` along Z, split into
+/// `subdivisions` squares each way.
+pub fn plane(width: f32, depth: f32, subdivisions: u32) -> Model {
+    let mut builder = Builder::default();
+    builder.grid(subdivisions, subdivisions, |u, v| {
+        let position = Vec3::new(width * (u - 0.5), 0.0, depth * (0.5 - v));
+        (position, Vec3::new(0.0, 1.0, 0.0))
+    });
+    builder.finish()
+}
+
+/// A cube centered on the origin, with each face textured 0 to 1 on its own.
+pub fn cube(size: f32) -> Model {
+    // Each face's normal and the directions its texture's u and v run in,
+    // which cross to the normal so the face winds counterclockwise.
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ];
+    let mut builder = Builder::default();
+    for (normal, u_axis, v_axis) in faces {
+        let (normal, u_axis, v_axis) = (Vec3::from(normal), Vec3::from(u_axis), Vec3::from(v_axis));
+        builder.grid(1, 1, |u, v| {
+            let position = (normal * 0.5 + u_axis * (u - 0.5) + v_axis * (v - 0.5)) * size;
+            (position, normal)
+        });
+    }
+    builder.finish()
+}
+
+/// A sphere around the origin with its poles on Y, `rings` bands high and
+/// `segments` around. The texture wraps around once, seamed at +Z.
+pub fn uv_sphere(radius: f32, rings: u32, segments: u32) -> Model {
+    let mut builder = Builder::default();
+    builder.grid(segments, rings, |u, v| {
+        let (sin_longitude, cos_longitude) = (TAU * u).sin_cos();
+        let (sin_latitude, cos_latitude) = (PI * (v - 0.5)).sin_cos();
+        // Exactly on the axis, so the triangles that meet there collapse and
+        // get dropped.
+        let normal = match v {
+            v if v <= 0.0 => Vec3::new(0.0, -1.0, 0.0),
+            v if v >= 1.0 => Vec3::new(0.0, 1.0, 0.0),
+            _ => Vec3::new(
+                cos_latitude * sin_longitude,
+                sin_latitude,
+                cos_latitude * cos_longitude,
+            ),
+        };
+        (normal * radius, normal)
+    });
+    builder.finish()
+}
+
+/// A ring around the Y axis, `major_radius` to the middle of the tube and
+/// `minor_radius` across it, `rings` steps around the ring and `segments`
+/// around the tube.
+pub fn torus(major_radius: f32, minor_radius: f32, rings: u32, segments: u32) -> Model {
+    let mut builder = Builder::default();
+    builder.grid(rings, segments, |u, v| {
+        let (sin_ring, cos_ring) = (TAU * u).sin_cos();
+        let (sin_tube, cos_tube) = (TAU * v).sin_cos();
+        let normal = Vec3::new(cos_tube * sin_ring, sin_tube, cos_tube * cos_ring);
+        let center = Vec3::new(sin_ring, 0.0, cos_ring) * major_radius;
+        (center + normal * minor_radius, normal)
+    });
+    builder.finish()
+}
+
+/// An upright capped cylinder centered on the origin. The side is textured
+/// like the sphere and each cap with a disc cut from the middle of the
+/// texture.
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> Model {
+    let mut builder = Builder::default();
+    builder.grid(segments, 1, |u, v| {
+        let (sin, cos) = (TAU * u).sin_cos();
+        let normal = Vec3::new(sin, 0.0, cos);
+        (
+            normal * radius + Vec3::new(0.0, height * (v - 0.5), 0.0),
+            normal,
+        )
+    });
+    builder.cap(radius, height / 2.0, segments, 1.0);
+    builder.cap(radius, -height / 2.0, segments, -1.0);
+    builder.finish()
+}
+
+/// Vertices and triangles gathered for one primitive.
+#[derive(Default)]
+struct Builder {
+    vertices: Vec<Vertex3D>,
+    indexes: Vec<u32>,
+}
+
+impl Builder {
+    /// Adds a `columns` by `rows` grid of quads over the texture, with
+    /// `point` giving the position and normal at each `(u, v)`. The quads
+    /// face the way `u` crossed with `v` points; triangles with two corners
+    /// in the same place, as at a sphere's poles, are left out.
+    fn grid(&mut self, columns: u32, rows: u32, point: impl Fn(f32, f32) -> (Vec3, Vec3)) {
+        let first = self.vertices.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let (u, v) = (column as f32 / columns as f32, row as f32 / rows as f32);
+                let (position, normal) = point(u, v);
+                self.vertices.push(Vertex3D {
+                    position: position.into(),
+                    normal: normal.normalize().into(),
+                    tex_coords: [u, v],
+                });
+            }
+        }
+        let index = |column: u32, row: u32| first + row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                let corners = [
+                    index(column, row),
+                    index(column + 1, row),
+                    index(column + 1, row + 1),
+                    index(column, row + 1),
+                ];
+                self.triangle(corners[0], corners[1], corners[2]);
+                self.triangle(corners[0], corners[2], corners[3]);
+            }
+        }
+    }
+
+    /// Adds a flat disc at height `y` facing up or down as `facing` is 1 or
+    /// -1, as a fan around its center.
+    fn cap(&mut self, radius: f32, y: f32, segments: u32, facing: f32) {
+        let normal = [0.0, facing, 0.0];
+        let center = self.vertices.len() as u32;
+        self.vertices.push(Vertex3D {
+            position: [0.0, y, 0.0],
+            normal,
+            tex_coords: [0.5, 0.5],
+        });
+        for segment in 0..segments {
+            let (sin, cos) = (TAU * segment as f32 / segments as f32).sin_cos();
+            self.vertices.push(Vertex3D {
+                position: [radius * sin, y, radius * cos],
+                normal,
+                // Mirrored underneath, so the texture reads the right way
+                // round from either side.
+                tex_coords: [0.5 + 0.5 * sin * facing, 0.5 - 0.5 * cos],
+            });
+        }
+        for segment in 0..segments {
+            let (this, next) = (center + 1 + segment, center + 1 + (segment + 1) % segments);
+            match facing > 0.0 {
+                true => self.triangle(center, this, next),
+                false => self.triangle(center, next, this),
+            }
+        }
+    }
+
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let position = |index: u32| self.vertices[index as usize].position;
+        if position(a) == position(b) || position(b) == position(c) || position(c) == position(a) {
+            return;
+        }
+        self.indexes.extend_from_slice(&[a, b, c]);
+    }
+
+    fn finish(self) -> Model {
+        Model {
+            bounds: Aabb::from_vertices(&self.vertices),
+            sub_meshes: vec![SubMesh {
+                material: 0,
+                range: 0..self.indexes.len(),
+            }],
+            vertices: self.vertices,
+            colors: Vec::new(),
+            indexes: self.indexes,
+            lines: Vec::new(),
+            materials: vec![Material::default()],
+            has_normals: true,
+            has_tex_coords: true,
+            patches: Vec::new(),
+            lods: Vec::new(),
+            occlusion: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{HashMap, HashSet};
+
+    /// V - E + F over the surface, with the vertices that seams and poles
+    /// duplicate counted once.
+    fn euler_characteristic(model: &Model) -> i64 {
+        let mut ids = HashMap::new();
+        let welded = model
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let key = vertex.position.map(|value| (value * 1e4).round() as i64);
+                let next = ids.len();
+                *ids.entry(key).or_insert(next)
+            })
+            .collect::<Vec<usize>>();
+        let mut edges = HashSet::new();
+        for triangle in model.indexes.chunks_exact(3) {
+            for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+                let (a, b) = (welded[triangle[a] as usize], welded[triangle[b] as usize]);
+                edges.insert((a.min(b), a.max(b)));
+            }
+        }
+        ids.len() as i64 - edges.len() as i64 + (model.indexes.len() / 3) as i64
+    }
+
+    fn check(model: &Model, vertices: usize, indexes: usize, euler: i64) {
+        assert_eq!(model.vertices.len(), vertices);
+        assert_eq!(model.indexes.len(), indexes);
+        assert_eq!(euler_characteristic(model), euler);
+        for vertex in &model.vertices {
+            let length = Vec3::from(vertex.normal).length();
+            assert!((length - 1.0).abs() < 1e-5, "normal {:?}", vertex.normal);
+        }
+        assert_eq!(model.sub_meshes[0].range, 0..indexes);
+    }
+
+    #[test]
+    fn plane_is_a_disc() {
+        check(&plane(2.0, 1.0, 4), 5 * 5, 6 * 4 * 4, 1);
+    }
+
+    #[test]
+    fn cube_is_a_sphere() {
+        check(&cube(2.0), 6 * 4, 6 * 6, 2);
+    }
+
+    #[test]
+    fn uv_sphere_is_a_sphere() {
+        // The triangles with two corners on a pole are dropped.
+        check(&uv_sphere(1.0, 6, 12), 13 * 7, 6 * 12 * (6 - 1), 2);
+    }
+
+    #[test]
+    fn torus_has_a_hole() {
+        check(&torus(1.0, 0.35, 12, 6), 13 * 7, 6 * 12 * 6, 0);
+    }
+
+    #[test]
+    fn cylinder_is_a_sphere() {
+        // The side, then a center and a ring for each cap.
+        check(
+            &cylinder(1.0, 2.0, 12),
+            13 * 2 + 2 * 13,
+            6 * 12 + 2 * 3 * 12,
+            2,
+        );
+    }
+}
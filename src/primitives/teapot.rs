@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+use super::Builder;
+use crate::math::Vec3;
+use crate::model_loader::Model;
+
+/// Martin Newell's teapot as the ten patches of one quarter (or for the
+/// handle and spout, one half), each a 4x4 grid of indices into
+/// `CONTROL_POINTS`. The rest is mirrored from these.
+#[rustfmt::skip]
+const PATCHES: [[usize; 16]; 10] = [
+    // Rim.
+    [102, 103, 104, 105, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    // Body.
+    [12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27],
+    [24, 25, 26, 27, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40],
+    // Lid.
+    [96, 96, 96, 96, 97, 98, 99, 100, 101, 101, 101, 101, 0, 1, 2, 3],
+    [0, 1, 2, 3, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117],
+    // Bottom.
+    [118, 118, 118, 118, 124, 122, 119, 121, 123, 126, 125, 120, 40, 39, 38, 37],
+    // Handle.
+    [41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56],
+    [53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 28, 65, 66, 67],
+    // Spout.
+    [68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83],
+    [80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95],
+];
+
+/// How many of `PATCHES` go all the way round and are mirrored in both X
+/// and Y; the handle and spout after them are only mirrored in Y.
+const ROUND_PATCHES: usize = 6;
+
+/// In the data's own axes, with Z up.
+#[rustfmt::skip]
+const CONTROL_POINTS: [[f32; 3]; 127] = [
+    [0.2, 0.0, 2.7], [0.2, -0.112, 2.7], [0.112, -0.2, 2.7], [0.0, -0.2, 2.7],
+    [1.3375, 0.0, 2.53125], [1.3375, -0.749, 2.53125], [0.749, -1.3375, 2.53125],
+    [0.0, -1.3375, 2.53125], [1.4375, 0.0, 2.53125], [1.4375, -0.805, 2.53125],
+    [0.805, -1.4375, 2.53125], [0.0, -1.4375, 2.53125], [1.5, 0.0, 2.4],
+    [1.5, -0.84, 2.4], [0.84, -1.5, 2.4], [0.0, -1.5, 2.4], [1.75, 0.0, 1.875],
+    [1.75, -0.98, 1.875], [0.98, -1.75, 1.875], [0.0, -1.75, 1.875], [2.0, 0.0, 1.35],
+    [2.0, -1.12, 1.35], [1.12, -2.0, 1.35], [0.0, -2.0, 1.35], [2.0, 0.0, 0.9],
+    [2.0, -1.12, 0.9], [1.12, -2.0, 0.9], [0.0, -2.0, 0.9], [-2.0, 0.0, 0.9],
+    [2.0, 0.0, 0.45], [2.0, -1.12, 0.45], [1.12, -2.0, 0.45], [0.0, -2.0, 0.45],
+    [1.5, 0.0, 0.225], [1.5, -0.84, 0.225], [0.84, -1.5, 0.225], [0.0, -1.5, 0.225],
+    [1.5, 0.0, 0.15], [1.5, -0.84, 0.15], [0.84, -1.5, 0.15], [0.0, -1.5, 0.15],
+    [-1.6, 0.0, 2.025], [-1.6, -0.3, 2.025], [-1.5, -0.3, 2.25], [-1.5, 0.0, 2.25],
+    [-2.3, 0.0, 2.025], [-2.3, -0.3, 2.025], [-2.5, -0.3, 2.25], [-2.5, 0.0, 2.25],
+    [-2.7, 0.0, 2.025], [-2.7, -0.3, 2.025], [-3.0, -0.3, 2.25], [-3.0, 0.0, 2.25],
+    [-2.7, 0.0, 1.8], [-2.7, -0.3, 1.8], [-3.0, -0.3, 1.8], [-3.0, 0.0, 1.8],
+    [-2.7, 0.0, 1.575], [-2.7, -0.3, 1.575], [-3.0, -0.3, 1.35], [-3.0, 0.0, 1.35],
+    [-2.5, 0.0, 1.125], [-2.5, -0.3, 1.125], [-2.65, -0.3, 0.9375],
+    [-2.65, 0.0, 0.9375], [-2.0, -0.3, 0.9], [-1.9, -0.3, 0.6], [-1.9, 0.0, 0.6],
+    [1.7, 0.0, 1.425], [1.7, -0.66, 1.425], [1.7, -0.66, 0.6], [1.7, 0.0, 0.6],
+    [2.6, 0.0, 1.425], [2.6, -0.66, 1.425], [3.1, -0.66, 0.825], [3.1, 0.0, 0.825],
+    [2.3, 0.0, 2.1], [2.3, -0.25, 2.1], [2.4, -0.25, 2.025], [2.4, 0.0, 2.025],
+    [2.7, 0.0, 2.4], [2.7, -0.25, 2.4], [3.3, -0.25, 2.4], [3.3, 0.0, 2.4],
+    [2.8, 0.0, 2.475], [2.8, -0.25, 2.475], [3.525, -0.25, 2.49375],
+    [3.525, 0.0, 2.49375], [2.9, 0.0, 2.475], [2.9, -0.15, 2.475],
+    [3.45, -0.15, 2.5125], [3.45, 0.0, 2.5125], [2.8, 0.0, 2.4], [2.8, -0.15, 2.4],
+    [3.2, -0.15, 2.4], [3.2, 0.0, 2.4], [0.0, 0.0, 3.15], [0.8, 0.0, 3.15],
+    [0.8, -0.45, 3.15], [0.45, -0.8, 3.15], [0.0, -0.8, 3.15], [0.0, 0.0, 2.85],
+    [1.4, 0.0, 2.4], [1.4, -0.784, 2.4], [0.784, -1.4, 2.4], [0.0, -1.4, 2.4],
+    [0.4, 0.0, 2.55], [0.4, -0.224, 2.55], [0.224, -0.4, 2.55], [0.0, -0.4, 2.55],
+    [1.3, 0.0, 2.55], [1.3, -0.728, 2.55], [0.728, -1.3, 2.55], [0.0, -1.3, 2.55],
+    [1.3, 0.0, 2.4], [1.3, -0.728, 2.4], [0.728, -1.3, 2.4], [0.0, -1.3, 2.4],
+    [0.0, 0.0, 0.0], [1.425, -0.798, 0.0], [1.5, 0.0, 0.075], [1.425, 0.0, 0.0],
+    [0.798, -1.425, 0.0], [0.0, -1.5, 0.075], [0.0, -1.425, 0.0], [1.5, -0.84, 0.075],
+    [0.84, -1.5, 0.075],
+];
+
+/// Where a normal is taken from instead, this far into the patch, when the
+/// patch pinches to a point and the derivatives there vanish.
+const PINCH_OFFSET: f32 = 1e-3;
+
+type Patch = [[Vec3; 4]; 4];
+/// Weights for the four control points of a cubic, and for its derivative.
+type Basis = ([f32; 4], [f32; 4]);
+
+/// Newell's teapot evaluated from its Bézier patches, `resolution` by
+/// `resolution` quads each. Normals come from the patches' derivatives and
+/// are averaged where patches meet, so the seams don't show. The patches
+/// come along for the renderer to tessellate itself where it can.
+pub fn teapot(resolution: u32) -> Model {
+    let mut builder = Builder::default();
+    let patches = patches();
+    for patch in &patches {
+        builder.grid(resolution, resolution, |u, v| evaluate(patch, u, v));
+    }
+    share_seam_normals(&mut builder);
+    let mut model = builder.finish();
+    model.patches = patches
+        .iter()
+        .map(|patch| {
+            let points = patch.as_flattened();
+            std::array::from_fn(|index| points[index].to_array())
+        })
+        .collect();
+    model
+}
+
+/// All 32 patches, turned so Y is up. Copies mirrored once have each row
+/// reversed too, which keeps them facing outwards.
+fn patches() -> Vec<Patch> {
+    let mut patches = Vec::new();
+    for (number, indices) in PATCHES.iter().enumerate() {
+        let point = |row: usize, column: usize, x: f32, y: f32| {
+            let [px, py, pz] = CONTROL_POINTS[indices[row * 4 + column]];
+            // Adding zero turns -0 into 0, so points on a mirror plane come
+            // out identical from both sides.
+            Vec3::new(px * x + 0.0, pz, -py * y + 0.0)
+        };
+        let mirror = |x: f32, y: f32, reversed: bool| {
+            let mut patch = [[Vec3::default(); 4]; 4];
+            for (row, points) in patch.iter_mut().enumerate() {
+                for (column, point_at) in points.iter_mut().enumerate() {
+                    let column = if reversed { 3 - column } else { column };
+                    *point_at = point(row, column, x, y);
+                }
+            }
+            patch
+        };
+        patches.push(mirror(1.0, 1.0, false));
+        patches.push(mirror(1.0, -1.0, true));
+        if number < ROUND_PATCHES {
+            patches.push(mirror(-1.0, 1.0, true));
+            patches.push(mirror(-1.0, -1.0, false));
+        }
+    }
+    patches
+}
+
+/// The cubic Bernstein polynomials at `t`, and their derivatives.
+fn bernstein(t: f32) -> Basis {
+    let s = 1.0 - t;
+    (
+        [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t],
+        [
+            -3.0 * s * s,
+            3.0 * s * s - 6.0 * t * s,
+            6.0 * t * s - 3.0 * t * t,
+            3.0 * t * t,
+        ],
+    )
+}
+
+/// The point at `(u, v)`, with `u` running along each row of control points
+/// and `v` across the rows, and the normal there.
+fn evaluate(patch: &Patch, u: f32, v: f32) -> (Vec3, Vec3) {
+    let (position, along_u, along_v) = derivatives(patch, u, v);
+    let mut normal = along_u.cross(along_v);
+    if normal.length() <= f32::EPSILON {
+        let nudge = |t: f32| t + (0.5 - t).signum() * PINCH_OFFSET;
+        let (_, along_u, along_v) = derivatives(patch, nudge(u), nudge(v));
+        normal = along_u.cross(along_v);
+    }
+    (position, normal)
+}
+
+fn derivatives(patch: &Patch, u: f32, v: f32) -> (Vec3, Vec3, Vec3) {
+    let (u_basis, v_basis) = (bernstein(u), bernstein(v));
+    let mut rows = [Vec3::default(); 4];
+    let mut row_tangents = [Vec3::default(); 4];
+    for (row, points) in patch.iter().enumerate() {
+        (rows[row], row_tangents[row]) = curve(points, &u_basis);
+    }
+    let (position, along_v) = curve(&rows, &v_basis);
+    let (along_u, _) = curve(&row_tangents, &v_basis);
+    (position, along_u, along_v)
+}
+
+/// A cubic Bézier curve's point and derivative at the `t` `basis` was made
+/// for. When the four points are the same the curve is that point exactly,
+/// which is where the lid and the bottom pinch together, so every patch
+/// meeting there agrees on it and the derivative is exactly zero.
+fn curve(points: &[Vec3; 4], (basis, derivative): &Basis) -> (Vec3, Vec3) {
+    if points.iter().all(|&point| point == points[0]) {
+        return (points[0], Vec3::default());
+    }
+    let mut point = Vec3::default();
+    let mut tangent = Vec3::default();
+    for (index, &control) in points.iter().enumerate() {
+        point = point + control * basis[index];
+        tangent = tangent + control * derivative[index];
+    }
+    (point, tangent)
+}
+
+/// Gives every vertex the average normal of all the vertices in exactly the
+/// same place, which is where patches meet. They keep their own texture
+/// coordinates.
+fn share_seam_normals(builder: &mut Builder) {
+    let mut sums: HashMap<[u32; 3], Vec3> = HashMap::new();
+    let key = |position: [f32; 3]| position.map(f32::to_bits);
+    for vertex in &builder.vertices {
+        let sum = sums.entry(key(vertex.position)).or_default();
+        *sum = *sum + Vec3::from(vertex.normal);
+    }
+    for vertex in &mut builder.vertices {
+        vertex.normal = sums[&key(vertex.position)].normalize().into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    const RESOLUTION: u32 = 4;
+
+    /// Which of `PATCHES` each of the 32 patches was mirrored from.
+    fn patch_numbers() -> Vec<usize> {
+        (0..PATCHES.len())
+            .flat_map(|number| match number < ROUND_PATCHES {
+                true => vec![number; 4],
+                false => vec![number; 2],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn patches_share_their_seams() {
+        let model = teapot(RESOLUTION);
+        let side = RESOLUTION as usize + 1;
+        let (last, per_patch) = (side - 1, side * side);
+        let mut open = BTreeSet::new();
+        for (patch, number) in patch_numbers().into_iter().enumerate() {
+            let edges: [(&str, Vec<usize>); 4] = [
+                ("u = 0", (0..side).map(|row| row * side).collect()),
+                ("u = 1", (0..side).map(|row| row * side + last).collect()),
+                ("v = 0", (0..side).collect()),
+                (
+                    "v = 1",
+                    (0..side).map(|column| last * side + column).collect(),
+                ),
+            ];
+            for (edge, corners) in edges {
+                let mut shared = true;
+                for corner in corners {
+                    let vertex = &model.vertices[patch * per_patch + corner];
+                    let twins = model.vertices.iter().enumerate().filter(|(other, twin)| {
+                        other / per_patch != patch && twin.position == vertex.position
+                    });
+                    let mut any = false;
+                    for (_, twin) in twins {
+                        assert_eq!(twin.normal, vertex.normal, "at {:?}", vertex.position);
+                        any = true;
+                    }
+                    shared &= any;
+                }
+                if !shared {
+                    open.insert((number, edge));
+                }
+            }
+        }
+        // Only the rim's top, the lid's edge and the two ends of the handle
+        // and of the spout meet nothing.
+        let expected = [
+            (0, "v = 0"),
+            (4, "v = 1"),
+            (6, "v = 0"),
+            (7, "v = 1"),
+            (8, "v = 0"),
+            (9, "v = 1"),
+        ];
+        assert_eq!(open, BTreeSet::from(expected));
+    }
+
+    #[test]
+    fn extremes_are_where_the_control_points_put_them() {
+        let bounds = teapot(RESOLUTION).bounds;
+        let mut hull_min = [f32::MAX; 3];
+        let mut hull_max = [f32::MIN; 3];
+        for patch in patches() {
+            for point in patch.as_flattened() {
+                for (axis, value) in point.to_array().into_iter().enumerate() {
+                    hull_min[axis] = hull_min[axis].min(value);
+                    hull_max[axis] = hull_max[axis].max(value);
+                }
+            }
+        }
+        // The surface stays inside its control points' hull...
+        for axis in 0..3 {
+            assert!(bounds.min[axis] >= hull_min[axis], "{:?}", bounds);
+            assert!(bounds.max[axis] <= hull_max[axis], "{:?}", bounds);
+        }
+        // ...and goes through the ones at patch corners: the knob on the
+        // lid, the middle of the bottom, the body at its widest and the tip
+        // of the spout.
+        assert_eq!(bounds.max[1], 3.15);
+        assert_eq!(bounds.min[1], 0.0);
+        assert_eq!(bounds.max[2], 2.0);
+        assert_eq!(bounds.min[2], -2.0);
+        assert!(bounds.max[0] >= 3.2, "{:?}", bounds);
+        // The handle bulges out beyond the body.
+        assert!(bounds.min[0] < -2.0, "{:?}", bounds);
+    }
+}
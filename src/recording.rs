@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use glium::backend::Facade;
+use glium::framebuffer::{DepthStencilRenderBuffer, SimpleFrameBuffer};
+use glium::texture::pixel_buffer::PixelBuffer;
+use glium::texture::{
+    DepthStencilFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat,
+};
+use glium::Surface;
+
+use crate::screenshot;
+
+/// Every frame of a recording advances the animations by this much, however
+/// long it took to capture.
+pub const FRAME_TIME: f32 = 1.0 / 60.0;
+/// Readbacks kept in flight before the oldest is mapped, so the GPU has
+/// finished with it by then and the CPU doesn't wait.
+const READBACK_DEPTH: usize = 3;
+/// Frames waiting for an encoder before rendering blocks, which bounds the
+/// memory a slow disk can use up.
+const QUEUE_LENGTH: usize = 8;
+/// Progress is printed every this many frames.
+const PROGRESS_EVERY: usize = 60;
+
+/// Frame numbers from 0, with the pixels read back for them.
+type Frame = (usize, RawImage2d<'static, u8>);
+type Readback = (usize, PixelBuffer<(u8, u8, u8, u8)>);
+
+/// Captures `--frames` frames for `--record` as numbered PNGs. Each frame is
+/// drawn offscreen, copied to the window to show progress, and read back
+/// asynchronously; PNG encoding happens on worker threads.
+pub struct FrameRecorder {
+    directory: PathBuf,
+    total: usize,
+    captured: usize,
+    color: Texture2d,
+    depth: DepthStencilRenderBuffer,
+    pending: VecDeque<Readback>,
+    sender: Option<SyncSender<Frame>>,
+    workers: Vec<JoinHandle<()>>,
+    started: Instant,
+}
+
+impl FrameRecorder {
+    /// Creates `directory` if needed and starts the encoders for frames of
+    /// `size`.
+    pub fn new(
+        facade: &impl Facade,
+        directory: &Path,
+        total: usize,
+        (width, height): (u32, u32),
+    ) -> Result<Self, String> {
+        std::fs::create_dir_all(directory)
+            .map_err(|error| format!("{}: {}", directory.display(), error))?;
+        let (sender, receiver) = mpsc::sync_channel::<Frame>(QUEUE_LENGTH);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let threads = thread::available_parallelism().map_or(2, |count| count.get());
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                let directory = directory.to_path_buf();
+                thread::spawn(move || encode_frames(&receiver, &directory))
+            })
+            .collect();
+        Ok(FrameRecorder {
+            directory: directory.to_path_buf(),
+            total,
+            captured: 0,
+            color: Texture2d::empty_with_format(
+                facade,
+                UncompressedFloatFormat::U8U8U8U8,
+                MipmapsOption::NoMipmap,
+                width,
+                height,
+            )
+            .unwrap(),
+            // With a stencil, the only depth buffer glium makes on OpenGL ES.
+            depth: DepthStencilRenderBuffer::new(facade, DepthStencilFormat::I24I8, width, height)
+                .unwrap(),
+            pending: VecDeque::new(),
+            sender: Some(sender),
+            workers,
+            started: Instant::now(),
+        })
+    }
+
+    /// The size every frame is captured at, fixed when recording started.
+    pub fn size(&self) -> (u32, u32) {
+        self.color.dimensions()
+    }
+
+    /// Calls `draw` with the offscreen target for the next frame, shows it
+    /// in `frame` and queues it for saving. Returns false once every frame
+    /// has been captured and saved.
+    pub fn capture(
+        &mut self,
+        facade: &impl Facade,
+        frame: &mut impl Surface,
+        draw: impl FnOnce(&mut SimpleFrameBuffer),
+    ) -> bool {
+        let mut target =
+            SimpleFrameBuffer::with_depth_stencil_buffer(facade, &self.color, &self.depth).unwrap();
+        draw(&mut target);
+        self.color
+            .as_surface()
+            .fill(frame, glium::uniforms::MagnifySamplerFilter::Linear);
+        self.pending
+            .push_back((self.captured, self.color.read_to_pixel_buffer()));
+        self.captured += 1;
+
+        if self.captured.is_multiple_of(PROGRESS_EVERY) || self.captured == self.total {
+            let elapsed = self.started.elapsed().as_secs_f32();
+            println!(
+                "recorded {}/{} frames ({:.1} fps)",
+                self.captured,
+                self.total,
+                self.captured as f32 / elapsed.max(f32::EPSILON)
+            );
+        }
+        while self.pending.len() > READBACK_DEPTH {
+            self.send_oldest();
+        }
+        if self.captured < self.total {
+            return true;
+        }
+        self.finish();
+        false
+    }
+
+    fn send_oldest(&mut self) {
+        let (number, buffer) = match self.pending.pop_front() {
+            Some(pending) => pending,
+            None => return,
+        };
+        match buffer.read_as_texture_2d::<RawImage2d<u8>>() {
+            Ok(image) => {
+                if let Some(sender) = &self.sender {
+                    // Only fails if every encoder has died, which they
+                    // report themselves.
+                    let _ = sender.send((number, image));
+                }
+            }
+            Err(error) => eprintln!("failed to read back frame {}: {:?}", number + 1, error),
+        }
+    }
+
+    /// Saves the frames still in flight and waits for the encoders.
+    fn finish(&mut self) {
+        while !self.pending.is_empty() {
+            self.send_oldest();
+        }
+        self.sender = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+        let elapsed = self.started.elapsed().as_secs_f32();
+        println!(
+            "saved {} frames to {} in {:.1} s ({:.1} fps)",
+            self.captured,
+            self.directory.display(),
+            elapsed,
+            self.captured as f32 / elapsed.max(f32::EPSILON)
+        );
+    }
+}
+
+/// Writes frames as they arrive, until the recorder hangs up.
+fn encode_frames(receiver: &Mutex<Receiver<Frame>>, directory: &Path) {
+    loop {
+        // The lock is only held while waiting, not while encoding.
+        let received = receiver.lock().unwrap().recv();
+        let (number, image) = match received {
+            Ok(frame) => frame,
+            Err(_) => return,
+        };
+        let path = directory.join(format!("frame_{:06}.png", number + 1));
+        if let Err(error) = screenshot::write_png(&image, &path) {
+            eprintln!("failed to save {}: {}", path.display(), error);
+        }
+    }
+}
@@ -0,0 +1,2118 @@
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use glium::backend::{Context, Facade};
+use glium::glutin::dpi::PhysicalSize;
+use glium::program::BlockLayout;
+use glium::uniforms::{
+    AsUniformValue, LayoutMismatchError, UniformBlock, UniformBuffer, UniformValue, Uniforms,
+};
+use glium::Surface;
+
+use crate::bvh::Bvh;
+use crate::camera::{self, Camera};
+use crate::frustum::Frustum;
+use crate::gizmo::Gizmo;
+use crate::math::{Mat4, Vec3};
+use crate::model_loader::{self, Model};
+use crate::picking::{self, Hit, Ray};
+use crate::scene::{Light, LightKind, Object, Scene};
+use crate::texture_loader;
+
+mod arrows;
+mod bloom;
+mod edges;
+mod grid;
+mod highlight;
+mod lines;
+mod normals;
+mod outline;
+mod points;
+mod post;
+mod reflection;
+mod ripple;
+mod shader_errors;
+mod shaders;
+mod shadows;
+mod skybox;
+mod ssao;
+mod targets;
+mod tessellation;
+mod text;
+
+pub use post::{Effect, Output};
+pub use text::{TextOverlay, LINE_HEIGHT};
+
+/// The near and far planes as fractions of the camera's distance to its
+/// target, so models of any size keep the same depth precision.
+const ZNEAR_FRACTION: f32 = 0.01;
+const ZFAR_FRACTION: f32 = 100.0;
+/// The ground reaches this many times the scene's radius out from its
+/// center.
+const GROUND_EXTENT: f32 = 3.0;
+/// The grid fades out between these multiples of the camera distance.
+const GRID_FADE_START: f32 = 1.0;
+const GRID_FADE_END: f32 = 3.0;
+/// Triangles an object is drawn with for each pixel its bounding sphere
+/// covers, as long as it has a level of detail that coarse.
+const LOD_TRIANGLES_PER_PIXEL: f32 = 0.5;
+/// How far past a level's budget an object has to get before it's drawn at
+/// another, as a fraction of the budget.
+const LOD_HYSTERESIS: f32 = 0.25;
+
+/// Length of the light arrays in the fragment shader.
+pub const MAX_LIGHTS: usize = 8;
+
+/// A light as the fragment shader takes it, see `FrameUniforms`.
+struct ShaderLight {
+    /// 0 for directional, 1 for point lights.
+    kind: i32,
+    /// Direction towards a directional light or position of a point light, in
+    /// view space.
+    vector: [f32; 3],
+    /// Color times intensity.
+    color: [f32; 3],
+    attenuation: f32,
+}
+
+impl ShaderLight {
+    fn new(light: &Light, view: &Mat4) -> Self {
+        let (kind, vector, attenuation) = match light.kind {
+            LightKind::Directional { direction } => (0, view.transform_vector(direction), 0.0),
+            LightKind::Point {
+                position,
+                attenuation,
+            } => (1, view.transform_point(position), attenuation),
+        };
+        ShaderLight {
+            kind,
+            vector: vector.to_array(),
+            color: (Vec3::from(light.color) * light.intensity).to_array(),
+            attenuation,
+        }
+    }
+}
+
+/// The main program's `u_frame` block: what stays the same for a whole
+/// pass over the scene, written to a buffer once per pass instead of being
+/// sent with every draw. The fields follow std140's layout, where matrix
+/// columns and array elements take 16 bytes each and a float fits in the
+/// last 4 of a vec3, so there's no padding to add.
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+struct FrameUniforms {
+    perspective: [[f32; 4]; 4],
+    view: [[f32; 4]; 4],
+    u_inverse_view: [[f32; 4]; 4],
+    u_light_space: [[f32; 4]; 4],
+    u_camera_position: [f32; 3],
+    u_time: f32,
+    u_znear: f32,
+    u_zfar: f32,
+    u_shadow_light: i32,
+    u_light_count: i32,
+    /// A light's `vector` and its kind as w, 0 for a direction and 1 for a
+    /// position.
+    u_light_vector: [[f32; 4]; MAX_LIGHTS],
+    /// A light's `color` and attenuation.
+    u_light_color: [[f32; 4]; MAX_LIGHTS],
+}
+
+/// Written out rather than with `implement_uniform_block!`, which finds
+/// the fields' offsets through a null pointer, and debug builds now abort
+/// on that.
+impl UniformBlock for FrameUniforms {
+    fn matches(layout: &BlockLayout, base_offset: usize) -> Result<(), LayoutMismatchError> {
+        let (BlockLayout::Struct { members }, BlockLayout::Struct { members: ours }) =
+            (layout, Self::build_layout(base_offset))
+        else {
+            return Err(LayoutMismatchError::LayoutMismatch {
+                expected: layout.clone(),
+                obtained: Self::build_layout(base_offset),
+            });
+        };
+        let unused = ours
+            .iter()
+            .find(|(name, _)| members.iter().all(|(other, _)| other != name));
+        if let Some((name, _)) = unused {
+            return Err(LayoutMismatchError::MissingField { name: name.clone() });
+        }
+        for (name, expected) in members {
+            let Some((_, obtained)) = ours.iter().find(|(other, _)| other == name) else {
+                return Err(LayoutMismatchError::MissingField { name: name.clone() });
+            };
+            if obtained != expected {
+                return Err(LayoutMismatchError::MemberMismatch {
+                    member: name.clone(),
+                    err: Box::new(LayoutMismatchError::LayoutMismatch {
+                        expected: expected.clone(),
+                        obtained: obtained.clone(),
+                    }),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn build_layout(base_offset: usize) -> BlockLayout {
+        macro_rules! member {
+            ($field:ident: $ty:ty) => {
+                (
+                    stringify!($field).to_string(),
+                    <$ty>::build_layout(base_offset + std::mem::offset_of!(Self, $field)),
+                )
+            };
+        }
+        BlockLayout::Struct {
+            members: vec![
+                member!(perspective: [[f32; 4]; 4]),
+                member!(view: [[f32; 4]; 4]),
+                member!(u_inverse_view: [[f32; 4]; 4]),
+                member!(u_light_space: [[f32; 4]; 4]),
+                member!(u_camera_position: [f32; 3]),
+                member!(u_time: f32),
+                member!(u_znear: f32),
+                member!(u_zfar: f32),
+                member!(u_shadow_light: i32),
+                member!(u_light_count: i32),
+                member!(u_light_vector: [[f32; 4]; MAX_LIGHTS]),
+                member!(u_light_color: [[f32; 4]; MAX_LIGHTS]),
+            ],
+        }
+    }
+}
+
+// Four matrices, then 32 bytes of scalars, then the two light arrays.
+const _: () =
+    assert!(std::mem::size_of::<FrameUniforms>() == 4 * 64 + 32 + 2 * MAX_LIGHTS * 16);
+
+impl FrameUniforms {
+    /// Fails if `program`'s `u_frame` block isn't laid out like this, which
+    /// would otherwise only come up when drawing with it.
+    fn check_layout(program: &glium::Program) -> Result<(), String> {
+        match program.get_uniform_blocks().get("u_frame") {
+            Some(block) => <Self as UniformBlock>::matches(&block.layout, 0)
+                .map_err(|error| format!("u_frame doesn't match FrameUniforms: {}", error)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A draw's uniforms with `u_frame` added: the buffer where the program
+/// has the block, or each member on its own where `shaders::es_variant`
+/// unwrapped it, on OpenGL ES.
+struct WithFrame<'a, U> {
+    buffer: &'a UniformBuffer<FrameUniforms>,
+    values: &'a FrameUniforms,
+    block: bool,
+    rest: U,
+}
+
+impl<U: Uniforms> Uniforms for WithFrame<'_, U> {
+    fn visit_values<'b, F: FnMut(&str, UniformValue<'b>)>(&'b self, mut visit: F) {
+        let values = self.values;
+        if self.block {
+            visit("u_frame", self.buffer.as_uniform_value());
+        } else {
+            visit("perspective", UniformValue::Mat4(values.perspective));
+            visit("view", UniformValue::Mat4(values.view));
+            visit("u_inverse_view", UniformValue::Mat4(values.u_inverse_view));
+            visit("u_light_space", UniformValue::Mat4(values.u_light_space));
+            visit("u_camera_position", UniformValue::Vec3(values.u_camera_position));
+            visit("u_time", UniformValue::Float(values.u_time));
+            visit("u_znear", UniformValue::Float(values.u_znear));
+            visit("u_zfar", UniformValue::Float(values.u_zfar));
+            visit("u_shadow_light", UniformValue::SignedInt(values.u_shadow_light));
+            visit("u_light_count", UniformValue::SignedInt(values.u_light_count));
+            // glium names array elements one by one.
+            for index in 0..MAX_LIGHTS {
+                let vector = UniformValue::Vec4(values.u_light_vector[index]);
+                visit(&format!("u_light_vector[{}]", index), vector);
+                let color = UniformValue::Vec4(values.u_light_color[index]);
+                visit(&format!("u_light_color[{}]", index), color);
+            }
+        }
+        self.rest.visit_values(visit);
+    }
+}
+
+/// What a call to `Renderer::render` drew.
+#[derive(Default)]
+pub struct RenderStats {
+    pub draw_calls: usize,
+    /// Not counting those the GPU tessellates patches into.
+    pub triangles: usize,
+    /// Scene objects in view, and in the scene.
+    pub objects_drawn: usize,
+    pub objects: usize,
+    /// Objects in view drawn at each level of detail, the models themselves
+    /// first. Empty when no model has levels of detail.
+    pub objects_per_lod: Vec<usize>,
+    /// GPU milliseconds spent on bloom, when it's on and timed.
+    pub bloom_gpu_time: Option<f32>,
+    /// CPU milliseconds spent uploading rippled vertices, when they are.
+    pub ripple_upload_time: Option<f32>,
+}
+
+/// Position and color of one end of an unlit line.
+#[derive(Clone, Copy)]
+struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+implement_vertex!(LineVertex, position, color);
+
+/// Per-instance attributes, see the vertex shader. The normal matrix is
+/// worked out here once per object rather than for every vertex.
+#[derive(Clone, Copy)]
+struct Instance {
+    i_model_0: [f32; 4],
+    i_model_1: [f32; 4],
+    i_model_2: [f32; 4],
+    i_model_3: [f32; 4],
+    i_normal_0: [f32; 3],
+    i_normal_1: [f32; 3],
+    i_normal_2: [f32; 3],
+    i_tint: [f32; 3],
+}
+
+implement_vertex!(
+    Instance, i_model_0, i_model_1, i_model_2, i_model_3, i_normal_0, i_normal_1, i_normal_2,
+    i_tint
+);
+
+impl Instance {
+    fn new(model: Mat4, tint: [f32; 3]) -> Self {
+        let [i_model_0, i_model_1, i_model_2, i_model_3] = model.to_array();
+        let [i_normal_0, i_normal_1, i_normal_2] = model.normal_matrix().to_array();
+        Instance {
+            i_model_0,
+            i_model_1,
+            i_model_2,
+            i_model_3,
+            i_normal_0,
+            i_normal_1,
+            i_normal_2,
+            i_tint: tint,
+        }
+    }
+}
+
+/// An index buffer of whichever type `Model::gpu_indexes` picked.
+enum GpuIndices {
+    U16(glium::IndexBuffer<u16>),
+    U32(glium::IndexBuffer<u32>),
+}
+
+impl GpuIndices {
+    fn new(facade: &impl Facade, model: &Model, indexes: &[u32]) -> Self {
+        Self::of(facade, model, indexes, glium::index::PrimitiveType::TrianglesList)
+    }
+
+    /// Line segments, two indexes each.
+    fn lines(facade: &impl Facade, model: &Model, indexes: &[u32]) -> Self {
+        Self::of(facade, model, indexes, glium::index::PrimitiveType::LinesList)
+    }
+
+    fn of(
+        facade: &impl Facade,
+        model: &Model,
+        indexes: &[u32],
+        primitive: glium::index::PrimitiveType,
+    ) -> Self {
+        match model.gpu_indexes(indexes) {
+            model_loader::GpuIndexes::U16(indexes) => GpuIndices::U16(
+                glium::IndexBuffer::new(facade, primitive, &indexes).unwrap(),
+            ),
+            model_loader::GpuIndexes::U32(indexes) => GpuIndices::U32(
+                glium::IndexBuffer::new(facade, primitive, indexes).unwrap(),
+            ),
+        }
+    }
+
+    fn all(&self) -> glium::index::IndicesSource<'_> {
+        match self {
+            GpuIndices::U16(buffer) => buffer.into(),
+            GpuIndices::U32(buffer) => buffer.into(),
+        }
+    }
+
+    fn slice(&self, range: std::ops::Range<usize>) -> glium::index::IndicesSource<'_> {
+        match self {
+            GpuIndices::U16(buffer) => buffer.slice(range).unwrap().into(),
+            GpuIndices::U32(buffer) => buffer.slice(range).unwrap().into(),
+        }
+    }
+}
+
+/// GPU buffers for one mesh of the loaded scene.
+struct GpuMesh {
+    /// Dynamic, since the ripple rewrites it every frame.
+    vertices: glium::VertexBuffer<model_loader::Vertex3D>,
+    /// The CPU ripple's last output, kept to reuse its allocation. Empty
+    /// while `vertices` holds the model's own.
+    rippled: Vec<model_loader::Vertex3D>,
+    /// Plain white when the model has no vertex colors of its own.
+    colors: glium::VertexBuffer<model_loader::Color>,
+    /// Zero when the model has no texture coordinates to map normals by.
+    tangents: glium::VertexBuffer<model_loader::Tangent>,
+    /// Fully open when the model has no ambient occlusion baked.
+    occlusion: glium::VertexBuffer<model_loader::Occlusion>,
+    indices: GpuIndices,
+    /// `model.lines`, if it has any.
+    lines: Option<GpuIndices>,
+    /// One for each of `model.lods`, over the same vertices.
+    lods: Vec<GpuIndices>,
+    /// How many of the instances in view are drawn at each level of detail,
+    /// the model itself first. They're in that order in `instances`.
+    level_counts: Vec<usize>,
+    /// One diffuse texture, one normal map and one metallic-roughness map
+    /// per entry in `model.materials`.
+    textures: Vec<glium::texture::SrgbTexture2d>,
+    normal_maps: Vec<glium::texture::Texture2d>,
+    metallic_roughness_maps: Vec<glium::texture::Texture2d>,
+    /// Endpoints of the lines drawn when normals are shown.
+    normal_lines: glium::VertexBuffer<LineVertex>,
+    /// The control points of `model.patches`, if it has any, drawn in
+    /// place of the triangles where the GPU can tessellate them.
+    patches: Option<glium::VertexBuffer<tessellation::ControlPoint>>,
+    model: Model,
+    /// Over `model`'s triangles, for picking.
+    bvh: Bvh,
+    /// Center and radius of a sphere around the vertices.
+    sphere: (Vec3, f32),
+    /// The middle of each sub-mesh's bounding box, which its translucent
+    /// faces are sorted by.
+    sub_mesh_centers: Vec<Vec3>,
+    /// Rewritten every frame; only reallocated when it runs out of room.
+    /// The first `visible_count` are the ones in view, the rest are only
+    /// drawn into the shadow map.
+    instances: glium::VertexBuffer<Instance>,
+    instance_count: usize,
+    visible_count: usize,
+}
+
+impl GpuMesh {
+    fn new(facade: &impl Facade, model: Model) -> Self {
+        let stats = model.stats();
+        GpuMesh {
+            vertices: glium::VertexBuffer::dynamic(facade, &model.vertices).unwrap(),
+            rippled: Vec::new(),
+            colors: match model.colors.is_empty() {
+                true => glium::VertexBuffer::new(
+                    facade,
+                    &vec![model_loader::Color::WHITE; model.vertices.len()],
+                )
+                .unwrap(),
+                false => glium::VertexBuffer::new(facade, &model.colors).unwrap(),
+            },
+            tangents: {
+                let mut tangents = model_loader::compute_tangents(&model);
+                tangents.resize(model.vertices.len(), model_loader::Tangent::default());
+                glium::VertexBuffer::new(facade, &tangents).unwrap()
+            },
+            occlusion: {
+                let mut occlusion = model
+                    .occlusion
+                    .iter()
+                    .map(|&occlusion| model_loader::Occlusion { occlusion })
+                    .collect::<Vec<model_loader::Occlusion>>();
+                occlusion.resize(model.vertices.len(), model_loader::Occlusion::OPEN);
+                glium::VertexBuffer::new(facade, &occlusion).unwrap()
+            },
+            indices: GpuIndices::new(facade, &model, &model.indexes),
+            lines: (!model.lines.is_empty())
+                .then(|| GpuIndices::lines(facade, &model, &model.lines)),
+            lods: model
+                .lods
+                .iter()
+                .map(|lod| GpuIndices::new(facade, &model, &lod.indexes))
+                .collect(),
+            level_counts: Vec::new(),
+            textures: model
+                .materials
+                .iter()
+                .map(|material| {
+                    texture_loader::load_texture(facade, material.diffuse_map.as_deref())
+                })
+                .collect(),
+            normal_maps: model
+                .materials
+                .iter()
+                .map(|material| {
+                    texture_loader::load_normal_map(facade, material.normal_map.as_deref())
+                })
+                .collect(),
+            // White where there's no map leaves the factors as they are.
+            metallic_roughness_maps: model
+                .materials
+                .iter()
+                .map(|material| {
+                    let path = material.metallic_roughness_map.as_deref();
+                    texture_loader::load_linear_texture(facade, path, [255; 4])
+                })
+                .collect(),
+            normal_lines: normals::NormalLines::build(facade, &model),
+            patches: tessellation::control_points(facade, &model.patches),
+            instances: glium::VertexBuffer::empty_dynamic(facade, 1).unwrap(),
+            instance_count: 0,
+            visible_count: 0,
+            sphere: (Vec3::from(stats.bounds.center()), stats.radius),
+            sub_mesh_centers: sub_mesh_centers(&model),
+            bvh: Bvh::build(&model),
+            model,
+        }
+    }
+
+    /// The indices and sub-meshes to draw at `level` of detail, 0 being
+    /// the model itself.
+    fn level(&self, level: usize) -> (&GpuIndices, &[model_loader::SubMesh]) {
+        match level {
+            0 => (&self.indices, &self.model.sub_meshes),
+            _ => (&self.lods[level - 1], &self.model.lods[level - 1].sub_meshes),
+        }
+    }
+
+    fn level_triangles(&self, level: usize) -> usize {
+        match level {
+            0 => self.model.indexes.len() / 3,
+            _ => self.model.lods[level - 1].indexes.len() / 3,
+        }
+    }
+
+    /// The level of detail for an object whose bounding sphere covers
+    /// `pixels` on the screen, and was drawn at `current` last frame. The
+    /// finest level within the budget of triangles for that area, except
+    /// that it only changes once the budget is well past the current
+    /// level's, so an object near the threshold doesn't flick between two.
+    fn pick_level(&self, pixels: f32, current: usize) -> usize {
+        let budget = pixels * LOD_TRIANGLES_PER_PIXEL;
+        let finest_within = |budget: f32| {
+            (0..self.lods.len())
+                .find(|&level| self.level_triangles(level) as f32 <= budget)
+                .unwrap_or(self.lods.len())
+        };
+        current.clamp(
+            finest_within(budget * (1.0 + LOD_HYSTERESIS)),
+            finest_within(budget / (1.0 + LOD_HYSTERESIS)),
+        )
+    }
+
+    /// Takes the instances in view first, `visible_count` of them: the
+    /// opaque ones, `level_counts` of them at each level of detail in turn,
+    /// then those less than opaque. The rest come after.
+    fn write_instances(
+        &mut self,
+        facade: &impl Facade,
+        instances: &[Instance],
+        level_counts: Vec<usize>,
+        visible_count: usize,
+    ) {
+        if instances.len() > self.instances.len() {
+            let capacity = instances.len().next_power_of_two();
+            self.instances = glium::VertexBuffer::empty_dynamic(facade, capacity).unwrap();
+        }
+        if let Some(slice) = self.instances.slice(0..instances.len()) {
+            slice.write(instances);
+        }
+        self.instance_count = instances.len();
+        self.visible_count = visible_count;
+        self.level_counts = level_counts;
+    }
+
+    /// Writes the model's vertices rippled as they are `time` seconds in,
+    /// or as loaded for `None`, returning how long the upload took. Does
+    /// nothing when there's no change to make.
+    fn write_ripple(&mut self, time: Option<f32>) -> Option<Duration> {
+        match time {
+            Some(time) => ripple::Ripple::new(self.sphere).apply(
+                &self.model.vertices,
+                time,
+                &mut self.rippled,
+            ),
+            None if self.rippled.is_empty() => return None,
+            None => self.rippled.clear(),
+        }
+        let vertices = match self.rippled.is_empty() {
+            true => &self.model.vertices,
+            false => &self.rippled,
+        };
+        let start = Instant::now();
+        self.vertices.write(vertices);
+        Some(start.elapsed())
+    }
+}
+
+/// How triangles are rasterized, cycled with W.
+#[derive(Clone, Copy)]
+pub enum WireframeMode {
+    Shaded,
+    Wireframe,
+    /// Shaded with the wireframe on top, in the same pass where there are
+    /// geometry shaders and in a second pass of lines where there aren't.
+    Overlay,
+}
+
+impl WireframeMode {
+    pub fn next(self) -> Self {
+        match self {
+            WireframeMode::Shaded => WireframeMode::Wireframe,
+            WireframeMode::Wireframe => WireframeMode::Overlay,
+            WireframeMode::Overlay => WireframeMode::Shaded,
+        }
+    }
+
+    /// Whether each pass draws lines, in drawing order.
+    fn passes(self) -> &'static [bool] {
+        match self {
+            WireframeMode::Shaded => &[false],
+            WireframeMode::Wireframe => &[true],
+            WireframeMode::Overlay => &[false, true],
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            WireframeMode::Shaded => "shaded",
+            WireframeMode::Wireframe => "wireframe",
+            WireframeMode::Overlay => "wireframe overlay",
+        }
+    }
+}
+
+/// What the fragment shader outputs, cycled with M. The discriminant is the
+/// `u_render_mode` uniform.
+#[derive(Clone, Copy, Debug)]
+pub enum RenderMode {
+    Shaded = 0,
+    /// View-space normals mapped to colors.
+    Normals = 1,
+    /// Linearized depth as grayscale, white at the far plane.
+    Depth = 2,
+    /// View-space tangents mapped to colors, darker where the texture is
+    /// mirrored, to find seams in the normal mapping.
+    Tangents = 3,
+}
+
+impl RenderMode {
+    pub fn next(self) -> Self {
+        match self {
+            RenderMode::Shaded => RenderMode::Normals,
+            RenderMode::Normals => RenderMode::Depth,
+            RenderMode::Depth => RenderMode::Tangents,
+            RenderMode::Tangents => RenderMode::Shaded,
+        }
+    }
+}
+
+/// How the lights are applied in `RenderMode::Shaded`, cycled with V. The
+/// discriminant is the `u_shading_model` uniform.
+#[derive(Clone, Copy, Debug)]
+pub enum ShadingModel {
+    BlinnPhong = 0,
+    Phong = 1,
+    /// Diffuse light quantized into `Renderer::toon_bands` steps, with dark
+    /// silhouettes.
+    Toon = 2,
+    /// Cook-Torrance, with GGX microfacets, Smith's geometry term and
+    /// Schlick's Fresnel, from each material's metallic and roughness.
+    Pbr = 3,
+}
+
+impl ShadingModel {
+    pub fn next(self) -> Self {
+        match self {
+            ShadingModel::BlinnPhong => ShadingModel::Phong,
+            ShadingModel::Phong => ShadingModel::Toon,
+            ShadingModel::Toon => ShadingModel::Pbr,
+            ShadingModel::Pbr => ShadingModel::BlinnPhong,
+        }
+    }
+}
+
+/// Which faces are culled, cycled with C.
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CullMode {
+    Off,
+    Back,
+    Front,
+}
+
+impl CullMode {
+    pub fn next(self) -> Self {
+        match self {
+            CullMode::Off => CullMode::Back,
+            CullMode::Back => CullMode::Front,
+            CullMode::Front => CullMode::Off,
+        }
+    }
+
+    /// The view matrix is left-handed, so counter-clockwise front faces end
+    /// up clockwise on screen. A `mirrored` view flips them back.
+    fn backface_culling(self, mirrored: bool) -> glium::BackfaceCullingMode {
+        match (self, mirrored) {
+            (CullMode::Off, _) => glium::BackfaceCullingMode::CullingDisabled,
+            (CullMode::Back, false) | (CullMode::Front, true) => {
+                glium::BackfaceCullingMode::CullCounterClockwise
+            }
+            (CullMode::Front, false) | (CullMode::Back, true) => {
+                glium::BackfaceCullingMode::CullClockwise
+            }
+        }
+    }
+}
+
+/// How faces fade into `Fog::color` with their depth, cycled with Shift+F.
+/// The discriminant is the `u_fog_mode` uniform.
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FogMode {
+    Off = 0,
+    /// None at `Fog::start`, rising evenly to all at `Fog::end`.
+    Linear = 1,
+    /// `exp(-density * depth)` of the face's own color is left.
+    Exponential = 2,
+}
+
+impl FogMode {
+    pub fn next(self) -> Self {
+        match self {
+            FogMode::Off => FogMode::Linear,
+            FogMode::Linear => FogMode::Exponential,
+            FogMode::Exponential => FogMode::Off,
+        }
+    }
+}
+
+/// Distance fog over the models and the ground, but not the sky. Depths
+/// are along the view axis, in world units.
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Fog {
+    pub mode: FogMode,
+    pub density: f32,
+    pub start: f32,
+    pub end: f32,
+    /// sRGB. `None` uses the clear color, so faces fade into the
+    /// background where the sky doesn't cover it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<[f32; 3]>,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Fog {
+            mode: FogMode::Off,
+            density: 0.02,
+            start: 10.0,
+            end: 100.0,
+            color: None,
+        }
+    }
+}
+
+/// One drawing of the scene into a target.
+struct ScenePass<'a> {
+    view: Mat4,
+    lights: &'a [ShaderLight],
+    /// The light casting shadows and its world to clip space matrix.
+    shadow: Option<(usize, Mat4)>,
+    /// Whether `view` is mirrored in the ground, for its reflection. Only
+    /// the models are drawn, and only above the ground.
+    mirrored: bool,
+    /// The selected object's mesh and model matrix, to outline it.
+    selected: Option<(usize, Mat4)>,
+    gizmo: Option<Gizmo>,
+    /// Whether only the opaque faces' normals and depths are drawn, with
+    /// `ssao::NORMAL_DEPTH_SRC`, for the ambient occlusion.
+    normals_only: bool,
+}
+
+/// One sub-mesh of a run of a mesh's instances at one level of detail.
+struct Part<'a> {
+    mesh: &'a GpuMesh,
+    instances: std::ops::Range<usize>,
+    level: usize,
+    /// Index in the level's sub-meshes.
+    sub_mesh: usize,
+    /// Multiplies the material's opacity. `None` for opaque parts, which
+    /// write depth rather than blending.
+    opacity: Option<f32>,
+}
+
+/// One sub-mesh of an object in view that's drawn blended, because the
+/// object or the sub-mesh's material is less than opaque.
+struct Translucent {
+    mesh_id: usize,
+    /// Which of the mesh's instances is the object's.
+    instance: usize,
+    level: usize,
+    /// Index in the level's sub-meshes.
+    sub_mesh: usize,
+    /// The object's, which the material's multiplies.
+    opacity: f32,
+    /// Where the sub-mesh's middle is in the world, to sort by.
+    center: Vec3,
+}
+
+/// The lit, textured shaders, and the variants built from them where the
+/// context allows.
+struct Programs {
+    triangles: glium::Program,
+    /// Drawing the wireframe overlay in the same pass, where geometry
+    /// shaders allow.
+    triangles_with_edges: Option<glium::Program>,
+    /// Tessellating Bézier patches, where tessellation shaders allow, with
+    /// and without the one-pass overlay.
+    patches: Option<glium::Program>,
+    patches_with_edges: Option<glium::Program>,
+    /// Drawing view-space normals and depths for the ambient occlusion
+    /// instead of shading, over triangles and patches.
+    normals: glium::Program,
+    patch_normals: Option<glium::Program>,
+}
+
+impl Programs {
+    /// Fails only if the main program does. The variants that don't build
+    /// are left out with a warning.
+    fn new(
+        facade: &impl Facade,
+        vertex: &str,
+        fragment: &str,
+    ) -> Result<Self, glium::ProgramCreationError> {
+        Ok(Programs {
+            triangles: shaders::program(facade, vertex, fragment, false)?,
+            triangles_with_edges: edges::program(facade, vertex, fragment),
+            patches: tessellation::program(facade, fragment, false),
+            patches_with_edges: tessellation::program(facade, fragment, true),
+            normals: shaders::program(facade, vertex, ssao::NORMAL_DEPTH_SRC, false)?,
+            patch_normals: tessellation::program(facade, ssao::NORMAL_DEPTH_SRC, false),
+        })
+    }
+
+    /// The program for triangles in `wireframe` mode, and its passes.
+    fn for_triangles(&self, wireframe: WireframeMode) -> Chosen<'_> {
+        with_overlay(&self.triangles, self.triangles_with_edges.as_ref(), wireframe)
+    }
+
+    /// The same for patches, or `None` where they can't be tessellated.
+    fn for_patches(&self, wireframe: WireframeMode) -> Option<Chosen<'_>> {
+        let program = self.patches.as_ref()?;
+        Some(with_overlay(program, self.patches_with_edges.as_ref(), wireframe))
+    }
+}
+
+/// A program and the passes of `WireframeMode::passes` to draw with it.
+type Chosen<'a> = (&'a glium::Program, &'static [bool]);
+
+/// The one pass of `WireframeMode::Shaded`, with the faces filled.
+const FILLED: &[bool] = &[false];
+
+/// `with_edges` for the overlay, when there is one, since the geometry
+/// shader's overlay needs no pass of its own. Otherwise `program`.
+fn with_overlay<'a>(
+    program: &'a glium::Program,
+    with_edges: Option<&'a glium::Program>,
+    wireframe: WireframeMode,
+) -> Chosen<'a> {
+    match (wireframe, with_edges) {
+        (WireframeMode::Overlay, Some(with_edges)) => (with_edges, &[false]),
+        _ => (program, wireframe.passes()),
+    }
+}
+
+/// Draws the loaded models with the lit, textured shader.
+pub struct Renderer {
+    context: Rc<Context>,
+    programs: Programs,
+    shader_files: shaders::ShaderFiles,
+    /// The program's `u_frame` block, rewritten for every pass.
+    frame_uniforms: UniformBuffer<FrameUniforms>,
+    /// When `u_time` counts from, and its value for the frame being drawn.
+    started: Instant,
+    time: f32,
+    meshes: Vec<GpuMesh>,
+    /// Framebuffer size, and the MSAA samples of the offscreen target.
+    size: (u32, u32),
+    samples: u32,
+    aspect_ratio: f32,
+    /// What's left where nothing is drawn, sRGB-encoded like material
+    /// colors.
+    pub background: [f32; 3],
+    pub wireframe: WireframeMode,
+    pub cull_mode: CullMode,
+    pub render_mode: RenderMode,
+    pub shading_model: ShadingModel,
+    pub toon_bands: u32,
+    /// Added to every material's roughness in the PBR shading, swept with
+    /// Shift+U and Shift+I.
+    pub roughness_offset: f32,
+    normal_lines: normals::NormalLines,
+    /// Whether vertex normals are drawn as lines, toggled with N.
+    pub show_normals: bool,
+    lines: lines::Lines,
+    /// Whether the line elements of OBJ files are drawn, toggled with
+    /// Shift+L.
+    pub show_lines: bool,
+    /// What color they're drawn in, sRGB-encoded like `background`.
+    pub line_color: [f32; 3],
+    points: points::Points,
+    /// Whether every model is drawn as its vertices alone, set by
+    /// `--points`. Point clouds always are.
+    pub points_only: bool,
+    /// How big the points are, 1 by default, changed with Alt+[ and Alt+].
+    pub point_size: f32,
+    grid: grid::Grid,
+    /// Whether the floor grid and axes are drawn, toggled with G.
+    pub show_grid: bool,
+    /// Extra spin about each object's Y axis, in radians.
+    pub model_rotation: f32,
+    /// Whether the models ripple, toggled with H.
+    pub ripple: bool,
+    /// Has the vertex shader ripple the models instead of rewriting their
+    /// vertex buffers, with `--gpu-deform`.
+    pub ripple_on_gpu: bool,
+    /// Colors tessellated patches by how finely they're cut, toggled with
+    /// Shift+Y.
+    pub show_tessellation_levels: bool,
+    /// Keeps patches cut and models at the levels of detail they were for
+    /// the camera when it was set, toggled with Y, to see them from
+    /// elsewhere.
+    pub freeze_lod: bool,
+    /// The view and projection patches' level of detail is worked out for.
+    lod_view_projection: Mat4,
+    /// Each scene object's level of detail in the last frame it was in
+    /// view, by its index in the scene.
+    lod_levels: Vec<usize>,
+    /// What's drawn blended after the opaque faces this frame.
+    translucent: Vec<Translucent>,
+    highlight: highlight::Highlight,
+    /// The triangle under the cursor, drawn highlighted.
+    pub hovered: Option<Hit>,
+    outline: outline::Outline,
+    /// Index of the scene object drawn outlined, chosen by clicking it or
+    /// with Tab.
+    pub selected: Option<usize>,
+    arrows: arrows::Arrows,
+    shadow_map: shadows::ShadowMap,
+    /// A quad under the models at y = 0, drawn with the shadows.
+    ground: GpuMesh,
+    /// Whether the first directional light casts shadows onto the models
+    /// and the ground, toggled with K.
+    pub shadows: bool,
+    /// Shows the shadow map in a corner, toggled with F10.
+    pub show_shadow_map: bool,
+    reflection: reflection::Reflection,
+    /// Whether the ground mirrors the models, toggled with J. Draws them a
+    /// second time.
+    pub reflections: bool,
+    /// How much of the ground's color is its reflection, from 0 to 1.
+    pub reflectivity: f32,
+    /// Drawn behind the models, a gradient until `set_skybox` is called.
+    skybox: skybox::Skybox,
+    /// How much materials without their own reflectivity mirror the sky,
+    /// from 0 to 1, changed with U and I.
+    pub environment_reflectivity: f32,
+    pub fog: Fog,
+    ssao: ssao::Ssao,
+    /// Whether the ambient light is darkened where the models enclose it,
+    /// toggled with Shift+K. Draws the opaque faces a second time.
+    pub ambient_occlusion: bool,
+    /// How far around each point, in world units, the ambient occlusion
+    /// looks for what encloses it.
+    pub ao_radius: f32,
+    /// The power the ambient occlusion is raised to; more darkens more.
+    pub ao_intensity: f32,
+    /// Shows the ambient occlusion before it's blurred over the whole
+    /// window, toggled with Shift+F10.
+    pub show_ambient_occlusion: bool,
+    /// Whether the occlusion baked into the models with `--bake-ao` darkens
+    /// them, toggled with Ctrl+K.
+    pub baked_occlusion: bool,
+    post: post::PostProcess,
+    pub output: Output,
+}
+
+impl Renderer {
+    /// Call `resize` with the target's size before rendering.
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        let mut shader_files = shaders::ShaderFiles::find();
+        let programs = match compile_shader_files(facade, &mut shader_files) {
+            Some(programs) => programs,
+            None => Programs::new(facade, shaders::EMBEDDED_VERTEX, shaders::EMBEDDED_FRAGMENT)?,
+        };
+        if !edges::supported(facade) {
+            println!("no geometry shaders, so the wireframe overlay takes two passes");
+        }
+        if !tessellation::supported(facade) {
+            println!("no tessellation shaders, so patches are drawn as the CPU cut them");
+        }
+        Ok(Renderer {
+            context: facade.get_context().clone(),
+            programs,
+            shader_files,
+            frame_uniforms: UniformBuffer::dynamic(facade, FrameUniforms::default()).unwrap(),
+            started: Instant::now(),
+            time: 0.0,
+            meshes: Vec::new(),
+            size: (1, 1),
+            samples: 0,
+            aspect_ratio: 1.0,
+            background: [0.12, 0.12, 0.12],
+            wireframe: WireframeMode::Shaded,
+            cull_mode: CullMode::Back,
+            render_mode: RenderMode::Shaded,
+            shading_model: ShadingModel::BlinnPhong,
+            toon_bands: 4,
+            roughness_offset: 0.0,
+            normal_lines: normals::NormalLines::new(facade)?,
+            show_normals: false,
+            lines: lines::Lines::new(facade)?,
+            show_lines: true,
+            line_color: [0.9, 0.9, 0.9],
+            points: points::Points::new(facade)?,
+            points_only: false,
+            point_size: 1.0,
+            grid: grid::Grid::new(facade)?,
+            show_grid: true,
+            model_rotation: 0.0,
+            ripple: false,
+            ripple_on_gpu: false,
+            show_tessellation_levels: false,
+            freeze_lod: false,
+            lod_view_projection: Mat4::IDENTITY,
+            lod_levels: Vec::new(),
+            translucent: Vec::new(),
+            highlight: highlight::Highlight::new(facade)?,
+            hovered: None,
+            outline: outline::Outline::new(facade)?,
+            selected: None,
+            arrows: arrows::Arrows::new(facade)?,
+            shadow_map: shadows::ShadowMap::new(facade)?,
+            ground: GpuMesh::new(facade, ground_model()),
+            shadows: true,
+            show_shadow_map: false,
+            reflection: reflection::Reflection::new(facade),
+            reflections: true,
+            reflectivity: 0.3,
+            skybox: skybox::Skybox::new(facade, texture_loader::gradient_cubemap(facade))?,
+            environment_reflectivity: 0.25,
+            fog: Fog::default(),
+            ssao: ssao::Ssao::new(facade)?,
+            ambient_occlusion: false,
+            ao_radius: 0.5,
+            ao_intensity: 1.0,
+            show_ambient_occlusion: false,
+            baked_occlusion: true,
+            post: post::PostProcess::new(facade)?,
+            output: Output::default(),
+        })
+    }
+
+    /// Rebuilds the program if the shader files were saved since they were
+    /// last read. A shader that doesn't compile leaves the old one in place.
+    pub fn reload_changed_shaders(&mut self) {
+        if !self.shader_files.changed() {
+            return;
+        }
+        if let Some(programs) = compile_shader_files(&self.context, &mut self.shader_files) {
+            self.programs = programs;
+            println!("reloaded shaders");
+        }
+    }
+
+    /// Uploads `model`, returning the mesh id scene objects refer to it by.
+    pub fn add_mesh(&mut self, model: Model) -> usize {
+        self.meshes.push(GpuMesh::new(&self.context, model));
+        self.meshes.len() - 1
+    }
+
+    pub fn clear_meshes(&mut self) {
+        self.meshes.clear();
+        self.lod_levels.clear();
+    }
+
+    /// Replaces the sky, e.g. with one from `texture_loader::load_cubemap`.
+    pub fn set_skybox(&mut self, cubemap: glium::texture::SrgbCubemap) {
+        self.skybox.cubemap = cubemap;
+    }
+
+    /// Width over height of the framebuffer.
+    pub fn aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    /// Returns the center and radius of a world-space sphere holding every
+    /// object in `scene`, as currently spun.
+    pub fn bounding_sphere(&self, scene: &Scene) -> (Vec3, f32) {
+        let spheres = scene
+            .objects
+            .iter()
+            .map(|object| self.object_sphere(object))
+            .collect::<Vec<(Vec3, f32)>>();
+        if spheres.is_empty() {
+            return (Vec3::default(), 0.0);
+        }
+
+        let mut min = Vec3::new(f32::MAX, f32::MAX, f32::MAX);
+        let mut max = Vec3::new(f32::MIN, f32::MIN, f32::MIN);
+        for (center, radius) in &spheres {
+            min = Vec3::new(
+                min.x.min(center.x - radius),
+                min.y.min(center.y - radius),
+                min.z.min(center.z - radius),
+            );
+            max = Vec3::new(
+                max.x.max(center.x + radius),
+                max.y.max(center.y + radius),
+                max.z.max(center.z + radius),
+            );
+        }
+        let center = (min + max) * 0.5;
+        let radius = spheres
+            .iter()
+            .map(|(other, radius)| (*other - center).length() + radius)
+            .fold(0.0, f32::max);
+        (center, radius)
+    }
+
+    /// The ray through the pixel at `cursor` as `camera` sees it, in a
+    /// framebuffer the size last passed to `resize`.
+    pub fn ray(&self, camera: &Camera, cursor: (f64, f64)) -> Option<Ray> {
+        let view = Mat4::look_at(camera.eye().into(), camera.target.into(), camera::UP.into());
+        let (projection, _, _) = self.projection(camera);
+        Ray::from_screen(cursor, self.size, view, projection)
+    }
+
+    /// The translation gizmo for the selected object, if there is one.
+    pub fn gizmo(&self, scene: &Scene, camera: &Camera) -> Option<Gizmo> {
+        let object = scene.objects.get(self.selected?)?;
+        Some(Gizmo::new(object.transform.translation, camera))
+    }
+
+    /// The first triangle of `scene` under the pixel at `cursor`, seen by
+    /// `camera` in a framebuffer the size last passed to `resize`.
+    pub fn pick(&self, scene: &Scene, camera: &Camera, cursor: (f64, f64)) -> Option<Hit> {
+        let ray = self.ray(camera, cursor)?;
+        let objects = scene.objects.iter().enumerate().map(|(index, object)| {
+            let mesh = &self.meshes[object.mesh_id];
+            (index, self.model_matrix(object), &mesh.model, &mesh.bvh)
+        });
+        picking::pick(&ray, objects)
+    }
+
+    /// The camera's projection matrix, and its near and far planes.
+    fn projection(&self, camera: &Camera) -> (Mat4, f32, f32) {
+        let znear = camera.distance * ZNEAR_FRACTION;
+        let zfar = camera.distance * ZFAR_FRACTION;
+        let projection = match camera.projection {
+            camera::Projection::Orthographic => {
+                let half_height = camera.distance * (camera.fov / 2.0).tan() * camera.ortho_scale;
+                let half_width = half_height * self.aspect_ratio;
+                Mat4::orthographic(-half_width, half_width, -half_height, half_height, znear, zfar)
+            }
+            camera::Projection::Perspective => {
+                Mat4::perspective(camera.fov, self.aspect_ratio, znear, zfar)
+            }
+        };
+        (projection, znear, zfar)
+    }
+
+    /// Roughly how many pixels a world-space sphere covers on the screen,
+    /// or infinitely many with the camera inside it.
+    fn projected_area(&self, camera: &Camera, center: Vec3, radius: f32) -> f32 {
+        let half_height = match camera.projection {
+            camera::Projection::Orthographic => {
+                camera.distance * (camera.fov / 2.0).tan() * camera.ortho_scale
+            }
+            camera::Projection::Perspective => {
+                let distance = (center - Vec3::from(camera.eye())).length();
+                if distance <= radius {
+                    return f32::INFINITY;
+                }
+                distance * (camera.fov / 2.0).tan()
+            }
+        };
+        let pixels = radius / half_height * self.size.1 as f32 / 2.0;
+        std::f32::consts::PI * pixels * pixels
+    }
+
+    /// The world-space sphere around an object's mesh.
+    fn object_sphere(&self, object: &Object) -> (Vec3, f32) {
+        let (center, radius) = self.meshes[object.mesh_id].sphere;
+        let scale = object.transform.scale;
+        let largest_scale = scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+        let center = self.model_matrix(object).transform_point(center);
+        (center, radius * largest_scale)
+    }
+
+    fn model_matrix(&self, object: &Object) -> Mat4 {
+        let mut transform = object.transform;
+        transform.rotation.y += self.model_rotation;
+        transform.matrix()
+    }
+
+    /// Call with the new framebuffer size whenever the window changes size.
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        // A minimised window can report a zero size.
+        self.size = (size.width.max(1), size.height.max(1));
+        self.aspect_ratio = self.size.0 as f32 / self.size.1 as f32;
+        self.post.resize(&self.context, self.size, self.samples);
+        self.reflection.resize(&self.context, self.size);
+        self.ssao.resize(&self.context, self.size);
+    }
+
+    /// Multisamples the offscreen target the scene is drawn into, with 0 or
+    /// 1 for none.
+    pub fn set_samples(&mut self, samples: u32) {
+        self.samples = samples;
+        self.post.resize(&self.context, self.size, self.samples);
+    }
+
+    /// Draws `scene` offscreen, then to `frame` as `output` says.
+    /// `frame` should be the size last passed to `resize`.
+    pub fn render(
+        &mut self,
+        frame: &mut impl Surface,
+        scene: &Scene,
+        camera: &Camera,
+    ) -> RenderStats {
+        let view = Mat4::look_at(camera.eye().into(), camera.target.into(), camera::UP.into());
+        let lights = scene
+            .lights
+            .iter()
+            .take(MAX_LIGHTS)
+            .map(|light| ShaderLight::new(light, &view))
+            .collect::<Vec<ShaderLight>>();
+        // The same time for both ripples, so they match.
+        self.time = self.started.elapsed().as_secs_f32();
+        let ripple_time = match self.ripple && !self.ripple_on_gpu {
+            true => Some(self.time),
+            false => None,
+        };
+        let ripple_upload_time = self
+            .meshes
+            .iter_mut()
+            .filter_map(|mesh| mesh.write_ripple(ripple_time))
+            .reduce(|total, time| total + time);
+        // Objects wholly outside the view aren't drawn, except into the
+        // shadow map, where they can still cast shadows into it. An object
+        // counts as in view if its reflection in the ground is.
+        let (projection, _, _) = self.projection(camera);
+        if !self.freeze_lod {
+            self.lod_view_projection = projection * view;
+        }
+        let frustum = Frustum::from_matrix(projection * view);
+        // Each object in view keeps its level of detail from the last frame
+        // unless it's well past that level's budget.
+        self.lod_levels.resize(scene.objects.len(), 0);
+        let mut visible: Vec<Vec<(usize, usize, Instance)>> = vec![Vec::new(); self.meshes.len()];
+        let mut hidden: Vec<Vec<Instance>> = vec![Vec::new(); self.meshes.len()];
+        let translucent_materials = self
+            .meshes
+            .iter()
+            .map(|mesh| mesh.model.materials.iter().any(|material| material.opacity < 1.0))
+            .collect::<Vec<bool>>();
+        // Objects in view with a translucent sub-mesh, and their levels.
+        let mut with_translucency: Vec<(usize, usize)> = Vec::new();
+        let mut objects_drawn = 0;
+        for (index, object) in scene.objects.iter().enumerate() {
+            let (center, radius) = self.object_sphere(object);
+            let mirrored = Vec3::new(center.x, -center.y, center.z);
+            let in_view = frustum.intersects_sphere(center, radius)
+                || (self.reflections && frustum.intersects_sphere(mirrored, radius));
+            let instance = Instance::new(self.model_matrix(object), object.tint);
+            if !in_view {
+                hidden[object.mesh_id].push(instance);
+                continue;
+            }
+            let mesh = &self.meshes[object.mesh_id];
+            let current = self.lod_levels[index].min(mesh.lods.len());
+            let level = match self.freeze_lod {
+                true => current,
+                false => mesh.pick_level(self.projected_area(camera, center, radius), current),
+            };
+            self.lod_levels[index] = level;
+            visible[object.mesh_id].push((level, index, instance));
+            if object.opacity < 1.0 || translucent_materials[object.mesh_id] {
+                with_translucency.push((index, level));
+            }
+            objects_drawn += 1;
+        }
+        // Where each object in view is in its mesh's instances.
+        let mut slots = vec![0; scene.objects.len()];
+        let mut objects_per_lod: Vec<usize> = Vec::new();
+        for ((mesh, mut batch), hidden) in self.meshes.iter_mut().zip(visible).zip(hidden) {
+            batch.sort_by_key(|&(level, index, _)| (scene.objects[index].opacity < 1.0, level));
+            let mut level_counts = vec![0; mesh.lods.len() + 1];
+            for &(level, index, _) in &batch {
+                if scene.objects[index].opacity >= 1.0 {
+                    level_counts[level] += 1;
+                }
+            }
+            if !mesh.lods.is_empty() {
+                if objects_per_lod.len() < level_counts.len() {
+                    objects_per_lod.resize(level_counts.len(), 0);
+                }
+                for (total, count) in objects_per_lod.iter_mut().zip(&level_counts) {
+                    *total += count;
+                }
+            }
+            for (slot, &(_, index, _)) in batch.iter().enumerate() {
+                slots[index] = slot;
+            }
+            let visible_count = batch.len();
+            let instances = batch
+                .into_iter()
+                .map(|(_, _, instance)| instance)
+                .chain(hidden)
+                .collect::<Vec<Instance>>();
+            mesh.write_instances(&self.context, &instances, level_counts, visible_count);
+        }
+        self.translucent = with_translucency
+            .into_iter()
+            .flat_map(|(index, level)| {
+                let object = &scene.objects[index];
+                let mesh = &self.meshes[object.mesh_id];
+                let model = self.model_matrix(object);
+                let instance = slots[index];
+                let materials = &mesh.model.materials;
+                let (_, sub_meshes) = mesh.level(level);
+                sub_meshes
+                    .iter()
+                    .enumerate()
+                    .filter(move |(_, sub_mesh)| {
+                        object.opacity < 1.0 || materials[sub_mesh.material].opacity < 1.0
+                    })
+                    .map(move |(sub_mesh, _)| Translucent {
+                        mesh_id: object.mesh_id,
+                        instance,
+                        level,
+                        sub_mesh,
+                        opacity: object.opacity,
+                        center: model.transform_point(mesh.sub_mesh_centers[sub_mesh]),
+                    })
+            })
+            .collect();
+        let (center, radius) = self.bounding_sphere(scene);
+        let ground = match (self.shadows || self.reflections) && radius > 0.0 {
+            true => {
+                let extent = radius * GROUND_EXTENT;
+                let matrix = Mat4::translation(Vec3::new(center.x, 0.0, center.z))
+                    * Mat4::scale(Vec3::new(extent, 1.0, extent));
+                vec![Instance::new(matrix, [1.0; 3])]
+            }
+            false => Vec::new(),
+        };
+        self.ground.write_instances(&self.context, &ground, vec![ground.len()], ground.len());
+
+        // Only the first directional light casts shadows; a point light
+        // would need a map for every direction.
+        let shadow_light = scene
+            .lights
+            .iter()
+            .take(MAX_LIGHTS)
+            .enumerate()
+            .find_map(|(index, light)| match light.kind {
+                LightKind::Directional { direction } => Some((index, direction)),
+                LightKind::Point { .. } => None,
+            });
+        let mut shadow_draw_calls = 0;
+        let shadow = match (self.shadows, shadow_light) {
+            (true, Some((index, direction))) => {
+                let (light_space, draw_calls) = self.shadow_map.render(
+                    &self.context,
+                    &self.meshes,
+                    direction,
+                    (center, radius),
+                    (self.ripple && self.ripple_on_gpu).then_some(self.time),
+                );
+                shadow_draw_calls = draw_calls;
+                Some((index, light_space))
+            }
+            _ => None,
+        };
+
+        let mut occlusion_stats = RenderStats::default();
+        if self.ambient_occlusion {
+            let pass = ScenePass {
+                view,
+                lights: &[],
+                shadow: None,
+                mirrored: false,
+                selected: None,
+                gizmo: None,
+                normals_only: true,
+            };
+            occlusion_stats = self.ssao.draw_normals(&self.context, |target| {
+                self.draw_scene(target, camera, &pass)
+            });
+            let orthographic = camera.projection == camera::Projection::Orthographic;
+            self.ssao.apply(
+                &self.context,
+                projection,
+                orthographic,
+                self.ao_radius,
+                self.ao_intensity,
+            );
+            occlusion_stats.draw_calls += 2;
+        }
+
+        let [red, green, blue] = srgb_to_linear(self.background);
+        let background = (red, green, blue, 1.0);
+        let mut reflection_stats = RenderStats::default();
+        if self.reflections && radius > 0.0 {
+            let mirrored_view = view * Mat4::scale(Vec3::new(1.0, -1.0, 1.0));
+            let mirrored_lights = scene
+                .lights
+                .iter()
+                .take(MAX_LIGHTS)
+                .map(|light| ShaderLight::new(light, &mirrored_view))
+                .collect::<Vec<ShaderLight>>();
+            let pass = ScenePass {
+                view: mirrored_view,
+                lights: &mirrored_lights,
+                shadow,
+                mirrored: true,
+                selected: None,
+                gizmo: None,
+                normals_only: false,
+            };
+            reflection_stats = self.reflection.draw(&self.context, |target| {
+                target.clear_color_and_depth(background, 1.0);
+                self.draw_scene(target, camera, &pass)
+            });
+        }
+
+        let selected = self
+            .selected
+            .and_then(|index| scene.objects.get(index))
+            .map(|object| (object.mesh_id, self.model_matrix(object)));
+        let pass = ScenePass {
+            view,
+            lights: &lights,
+            shadow,
+            mirrored: false,
+            selected,
+            gizmo: self.gizmo(scene, camera),
+            normals_only: false,
+        };
+        let mut stats = self.post.draw_scene(&self.context, |target| {
+            target.clear_all(background, 1.0, 0);
+            self.draw_scene(target, camera, &pass)
+        });
+        stats.draw_calls += reflection_stats.draw_calls;
+        stats.triangles += reflection_stats.triangles;
+        stats.draw_calls += occlusion_stats.draw_calls;
+        stats.triangles += occlusion_stats.triangles;
+        self.post.draw(&self.context, frame, &self.output);
+        if self.show_shadow_map {
+            self.shadow_map.draw_debug(frame);
+            stats.draw_calls += 1;
+        }
+        if self.ambient_occlusion && self.show_ambient_occlusion {
+            self.ssao.draw_debug(frame);
+            stats.draw_calls += 1;
+        }
+        stats.draw_calls += 1 + shadow_draw_calls;
+        stats.bloom_gpu_time = self.post.bloom_gpu_time(&self.output);
+        stats.ripple_upload_time = ripple_upload_time.map(|time| time.as_secs_f32() * 1000.0);
+        stats.objects_drawn = objects_drawn;
+        stats.objects = scene.objects.len();
+        stats.objects_per_lod = objects_per_lod;
+        stats
+    }
+
+    fn draw_scene(
+        &self,
+        frame: &mut impl Surface,
+        camera: &Camera,
+        pass: &ScenePass,
+    ) -> RenderStats {
+        let view = pass.view;
+        let (shadow_light, light_space) = match pass.shadow {
+            Some((index, light_space)) => (index as i32, light_space),
+            None => (-1, Mat4::IDENTITY),
+        };
+        let (projection, znear, zfar) = self.projection(camera);
+        let perspective = Mat4::perspective(camera.fov, self.aspect_ratio, znear, zfar);
+        let orthographic = camera.projection == camera::Projection::Orthographic;
+        let inverse_view = view.inverse().unwrap_or(Mat4::IDENTITY);
+        let mut frame_uniforms = FrameUniforms {
+            perspective: projection.to_array(),
+            view: view.to_array(),
+            u_inverse_view: inverse_view.to_array(),
+            u_light_space: light_space.to_array(),
+            u_camera_position: inverse_view.transform_point(Vec3::new(0.0, 0.0, 0.0)).to_array(),
+            u_time: self.time,
+            u_znear: znear,
+            u_zfar: zfar,
+            u_shadow_light: shadow_light,
+            u_light_count: pass.lights.len() as i32,
+            ..Default::default()
+        };
+        for (index, light) in pass.lights.iter().enumerate() {
+            let [x, y, z] = light.vector;
+            let [red, green, blue] = light.color;
+            frame_uniforms.u_light_vector[index] = [x, y, z, light.kind as f32];
+            frame_uniforms.u_light_color[index] = [red, green, blue, light.attenuation];
+        }
+        self.frame_uniforms.write(&frame_uniforms);
+        let mut stats = RenderStats::default();
+        let ground = match pass.mirrored {
+            true => None,
+            false => Some(&self.ground),
+        };
+        for mesh in self.meshes.iter().chain(ground) {
+            // Points aren't mirrored, nor have the surfaces the ambient
+            // occlusion needs, and are always opaque.
+            if self.drawn_as_points(mesh) {
+                if !pass.mirrored && !pass.normals_only && mesh.visible_count > 0 {
+                    let height = self.size.1;
+                    self.points.draw(frame, mesh, self.point_size, projection, view, height);
+                    stats.draw_calls += 1;
+                }
+                continue;
+            }
+            // Patches are drawn all at once and cut as finely as each needs
+            // rather than by level of detail. Other instances are drawn in a
+            // run for each level. Objects less than opaque come after the
+            // runs and are left for the blended pass, as are translucent
+            // materials.
+            let opaque_count = mesh.level_counts.iter().sum::<usize>();
+            let runs = match self.patches(mesh, pass.normals_only) {
+                Some(_) => vec![(0..opaque_count, 0)],
+                None => {
+                    let mut start = 0;
+                    let mut runs = Vec::new();
+                    for (level, &count) in mesh.level_counts.iter().enumerate() {
+                        runs.push((start..start + count, level));
+                        start += count;
+                    }
+                    runs
+                }
+            };
+            for (instances, level) in runs {
+                if instances.is_empty() {
+                    continue;
+                }
+                let (_, sub_meshes) = mesh.level(level);
+                for (sub_mesh, part) in sub_meshes.iter().enumerate() {
+                    if mesh.model.materials[part.material].opacity < 1.0 {
+                        continue;
+                    }
+                    let part = Part {
+                        mesh,
+                        instances: instances.clone(),
+                        level,
+                        sub_mesh,
+                        opacity: None,
+                    };
+                    self.draw_part(frame, pass, &frame_uniforms, orthographic, &part, &mut stats);
+                }
+            }
+        }
+
+        if pass.normals_only {
+            return stats;
+        }
+
+        // Not in the reflection, where nothing would clip them below the
+        // ground as it does the faces.
+        if self.show_lines && !pass.mirrored {
+            let color = srgb_to_linear(self.line_color);
+            for mesh in &self.meshes {
+                if self.lines.draw(frame, mesh, color, projection, view) {
+                    stats.draw_calls += 1;
+                }
+            }
+        }
+
+        // Last of the opaque geometry, so only the background is shaded.
+        // Always in perspective: an orthographic sky would be one color.
+        self.skybox.draw(frame, perspective, view);
+        stats.draw_calls += 1;
+
+        // Blended from the back to the front, without writing depth so
+        // those behind still show through. The sub-meshes are sorted by
+        // their middles, which leaves the faces within each in whatever
+        // order they're listed; a translucent sub-mesh that overlaps
+        // itself, like the teapot's spout in front of its body, can blend
+        // the wrong way round where it does. Two sub-meshes that surround
+        // each other can be sorted wrongly too.
+        let mut translucent = self
+            .translucent
+            .iter()
+            .map(|part| (view.transform_point(part.center).z, part))
+            .collect::<Vec<(f32, &Translucent)>>();
+        // The camera looks down -Z, so the furthest are the most negative.
+        translucent.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        for (_, translucent) in translucent {
+            let mesh = &self.meshes[translucent.mesh_id];
+            if self.drawn_as_points(mesh) {
+                continue;
+            }
+            let part = Part {
+                mesh,
+                instances: translucent.instance..translucent.instance + 1,
+                level: translucent.level,
+                sub_mesh: translucent.sub_mesh,
+                opacity: Some(translucent.opacity),
+            };
+            self.draw_part(frame, pass, &frame_uniforms, orthographic, &part, &mut stats);
+        }
+
+        if pass.mirrored {
+            return stats;
+        }
+        if let Some(hit) = &self.hovered {
+            self.highlight.draw(frame, hit.corners, projection, view);
+            stats.draw_calls += 1;
+        }
+        if let Some((mesh_id, model)) = pass.selected {
+            let mesh = &self.meshes[mesh_id];
+            self.outline.draw(frame, mesh, model, projection, view, self.size);
+            stats.draw_calls += 2;
+        }
+        if let Some(gizmo) = &pass.gizmo {
+            self.arrows.draw(frame, gizmo, projection, view);
+            stats.draw_calls += 1;
+        }
+        if self.show_normals {
+            for mesh in &self.meshes {
+                if mesh.visible_count == 0 {
+                    continue;
+                }
+                let instances = mesh.instances.slice(0..mesh.visible_count).unwrap();
+                self.normal_lines.draw(
+                    frame,
+                    &mesh.normal_lines,
+                    instances.per_instance().unwrap(),
+                    projection,
+                    view,
+                );
+                stats.draw_calls += 1;
+            }
+        }
+
+        // After the models, so the faded lines blend over them.
+        if self.show_grid {
+            stats.draw_calls += self.grid.draw(
+                frame,
+                projection,
+                view,
+                camera.distance * GRID_FADE_START,
+                camera.distance * GRID_FADE_END,
+            );
+        }
+        stats
+    }
+
+    /// Whether `mesh` is drawn as points rather than its triangles. The
+    /// ground never is.
+    fn drawn_as_points(&self, mesh: &GpuMesh) -> bool {
+        mesh.model.is_point_cloud() || (self.points_only && !std::ptr::eq(mesh, &self.ground))
+    }
+
+    /// The patches to draw `mesh` from and the program and passes for them,
+    /// shading or, if `normals_only`, not, unless it has none, they can't
+    /// be tessellated, or it's rippling, which the patches don't know about.
+    fn patches<'a>(
+        &'a self,
+        mesh: &'a GpuMesh,
+        normals_only: bool,
+    ) -> Option<(&'a glium::VertexBuffer<tessellation::ControlPoint>, Chosen<'a>)> {
+        let chosen = match normals_only {
+            true => self.programs.patch_normals.as_ref().map(|program| (program, FILLED)),
+            false => self.programs.for_patches(self.wireframe),
+        };
+        match self.ripple {
+            true => None,
+            false => mesh.patches.as_ref(),
+        }
+        .zip(chosen)
+    }
+
+    /// Draws one `Part` of a mesh with the lit shader, in each of the
+    /// wireframe mode's passes.
+    fn draw_part(
+        &self,
+        frame: &mut impl Surface,
+        pass: &ScenePass,
+        frame_uniforms: &FrameUniforms,
+        orthographic: bool,
+        part: &Part,
+        stats: &mut RenderStats,
+    ) {
+        let mesh = part.mesh;
+        let patches = self.patches(mesh, pass.normals_only);
+        // Patches are all in the first material.
+        if patches.is_some() && part.sub_mesh > 0 {
+            return;
+        }
+        let (program, passes) = match patches {
+            Some((_, chosen)) => chosen,
+            None if pass.normals_only => (&self.programs.normals, FILLED),
+            None => self.programs.for_triangles(self.wireframe),
+        };
+        let (indices, sub_meshes) = mesh.level(part.level);
+        let sub_mesh = &sub_meshes[part.sub_mesh];
+        let material = &mesh.model.materials[sub_mesh.material];
+        let (width, height) = frame.get_dimensions();
+        let is_ground = std::ptr::eq(mesh, &self.ground);
+        let ripple = ripple::Ripple::new(mesh.sphere);
+        let ripple_amplitude = match self.ripple && self.ripple_on_gpu && !is_ground {
+            true => ripple.amplitude,
+            false => 0.0,
+        };
+        let instances = mesh.instances.slice(part.instances.clone()).unwrap();
+        for &lines in passes {
+            let params = glium::DrawParameters {
+                depth: glium::Depth {
+                    test: glium::draw_parameters::DepthTest::IfLess,
+                    write: part.opacity.is_none(),
+                    ..Default::default()
+                },
+                blend: match part.opacity {
+                    Some(_) => glium::Blend::alpha_blending(),
+                    None => Default::default(),
+                },
+                polygon_mode: match lines {
+                    true => glium::PolygonMode::Line,
+                    false => glium::PolygonMode::Fill,
+                },
+                line_width: Some(1.0),
+                backface_culling: self.cull_mode.backface_culling(pass.mirrored),
+                // Pushes filled faces back so overlaid lines win the depth
+                // test.
+                polygon_offset: glium::draw_parameters::PolygonOffset {
+                    factor: 1.0,
+                    units: 1.0,
+                    fill: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let uniforms = uniform! {
+                u_ambient: srgb_to_linear(material.ambient),
+                u_diffuse: srgb_to_linear(material.diffuse),
+                u_specular: srgb_to_linear(material.specular),
+                u_shininess: material.shininess,
+                u_diffuse_map: &mesh.textures[sub_mesh.material],
+                u_normal_map: &mesh.normal_maps[sub_mesh.material],
+                u_metallic: material.reflectivity.unwrap_or(0.0),
+                u_roughness: (material.pbr_roughness() + self.roughness_offset).clamp(0.0, 1.0),
+                u_metallic_roughness_map: &mesh.metallic_roughness_maps[sub_mesh.material],
+                u_opacity: material.opacity * part.opacity.unwrap_or(1.0),
+                // Patches have tangents of their own.
+                u_normal_mapping: material.normal_map.is_some()
+                    && (mesh.model.has_tex_coords || patches.is_some()),
+                // Patches have no vertex colors, but color by level through
+                // them.
+                u_has_vertex_colors: match patches {
+                    Some(_) => self.show_tessellation_levels,
+                    None => !mesh.model.colors.is_empty(),
+                },
+                u_wireframe: lines,
+                u_render_mode: self.render_mode as i32,
+                u_shading_model: self.shading_model as i32,
+                u_toon_bands: self.toon_bands as i32,
+                u_shadow_map: self.shadow_map.texture(),
+                // What the mirrored pass sees isn't what the occlusion was
+                // worked out from.
+                u_ambient_occlusion: self.ambient_occlusion && !pass.mirrored,
+                u_occlusion: self.ssao.texture(),
+                // Patches have no vertices to bake it into.
+                u_baked_occlusion: self.baked_occlusion
+                    && patches.is_none()
+                    && !mesh.model.occlusion.is_empty(),
+                u_reflection: self.reflection.texture(pass.mirrored),
+                u_reflectivity: match is_ground && self.reflections {
+                    true => self.reflectivity,
+                    false => 0.0,
+                },
+                u_clip_below_ground: pass.mirrored,
+                u_environment: self.skybox.cubemap.sampled(),
+                u_environment_reflectivity: material
+                    .reflectivity
+                    .unwrap_or(self.environment_reflectivity),
+                u_orthographic: orthographic,
+                u_ripple_center: ripple.center.to_array(),
+                u_ripple_amplitude: ripple_amplitude,
+                u_ripple_wavelength: ripple.wavelength,
+                u_ripple_frequency: ripple::FREQUENCY,
+                u_viewport: [width as f32, height as f32],
+                u_edge_width: edges::WIDTH,
+                u_lod_view_projection: self.lod_view_projection.to_array(),
+                u_pixels_per_segment: tessellation::PIXELS_PER_SEGMENT,
+                u_color_by_level: self.show_tessellation_levels,
+                u_fog_mode: self.fog.mode as i32,
+                u_fog_color: srgb_to_linear(self.fog.color.unwrap_or(self.background)),
+                u_fog_density: self.fog.density,
+                u_fog_start: self.fog.start,
+                u_fog_end: self.fog.end,
+            };
+            let uniforms = WithFrame {
+                buffer: &self.frame_uniforms,
+                values: frame_uniforms,
+                block: self.context.get_opengl_version().0 == glium::Api::Gl,
+                rest: uniforms,
+            };
+            match patches {
+                Some((points, _)) => frame.draw(
+                    (points, instances.per_instance().unwrap()),
+                    glium::index::NoIndices(glium::index::PrimitiveType::Patches {
+                        vertices_per_patch: 16,
+                    }),
+                    program,
+                    &uniforms,
+                    &params,
+                ),
+                None => frame.draw(
+                    (
+                        &mesh.vertices,
+                        &mesh.colors,
+                        &mesh.tangents,
+                        &mesh.occlusion,
+                        instances.per_instance().unwrap(),
+                    ),
+                    indices.slice(sub_mesh.range.clone()),
+                    program,
+                    &uniforms,
+                    &params,
+                ),
+            }
+            .unwrap();
+            stats.draw_calls += 1;
+            if patches.is_none() {
+                stats.triangles += sub_mesh.range.len() / 3 * part.instances.len();
+            }
+        }
+    }
+}
+
+/// The middle of the bounding box around each sub-mesh's triangles, or of
+/// the whole model's for a sub-mesh without any.
+fn sub_mesh_centers(model: &Model) -> Vec<Vec3> {
+    model
+        .sub_meshes
+        .iter()
+        .map(|sub_mesh| {
+            let mut min = [f32::INFINITY; 3];
+            let mut max = [f32::NEG_INFINITY; 3];
+            for &index in &model.indexes[sub_mesh.range.clone()] {
+                let position = model.vertices[index as usize].position;
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(position[axis]);
+                    max[axis] = max[axis].max(position[axis]);
+                }
+            }
+            match sub_mesh.range.is_empty() {
+                true => Vec3::from(model.bounds.center()),
+                false => Vec3::new(
+                    (min[0] + max[0]) / 2.0,
+                    (min[1] + max[1]) / 2.0,
+                    (min[2] + max[2]) / 2.0,
+                ),
+            }
+        })
+        .collect()
+}
+
+/// A two-unit square on the XZ plane facing up, in a plain gray.
+fn ground_model() -> Model {
+    let corners = [[-1.0, 0.0, -1.0], [-1.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 0.0, -1.0]];
+    let vertices = corners
+        .iter()
+        .map(|&position| model_loader::Vertex3D {
+            position,
+            normal: [0.0, 1.0, 0.0],
+            tex_coords: [0.0, 0.0],
+        })
+        .collect::<Vec<model_loader::Vertex3D>>();
+    Model {
+        bounds: model_loader::Aabb::from_vertices(&vertices),
+        vertices,
+        colors: Vec::new(),
+        indexes: vec![0, 1, 2, 0, 2, 3],
+        lines: Vec::new(),
+        materials: vec![model_loader::Material {
+            name: "ground".to_string(),
+            ambient: [0.1, 0.1, 0.1],
+            diffuse: [0.5, 0.5, 0.5],
+            specular: [0.1, 0.1, 0.1],
+            shininess: 8.0,
+            diffuse_map: None,
+            normal_map: None,
+            // It already has the planar reflection.
+            reflectivity: Some(0.0),
+            roughness: None,
+            metallic_roughness_map: None,
+            opacity: 1.0,
+        }],
+        sub_meshes: vec![model_loader::SubMesh {
+            material: 0,
+            range: 0..6,
+        }],
+        has_normals: true,
+        has_tex_coords: false,
+        patches: Vec::new(),
+        lods: Vec::new(),
+        occlusion: Vec::new(),
+    }
+}
+
+/// Decodes a color given the way MTL files and color pickers do, so the
+/// lighting is done in linear space.
+fn srgb_to_linear(color: [f32; 3]) -> [f32; 3] {
+    color.map(|channel| match channel <= 0.04045 {
+        true => channel / 12.92,
+        false => ((channel + 0.055) / 1.055).powf(2.4),
+    })
+}
+
+/// Reads and compiles the shader files, printing why when either step fails.
+/// Also builds them into the variants the context allows, see `Programs`.
+fn compile_shader_files(
+    facade: &impl Facade,
+    files: &mut shaders::ShaderFiles,
+) -> Option<Programs> {
+    let (vertex, fragment) = match files.read() {
+        Ok(sources) => sources,
+        Err(error) => {
+            eprintln!("failed to read shaders: {}", error);
+            return None;
+        }
+    };
+    match Programs::new(facade, &vertex, &fragment) {
+        Ok(programs) => match FrameUniforms::check_layout(&programs.triangles) {
+            Ok(()) => Some(programs),
+            Err(error) => {
+                eprintln!("{}", error);
+                None
+            }
+        },
+        Err(error) => {
+            let vertex_name = files.name(glium::program::ShaderType::Vertex);
+            let fragment_name = files.name(glium::program::ShaderType::Fragment);
+            let report = shader_errors::describe(
+                &error,
+                &shader_errors::Stage {
+                    name: &vertex_name,
+                    source: &vertex,
+                },
+                &shader_errors::Stage {
+                    name: &fragment_name,
+                    source: &fragment,
+                },
+            );
+            eprintln!("{}", report);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::Args;
+    use glium::texture::Texture2d;
+    use glium::vertex::EmptyVertexAttributes;
+
+    /// Floats in `FrameUniforms`: four matrices, eight scalars, then the
+    /// two light arrays.
+    const MEMBERS: usize = 4 * 16 + 8 + 2 * 4 * MAX_LIGHTS;
+
+    /// Writes member `x` of the block, counted in floats in declaration
+    /// order, to pixel `x`. The values are whole halves, so twice them is
+    /// split over red and green exactly, then read back as bytes: glium
+    /// can't read floats back on OpenGL ES.
+    const READ_BACK_SRC: &str = r#"
+        out vec4 color;
+
+        float member(int i) {
+            if (i < 64) {
+                mat4 matrix = i < 16 ? perspective : i < 32 ? view
+                    : i < 48 ? u_inverse_view : u_light_space;
+                return matrix[i % 16 / 4][i % 4];
+            }
+            i -= 64;
+            if (i < 3) {
+                return u_camera_position[i];
+            }
+            float[5] scalars = float[5](u_time, u_znear, u_zfar,
+                float(u_shadow_light), float(u_light_count));
+            if (i < 8) {
+                return scalars[i - 3];
+            }
+            i -= 8;
+            if (i < 4 * MAX_LIGHTS) {
+                return u_light_vector[i / 4][i % 4];
+            }
+            i -= 4 * MAX_LIGHTS;
+            return u_light_color[i / 4][i % 4];
+        }
+
+        void main() {
+            float doubled = member(int(gl_FragCoord.x)) * 2.0;
+            color = vec4(floor(doubled / 256.0), mod(doubled, 256.0), 0.0, 255.0) / 255.0;
+        }
+    "#;
+
+    fn take<const N: usize>(values: &mut impl Iterator<Item = f32>) -> [f32; N] {
+        std::array::from_fn(|_| values.next().unwrap())
+    }
+
+    /// Known values, the `i`th float being `i` and a half so none is 0.
+    fn numbered() -> FrameUniforms {
+        let mut values = (0..).map(|i| i as f32 + 0.5);
+        let mut matrix = || [(); 4].map(|_| take(&mut values));
+        let (perspective, view, u_inverse_view, u_light_space) =
+            (matrix(), matrix(), matrix(), matrix());
+        let u_camera_position = take(&mut values);
+        let [u_time, u_znear, u_zfar, u_shadow_light, u_light_count] = take(&mut values);
+        let mut lights = || [(); MAX_LIGHTS].map(|_| take(&mut values));
+        let (u_light_vector, u_light_color) = (lights(), lights());
+        FrameUniforms {
+            perspective,
+            view,
+            u_inverse_view,
+            u_light_space,
+            u_camera_position,
+            u_time,
+            u_znear,
+            u_zfar,
+            // Rounded down, to go through ints.
+            u_shadow_light: u_shadow_light as i32,
+            u_light_count: u_light_count as i32,
+            u_light_vector,
+            u_light_color,
+        }
+    }
+
+    /// Draws `READ_BACK_SRC` with the block as `basic.vert` declares it, on
+    /// desktop GL through the buffer or on OpenGL ES one uniform at a time,
+    /// and returns what each pixel read.
+    fn round_trip(gles: bool) -> Option<Vec<f32>> {
+        let flags = match gles {
+            true => vec!["--gles".to_string()],
+            false => Vec::new(),
+        };
+        let mut args = Args::parse_from(flags).unwrap();
+        (args.width, args.height) = (MEMBERS as u32, 1);
+        let context = match crate::context::create_surfaceless(&args) {
+            Ok(context) => context,
+            Err(error) => {
+                eprintln!("skipping, no context can be made here: {}", error);
+                return None;
+            }
+        };
+        let source = shaders::EMBEDDED_VERTEX;
+        let start = source.find("const int MAX_LIGHTS").unwrap();
+        let end = start + source[start..].find("};").unwrap() + 2;
+        let fragment = format!("#version 150\n{}\n{}", &source[start..end], READ_BACK_SRC);
+        let program =
+            shaders::program(&context, post::FULLSCREEN_VERTEX_SRC, &fragment, false).unwrap();
+        FrameUniforms::check_layout(&program).unwrap();
+
+        let values = numbered();
+        let buffer = UniformBuffer::new(&context, values).unwrap();
+        let uniforms = WithFrame {
+            buffer: &buffer,
+            values: &values,
+            block: !gles,
+            rest: uniform! {},
+        };
+        let size = (MEMBERS as u32, 1);
+        let texture = Texture2d::empty(&context, size.0, size.1).unwrap();
+        let mut target = glium::framebuffer::SimpleFrameBuffer::new(&context, &texture).unwrap();
+        let triangles = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+        target
+            .draw(
+                EmptyVertexAttributes { len: 3 },
+                triangles,
+                &program,
+                &uniforms,
+                &Default::default(),
+            )
+            .unwrap();
+        let pixels: Vec<Vec<(u8, u8, u8, u8)>> = texture.read();
+        let read = pixels[0]
+            .iter()
+            .map(|&(high, low, _, _)| (high as f32 * 256.0 + low as f32) / 2.0);
+        Some(read.collect())
+    }
+
+    const RIPPLE_SIZE: (u32, u32) = (256, 192);
+    /// A time with the crests well clear of where they start.
+    const RIPPLE_TIME: f32 = 0.3;
+
+    /// A sphere rippled `RIPPLE_TIME` seconds in, by `ripple` in basic.vert
+    /// or by `Ripple::apply`, or not rippled at all, read back from a
+    /// surfaceless context.
+    fn rippled_sphere(on_gpu: Option<bool>) -> Option<Vec<u8>> {
+        let mut args = Args::parse_from(Vec::new()).unwrap();
+        (args.width, args.height) = RIPPLE_SIZE;
+        let context = match crate::context::create_surfaceless(&args) {
+            Ok(context) => context,
+            Err(error) => {
+                eprintln!("skipping, no context can be made here: {}", error);
+                return None;
+            }
+        };
+        let mut renderer = Renderer::new(&context).unwrap();
+        renderer.resize(RIPPLE_SIZE.into());
+        renderer.show_grid = false;
+        renderer.ripple = on_gpu.is_some();
+        renderer.ripple_on_gpu = on_gpu.unwrap_or(false);
+        let mut scene = Scene::default();
+        let sphere = renderer.add_mesh(crate::primitives::uv_sphere(1.0, 64, 128));
+        scene.add(sphere, Default::default());
+        scene.lights.push(Light::default());
+        let camera = Camera::looking_at([0.0, 0.5, 2.6], [0.0, 0.0, 0.0]);
+        // `render` takes the time from when the renderer started.
+        renderer.started = Instant::now() - Duration::from_secs_f32(RIPPLE_TIME);
+        let image = crate::screenshot::render_offscreen(&context, RIPPLE_SIZE, |target| {
+            renderer.render(target, &scene, &camera);
+        });
+        Some(image.data.into_owned())
+    }
+
+    /// How many pixels of `a` and `b` are more than a few levels apart.
+    fn differing(a: &[u8], b: &[u8]) -> usize {
+        a.chunks_exact(4)
+            .zip(b.chunks_exact(4))
+            .filter(|(a, b)| a.iter().zip(b.iter()).any(|(a, b)| a.abs_diff(*b) > 8))
+            .count()
+    }
+
+    #[test]
+    fn ripple_on_the_gpu_matches_the_cpu() {
+        let Some(on_gpu) = rippled_sphere(Some(true)) else {
+            return;
+        };
+        let on_cpu = rippled_sphere(Some(false)).unwrap();
+        let still = rippled_sphere(None).unwrap();
+        let moved = differing(&still, &on_cpu);
+        assert!(moved > 500, "the ripple moved only {} pixels", moved);
+        // Rounding can put a few silhouette pixels on the other side.
+        let mismatched = differing(&on_gpu, &on_cpu);
+        assert!(mismatched * 50 < moved, "{} pixels differ", mismatched);
+    }
+
+    #[test]
+    fn frame_uniforms_reach_the_shader() {
+        let expected = (0..MEMBERS).map(|i| i as f32 + 0.5).collect::<Vec<f32>>();
+        for gles in [false, true] {
+            if let Some(read) = round_trip(gles) {
+                let mut expected = expected.clone();
+                // The two ints, which lost their halves.
+                expected[70] = expected[70].trunc();
+                expected[71] = expected[71].trunc();
+                assert_eq!(read, expected, "gles: {}", gles);
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use glium::backend::Facade;
+use glium::Surface;
+
+use crate::gizmo::{Gizmo, AXES};
+use crate::math::{Mat4, Vec3};
+
+/// Linear colors of the X, Y and Z arrows.
+const COLORS: [[f32; 3]; 3] = [[0.9, 0.1, 0.1], [0.1, 0.8, 0.1], [0.15, 0.3, 1.0]];
+/// Sizes for an arrow one unit long.
+const SHAFT_RADIUS: f32 = 0.02;
+const SHAFT_LENGTH: f32 = 0.8;
+const HEAD_RADIUS: f32 = 0.07;
+/// Sides around each shaft and head.
+const SEGMENTS: usize = 12;
+
+const VERTEX_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 position;
+    in vec3 color;
+
+    uniform mat4 perspective;
+    uniform mat4 view;
+    uniform mat4 model;
+
+    out vec3 v_color;
+
+    void main() {
+        v_color = color;
+        gl_Position = perspective * view * model * vec4(position, 1.0);
+    }
+"#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 v_color;
+    out vec4 color;
+
+    void main() {
+        color = vec4(v_color, 1.0);
+    }
+"#;
+
+#[derive(Copy, Clone)]
+struct ArrowVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+implement_vertex!(ArrowVertex, position, color);
+
+/// Draws the translation gizmo's arrows, unlit and over everything else.
+pub struct Arrows {
+    program: glium::Program,
+    vertices: glium::VertexBuffer<ArrowVertex>,
+}
+
+impl Arrows {
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        Ok(Arrows {
+            program: super::shaders::program(
+                facade,
+                VERTEX_SHADER_SRC,
+                FRAGMENT_SHADER_SRC,
+                false,
+            )?,
+            vertices: glium::VertexBuffer::new(facade, &arrow_vertices()).unwrap(),
+        })
+    }
+
+    pub fn draw(&self, frame: &mut impl Surface, gizmo: &Gizmo, perspective: Mat4, view: Mat4) {
+        let model = Mat4::translation(gizmo.position) * Mat4::uniform_scale(gizmo.length);
+        let uniforms = uniform! {
+            perspective: perspective.to_array(),
+            view: view.to_array(),
+            model: model.to_array(),
+        };
+        // The default depth test always passes and writes nothing.
+        frame
+            .draw(
+                &self.vertices,
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.program,
+                &uniforms,
+                &Default::default(),
+            )
+            .unwrap();
+    }
+}
+
+/// A unit-length arrow along each axis from the origin, as a triangle list:
+/// a capped cylinder for the shaft and a cone for the head.
+fn arrow_vertices() -> Vec<ArrowVertex> {
+    let mut vertices = Vec::with_capacity(AXES.len() * SEGMENTS * 15);
+    for (index, &axis) in AXES.iter().enumerate() {
+        let side = AXES[(index + 1) % 3];
+        let up = AXES[(index + 2) % 3];
+        let ring = |segment: usize, radius: f32, along: f32| {
+            let angle = segment as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+            axis * along + side * (radius * angle.cos()) + up * (radius * angle.sin())
+        };
+        let mut triangle = |corners: [Vec3; 3]| {
+            vertices.extend(corners.map(|corner| ArrowVertex {
+                position: corner.into(),
+                color: COLORS[index],
+            }));
+        };
+        for segment in 0..SEGMENTS {
+            let next = segment + 1;
+            let (base, base_next) = (
+                ring(segment, SHAFT_RADIUS, 0.0),
+                ring(next, SHAFT_RADIUS, 0.0),
+            );
+            let (top, top_next) = (
+                ring(segment, SHAFT_RADIUS, SHAFT_LENGTH),
+                ring(next, SHAFT_RADIUS, SHAFT_LENGTH),
+            );
+            triangle([Vec3::default(), base_next, base]);
+            triangle([base, base_next, top_next]);
+            triangle([base, top_next, top]);
+            let (rim, rim_next) = (
+                ring(segment, HEAD_RADIUS, SHAFT_LENGTH),
+                ring(next, HEAD_RADIUS, SHAFT_LENGTH),
+            );
+            triangle([axis * SHAFT_LENGTH, rim_next, rim]);
+            triangle([rim, rim_next, axis]);
+        }
+    }
+    vertices
+}
@@ -0,0 +1,177 @@
+use glium::backend::Facade;
+use glium::draw_parameters::TimeElapsedQuery;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{Sampler, SamplerWrapFunction};
+use glium::vertex::EmptyVertexAttributes;
+use glium::Surface;
+
+use super::post::FULLSCREEN_VERTEX_SRC;
+use super::targets;
+
+/// Keeps the glow as bright as the highlights it comes from.
+const FORMAT: UncompressedFloatFormat = UncompressedFloatFormat::F16F16F16F16;
+
+/// Keeps the part of each pixel brighter than `u_threshold` once exposed,
+/// so everything else doesn't glow.
+const BRIGHT_PASS_SRC: &str = r#"
+    #version 150
+
+    in vec2 v_uv;
+    out vec4 color;
+
+    uniform sampler2D u_scene;
+    uniform float u_exposure;
+    uniform float u_threshold;
+
+    void main() {
+        vec3 scene = texture(u_scene, v_uv).rgb * u_exposure;
+        float brightness = max(scene.r, max(scene.g, scene.b));
+        color = vec4(scene * max(brightness - u_threshold, 0.0) / max(brightness, 0.0001), 1.0);
+    }
+"#;
+
+/// One direction of the Gaussian blur, `u_radius` texels either side with
+/// `u_step` between them.
+const BLUR_SRC: &str = r#"
+    #version 150
+
+    in vec2 v_uv;
+    out vec4 color;
+
+    uniform sampler2D u_source;
+    uniform vec2 u_step;
+    uniform int u_radius;
+
+    void main() {
+        float sigma = max(float(u_radius) / 2.0, 0.5);
+        vec3 sum = vec3(0.0);
+        float total = 0.0;
+        for (int i = -u_radius; i <= u_radius; i++) {
+            float weight = exp(-0.5 * float(i * i) / (sigma * sigma));
+            sum += texture(u_source, v_uv + u_step * float(i)).rgb * weight;
+            total += weight;
+        }
+        color = vec4(sum / total, 1.0);
+    }
+"#;
+
+/// Blurs the brightest parts of the scene at half resolution, for the
+/// effects pass to add on top of the tone-mapped frame.
+pub struct Bloom {
+    bright_pass: glium::Program,
+    blur: glium::Program,
+    /// Ping-pong targets: the bright pass and vertical blur write the first,
+    /// the horizontal blur the second.
+    targets: [Texture2d; 2],
+    /// Times the last frame's passes; read a frame later so the CPU doesn't
+    /// wait for the GPU.
+    pending_query: Option<TimeElapsedQuery>,
+    /// GPU time of the most recent passes whose timing has come back, in
+    /// milliseconds.
+    pub gpu_time: Option<f32>,
+}
+
+impl Bloom {
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        Ok(Bloom {
+            bright_pass: super::shaders::program(
+                facade,
+                FULLSCREEN_VERTEX_SRC,
+                BRIGHT_PASS_SRC,
+                false,
+            )?,
+            blur: super::shaders::program(facade, FULLSCREEN_VERTEX_SRC, BLUR_SRC, false)?,
+            targets: [target(facade, (1, 1)), target(facade, (1, 1))],
+            pending_query: None,
+            gpu_time: None,
+        })
+    }
+
+    /// Recreates the targets at half of `size` if that changed.
+    pub fn resize(&mut self, facade: &impl Facade, (width, height): (u32, u32)) {
+        let size = ((width / 2).max(1), (height / 2).max(1));
+        if self.targets[0].dimensions() != size {
+            self.targets = [target(facade, size), target(facade, size)];
+        }
+    }
+
+    /// The blurred highlights from the last `apply`.
+    pub fn texture(&self) -> Sampler<'_, Texture2d> {
+        clamped(&self.targets[0])
+    }
+
+    /// Extracts what's brighter than `threshold` from `scene` and blurs it
+    /// by `radius` half-resolution texels.
+    pub fn apply(
+        &mut self,
+        facade: &impl Facade,
+        scene: &Texture2d,
+        exposure: f32,
+        threshold: f32,
+        radius: u32,
+    ) {
+        if let Some(query) = self.pending_query.take() {
+            // A query still running is dropped rather than waited for.
+            if query.is_ready() {
+                self.gpu_time = Some(query.get() as f32 / 1_000_000.0);
+            }
+        }
+        let query = TimeElapsedQuery::new(facade).ok();
+        let params = glium::DrawParameters {
+            time_elapsed_query: query.as_ref(),
+            ..Default::default()
+        };
+        let triangles = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+
+        let [first, second] = &self.targets;
+        let mut first_target = SimpleFrameBuffer::new(facade, first).unwrap();
+        let mut second_target = SimpleFrameBuffer::new(facade, second).unwrap();
+        let uniforms = uniform! {
+            u_scene: clamped(scene),
+            u_exposure: exposure,
+            u_threshold: threshold,
+        };
+        first_target
+            .draw(
+                EmptyVertexAttributes { len: 3 },
+                triangles,
+                &self.bright_pass,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+
+        let (width, height) = first.dimensions();
+        let passes = [
+            (first, &mut second_target, [1.0 / width as f32, 0.0]),
+            (second, &mut first_target, [0.0, 1.0 / height as f32]),
+        ];
+        for (source, target, step) in passes {
+            let uniforms = uniform! {
+                u_source: clamped(source),
+                u_step: step,
+                u_radius: radius as i32,
+            };
+            target
+                .draw(
+                    EmptyVertexAttributes { len: 3 },
+                    triangles,
+                    &self.blur,
+                    &uniforms,
+                    &params,
+                )
+                .unwrap();
+        }
+        self.pending_query = query;
+    }
+}
+
+fn target(facade: &impl Facade, size: (u32, u32)) -> Texture2d {
+    targets::color(facade, FORMAT, size)
+}
+
+/// Samples without wrapping, so the edges don't pick up the opposite side.
+fn clamped(texture: &Texture2d) -> Sampler<'_, Texture2d> {
+    texture.sampled().wrap_function(SamplerWrapFunction::Clamp)
+}
@@ -0,0 +1,113 @@
+use glium::backend::Facade;
+use glium::{Api, Program};
+
+use super::shaders::{self, ExtraStages};
+
+/// Width of the overlay's lines in pixels, the same at any zoom.
+pub const WIDTH: f32 = 1.5;
+
+/// Defined in the main shaders when they're built around the geometry
+/// shader, which takes the vertex shader's outputs under other names.
+pub const DEFINE: &str = "EDGE_OVERLAY";
+
+/// Passes each triangle through, adding every corner's distance in pixels
+/// from the opposite edge. Interpolated without perspective, that's each
+/// fragment's distance from the three edges.
+pub const GEOMETRY_SHADER_SRC: &str = r#"
+    #version 150
+
+    layout(triangles) in;
+    layout(triangle_strip, max_vertices = 3) out;
+
+    in vec3 gs_normal[];
+    in vec3 gs_tangent[];
+    in vec3 gs_bitangent[];
+    in vec3 gs_position[];
+    in vec2 gs_tex_coords[];
+    in vec3 gs_color[];
+    in vec3 gs_tint[];
+    in vec4 gs_light_space_position[];
+    in float gs_world_y[];
+    in float gs_occlusion[];
+    out vec3 v_normal;
+    out vec3 v_tangent;
+    out vec3 v_bitangent;
+    out vec3 v_position;
+    out vec2 v_tex_coords;
+    out vec3 v_color;
+    out vec3 v_tint;
+    out vec4 v_light_space_position;
+    out float v_world_y;
+    out float v_occlusion;
+    noperspective out vec3 v_edge_distance;
+
+    uniform vec2 u_viewport;
+
+    void main() {
+        vec2 corners[3];
+        for (int i = 0; i < 3; i++) {
+            corners[i] = gl_in[i].gl_Position.xy / gl_in[i].gl_Position.w * 0.5 * u_viewport;
+        }
+        vec2 a = corners[1] - corners[0];
+        vec2 b = corners[2] - corners[0];
+        float twice_area = abs(a.x * b.y - a.y * b.x);
+        // A corner's height over the opposite edge, from the triangle's area.
+        vec3 heights = twice_area / vec3(
+            length(corners[2] - corners[1]),
+            length(b),
+            length(a)
+        );
+        for (int i = 0; i < 3; i++) {
+            v_normal = gs_normal[i];
+            v_tangent = gs_tangent[i];
+            v_bitangent = gs_bitangent[i];
+            v_position = gs_position[i];
+            v_tex_coords = gs_tex_coords[i];
+            v_color = gs_color[i];
+            v_tint = gs_tint[i];
+            v_light_space_position = gs_light_space_position[i];
+            v_world_y = gs_world_y[i];
+            v_occlusion = gs_occlusion[i];
+            v_edge_distance = vec3(0.0);
+            v_edge_distance[i] = heights[i];
+            gl_Position = gl_in[i].gl_Position;
+            EmitVertex();
+        }
+        EndPrimitive();
+    }
+"#;
+
+/// Whether the context has geometry shaders, which desktop GL does from
+/// 3.2 on.
+pub fn supported(facade: &impl Facade) -> bool {
+    let context = facade.get_context();
+    context.get_opengl_version().0 == Api::Gl
+        && glium::program::is_geometry_shader_supported(&**context)
+}
+
+/// The main program with the geometry shader drawing the wireframe overlay
+/// in the same pass, or `None` where it can't, and the overlay takes a
+/// second pass of lines.
+pub fn program(facade: &impl Facade, vertex: &str, fragment: &str) -> Option<Program> {
+    if !supported(facade) {
+        return None;
+    }
+    let extra = ExtraStages {
+        geometry: Some(GEOMETRY_SHADER_SRC),
+        ..Default::default()
+    };
+    let (vertex, fragment) = (
+        shaders::with_define(vertex, DEFINE),
+        shaders::with_define(fragment, DEFINE),
+    );
+    match shaders::program_with_stages(facade, &vertex, extra, &fragment, false) {
+        Ok(program) => Some(program),
+        Err(error) => {
+            eprintln!(
+                "warning: the one-pass wireframe overlay didn't build, drawing it in two: {}",
+                error
+            );
+            None
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use glium::backend::Facade;
+use glium::Surface;
+
+use super::LineVertex;
+use crate::math::Mat4;
+
+/// Lines are drawn this many units out from the origin in each direction.
+const HALF_EXTENT: i32 = 100;
+/// Every this many lines is a major one.
+const MAJOR_EVERY: i32 = 10;
+/// Colors are linear; these are about 0.3 and 0.5 once encoded.
+const MINOR_COLOR: [f32; 3] = [0.073, 0.073, 0.073];
+const MAJOR_COLOR: [f32; 3] = [0.214, 0.214, 0.214];
+/// Axis lines run this far along +X, +Y and +Z.
+const AXIS_LENGTH: f32 = MAJOR_EVERY as f32;
+
+/// Unlit, and fades out between `u_fade_start` and `u_fade_end` units from
+/// the eye so far-away lines don't alias into noise.
+const VERTEX_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 position;
+    in vec3 color;
+    out vec3 v_color;
+    out vec3 v_view_position;
+
+    uniform mat4 perspective;
+    uniform mat4 view;
+
+    void main() {
+        vec4 view_position = view * vec4(position, 1.0);
+        v_view_position = view_position.xyz;
+        v_color = color;
+        gl_Position = perspective * view_position;
+    }
+"#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 v_color;
+    in vec3 v_view_position;
+    out vec4 color;
+
+    uniform float u_fade_start;
+    uniform float u_fade_end;
+
+    void main() {
+        float fade = 1.0 - smoothstep(u_fade_start, u_fade_end, length(v_view_position));
+        color = vec4(v_color, fade);
+    }
+"#;
+
+/// A floor grid on the XZ plane with RGB axes at the origin.
+pub struct Grid {
+    program: glium::Program,
+    vertices: glium::VertexBuffer<LineVertex>,
+    /// Minor lines, drawn thin.
+    minor: glium::IndexBuffer<u16>,
+    /// Major lines and the axes, drawn thicker.
+    major: glium::IndexBuffer<u16>,
+}
+
+impl Grid {
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        let mut vertices: Vec<LineVertex> = Vec::new();
+        let mut minor: Vec<u16> = Vec::new();
+        let mut major: Vec<u16> = Vec::new();
+        let mut line = |start: [f32; 3], end: [f32; 3], color: [f32; 3], thick: bool| {
+            let first = vertices.len() as u16;
+            vertices.push(LineVertex {
+                position: start,
+                color,
+            });
+            vertices.push(LineVertex {
+                position: end,
+                color,
+            });
+            let indexes = if thick { &mut major } else { &mut minor };
+            indexes.extend_from_slice(&[first, first + 1]);
+        };
+
+        let extent = HALF_EXTENT as f32;
+        for i in -HALF_EXTENT..=HALF_EXTENT {
+            let offset = i as f32;
+            if i == 0 {
+                // The positive halves are replaced by the axes below.
+                line([0.0, 0.0, -extent], [0.0; 3], MAJOR_COLOR, true);
+                line([-extent, 0.0, 0.0], [0.0; 3], MAJOR_COLOR, true);
+                continue;
+            }
+            let thick = i % MAJOR_EVERY == 0;
+            let color = if thick { MAJOR_COLOR } else { MINOR_COLOR };
+            line([offset, 0.0, -extent], [offset, 0.0, extent], color, thick);
+            line([-extent, 0.0, offset], [extent, 0.0, offset], color, thick);
+        }
+        line([0.0; 3], [AXIS_LENGTH, 0.0, 0.0], [0.79, 0.033, 0.033], true);
+        line([0.0; 3], [0.0, AXIS_LENGTH, 0.0], [0.033, 0.79, 0.033], true);
+        line([0.0; 3], [0.0, 0.0, AXIS_LENGTH], [0.033, 0.073, 0.79], true);
+        line([AXIS_LENGTH, 0.0, 0.0], [extent, 0.0, 0.0], MAJOR_COLOR, true);
+        line([0.0, 0.0, AXIS_LENGTH], [0.0, 0.0, extent], MAJOR_COLOR, true);
+
+        let lines = glium::index::PrimitiveType::LinesList;
+        Ok(Grid {
+            program: super::shaders::program(
+                facade,
+                VERTEX_SHADER_SRC,
+                FRAGMENT_SHADER_SRC,
+                false,
+            )?,
+            vertices: glium::VertexBuffer::new(facade, &vertices).unwrap(),
+            minor: glium::IndexBuffer::new(facade, lines, &minor).unwrap(),
+            major: glium::IndexBuffer::new(facade, lines, &major).unwrap(),
+        })
+    }
+
+    /// Draws the grid faded out from `fade_start` to `fade_end` units from
+    /// the eye, returning the number of draw calls made.
+    pub fn draw(
+        &self,
+        frame: &mut impl Surface,
+        perspective: Mat4,
+        view: Mat4,
+        fade_start: f32,
+        fade_end: f32,
+    ) -> usize {
+        let uniforms = uniform! {
+            perspective: perspective.to_array(),
+            view: view.to_array(),
+            u_fade_start: fade_start,
+            u_fade_end: fade_end,
+        };
+        for (indices, width) in [(&self.minor, 1.0), (&self.major, 2.0)] {
+            let params = glium::DrawParameters {
+                // Tested against the models, but never hides them.
+                depth: glium::Depth {
+                    test: glium::draw_parameters::DepthTest::IfLess,
+                    write: false,
+                    ..Default::default()
+                },
+                blend: glium::Blend::alpha_blending(),
+                line_width: Some(width),
+                ..Default::default()
+            };
+            frame
+                .draw(&self.vertices, indices, &self.program, &uniforms, &params)
+                .unwrap();
+        }
+        2
+    }
+}
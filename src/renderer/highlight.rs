@@ -0,0 +1,101 @@
+use glium::backend::Facade;
+use glium::Surface;
+
+use crate::math::{Mat4, Vec3};
+
+/// Linear color the hovered triangle is filled with.
+const COLOR: [f32; 3] = [1.0, 0.45, 0.0];
+
+const VERTEX_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 position;
+
+    uniform mat4 perspective;
+    uniform mat4 view;
+
+    void main() {
+        gl_Position = perspective * view * vec4(position, 1.0);
+    }
+"#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 150
+
+    uniform vec3 u_color;
+    out vec4 color;
+
+    void main() {
+        color = vec4(u_color, 1.0);
+    }
+"#;
+
+#[derive(Copy, Clone)]
+struct CornerVertex {
+    position: [f32; 3],
+}
+
+implement_vertex!(CornerVertex, position);
+
+/// Draws one triangle in a flat color over the model it's part of, for the
+/// triangle under the cursor.
+pub struct Highlight {
+    program: glium::Program,
+    corners: glium::VertexBuffer<CornerVertex>,
+}
+
+impl Highlight {
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        Ok(Highlight {
+            program: super::shaders::program(
+                facade,
+                VERTEX_SHADER_SRC,
+                FRAGMENT_SHADER_SRC,
+                false,
+            )?,
+            corners: glium::VertexBuffer::empty_dynamic(facade, 3).unwrap(),
+        })
+    }
+
+    /// Draws the triangle with world-space `corners`, pulled slightly
+    /// towards the camera so it wins the depth test against itself.
+    pub fn draw(
+        &self,
+        frame: &mut impl Surface,
+        corners: [Vec3; 3],
+        perspective: Mat4,
+        view: Mat4,
+    ) {
+        self.corners.write(&corners.map(|corner| CornerVertex {
+            position: corner.into(),
+        }));
+        let uniforms = uniform! {
+            perspective: perspective.to_array(),
+            view: view.to_array(),
+            u_color: COLOR,
+        };
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLessOrEqual,
+                write: false,
+                ..Default::default()
+            },
+            polygon_offset: glium::draw_parameters::PolygonOffset {
+                factor: -1.0,
+                units: -1.0,
+                fill: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        frame
+            .draw(
+                &self.corners,
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use glium::backend::Facade;
+use glium::Surface;
+
+use super::GpuMesh;
+use crate::math::Mat4;
+
+/// Transforms each vertex by its instance's model matrix, like the main
+/// shader, without lighting anything.
+const VERTEX_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 position;
+    in vec4 i_model_0;
+    in vec4 i_model_1;
+    in vec4 i_model_2;
+    in vec4 i_model_3;
+
+    uniform mat4 perspective;
+    uniform mat4 view;
+
+    void main() {
+        mat4 model = mat4(i_model_0, i_model_1, i_model_2, i_model_3);
+        gl_Position = perspective * view * model * vec4(position, 1.0);
+    }
+"#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 150
+
+    out vec4 color;
+
+    uniform vec3 u_color;
+
+    void main() {
+        color = vec4(u_color, 1.0);
+    }
+"#;
+
+/// Draws the line elements of OBJ files, the edges and curves CAD programs
+/// export alongside or instead of faces.
+pub struct Lines {
+    program: glium::Program,
+}
+
+impl Lines {
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        Ok(Lines {
+            program: super::shaders::program(
+                facade,
+                VERTEX_SHADER_SRC,
+                FRAGMENT_SHADER_SRC,
+                false,
+            )?,
+        })
+    }
+
+    /// Draws `mesh`'s lines in `color`, which is linear, for each of its
+    /// instances in view, hidden by the models in front of them. Returns
+    /// whether it had any to draw.
+    pub fn draw(
+        &self,
+        frame: &mut impl Surface,
+        mesh: &GpuMesh,
+        color: [f32; 3],
+        perspective: Mat4,
+        view: Mat4,
+    ) -> bool {
+        let lines = match &mesh.lines {
+            Some(lines) if mesh.visible_count > 0 => lines,
+            _ => return false,
+        };
+        let uniforms = uniform! {
+            perspective: perspective.to_array(),
+            view: view.to_array(),
+            u_color: color,
+        };
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLessOrEqual,
+                write: true,
+                ..Default::default()
+            },
+            line_width: Some(1.0),
+            ..Default::default()
+        };
+        let instances = mesh.instances.slice(0..mesh.visible_count).unwrap();
+        frame
+            .draw(
+                (&mesh.vertices, instances.per_instance().unwrap()),
+                lines.all(),
+                &self.program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+        true
+    }
+}
@@ -0,0 +1,119 @@
+use glium::backend::Facade;
+use glium::vertex::PerInstance;
+use glium::Surface;
+
+use super::LineVertex;
+use crate::math::{Mat4, Vec3};
+use crate::model_loader::Model;
+
+/// Length of each line as a fraction of the model's bounding box diagonal.
+const LENGTH: f32 = 0.02;
+/// Linear color at the vertex end; the far end is colored by the normal
+/// itself.
+const BASE_COLOR: [f32; 3] = [1.0, 1.0, 0.033];
+
+/// Transforms the lines by each instance's model matrix, like the main
+/// shader, but draws them unlit.
+const VERTEX_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 position;
+    in vec3 color;
+    in vec4 i_model_0;
+    in vec4 i_model_1;
+    in vec4 i_model_2;
+    in vec4 i_model_3;
+    out vec3 v_color;
+
+    uniform mat4 perspective;
+    uniform mat4 view;
+
+    void main() {
+        mat4 model = mat4(i_model_0, i_model_1, i_model_2, i_model_3);
+        v_color = color;
+        gl_Position = perspective * view * model * vec4(position, 1.0);
+    }
+"#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 v_color;
+    out vec4 color;
+
+    void main() {
+        color = vec4(v_color, 1.0);
+    }
+"#;
+
+/// Draws each vertex normal as a short line, toggled with N.
+pub struct NormalLines {
+    program: glium::Program,
+}
+
+impl NormalLines {
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        Ok(NormalLines {
+            program: super::shaders::program(
+                facade,
+                VERTEX_SHADER_SRC,
+                FRAGMENT_SHADER_SRC,
+                false,
+            )?,
+        })
+    }
+
+    /// Builds the line endpoints for `model`, two vertices per normal.
+    pub fn build(facade: &impl Facade, model: &Model) -> glium::VertexBuffer<LineVertex> {
+        let diagonal = Vec3::from(model.bounds.max) - Vec3::from(model.bounds.min);
+        let length = diagonal.length() * LENGTH;
+
+        let mut lines: Vec<LineVertex> = Vec::with_capacity(model.vertices.len() * 2);
+        for vertex in &model.vertices {
+            let [px, py, pz] = vertex.position;
+            let [nx, ny, nz] = vertex.normal;
+            lines.push(LineVertex {
+                position: vertex.position,
+                color: BASE_COLOR,
+            });
+            lines.push(LineVertex {
+                position: [px + nx * length, py + ny * length, pz + nz * length],
+                color: [nx * 0.5 + 0.5, ny * 0.5 + 0.5, nz * 0.5 + 0.5],
+            });
+        }
+        glium::VertexBuffer::new(facade, &lines).unwrap()
+    }
+
+    /// Draws one mesh's lines for every instance, hidden by the models.
+    pub fn draw(
+        &self,
+        frame: &mut impl Surface,
+        lines: &glium::VertexBuffer<LineVertex>,
+        instances: PerInstance,
+        perspective: Mat4,
+        view: Mat4,
+    ) {
+        let uniforms = uniform! {
+            perspective: perspective.to_array(),
+            view: view.to_array(),
+        };
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            line_width: Some(1.0),
+            ..Default::default()
+        };
+        frame
+            .draw(
+                (lines, instances),
+                glium::index::NoIndices(glium::index::PrimitiveType::LinesList),
+                &self.program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,137 @@
+use glium::backend::Facade;
+use glium::draw_parameters::{Stencil, StencilOperation, StencilTest};
+use glium::Surface;
+
+use super::GpuMesh;
+use crate::math::Mat4;
+
+/// Linear color of the outline around the selected object.
+const COLOR: [f32; 3] = [1.0, 0.8, 0.1];
+/// How many pixels the outline reaches past the object, whatever its
+/// distance.
+const WIDTH: f32 = 3.0;
+
+/// Pushes each vertex out along its normal as projected on the screen, by
+/// `u_width` pixels, so the outline keeps its thickness at any distance.
+/// With a width of 0 it draws the object as it is.
+const VERTEX_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 position;
+    in vec3 normal;
+
+    uniform mat4 perspective;
+    uniform mat4 view;
+    uniform mat4 model;
+    uniform mat3 u_normal_matrix;
+    uniform vec2 u_viewport;
+    uniform float u_width;
+
+    void main() {
+        mat4 view_projection = perspective * view;
+        vec4 clip = view_projection * model * vec4(position, 1.0);
+        vec2 direction = (view_projection * vec4(u_normal_matrix * normal, 0.0)).xy;
+        if (length(direction) > 0.0) {
+            clip.xy += normalize(direction) * u_width * 2.0 / u_viewport * clip.w;
+        }
+        gl_Position = clip;
+    }
+"#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 150
+
+    uniform vec3 u_color;
+    out vec4 color;
+
+    void main() {
+        color = vec4(u_color, 1.0);
+    }
+"#;
+
+/// Outlines the selected object with the stencil buffer: the object marks
+/// its pixels with 1, then a fattened copy is drawn everywhere else. Both
+/// ignore depth, so the outline shows through whatever is in front.
+pub struct Outline {
+    program: glium::Program,
+}
+
+impl Outline {
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        Ok(Outline {
+            program: super::shaders::program(
+                facade,
+                VERTEX_SHADER_SRC,
+                FRAGMENT_SHADER_SRC,
+                false,
+            )?,
+        })
+    }
+
+    /// Draws the outline of `mesh` placed by `model`, into a target whose
+    /// stencil was cleared to 0 and whose size is `viewport`.
+    pub fn draw(
+        &self,
+        frame: &mut impl Surface,
+        mesh: &GpuMesh,
+        model: Mat4,
+        perspective: Mat4,
+        view: Mat4,
+        viewport: (u32, u32),
+    ) {
+        let normal_matrix = model.normal_matrix().to_array();
+        let pass = |width: f32, color_mask: bool, stencil: Stencil| {
+            let uniforms = uniform! {
+                perspective: perspective.to_array(),
+                view: view.to_array(),
+                model: model.to_array(),
+                u_normal_matrix: normal_matrix,
+                u_viewport: [viewport.0 as f32, viewport.1 as f32],
+                u_width: width,
+                u_color: COLOR,
+            };
+            let params = glium::DrawParameters {
+                color_mask: (color_mask, color_mask, color_mask, color_mask),
+                stencil,
+                ..Default::default()
+            };
+            (uniforms, params)
+        };
+
+        let (uniforms, params) = pass(0.0, false, both_faces(StencilTest::AlwaysPass));
+        frame
+            .draw(
+                &mesh.vertices,
+                mesh.indices.all(),
+                &self.program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+        let outside = both_faces(StencilTest::IfNotEqual { mask: 0xff });
+        let (uniforms, params) = pass(WIDTH, true, outside);
+        frame
+            .draw(
+                &mesh.vertices,
+                mesh.indices.all(),
+                &self.program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+    }
+}
+
+/// Compares the stencil against 1 with `test` and writes 1 wherever it
+/// passes, for front and back faces alike.
+fn both_faces(test: StencilTest) -> Stencil {
+    Stencil {
+        test_clockwise: test,
+        reference_value_clockwise: 1,
+        depth_pass_operation_clockwise: StencilOperation::Replace,
+        test_counter_clockwise: test,
+        reference_value_counter_clockwise: 1,
+        depth_pass_operation_counter_clockwise: StencilOperation::Replace,
+        ..Default::default()
+    }
+}
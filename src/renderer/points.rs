@@ -0,0 +1,129 @@
+use glium::backend::Facade;
+use glium::Surface;
+
+use super::GpuMesh;
+use crate::math::Mat4;
+
+/// How wide a point is at a point size of 1, as a fraction of its model's
+/// bounding radius.
+const SIZE_PER_RADIUS: f32 = 0.005;
+
+/// Transforms each vertex by its instance's model matrix and sizes it by
+/// its distance, so a point covers about `u_point_size` world units across
+/// however far away it is, but never less than a pixel. Without vertex
+/// colors the points are colored by their height within the model.
+const VERTEX_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 position;
+    in vec3 color;
+    in vec4 i_model_0;
+    in vec4 i_model_1;
+    in vec4 i_model_2;
+    in vec4 i_model_3;
+    in vec3 i_tint;
+    out vec3 v_color;
+
+    uniform mat4 perspective;
+    uniform mat4 view;
+    uniform float u_point_size;
+    uniform float u_viewport_height;
+    uniform bool u_has_vertex_colors;
+    // The model's lowest and highest y, blue and red.
+    uniform vec2 u_height_range;
+
+    vec3 srgb_to_linear(vec3 srgb) {
+        vec3 curve = pow((srgb + 0.055) / 1.055, vec3(2.4));
+        return mix(srgb / 12.92, curve, step(0.04045, srgb));
+    }
+
+    // Blue through green to red, as the tessellation levels are colored.
+    vec3 height_color(float t) {
+        return clamp(vec3(2.0 * t - 0.5, 1.0 - abs(2.0 * t - 1.0), 1.5 - 2.0 * t), 0.0, 1.0);
+    }
+
+    void main() {
+        mat4 model = mat4(i_model_0, i_model_1, i_model_2, i_model_3);
+        gl_Position = perspective * view * model * vec4(position, 1.0);
+        // w is the distance along the view axis in perspective and 1 in an
+        // orthographic projection, which keeps every point the same size.
+        float pixels = u_point_size * perspective[1][1] * 0.5 * u_viewport_height;
+        gl_PointSize = max(pixels / gl_Position.w, 1.0);
+        float height = u_height_range.y - u_height_range.x;
+        float t = height > 0.0 ? (position.y - u_height_range.x) / height : 0.5;
+        v_color = (u_has_vertex_colors ? srgb_to_linear(color) : height_color(t)) * i_tint;
+    }
+"#;
+
+/// Rounds each point off, unlit.
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 v_color;
+    out vec4 color;
+
+    void main() {
+        vec2 offset = gl_PointCoord * 2.0 - 1.0;
+        if (dot(offset, offset) > 1.0) {
+            discard;
+        }
+        color = vec4(v_color, 1.0);
+    }
+"#;
+
+/// Draws a mesh's vertices as points, for point clouds and `--points`.
+pub struct Points {
+    program: glium::Program,
+}
+
+impl Points {
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        Ok(Points {
+            program: super::shaders::point_program(facade, VERTEX_SHADER_SRC, FRAGMENT_SHADER_SRC)?,
+        })
+    }
+
+    /// Draws every vertex of `mesh` for each of its instances in view, at
+    /// `size` times the default.
+    pub fn draw(
+        &self,
+        frame: &mut impl Surface,
+        mesh: &GpuMesh,
+        size: f32,
+        perspective: Mat4,
+        view: Mat4,
+        viewport_height: u32,
+    ) {
+        let bounds = &mesh.model.bounds;
+        let uniforms = uniform! {
+            perspective: perspective.to_array(),
+            view: view.to_array(),
+            u_point_size: size * SIZE_PER_RADIUS * mesh.sphere.1,
+            u_viewport_height: viewport_height as f32,
+            u_has_vertex_colors: !mesh.model.colors.is_empty(),
+            u_height_range: [bounds.min[1], bounds.max[1]],
+        };
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let instances = mesh.instances.slice(0..mesh.visible_count).unwrap();
+        frame
+            .draw(
+                (
+                    &mesh.vertices,
+                    &mesh.colors,
+                    instances.per_instance().unwrap(),
+                ),
+                glium::index::NoIndices(glium::index::PrimitiveType::Points),
+                &self.program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,361 @@
+use glium::backend::Facade;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{DepthStencilFormat, MipmapsOption, UncompressedFloatFormat};
+use glium::Surface;
+
+use super::bloom::Bloom;
+
+/// Half floats, so linear colors keep their precision and lights adding up
+/// past 1.0 aren't clipped before tone mapping.
+const COLOR_FORMAT: UncompressedFloatFormat = UncompressedFloatFormat::F16F16F16F16;
+/// With a stencil, which the selection outline marks the selected object in.
+const DEPTH_FORMAT: DepthStencilFormat = DepthStencilFormat::I24I8;
+
+/// Covers the target with one triangle whose corners are worked out from
+/// `gl_VertexID`, so no vertex buffer is needed. `v_uv` runs from 0 to 1
+/// across the target.
+pub const FULLSCREEN_VERTEX_SRC: &str = r#"
+    #version 150
+
+    out vec2 v_uv;
+
+    void main() {
+        vec2 corner = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+        v_uv = corner;
+        gl_Position = vec4(corner * 2.0 - 1.0, 0.0, 1.0);
+    }
+"#;
+
+/// Reads the scene pixel for pixel, scales it by the exposure and tone maps
+/// it into 0 to 1, adding the bloom on top. Then applies each effect whose bit is set in
+/// `u_effect_flags`, in the order of the `Effect` discriminants, and encodes
+/// the linear result for the screen. With `u_gamma_split` only the left half
+/// is encoded.
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec2 v_uv;
+    out vec4 color;
+
+    uniform sampler2D u_scene;
+    uniform sampler2D u_bloom;
+    uniform bool u_bloom_enabled;
+    uniform float u_bloom_intensity;
+    uniform float u_exposure;
+    // See `ToneMap`.
+    uniform int u_tone_map;
+    uniform bool u_show_clipping;
+    uniform int u_effect_flags;
+    uniform bool u_srgb;
+    uniform bool u_gamma_split;
+
+    const int GRAYSCALE = 1;
+    const int INVERT = 2;
+    const int VIGNETTE = 4;
+
+    // Krzysztof Narkowicz's fit of the ACES filmic curve.
+    vec3 aces(vec3 x) {
+        return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+    }
+
+    vec3 linear_to_srgb(vec3 linear) {
+        vec3 curve = 1.055 * pow(linear, vec3(1.0 / 2.4)) - 0.055;
+        return mix(linear * 12.92, curve, step(0.0031308, linear));
+    }
+
+    void main() {
+        vec3 scene = texelFetch(u_scene, ivec2(gl_FragCoord.xy), 0).rgb * u_exposure;
+        if (u_show_clipping && max(scene.r, max(scene.g, scene.b)) > 1.0) {
+            color = vec4(1.0, 0.0, 1.0, 1.0);
+            return;
+        }
+        scene = u_tone_map == 1 ? aces(scene) : scene / (1.0 + scene);
+        if (u_bloom_enabled) {
+            scene += texture(u_bloom, v_uv).rgb * u_bloom_intensity;
+        }
+        if ((u_effect_flags & GRAYSCALE) != 0) {
+            scene = vec3(dot(scene, vec3(0.2126, 0.7152, 0.0722)));
+        }
+        if ((u_effect_flags & INVERT) != 0) {
+            scene = 1.0 - scene;
+        }
+        if ((u_effect_flags & VIGNETTE) != 0) {
+            scene *= 1.0 - smoothstep(0.4, 0.75, distance(v_uv, vec2(0.5)));
+        }
+        bool encode = u_gamma_split ? v_uv.x < 0.5 : u_srgb;
+        if (encode) {
+            scene = linear_to_srgb(clamp(scene, 0.0, 1.0));
+        }
+        color = vec4(scene, 1.0);
+    }
+"#;
+
+/// How the HDR scene is brought into 0 to 1, cycled with T. The
+/// discriminant is the `u_tone_map` uniform.
+#[derive(Clone, Copy, Debug)]
+pub enum ToneMap {
+    /// `c / (1 + c)`: never clips, but flattens bright colors.
+    Reinhard = 0,
+    /// A filmic curve with more contrast.
+    Aces = 1,
+}
+
+impl ToneMap {
+    pub fn next(self) -> Self {
+        match self {
+            ToneMap::Reinhard => ToneMap::Aces,
+            ToneMap::Aces => ToneMap::Reinhard,
+        }
+    }
+}
+
+/// A full-screen effect, toggled with F5 to F7. The discriminant is its bit
+/// in `u_effect_flags`.
+#[derive(Clone, Copy, Debug)]
+pub enum Effect {
+    Grayscale = 1,
+    Invert = 2,
+    /// Darkens towards the corners.
+    Vignette = 4,
+}
+
+impl Effect {
+    pub const ALL: [Effect; 3] = [Effect::Grayscale, Effect::Invert, Effect::Vignette];
+
+    pub fn bit(self) -> u32 {
+        self as u32
+    }
+}
+
+/// How the finished HDR frame is turned into the one on screen.
+#[derive(Clone, Copy)]
+pub struct Output {
+    /// Scene colors are multiplied by this before tone mapping, changed
+    /// with `[` and `]`.
+    pub exposure: f32,
+    pub tone_map: ToneMap,
+    /// Paints pixels that would have clipped without tone mapping magenta,
+    /// toggled with F9.
+    pub show_clipping: bool,
+    /// Whether highlights glow, toggled with B.
+    pub bloom: bool,
+    /// Exposed brightness above which pixels glow.
+    pub bloom_threshold: f32,
+    /// How much of the glow is added, changed with `,` and `.`.
+    pub bloom_intensity: f32,
+    /// Blur radius in half-resolution pixels, changed with `;` and `'`.
+    pub bloom_radius: u32,
+    /// `Effect` bits applied after tone mapping.
+    pub effects: u32,
+    /// Whether the result is sRGB-encoded for the screen; turned off with
+    /// --no-srgb.
+    pub srgb: bool,
+    /// Encodes only the left half of the frame, toggled with F8.
+    pub gamma_split: bool,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output {
+            exposure: 1.0,
+            tone_map: ToneMap::Reinhard,
+            show_clipping: false,
+            bloom: true,
+            bloom_threshold: 1.0,
+            bloom_intensity: 0.5,
+            bloom_radius: 8,
+            effects: 0,
+            srgb: true,
+            gamma_split: false,
+        }
+    }
+}
+
+impl Output {
+    pub fn toggle_effect(&mut self, effect: Effect) {
+        self.effects ^= effect.bit();
+    }
+
+    /// The effects currently applied, or "no effects".
+    pub fn effects_label(&self) -> String {
+        let active = Effect::ALL
+            .iter()
+            .filter(|effect| self.effects & effect.bit() != 0)
+            .map(|effect| format!("{:?}", effect).to_lowercase())
+            .collect::<Vec<String>>();
+        match active.is_empty() {
+            true => "no effects".to_string(),
+            false => active.join(" + "),
+        }
+    }
+}
+
+/// What the scene is drawn into. Replacing a field frees the old texture,
+/// so resizing doesn't leak.
+struct Targets {
+    size: (u32, u32),
+    samples: u32,
+    /// Sampled by the effects pass; drawn into directly without MSAA.
+    color: glium::texture::Texture2d,
+    depth: glium::framebuffer::DepthStencilRenderBuffer,
+    /// Drawn into with MSAA, then resolved into `color`.
+    multisampled: Option<(
+        glium::texture::Texture2dMultisample,
+        glium::texture::DepthStencilTexture2dMultisample,
+    )>,
+}
+
+impl Targets {
+    fn new(facade: &impl Facade, (width, height): (u32, u32), samples: u32) -> Self {
+        let multisampled = match samples {
+            0 | 1 => None,
+            samples => Some((
+                glium::texture::Texture2dMultisample::empty_with_format(
+                    facade,
+                    COLOR_FORMAT,
+                    MipmapsOption::NoMipmap,
+                    width,
+                    height,
+                    samples,
+                )
+                .unwrap(),
+                glium::texture::DepthStencilTexture2dMultisample::empty_with_format(
+                    facade,
+                    DEPTH_FORMAT,
+                    MipmapsOption::NoMipmap,
+                    width,
+                    height,
+                    samples,
+                )
+                .unwrap(),
+            )),
+        };
+        Targets {
+            size: (width, height),
+            samples,
+            color: super::targets::color(facade, COLOR_FORMAT, (width, height)),
+            depth: glium::framebuffer::DepthStencilRenderBuffer::new(
+                facade,
+                DEPTH_FORMAT,
+                width,
+                height,
+            )
+            .unwrap(),
+            multisampled,
+        }
+    }
+}
+
+/// Draws the scene offscreen at the window's size, then draws it to the
+/// screen tone mapped, bloomed and through the selected effects.
+pub struct PostProcess {
+    program: glium::Program,
+    targets: Targets,
+    bloom: Bloom,
+}
+
+impl PostProcess {
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        // The shader encodes the output itself, so glium mustn't turn on
+        // the hardware conversion as well.
+        Ok(PostProcess {
+            program: super::shaders::program(
+                facade,
+                FULLSCREEN_VERTEX_SRC,
+                FRAGMENT_SHADER_SRC,
+                true,
+            )?,
+            targets: Targets::new(facade, (1, 1), 0),
+            bloom: Bloom::new(facade)?,
+        })
+    }
+
+    /// Recreates the targets if the size or sample count changed, keeping
+    /// them at least 1x1.
+    pub fn resize(&mut self, facade: &impl Facade, (width, height): (u32, u32), samples: u32) {
+        let size = (width.max(1), height.max(1));
+        if size != self.targets.size || samples != self.targets.samples {
+            self.targets = Targets::new(facade, size, samples);
+        }
+        self.bloom.resize(facade, size);
+    }
+
+    /// GPU milliseconds the bloom passes took, when they're on and timed.
+    pub fn bloom_gpu_time(&self, output: &Output) -> Option<f32> {
+        self.bloom.gpu_time.filter(|_| output.bloom)
+    }
+
+    /// Calls `draw` with the offscreen target, resolving the samples
+    /// afterwards when multisampling.
+    pub fn draw_scene<R>(
+        &self,
+        facade: &impl Facade,
+        draw: impl FnOnce(&mut SimpleFrameBuffer) -> R,
+    ) -> R {
+        let targets = &self.targets;
+        match &targets.multisampled {
+            Some((color, depth)) => {
+                let mut target =
+                    SimpleFrameBuffer::with_depth_stencil_buffer(facade, color, depth).unwrap();
+                let result = draw(&mut target);
+                let resolved = SimpleFrameBuffer::new(facade, &targets.color).unwrap();
+                let (width, height) = targets.size;
+                target.blit_whole_color_to(
+                    &resolved,
+                    &glium::BlitTarget {
+                        left: 0,
+                        bottom: 0,
+                        width: width as i32,
+                        height: height as i32,
+                    },
+                    glium::uniforms::MagnifySamplerFilter::Nearest,
+                );
+                result
+            }
+            None => {
+                let mut target = SimpleFrameBuffer::with_depth_stencil_buffer(
+                    facade,
+                    &targets.color,
+                    &targets.depth,
+                )
+                .unwrap();
+                draw(&mut target)
+            }
+        }
+    }
+
+    /// Draws the last `draw_scene` to `frame` as `output` says. `frame`
+    /// should be the same size as the targets.
+    pub fn draw(&mut self, facade: &impl Facade, frame: &mut impl Surface, output: &Output) {
+        if output.bloom {
+            self.bloom.apply(
+                facade,
+                &self.targets.color,
+                output.exposure,
+                output.bloom_threshold,
+                output.bloom_radius,
+            );
+        }
+        let uniforms = uniform! {
+            u_scene: &self.targets.color,
+            u_bloom: self.bloom.texture(),
+            u_bloom_enabled: output.bloom,
+            u_bloom_intensity: output.bloom_intensity,
+            u_exposure: output.exposure,
+            u_tone_map: output.tone_map as i32,
+            u_show_clipping: output.show_clipping,
+            u_effect_flags: output.effects as i32,
+            u_srgb: output.srgb,
+            u_gamma_split: output.gamma_split,
+        };
+        frame
+            .draw(
+                glium::vertex::EmptyVertexAttributes { len: 3 },
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.program,
+                &uniforms,
+                &Default::default(),
+            )
+            .unwrap();
+    }
+}
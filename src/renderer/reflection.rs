@@ -0,0 +1,58 @@
+use glium::backend::Facade;
+use glium::framebuffer::{DepthStencilRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{Texture2d, UncompressedFloatFormat};
+
+use super::targets;
+
+/// The scene mirrored under the ground, drawn at the framebuffer's size so
+/// the ground can look it up by screen position.
+pub struct Reflection {
+    color: Texture2d,
+    depth: DepthStencilRenderBuffer,
+    /// Bound in its place while drawing into it, since a texture mustn't be
+    /// sampled and drawn to at once.
+    placeholder: Texture2d,
+}
+
+impl Reflection {
+    pub fn new(facade: &impl Facade) -> Self {
+        Reflection {
+            color: color(facade, (1, 1)),
+            depth: targets::depth(facade, (1, 1)),
+            placeholder: color(facade, (1, 1)),
+        }
+    }
+
+    /// Recreates the target if `size` changed.
+    pub fn resize(&mut self, facade: &impl Facade, (width, height): (u32, u32)) {
+        if self.color.dimensions() != (width, height) {
+            self.color = color(facade, (width, height));
+            self.depth = targets::depth(facade, (width, height));
+        }
+    }
+
+    /// The mirrored scene from the last `draw`, or a stand-in while
+    /// `drawing` it.
+    pub fn texture(&self, drawing: bool) -> &Texture2d {
+        match drawing {
+            true => &self.placeholder,
+            false => &self.color,
+        }
+    }
+
+    /// Calls `draw` with the target to draw the mirrored scene into.
+    pub fn draw<R>(
+        &self,
+        facade: &impl Facade,
+        draw: impl FnOnce(&mut SimpleFrameBuffer) -> R,
+    ) -> R {
+        let mut target =
+            SimpleFrameBuffer::with_depth_stencil_buffer(facade, &self.color, &self.depth).unwrap();
+        draw(&mut target)
+    }
+}
+
+/// Half floats, like the scene target the reflection ends up in.
+fn color(facade: &impl Facade, size: (u32, u32)) -> Texture2d {
+    targets::color(facade, UncompressedFloatFormat::F16F16F16F16, size)
+}
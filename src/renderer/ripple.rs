@@ -0,0 +1,54 @@
+use std::f32::consts::TAU;
+
+use crate::math::Vec3;
+use crate::model_loader::Vertex3D;
+
+/// Height of the crests and the distance between them, as fractions of the
+/// mesh's bounding radius, so every model ripples visibly.
+const AMPLITUDE: f32 = 0.02;
+const WAVELENGTH: f32 = 0.25;
+/// Crests passing any one point each second.
+pub const FREQUENCY: f32 = 0.5;
+
+/// Rings spreading out from a mesh's center, moving its vertices in and out
+/// along their normals. `ripple` in basic.vert, and in the shadow map's
+/// shader, is the same on the GPU, with these fields as its `u_ripple_*`
+/// uniforms.
+#[derive(Clone, Copy)]
+pub struct Ripple {
+    pub center: Vec3,
+    pub amplitude: f32,
+    pub wavelength: f32,
+}
+
+impl Ripple {
+    /// For a mesh with the bounding sphere `(center, radius)`.
+    pub fn new((center, radius): (Vec3, f32)) -> Self {
+        Ripple {
+            center,
+            amplitude: AMPLITUDE * radius,
+            wavelength: (WAVELENGTH * radius).max(f32::EPSILON),
+        }
+    }
+
+    /// How far along its normal the vertex at `position` is moved `time`
+    /// seconds in.
+    fn offset(&self, position: Vec3, time: f32) -> f32 {
+        let phase = (position - self.center).length() / self.wavelength - time * FREQUENCY;
+        self.amplitude * (phase * TAU).sin()
+    }
+
+    /// Replaces `rippled` with `vertices` moved as they are `time` seconds
+    /// in. Normals are left alone, as they are on the GPU.
+    pub fn apply(&self, vertices: &[Vertex3D], time: f32, rippled: &mut Vec<Vertex3D>) {
+        rippled.clear();
+        rippled.extend(vertices.iter().map(|vertex| {
+            let position = Vec3::from(vertex.position);
+            let offset = self.offset(position, time);
+            Vertex3D {
+                position: (position + Vec3::from(vertex.normal) * offset).to_array(),
+                ..*vertex
+            }
+        }));
+    }
+}
@@ -0,0 +1,251 @@
+use glium::program::ShaderType;
+use glium::ProgramCreationError;
+
+/// Lines of source shown before and after each line with an error.
+const CONTEXT_LINES: usize = 2;
+
+/// One stage's source, named for the messages.
+pub struct Stage<'a> {
+    pub name: &'a str,
+    pub source: &'a str,
+}
+
+/// Turns a program creation error into a readable report. Compile errors
+/// quote the offending lines of the failing stage under a caret; link errors
+/// list the interface of both stages, where mismatches usually are.
+pub fn describe(error: &ProgramCreationError, vertex: &Stage, fragment: &Stage) -> String {
+    match error {
+        ProgramCreationError::CompilationError(log, stage) => {
+            let (label, stage) = match stage {
+                ShaderType::Fragment => ("fragment", fragment),
+                _ => ("vertex", vertex),
+            };
+            describe_compilation(log, label, stage)
+        }
+        ProgramCreationError::LinkingError(log) => {
+            let mut report = format!("failed to link shaders:\n{}\n", log.trim_end());
+            for (label, stage) in [("vertex", vertex), ("fragment", fragment)] {
+                report += &format!("{} shader {} interface:\n", label, stage.name);
+                for line in interface(stage.source) {
+                    report += &format!("    {}\n", line);
+                }
+            }
+            report.trim_end().to_string()
+        }
+        other => format!("failed to build shaders: {}", other),
+    }
+}
+
+fn describe_compilation(log: &str, label: &str, stage: &Stage) -> String {
+    let source: Vec<&str> = stage.source.lines().collect();
+    let mut report = String::new();
+    for message in log.lines().filter(|message| !message.trim().is_empty()) {
+        let (line, column) = match parse_location(message) {
+            Some(location) => location,
+            None => {
+                report += &format!("{} shader {}: {}\n", label, stage.name, message.trim());
+                continue;
+            }
+        };
+        report += &format!(
+            "{} shader {}:{}: {}\n",
+            label,
+            stage.name,
+            line,
+            message.trim()
+        );
+        if line == 0 || line > source.len() {
+            continue;
+        }
+        let first = line.saturating_sub(CONTEXT_LINES).max(1);
+        let last = (line + CONTEXT_LINES).min(source.len());
+        for number in first..=last {
+            let text = source[number - 1];
+            report += &format!("{:>5} | {}\n", number, text);
+            if number == line {
+                // Without a column from the driver, point at the statement.
+                let column = column.unwrap_or_else(|| text.len() - text.trim_start().len() + 1);
+                report += &format!("      | {}^\n", " ".repeat(column.saturating_sub(1)));
+            }
+        }
+    }
+    report.trim_end().to_string()
+}
+
+/// Finds the 1-based line, and column if given, in one line of a driver's
+/// log. Understands Mesa (`0:12(5): error`), AMD and Intel (`ERROR: 0:12:`)
+/// and NVIDIA (`0(12) : error`).
+fn parse_location(message: &str) -> Option<(usize, Option<usize>)> {
+    let message = message.trim_start();
+    let message = ["ERROR: ", "WARNING: "]
+        .iter()
+        .find_map(|prefix| message.strip_prefix(prefix))
+        .unwrap_or(message);
+    let file_end = message.find(|c: char| !c.is_ascii_digit())?;
+    if file_end == 0 {
+        return None;
+    }
+    let rest = &message[file_end..];
+
+    if let Some(rest) = rest.strip_prefix('(') {
+        let (line, _) = rest.split_once(')')?;
+        return Some((line.parse().ok()?, None));
+    }
+    let rest = rest.strip_prefix(':')?;
+    let line_end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    let line = rest[..line_end].parse().ok()?;
+    let column = rest[line_end..]
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(')'))
+        .and_then(|(column, _)| column.parse().ok());
+    Some((line, column))
+}
+
+/// The `in`, `out` and `uniform` declarations of a stage.
+fn interface(source: &str) -> Vec<&str> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            ["in ", "out ", "uniform "]
+                .iter()
+                .any(|keyword| line.starts_with(keyword))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fragment shader missing the semicolon at the end of line 7. Mesa
+    /// reports it on line 8, where the next statement starts; other drivers
+    /// on line 7.
+    const BAD_FRAGMENT: &str = "#version 150
+
+in vec3 v_normal;
+out vec4 color;
+
+void main() {
+    vec3 normal = normalize(v_normal)
+    color = vec4(normal, 1.0);
+}
+";
+
+    const VERTEX: &str = "#version 150
+
+in vec3 position;
+out vec3 v_normal;
+
+void main() {
+    v_normal = position;
+    gl_Position = vec4(position, 1.0);
+}
+";
+
+    fn stages() -> (Stage<'static>, Stage<'static>) {
+        let vertex = Stage {
+            name: "basic.vert",
+            source: VERTEX,
+        };
+        let fragment = Stage {
+            name: "basic.frag",
+            source: BAD_FRAGMENT,
+        };
+        (vertex, fragment)
+    }
+
+    fn compile_error(log: &str, stage: ShaderType) -> String {
+        let (vertex, fragment) = stages();
+        let error = ProgramCreationError::CompilationError(log.to_string(), stage);
+        describe(&error, &vertex, &fragment)
+    }
+
+    #[test]
+    fn mesa_error_quotes_the_line_under_a_caret() {
+        let log = "0:8(5): error: syntax error, unexpected IDENTIFIER, expecting ',' or ';'\n";
+        let report = compile_error(log, ShaderType::Fragment);
+        let lines = report.lines().collect::<Vec<&str>>();
+        assert_eq!(
+            lines,
+            [
+                "fragment shader basic.frag:8: \
+                 0:8(5): error: syntax error, unexpected IDENTIFIER, expecting ',' or ';'",
+                "    6 | void main() {",
+                "    7 |     vec3 normal = normalize(v_normal)",
+                "    8 |     color = vec4(normal, 1.0);",
+                "      |     ^",
+                "    9 | }",
+            ]
+        );
+    }
+
+    #[test]
+    fn every_driver_log_format_finds_the_line() {
+        let logs = [
+            "ERROR: 0:7: ';' : syntax error",
+            "0(7) : error C0000: syntax error, unexpected identifier",
+            "WARNING: 0:7: 'normal' : redefinition",
+        ];
+        for log in logs {
+            let report = compile_error(log, ShaderType::Fragment);
+            assert!(
+                report.starts_with("fragment shader basic.frag:7: "),
+                "{}",
+                report
+            );
+            assert!(
+                report.contains("    7 |     vec3 normal = normalize(v_normal)\n"),
+                "{}",
+                report
+            );
+        }
+    }
+
+    #[test]
+    fn vertex_errors_quote_the_vertex_source() {
+        let report = compile_error("0:7(16): error: `position2' undeclared", ShaderType::Vertex);
+        assert!(
+            report.starts_with("vertex shader basic.vert:7: "),
+            "{}",
+            report
+        );
+        assert!(
+            report.contains("    7 |     v_normal = position;\n"),
+            "{}",
+            report
+        );
+        // The column from the driver places the caret.
+        assert!(
+            report.contains("\n      |                ^\n"),
+            "{}",
+            report
+        );
+    }
+
+    #[test]
+    fn messages_without_a_line_are_kept() {
+        let report = compile_error("error: too many errors\n", ShaderType::Fragment);
+        assert_eq!(report, "fragment shader basic.frag: error: too many errors");
+    }
+
+    #[test]
+    fn link_errors_list_both_interfaces() {
+        let (vertex, fragment) = stages();
+        let error = ProgramCreationError::LinkingError(
+            "error: fragment shader input `v_color' has no matching output".to_string(),
+        );
+        let report = describe(&error, &vertex, &fragment);
+        assert_eq!(
+            report,
+            "failed to link shaders:
+error: fragment shader input `v_color' has no matching output
+vertex shader basic.vert interface:
+    in vec3 position;
+    out vec3 v_normal;
+fragment shader basic.frag interface:
+    in vec3 v_normal;
+    out vec4 color;"
+        );
+    }
+}
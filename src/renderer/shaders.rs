@@ -0,0 +1,312 @@
+use std::borrow::Cow;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use glium::backend::{Context, Facade};
+use glium::program::{ProgramCreationInput, ShaderType};
+use glium::{Api, Program, ProgramCreationError};
+
+/// Built into the binary, used when the files can't be found.
+pub const EMBEDDED_VERTEX: &str = include_str!("../../shaders/basic.vert");
+pub const EMBEDDED_FRAGMENT: &str = include_str!("../../shaders/basic.frag");
+
+const VERTEX_FILE: &str = "basic.vert";
+const FRAGMENT_FILE: &str = "basic.frag";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Replaces `#version 150` on OpenGL ES. Without default precisions for
+/// floats, fragment shaders don't compile, and ints and samplers would get
+/// too little to agree between the stages or hold HDR colors.
+const ES_HEADER: &str = "#version 300 es
+precision highp float;
+precision highp int;
+precision highp sampler2D;
+precision highp samplerCube;
+";
+
+/// The main program's GLSL files, watched by polling their modification
+/// times so edits can be swapped in without restarting.
+pub struct ShaderFiles {
+    /// `None` when neither location has the files.
+    directory: Option<PathBuf>,
+    modified: Option<(SystemTime, SystemTime)>,
+    last_poll: Instant,
+}
+
+impl ShaderFiles {
+    /// Looks for `shaders/` in the working directory, then in the source
+    /// tree the binary was built from.
+    pub fn find() -> Self {
+        let candidates = [
+            PathBuf::from("shaders"),
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("shaders"),
+        ];
+        let directory = candidates
+            .into_iter()
+            .find(|directory| directory.join(VERTEX_FILE).is_file());
+        ShaderFiles {
+            directory,
+            modified: None,
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn vertex_path(&self) -> Option<PathBuf> {
+        self.directory
+            .as_ref()
+            .map(|directory| directory.join(VERTEX_FILE))
+    }
+
+    pub fn fragment_path(&self) -> Option<PathBuf> {
+        self.directory
+            .as_ref()
+            .map(|directory| directory.join(FRAGMENT_FILE))
+    }
+
+    /// Names the file a stage was read from, for error messages.
+    pub fn name(&self, stage: ShaderType) -> String {
+        let (path, file) = match stage {
+            ShaderType::Fragment => (self.fragment_path(), FRAGMENT_FILE),
+            _ => (self.vertex_path(), VERTEX_FILE),
+        };
+        match path {
+            Some(path) => path.display().to_string(),
+            None => format!("{} (built in)", file),
+        }
+    }
+
+    /// Reads the vertex and fragment source, or returns the embedded copies
+    /// when there are no files to read.
+    pub fn read(&mut self) -> io::Result<(String, String)> {
+        let (vertex_path, fragment_path) = match (self.vertex_path(), self.fragment_path()) {
+            (Some(vertex), Some(fragment)) => (vertex, fragment),
+            _ => return Ok((EMBEDDED_VERTEX.to_string(), EMBEDDED_FRAGMENT.to_string())),
+        };
+        self.modified = modified_times(&vertex_path, &fragment_path).ok();
+        Ok((
+            fs::read_to_string(vertex_path)?,
+            fs::read_to_string(fragment_path)?,
+        ))
+    }
+
+    /// Whether either file changed since the last `read`, checking at most
+    /// once per poll interval.
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        match (self.vertex_path(), self.fragment_path()) {
+            (Some(vertex), Some(fragment)) => {
+                // A file being saved can briefly be missing; check again later.
+                match modified_times(&vertex, &fragment) {
+                    Ok(modified) => self.modified != Some(modified),
+                    Err(_) => false,
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+fn modified_times(vertex: &Path, fragment: &Path) -> io::Result<(SystemTime, SystemTime)> {
+    Ok((
+        fs::metadata(vertex)?.modified()?,
+        fs::metadata(fragment)?.modified()?,
+    ))
+}
+
+/// The source as the context's GLSL wants it. Desktop contexts take the
+/// `#version 150` shaders as they are, OpenGL ES ones, like the Raspberry
+/// Pi's or ANGLE's, the `es_variant`.
+pub fn for_context<'a>(context: &Context, source: &'a str) -> Cow<'a, str> {
+    match context.get_opengl_version().0 {
+        Api::Gl => Cow::Borrowed(source),
+        Api::GlEs => es_variant(source),
+    }
+}
+
+/// The shader as GLSL ES 3.00, which needs a different header, and with
+/// its uniform blocks unwrapped: glium reads a block's members with a
+/// function OpenGL ES doesn't have. A `#line` after the header keeps
+/// compile errors on the original lines.
+fn es_variant(source: &str) -> Cow<'_, str> {
+    let Some(start) = source.find("#version 150") else {
+        return Cow::Borrowed(source);
+    };
+    let rest = source[start..]
+        .split_once('\n')
+        .map_or("", |(_, rest)| rest);
+    let next_line = source[..start].matches('\n').count() + 2;
+    Cow::Owned(format!(
+        "{}{}#line {}\n{}",
+        &source[..start],
+        ES_HEADER,
+        next_line,
+        unwrap_blocks(rest)
+    ))
+}
+
+/// `source` with the members of its `layout(std140) uniform` blocks
+/// declared as uniforms of their own, line for line, and the lines opening
+/// and closing the blocks commented out.
+fn unwrap_blocks(source: &str) -> String {
+    let mut unwrapped = String::with_capacity(source.len());
+    let mut in_block = false;
+    for line in source.split_inclusive('\n') {
+        let text = line.trim();
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let newline = &line[line.trim_end().len()..];
+        let opens = text.starts_with("layout(std140) uniform") && text.ends_with('{');
+        match (in_block, text) {
+            (false, _) if opens => in_block = true,
+            (true, "};") => in_block = false,
+            (true, text) if !text.is_empty() && !text.starts_with("//") => {
+                unwrapped += &format!("{}uniform {}{}", indent, text, newline);
+                continue;
+            }
+            _ => {
+                unwrapped += line;
+                continue;
+            }
+        }
+        unwrapped += &format!("{}// {}{}", indent, text, newline);
+    }
+    unwrapped
+}
+
+/// `source` with `name` defined on the line after its `#version`, for
+/// building a variant of it. Compile errors are a line lower than in the
+/// original.
+pub fn with_define(source: &str, name: &str) -> String {
+    match source.find("#version") {
+        Some(start) => {
+            let end = source[start..]
+                .find('\n')
+                .map_or(source.len(), |end| start + end);
+            format!("{}\n#define {}{}", &source[..end], name, &source[end..])
+        }
+        None => format!("#define {}\n{}", name, source),
+    }
+}
+
+/// The stages a program can have besides its vertex and fragment shaders.
+#[derive(Default)]
+pub struct ExtraStages<'a> {
+    pub tessellation_control: Option<&'a str>,
+    pub tessellation_evaluation: Option<&'a str>,
+    pub geometry: Option<&'a str>,
+}
+
+/// Builds a program from `#version 150` sources for whichever OpenGL
+/// `facade` has. With `outputs_srgb` the fragment shader encodes its own
+/// output, so glium leaves the hardware conversion off.
+pub fn program(
+    facade: &impl Facade,
+    vertex: &str,
+    fragment: &str,
+    outputs_srgb: bool,
+) -> Result<Program, ProgramCreationError> {
+    program_with_stages(
+        facade,
+        vertex,
+        ExtraStages::default(),
+        fragment,
+        outputs_srgb,
+    )
+}
+
+/// Like `program`, with the `extra` stages between the two.
+pub fn program_with_stages<'a>(
+    facade: &impl Facade,
+    vertex: &str,
+    extra: ExtraStages<'a>,
+    fragment: &str,
+    outputs_srgb: bool,
+) -> Result<Program, ProgramCreationError> {
+    let context = facade.get_context();
+    let (vertex, fragment) = (for_context(context, vertex), for_context(context, fragment));
+    let stage = |source: Option<&'a str>| source.map(|source| for_context(context, source));
+    let control = stage(extra.tessellation_control);
+    let evaluation = stage(extra.tessellation_evaluation);
+    let geometry = stage(extra.geometry);
+    let input = ProgramCreationInput::SourceCode {
+        vertex_shader: &vertex,
+        tessellation_control_shader: control.as_deref(),
+        tessellation_evaluation_shader: evaluation.as_deref(),
+        geometry_shader: geometry.as_deref(),
+        fragment_shader: &fragment,
+        transform_feedback_varyings: None,
+        outputs_srgb,
+        uses_point_size: false,
+    };
+    Program::new(facade, input)
+}
+
+/// Like `program`, for drawing points with the vertex shader setting
+/// `gl_PointSize`, which desktop GL ignores unless asked to use it. OpenGL
+/// ES always uses it and has no way to ask, so glium refuses to.
+pub fn point_program(
+    facade: &impl Facade,
+    vertex: &str,
+    fragment: &str,
+) -> Result<Program, ProgramCreationError> {
+    let context = facade.get_context();
+    let (vertex, fragment) = (for_context(context, vertex), for_context(context, fragment));
+    let input = ProgramCreationInput::SourceCode {
+        vertex_shader: &vertex,
+        tessellation_control_shader: None,
+        tessellation_evaluation_shader: None,
+        geometry_shader: None,
+        fragment_shader: &fragment,
+        transform_feedback_varyings: None,
+        outputs_srgb: false,
+        uses_point_size: context.get_opengl_version().0 == Api::Gl,
+    };
+    Program::new(facade, input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn es_variant_unwraps_uniform_blocks() {
+        let source = "#version 150
+
+layout(std140) uniform u_frame {
+    mat4 view;
+    // Two of them.
+    vec4 u_light[2];
+};
+
+void main() {}
+";
+        let expected = format!(
+            "{}#line 2
+
+// layout(std140) uniform u_frame {{
+    uniform mat4 view;
+    // Two of them.
+    uniform vec4 u_light[2];
+// }};
+
+void main() {{}}
+",
+            ES_HEADER
+        );
+        assert_eq!(es_variant(source), expected);
+    }
+
+    #[test]
+    fn es_variants_of_the_main_shaders_keep_their_lines() {
+        for source in [EMBEDDED_VERTEX, EMBEDDED_FRAGMENT] {
+            let es = es_variant(source);
+            assert!(!es.lines().any(|line| line.starts_with("layout(std140)")));
+            // The header replaces the version line, and the #line is added.
+            let added = ES_HEADER.lines().count();
+            assert_eq!(es.lines().count(), source.lines().count() + added);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use glium::backend::Facade;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::DepthTexture2d;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Sampler};
+use glium::vertex::EmptyVertexAttributes;
+use glium::Surface;
+
+use super::post::FULLSCREEN_VERTEX_SRC;
+use super::ripple::{self, Ripple};
+use super::GpuMesh;
+use crate::camera;
+use crate::math::{Mat4, Vec3};
+
+/// Width and height of the shadow map in texels.
+const SIZE: u32 = 2048;
+/// The light's box is this much bigger than the scene's bounding sphere, so
+/// nothing at its edge is cut off.
+const MARGIN: f32 = 1.05;
+
+/// Transforms like the main vertex shader, ripple included, but only
+/// writes depth.
+const VERTEX_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 position;
+    in vec3 normal;
+    in vec4 i_model_0;
+    in vec4 i_model_1;
+    in vec4 i_model_2;
+    in vec4 i_model_3;
+
+    uniform mat4 u_light_space;
+    uniform float u_time;
+    uniform vec3 u_ripple_center;
+    uniform float u_ripple_amplitude;
+    uniform float u_ripple_wavelength;
+    uniform float u_ripple_frequency;
+
+    vec3 ripple(vec3 position) {
+        float phase = length(position - u_ripple_center) / u_ripple_wavelength
+            - u_time * u_ripple_frequency;
+        return position + normal * u_ripple_amplitude * sin(phase * 6.2831853);
+    }
+
+    void main() {
+        vec3 rippled = u_ripple_amplitude == 0.0 ? position : ripple(position);
+        mat4 model = mat4(i_model_0, i_model_1, i_model_2, i_model_3);
+        gl_Position = u_light_space * model * vec4(rippled, 1.0);
+    }
+"#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 150
+
+    void main() {
+    }
+"#;
+
+/// Shows the map's depth as grayscale, white at the far plane.
+const DEBUG_FRAGMENT_SRC: &str = r#"
+    #version 150
+
+    in vec2 v_uv;
+    out vec4 color;
+
+    uniform sampler2D u_shadow_map;
+
+    void main() {
+        color = vec4(vec3(texture(u_shadow_map, v_uv).r), 1.0);
+    }
+"#;
+
+/// The depth of the scene as seen from a directional light, which the main
+/// shader compares against to find what the light can't reach.
+pub struct ShadowMap {
+    program: glium::Program,
+    debug_program: glium::Program,
+    depth: DepthTexture2d,
+}
+
+impl ShadowMap {
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        Ok(ShadowMap {
+            program: super::shaders::program(
+                facade,
+                VERTEX_SHADER_SRC,
+                FRAGMENT_SHADER_SRC,
+                false,
+            )?,
+            debug_program: super::shaders::program(
+                facade,
+                FULLSCREEN_VERTEX_SRC,
+                DEBUG_FRAGMENT_SRC,
+                false,
+            )?,
+            depth: super::targets::depth_texture(facade, SIZE),
+        })
+    }
+
+    /// The map, sampled without filtering since depths can't be blended.
+    pub fn texture(&self) -> Sampler<'_, DepthTexture2d> {
+        self.depth
+            .sampled()
+            .magnify_filter(MagnifySamplerFilter::Nearest)
+            .minify_filter(MinifySamplerFilter::Nearest)
+    }
+
+    /// Renders the depth of every instance of `meshes` as seen from far off
+    /// along `direction`, towards the light. The orthographic box fits the
+    /// sphere at `center` with `radius`. With `ripple_time`, the meshes
+    /// are rippled as the main vertex shader ripples them that many seconds
+    /// in. Returns the world to light clip space matrix used, and the number
+    /// of draw calls made.
+    pub fn render(
+        &self,
+        facade: &impl Facade,
+        meshes: &[GpuMesh],
+        direction: Vec3,
+        (center, radius): (Vec3, f32),
+        ripple_time: Option<f32>,
+    ) -> (Mat4, usize) {
+        let direction = direction.normalize();
+        let radius = radius.max(f32::EPSILON) * MARGIN;
+        // Any up works as long as it isn't along the light.
+        let up = match direction.dot(camera::UP.into()).abs() > 0.99 {
+            true => Vec3::new(1.0, 0.0, 0.0),
+            false => camera::UP.into(),
+        };
+        let eye = center + direction * (radius * 2.0);
+        let view = Mat4::look_at(eye, center, up);
+        let projection = Mat4::orthographic(-radius, radius, -radius, radius, radius, radius * 3.0);
+        let light_space = projection * view;
+
+        let mut target = SimpleFrameBuffer::depth_only(facade, &self.depth).unwrap();
+        target.clear_depth(1.0);
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut draw_calls = 0;
+        for mesh in meshes {
+            if mesh.instance_count == 0 {
+                continue;
+            }
+            let instances = mesh.instances.slice(0..mesh.instance_count).unwrap();
+            let ripple = Ripple::new(mesh.sphere);
+            let uniforms = uniform! {
+                u_light_space: light_space.to_array(),
+                u_time: ripple_time.unwrap_or(0.0),
+                u_ripple_center: ripple.center.to_array(),
+                u_ripple_amplitude: match ripple_time {
+                    Some(_) => ripple.amplitude,
+                    None => 0.0,
+                },
+                u_ripple_wavelength: ripple.wavelength,
+                u_ripple_frequency: ripple::FREQUENCY,
+            };
+            target
+                .draw(
+                    (&mesh.vertices, instances.per_instance().unwrap()),
+                    mesh.indices.all(),
+                    &self.program,
+                    &uniforms,
+                    &params,
+                )
+                .unwrap();
+            draw_calls += 1;
+        }
+        (light_space, draw_calls)
+    }
+
+    /// Draws the map into the bottom left corner of `frame`, a quarter of
+    /// its height across.
+    pub fn draw_debug(&self, frame: &mut impl Surface) {
+        let (_, height) = frame.get_dimensions();
+        let side = height / 4;
+        let params = glium::DrawParameters {
+            viewport: Some(glium::Rect {
+                left: 0,
+                bottom: 0,
+                width: side,
+                height: side,
+            }),
+            ..Default::default()
+        };
+        let uniforms = uniform! {
+            u_shadow_map: self.texture(),
+        };
+        frame
+            .draw(
+                EmptyVertexAttributes { len: 3 },
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.debug_program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+    }
+}
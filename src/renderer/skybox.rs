@@ -0,0 +1,127 @@
+use glium::backend::Facade;
+use glium::texture::SrgbCubemap;
+use glium::Surface;
+
+use crate::math::Mat4;
+
+/// Only the view's rotation is applied, so the cube stays centered on the
+/// eye. `xyww` puts every vertex on the far plane, behind everything else,
+/// and the near plane never clips it however small the cube is.
+const VERTEX_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 position;
+    out vec3 v_direction;
+
+    uniform mat4 perspective;
+    uniform mat4 view;
+
+    void main() {
+        v_direction = position;
+        gl_Position = (perspective * view * vec4(position, 1.0)).xyww;
+    }
+"#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec3 v_direction;
+    out vec4 color;
+
+    uniform samplerCube u_skybox;
+
+    void main() {
+        color = vec4(texture(u_skybox, v_direction).rgb, 1.0);
+    }
+"#;
+
+#[derive(Copy, Clone)]
+struct SkyVertex {
+    position: [f32; 3],
+}
+
+implement_vertex!(SkyVertex, position);
+
+/// The cubemap around the scene, drawn after the models wherever they left
+/// the background showing.
+pub struct Skybox {
+    program: glium::Program,
+    vertices: glium::VertexBuffer<SkyVertex>,
+    indices: glium::IndexBuffer<u16>,
+    /// Also what the models' surroundings are taken to look like.
+    pub cubemap: SrgbCubemap,
+}
+
+impl Skybox {
+    pub fn new(
+        facade: &impl Facade,
+        cubemap: SrgbCubemap,
+    ) -> Result<Self, glium::ProgramCreationError> {
+        let corners = (0..8)
+            .map(|corner| SkyVertex {
+                position: [0, 1, 2].map(|axis| match corner >> axis & 1 {
+                    0 => -1.0,
+                    _ => 1.0,
+                }),
+            })
+            .collect::<Vec<SkyVertex>>();
+        // Two triangles for each face, as corner bit patterns. Culling is
+        // off, so their winding doesn't matter.
+        let faces: [[u16; 4]; 6] = [
+            [1, 3, 7, 5],
+            [0, 4, 6, 2],
+            [2, 6, 7, 3],
+            [0, 1, 5, 4],
+            [4, 5, 7, 6],
+            [0, 2, 3, 1],
+        ];
+        let indexes = faces
+            .iter()
+            .flat_map(|&[a, b, c, d]| [a, b, c, a, c, d])
+            .collect::<Vec<u16>>();
+        Ok(Skybox {
+            program: super::shaders::program(
+                facade,
+                VERTEX_SHADER_SRC,
+                FRAGMENT_SHADER_SRC,
+                false,
+            )?,
+            vertices: glium::VertexBuffer::new(facade, &corners).unwrap(),
+            indices: glium::IndexBuffer::new(
+                facade,
+                glium::index::PrimitiveType::TrianglesList,
+                &indexes,
+            )
+            .unwrap(),
+            cubemap,
+        })
+    }
+
+    /// Fills what's still at the far plane of `frame`'s depth buffer.
+    pub fn draw(&self, frame: &mut impl Surface, perspective: Mat4, view: Mat4) {
+        let mut rotation = view;
+        rotation.columns[3] = [0.0, 0.0, 0.0, 1.0];
+        let uniforms = uniform! {
+            perspective: perspective.to_array(),
+            view: rotation.to_array(),
+            u_skybox: self.cubemap.sampled(),
+        };
+        let params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfLessOrEqual,
+                write: false,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        frame
+            .draw(
+                &self.vertices,
+                &self.indices,
+                &self.program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,345 @@
+use std::borrow::Cow;
+use std::f32::consts::TAU;
+
+use glium::backend::Facade;
+use glium::framebuffer::{DepthStencilRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{ClientFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat};
+use glium::vertex::EmptyVertexAttributes;
+use glium::Surface;
+
+use super::post::FULLSCREEN_VERTEX_SRC;
+use super::targets;
+use crate::math::Mat4;
+
+/// Full floats, since half floats would round depths a few hundred units
+/// away to more than the occlusion radius.
+const NORMAL_DEPTH_FORMAT: UncompressedFloatFormat = UncompressedFloatFormat::F32F32F32F32;
+const OCCLUSION_FORMAT: UncompressedFloatFormat = UncompressedFloatFormat::U8;
+/// The noise repeats every this many pixels, and the blur is as wide.
+const NOISE_SIZE: u32 = 4;
+/// A 4x4 Bayer matrix, so neighbouring pixels turn the kernel as
+/// differently as they can.
+const NOISE_ORDER: [u32; 16] = [0, 8, 2, 10, 12, 4, 14, 6, 3, 11, 1, 9, 15, 7, 13, 5];
+
+/// With the main vertex shader or the tessellation stages in front of it:
+/// the view-space normal, and the depth along the view axis in alpha.
+/// Where nothing is drawn the target stays cleared to 0.
+pub const NORMAL_DEPTH_SRC: &str = r#"
+    #version 150
+
+    in vec3 v_normal;
+    in vec3 v_position;
+    out vec4 color;
+
+    void main() {
+        color = vec4(normalize(v_normal), -v_position.z);
+    }
+"#;
+
+/// How much of the hemisphere over each pixel's normal, out to `u_radius`,
+/// is left open by what's in front of it, raised to `u_intensity`.
+const OCCLUSION_SRC: &str = r#"
+    #version 150
+
+    in vec2 v_uv;
+    out vec4 color;
+
+    uniform sampler2D u_normal_depth;
+    uniform sampler2D u_noise;
+    uniform mat4 u_projection;
+    uniform bool u_orthographic;
+    uniform float u_radius;
+    uniform float u_intensity;
+
+    const int KERNEL_SIZE = 16;
+    const float GOLDEN_ANGLE = 2.39996323;
+    // Of the radius, so a surface doesn't occlude itself where its depth
+    // has been rounded.
+    const float BIAS = 0.025;
+
+    // Where the point at `uv` on the screen and `depth` along the view axis
+    // is in view space. The frustum is taken to be symmetric.
+    vec3 view_position(vec2 uv, float depth) {
+        vec2 ndc = uv * 2.0 - 1.0;
+        vec2 scale = vec2(u_projection[0][0], u_projection[1][1]);
+        vec2 xy = u_orthographic ? (ndc - u_projection[3].xy) / scale : ndc * depth / scale;
+        return vec3(xy, -depth);
+    }
+
+    // The i-th point of the kernel in the hemisphere about +Z, as far as 1
+    // from the middle. They spiral out by the golden angle, gathered toward
+    // the pole, with more of them close in where occlusion matters most.
+    vec3 kernel_point(int i) {
+        float t = (float(i) + 0.5) / float(KERNEL_SIZE);
+        float sin_theta = sqrt(t);
+        float phi = float(i) * GOLDEN_ANGLE;
+        vec3 direction = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, sqrt(1.0 - t));
+        float distance = fract(float(i) * 0.618034 + 0.25);
+        return direction * mix(0.1, 1.0, distance * distance);
+    }
+
+    void main() {
+        ivec2 pixel = ivec2(gl_FragCoord.xy);
+        vec4 normal_depth = texelFetch(u_normal_depth, pixel, 0);
+        // Nothing is drawn there, so there's nothing to occlude.
+        if (normal_depth.a <= 0.0) {
+            color = vec4(1.0);
+            return;
+        }
+        float depth = normal_depth.a;
+        vec3 position = view_position(v_uv, depth);
+        vec3 normal = normalize(normal_depth.xyz);
+        // The kernel is turned about the normal by the noise, which tiles
+        // every NOISE_SIZE pixels, trading the banding of the same few
+        // samples everywhere for noise the blur takes out.
+        vec3 turn = vec3(texelFetch(u_noise, pixel % 4, 0).xy, 0.0);
+        vec3 tangent = turn - normal * dot(turn, normal);
+        if (length(tangent) < 1e-3) {
+            tangent = cross(normal, vec3(0.0, 1.0, 0.0));
+        }
+        tangent = normalize(tangent);
+        mat3 basis = mat3(tangent, cross(normal, tangent), normal);
+
+        float occlusion = 0.0;
+        for (int i = 0; i < KERNEL_SIZE; i++) {
+            vec3 point = position + basis * kernel_point(i) * u_radius;
+            vec4 clip = u_projection * vec4(point, 1.0);
+            vec2 uv = clip.xy / clip.w * 0.5 + 0.5;
+            float surface = texture(u_normal_depth, uv).a;
+            if (surface <= 0.0) {
+                continue;
+            }
+            // Something much nearer the camera than the radius is in front
+            // of the pixel rather than around it, so counts for less.
+            float in_range = smoothstep(0.0, 1.0, u_radius / abs(depth - surface));
+            if (surface < -point.z - BIAS * u_radius) {
+                occlusion += in_range;
+            }
+        }
+        float open = 1.0 - occlusion / float(KERNEL_SIZE);
+        color = vec4(vec3(pow(open, u_intensity)), 1.0);
+    }
+"#;
+
+/// Averages the occlusion over the 4x4 pixels the noise repeats over,
+/// leaving out those at a different depth so it doesn't bleed across
+/// silhouettes.
+const BLUR_SRC: &str = r#"
+    #version 150
+
+    in vec2 v_uv;
+    out vec4 color;
+
+    uniform sampler2D u_occlusion;
+    uniform sampler2D u_normal_depth;
+
+    // Of the pixel's own depth.
+    const float DEPTH_TOLERANCE = 0.02;
+
+    void main() {
+        ivec2 pixel = ivec2(gl_FragCoord.xy);
+        ivec2 last = textureSize(u_occlusion, 0) - 1;
+        float depth = texelFetch(u_normal_depth, pixel, 0).a;
+        float sum = 0.0;
+        float total = 0.0;
+        for (int y = -2; y < 2; y++) {
+            for (int x = -2; x < 2; x++) {
+                ivec2 neighbour = clamp(pixel + ivec2(x, y), ivec2(0), last);
+                float neighbour_depth = texelFetch(u_normal_depth, neighbour, 0).a;
+                if (abs(neighbour_depth - depth) <= DEPTH_TOLERANCE * depth) {
+                    sum += texelFetch(u_occlusion, neighbour, 0).r;
+                    total += 1.0;
+                }
+            }
+        }
+        // The pixel itself always counts, so total is at least 1.
+        color = vec4(vec3(sum / total), 1.0);
+    }
+"#;
+
+/// Shows the occlusion as it comes out of the first pass, before the blur.
+const DEBUG_SRC: &str = r#"
+    #version 150
+
+    in vec2 v_uv;
+    out vec4 color;
+
+    uniform sampler2D u_occlusion;
+
+    void main() {
+        color = vec4(vec3(texelFetch(u_occlusion, ivec2(gl_FragCoord.xy), 0).r), 1.0);
+    }
+"#;
+
+/// What the passes draw into, at the window's size. Replacing a field
+/// frees the old texture, so resizing doesn't leak.
+struct Targets {
+    normal_depth: Texture2d,
+    depth: DepthStencilRenderBuffer,
+    /// The occlusion before and after the blur.
+    raw: Texture2d,
+    blurred: Texture2d,
+}
+
+impl Targets {
+    fn new(facade: &impl Facade, size: (u32, u32)) -> Self {
+        Targets {
+            normal_depth: targets::color(facade, NORMAL_DEPTH_FORMAT, size),
+            depth: targets::depth(facade, size),
+            raw: targets::color(facade, OCCLUSION_FORMAT, size),
+            blurred: targets::color(facade, OCCLUSION_FORMAT, size),
+        }
+    }
+}
+
+/// Screen-space ambient occlusion: the scene's normals and depths are drawn
+/// first, then how enclosed each pixel is by what's near it, which the main
+/// shader darkens its ambient light by.
+pub struct Ssao {
+    occlusion: glium::Program,
+    blur: glium::Program,
+    debug: glium::Program,
+    /// The directions the kernel is turned to, one per pixel of the tile.
+    noise: Texture2d,
+    targets: Targets,
+}
+
+impl Ssao {
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        let program =
+            |fragment| super::shaders::program(facade, FULLSCREEN_VERTEX_SRC, fragment, false);
+        Ok(Ssao {
+            occlusion: program(OCCLUSION_SRC)?,
+            blur: program(BLUR_SRC)?,
+            debug: program(DEBUG_SRC)?,
+            noise: noise_texture(facade),
+            targets: Targets::new(facade, (1, 1)),
+        })
+    }
+
+    /// Recreates the targets if `size` changed, keeping them at least 1x1.
+    pub fn resize(&mut self, facade: &impl Facade, (width, height): (u32, u32)) {
+        let size = (width.max(1), height.max(1));
+        if self.targets.raw.dimensions() != size {
+            self.targets = Targets::new(facade, size);
+        }
+    }
+
+    /// The blurred occlusion from the last `apply`, 1 where nothing
+    /// occludes, to read pixel for pixel.
+    pub fn texture(&self) -> &Texture2d {
+        &self.targets.blurred
+    }
+
+    /// Calls `draw` with the normal and depth target cleared, to draw the
+    /// opaque faces into with `NORMAL_DEPTH_SRC`.
+    pub fn draw_normals<R>(
+        &self,
+        facade: &impl Facade,
+        draw: impl FnOnce(&mut SimpleFrameBuffer) -> R,
+    ) -> R {
+        let targets = &self.targets;
+        let mut target = SimpleFrameBuffer::with_depth_stencil_buffer(
+            facade,
+            &targets.normal_depth,
+            &targets.depth,
+        )
+        .unwrap();
+        target.clear_color_and_depth((0.0, 0.0, 0.0, 0.0), 1.0);
+        draw(&mut target)
+    }
+
+    /// Works out the occlusion from the last `draw_normals`, which used
+    /// `projection`, within `radius` in world units, then blurs it.
+    pub fn apply(
+        &self,
+        facade: &impl Facade,
+        projection: Mat4,
+        orthographic: bool,
+        radius: f32,
+        intensity: f32,
+    ) {
+        let triangles = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+        let targets = &self.targets;
+        let normal_depth = targets
+            .normal_depth
+            .sampled()
+            .wrap_function(glium::uniforms::SamplerWrapFunction::Clamp)
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest)
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest);
+        let uniforms = uniform! {
+            u_normal_depth: normal_depth,
+            u_noise: &self.noise,
+            u_projection: projection.to_array(),
+            u_orthographic: orthographic,
+            u_radius: radius,
+            u_intensity: intensity,
+        };
+        SimpleFrameBuffer::new(facade, &targets.raw)
+            .unwrap()
+            .draw(
+                EmptyVertexAttributes { len: 3 },
+                triangles,
+                &self.occlusion,
+                &uniforms,
+                &Default::default(),
+            )
+            .unwrap();
+        let uniforms = uniform! {
+            u_occlusion: &targets.raw,
+            u_normal_depth: &targets.normal_depth,
+        };
+        SimpleFrameBuffer::new(facade, &targets.blurred)
+            .unwrap()
+            .draw(
+                EmptyVertexAttributes { len: 3 },
+                triangles,
+                &self.blur,
+                &uniforms,
+                &Default::default(),
+            )
+            .unwrap();
+    }
+
+    /// Covers `frame`, which should be the targets' size, with the
+    /// occlusion from the last `apply` before it was blurred.
+    pub fn draw_debug(&self, frame: &mut impl Surface) {
+        let uniforms = uniform! {
+            u_occlusion: &self.targets.raw,
+        };
+        frame
+            .draw(
+                EmptyVertexAttributes { len: 3 },
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.debug,
+                &uniforms,
+                &Default::default(),
+            )
+            .unwrap();
+    }
+}
+
+/// Unit vectors in the XY plane, evenly spread around the circle and laid
+/// out over the tile in `NOISE_ORDER`.
+fn noise_texture(facade: &impl Facade) -> Texture2d {
+    let data = NOISE_ORDER
+        .iter()
+        .flat_map(|&step| {
+            let angle = step as f32 / NOISE_ORDER.len() as f32 * TAU;
+            [angle.cos(), angle.sin()]
+        })
+        .collect::<Vec<f32>>();
+    let image = RawImage2d {
+        data: Cow::Owned(data),
+        width: NOISE_SIZE,
+        height: NOISE_SIZE,
+        format: ClientFormat::F32F32,
+    };
+    Texture2d::with_format(
+        facade,
+        image,
+        UncompressedFloatFormat::F16F16,
+        MipmapsOption::NoMipmap,
+    )
+    .unwrap()
+}
@@ -0,0 +1,65 @@
+use glium::backend::Facade;
+use glium::framebuffer::DepthStencilRenderBuffer;
+use glium::texture::{
+    ClientFormat, DepthFormat, DepthStencilFormat, DepthTexture2d, MipmapsOption, PixelValue,
+    RawImage2d, Texture2d, UncompressedFloatFormat,
+};
+use glium::Api;
+
+/// A `format` texture to draw into, its contents undefined. With no pixels
+/// to upload glium still hands OpenGL ES a pixel format, RGBA bytes, which
+/// ES rejects for float and one-channel textures, so there it uploads zeros
+/// in a format that goes with the texture's.
+pub fn color(facade: &impl Facade, format: UncompressedFloatFormat, size: (u32, u32)) -> Texture2d {
+    let gles = facade.get_context().get_opengl_version().0 == Api::GlEs;
+    match format {
+        UncompressedFloatFormat::U8 if gles => zeroed::<u8>(facade, format, ClientFormat::U8, size),
+        UncompressedFloatFormat::F16F16F16F16 | UncompressedFloatFormat::F32F32F32F32 if gles => {
+            zeroed::<f32>(facade, format, ClientFormat::F32F32F32F32, size)
+        }
+        format => {
+            let (width, height) = size;
+            Texture2d::empty_with_format(facade, format, MipmapsOption::NoMipmap, width, height)
+                .unwrap()
+        }
+    }
+}
+
+fn zeroed<T: PixelValue + Clone + Default>(
+    facade: &impl Facade,
+    format: UncompressedFloatFormat,
+    client: ClientFormat,
+    (width, height): (u32, u32),
+) -> Texture2d {
+    let channels = client.get_num_components() as usize;
+    let image = RawImage2d {
+        data: vec![T::default(); width as usize * height as usize * channels].into(),
+        width,
+        height,
+        format: client,
+    };
+    Texture2d::with_format(facade, image, format, MipmapsOption::NoMipmap).unwrap()
+}
+
+/// A depth buffer for a color texture of the same size. 24 bits with a
+/// stencil, since glium only knows OpenGL ES has those packed together.
+pub fn depth(facade: &impl Facade, (width, height): (u32, u32)) -> DepthStencilRenderBuffer {
+    DepthStencilRenderBuffer::new(facade, DepthStencilFormat::I24I8, width, height).unwrap()
+}
+
+/// A square depth texture to draw into and sample, 24 bits on desktop GL.
+/// glium doesn't know OpenGL ES has sized depth formats at all, so there
+/// the driver picks.
+pub fn depth_texture(facade: &impl Facade, size: u32) -> DepthTexture2d {
+    match facade.get_context().get_opengl_version().0 {
+        Api::Gl => DepthTexture2d::empty_with_format(
+            facade,
+            DepthFormat::I24,
+            MipmapsOption::NoMipmap,
+            size,
+            size,
+        )
+        .unwrap(),
+        Api::GlEs => DepthTexture2d::empty(facade, size, size).unwrap(),
+    }
+}
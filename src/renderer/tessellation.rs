@@ -0,0 +1,289 @@
+use glium::backend::Facade;
+use glium::{Api, Program, Version};
+
+use super::edges;
+use super::shaders::{self, ExtraStages};
+
+/// Pixels of a patch's boundary on the screen for each segment it's cut
+/// into, up to the 64 segments GL guarantees.
+pub const PIXELS_PER_SEGMENT: f32 = 8.0;
+
+/// The oldest GLSL with tessellation shaders.
+const REQUIRED_GLSL: Version = Version(Api::Gl, 4, 0);
+
+/// Passes a patch's control points through with the instance they belong
+/// to; the evaluation shader does what the main vertex shader would.
+const VERTEX_SHADER_SRC: &str = r#"
+    #version 400
+
+    in vec3 position;
+    in vec4 i_model_0;
+    in vec4 i_model_1;
+    in vec4 i_model_2;
+    in vec4 i_model_3;
+    in vec3 i_normal_0;
+    in vec3 i_normal_1;
+    in vec3 i_normal_2;
+    in vec3 i_tint;
+    out vec3 tc_position;
+    out mat4 tc_model;
+    out mat3 tc_normal_matrix;
+    out vec3 tc_tint;
+
+    void main() {
+        tc_position = position;
+        tc_model = mat4(i_model_0, i_model_1, i_model_2, i_model_3);
+        tc_normal_matrix = mat3(i_normal_0, i_normal_1, i_normal_2);
+        tc_tint = i_tint;
+    }
+"#;
+
+/// Cuts each edge of a patch into segments of about u_pixels_per_segment
+/// pixels as u_lod_view_projection sees it, which is the camera's unless
+/// the level of detail is frozen.
+const CONTROL_SHADER_SRC: &str = r#"
+    #version 400
+
+    layout(vertices = 16) out;
+
+    in vec3 tc_position[];
+    in mat4 tc_model[];
+    in mat3 tc_normal_matrix[];
+    in vec3 tc_tint[];
+    out vec3 te_position[];
+    patch out mat4 te_model;
+    patch out mat3 te_normal_matrix;
+    patch out vec3 te_tint;
+
+    uniform mat4 u_lod_view_projection;
+    uniform vec2 u_viewport;
+    uniform float u_pixels_per_segment;
+
+    // Where a control point lands on the screen, in pixels. Points behind
+    // the camera are taken as just in front of it.
+    vec2 screen(int index) {
+        vec4 clip = u_lod_view_projection * tc_model[0] * vec4(tc_position[index], 1.0);
+        return clip.xy / max(clip.w, 1e-4) * 0.5 * u_viewport;
+    }
+
+    // Segments for the edge along four control points, from the length of
+    // their polygon on the screen. A neighbouring patch shares the points,
+    // so it cuts the edge alike and no cracks open between them.
+    float edge_level(int a, int b, int c, int d) {
+        vec2 pa = screen(a);
+        vec2 pb = screen(b);
+        vec2 pc = screen(c);
+        vec2 pd = screen(d);
+        float pixels = distance(pa, pb) + distance(pb, pc) + distance(pc, pd);
+        return clamp(pixels / u_pixels_per_segment, 1.0, 64.0);
+    }
+
+    void main() {
+        te_position[gl_InvocationID] = tc_position[gl_InvocationID];
+        if (gl_InvocationID == 0) {
+            te_model = tc_model[0];
+            te_normal_matrix = tc_normal_matrix[0];
+            te_tint = tc_tint[0];
+            // The edges at u = 0, v = 0, u = 1 and v = 1, with u along each
+            // row of control points.
+            gl_TessLevelOuter[0] = edge_level(0, 4, 8, 12);
+            gl_TessLevelOuter[1] = edge_level(0, 1, 2, 3);
+            gl_TessLevelOuter[2] = edge_level(3, 7, 11, 15);
+            gl_TessLevelOuter[3] = edge_level(12, 13, 14, 15);
+            gl_TessLevelInner[0] = max(gl_TessLevelOuter[1], gl_TessLevelOuter[3]);
+            gl_TessLevelInner[1] = max(gl_TessLevelOuter[0], gl_TessLevelOuter[2]);
+        }
+    }
+"#;
+
+/// Evaluates the bicubic patch at each generated vertex, as `evaluate` in
+/// primitives/teapot.rs does on the CPU, then does what basic.vert does
+/// with it.
+const EVALUATION_SHADER_SRC: &str = r#"
+    #version 400
+
+    layout(quads, fractional_odd_spacing, ccw) in;
+
+    // With the wireframe overlay's geometry shader after this stage, as in
+    // basic.vert.
+    #ifdef EDGE_OVERLAY
+    #define v_normal gs_normal
+    #define v_tangent gs_tangent
+    #define v_bitangent gs_bitangent
+    #define v_position gs_position
+    #define v_tex_coords gs_tex_coords
+    #define v_color gs_color
+    #define v_tint gs_tint
+    #define v_light_space_position gs_light_space_position
+    #define v_world_y gs_world_y
+    #define v_occlusion gs_occlusion
+    #endif
+
+    in vec3 te_position[];
+    patch in mat4 te_model;
+    patch in mat3 te_normal_matrix;
+    patch in vec3 te_tint;
+    out vec3 v_normal;
+    out vec3 v_tangent;
+    out vec3 v_bitangent;
+    out vec3 v_position;
+    out vec2 v_tex_coords;
+    out vec3 v_color;
+    out vec3 v_tint;
+    out vec4 v_light_space_position;
+    out float v_world_y;
+    out float v_occlusion;
+
+    // As declared in basic.vert and basic.frag.
+    const int MAX_LIGHTS = 8;
+    layout(std140) uniform u_frame {
+        mat4 perspective;
+        mat4 view;
+        mat4 u_inverse_view;
+        mat4 u_light_space;
+        vec3 u_camera_position;
+        float u_time;
+        float u_znear;
+        float u_zfar;
+        int u_shadow_light;
+        int u_light_count;
+        vec4 u_light_vector[MAX_LIGHTS];
+        vec4 u_light_color[MAX_LIGHTS];
+    };
+
+    // Colors the patches by how finely they're cut, blue for once through
+    // red for 64 times.
+    uniform bool u_color_by_level;
+
+    // The cubic Bernstein polynomials at t, and their derivatives.
+    void bernstein(float t, out vec4 basis, out vec4 derivative) {
+        float s = 1.0 - t;
+        basis = vec4(s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t);
+        derivative = vec4(
+            -3.0 * s * s,
+            3.0 * s * s - 6.0 * t * s,
+            6.0 * t * s - 3.0 * t * t,
+            3.0 * t * t
+        );
+    }
+
+    // The point at uv, the derivatives along u and v there, and their cross
+    // product.
+    vec3 evaluate(vec2 uv, out vec3 along_u, out vec3 along_v, out vec3 normal) {
+        vec4 u_basis, u_derivative, v_basis, v_derivative;
+        bernstein(uv.x, u_basis, u_derivative);
+        bernstein(uv.y, v_basis, v_derivative);
+        vec3 point = vec3(0.0);
+        along_u = vec3(0.0);
+        along_v = vec3(0.0);
+        for (int row = 0; row < 4; row++) {
+            for (int column = 0; column < 4; column++) {
+                vec3 control = te_position[row * 4 + column];
+                point += control * u_basis[column] * v_basis[row];
+                along_u += control * u_derivative[column] * v_basis[row];
+                along_v += control * u_basis[column] * v_derivative[row];
+            }
+        }
+        normal = cross(along_u, along_v);
+        return point;
+    }
+
+    // The finest edge's level on a log scale.
+    vec3 level_color() {
+        float level = max(
+            max(gl_TessLevelOuter[0], gl_TessLevelOuter[1]),
+            max(gl_TessLevelOuter[2], gl_TessLevelOuter[3])
+        );
+        float t = log2(level) / 6.0;
+        return clamp(vec3(2.0 * t - 0.5, 1.0 - abs(2.0 * t - 1.0), 1.5 - 2.0 * t), 0.0, 1.0);
+    }
+
+    void main() {
+        vec2 uv = gl_TessCoord.xy;
+        vec3 along_u, along_v, normal;
+        vec3 point = evaluate(uv, along_u, along_v, normal);
+        // Where the lid and the bottom pinch to a point the derivatives
+        // vanish, so they're taken from a little way in.
+        if (length(normal) < 1e-5) {
+            evaluate(uv + sign(0.5 - uv) * 1e-3, along_u, along_v, normal);
+        }
+        vec4 world_position = te_model * vec4(point, 1.0);
+        vec4 view_position = view * world_position;
+        v_normal = mat3(view) * (te_normal_matrix * normal);
+        // The texture coordinates are uv, so the derivatives are the
+        // tangent and bitangent.
+        v_tangent = mat3(view * te_model) * along_u;
+        v_bitangent = mat3(view * te_model) * along_v;
+        v_position = view_position.xyz;
+        gl_Position = perspective * view_position;
+        v_tex_coords = uv;
+        v_color = u_color_by_level ? level_color() : vec3(1.0);
+        v_tint = te_tint;
+        v_light_space_position = u_light_space * world_position;
+        v_world_y = world_position.y;
+        v_occlusion = 1.0;
+    }
+"#;
+
+/// A Bézier patch's control point, sixteen of them to a patch.
+#[derive(Clone, Copy)]
+pub struct ControlPoint {
+    position: [f32; 3],
+}
+
+implement_vertex!(ControlPoint, position);
+
+/// Whether the context has tessellation shaders, which desktop GL does
+/// from 4.0 on.
+pub fn supported(facade: &impl Facade) -> bool {
+    let context = facade.get_context();
+    context.get_opengl_version().0 == Api::Gl
+        && context.get_supported_glsl_version() >= REQUIRED_GLSL
+        && glium::program::is_tessellation_shader_supported(&**context)
+}
+
+/// The main fragment shader over tessellated patches, with the one-pass
+/// wireframe overlay if `edges`, or `None` where it can't be built.
+pub fn program(facade: &impl Facade, fragment: &str, edges: bool) -> Option<Program> {
+    if !supported(facade) || (edges && !edges::supported(facade)) {
+        return None;
+    }
+    let (evaluation, fragment) = match edges {
+        true => (
+            shaders::with_define(EVALUATION_SHADER_SRC, edges::DEFINE),
+            shaders::with_define(fragment, edges::DEFINE),
+        ),
+        false => (EVALUATION_SHADER_SRC.to_string(), fragment.to_string()),
+    };
+    let extra = ExtraStages {
+        tessellation_control: Some(CONTROL_SHADER_SRC),
+        tessellation_evaluation: Some(&evaluation),
+        geometry: edges.then_some(edges::GEOMETRY_SHADER_SRC),
+    };
+    match shaders::program_with_stages(facade, VERTEX_SHADER_SRC, extra, &fragment, false) {
+        Ok(program) => Some(program),
+        Err(error) => {
+            eprintln!(
+                "warning: the tessellation shaders didn't build, drawing patches as triangles: {}",
+                error
+            );
+            None
+        }
+    }
+}
+
+/// A buffer of `patches`' control points, or `None` if there are none.
+pub fn control_points(
+    facade: &impl Facade,
+    patches: &[[[f32; 3]; 16]],
+) -> Option<glium::VertexBuffer<ControlPoint>> {
+    if patches.is_empty() {
+        return None;
+    }
+    let points = patches
+        .iter()
+        .flatten()
+        .map(|&position| ControlPoint { position })
+        .collect::<Vec<ControlPoint>>();
+    Some(glium::VertexBuffer::new(facade, &points).unwrap())
+}
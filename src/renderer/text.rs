@@ -0,0 +1,192 @@
+use glium::backend::Facade;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+use glium::Surface;
+
+use crate::font_loader::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+/// Pixels from the top of one line of text to the top of the next.
+pub const LINE_HEIGHT: u32 = GLYPH_HEIGHT + 2;
+/// sRGB color of the shadow each character is drawn over, one pixel down
+/// and to the right, so text reads on light and dark backgrounds alike.
+const SHADOW_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.8];
+
+/// Positions are in pixels from the frame's top left corner.
+const VERTEX_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec2 position;
+    in vec2 tex_coords;
+    in vec4 color;
+    out vec2 v_tex_coords;
+    out vec4 v_color;
+
+    uniform vec2 u_screen;
+
+    void main() {
+        v_tex_coords = tex_coords;
+        v_color = color;
+        gl_Position = vec4(
+            position.x / u_screen.x * 2.0 - 1.0,
+            1.0 - position.y / u_screen.y * 2.0,
+            0.0,
+            1.0
+        );
+    }
+"#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+    #version 150
+
+    in vec2 v_tex_coords;
+    in vec4 v_color;
+    out vec4 color;
+
+    uniform sampler2D u_font;
+
+    void main() {
+        color = v_color * vec4(1.0, 1.0, 1.0, texture(u_font, v_tex_coords).a);
+    }
+"#;
+
+#[derive(Copy, Clone)]
+struct TextVertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+    color: [f32; 4],
+}
+
+implement_vertex!(TextVertex, position, tex_coords, color);
+
+/// Text drawn over the frame in the built-in bitmap font, one pixel to each
+/// of the font's, so it stays sharp at any window size.
+pub struct TextOverlay {
+    program: glium::Program,
+    font: glium::texture::Texture2d,
+    /// Two triangles for every character and rectangle queued since the
+    /// last draw.
+    quads: Vec<TextVertex>,
+    /// Reused from frame to frame, and replaced by a bigger one when the
+    /// text outgrows it.
+    buffer: glium::VertexBuffer<TextVertex>,
+}
+
+impl TextOverlay {
+    pub fn new(facade: &impl Facade) -> Result<Self, glium::ProgramCreationError> {
+        // Colors are given sRGB-encoded, so they're written as they are.
+        Ok(TextOverlay {
+            program: super::shaders::program(facade, VERTEX_SHADER_SRC, FRAGMENT_SHADER_SRC, true)?,
+            font: glium::texture::Texture2d::new(facade, font_loader::load_font()).unwrap(),
+            quads: Vec::new(),
+            buffer: glium::VertexBuffer::empty_dynamic(facade, 0).unwrap(),
+        })
+    }
+
+    /// Queues `text` to be drawn in the sRGB `color` with its top left corner
+    /// `x` and `y` pixels from the frame's, each newline starting a line
+    /// below. Characters the font lacks are drawn as `?`.
+    pub fn print(&mut self, x: u32, y: u32, text: &str, color: [f32; 3]) {
+        let [red, green, blue] = color;
+        for (line_index, line) in text.lines().enumerate() {
+            let top = y + line_index as u32 * LINE_HEIGHT;
+            for (column, character) in line.chars().enumerate() {
+                let left = x + column as u32 * GLYPH_WIDTH;
+                let cell = font_loader::glyph_cell(character);
+                self.push_glyph(left + 1, top + 1, cell, SHADOW_COLOR);
+                self.push_glyph(left, top, cell, [red, green, blue, 1.0]);
+            }
+        }
+    }
+
+    /// Queues a rectangle `width` by `height` pixels filled with the sRGB
+    /// `color`, its alpha blending it over what's below.
+    pub fn fill(&mut self, x: u32, y: u32, width: u32, height: u32, color: [f32; 4]) {
+        // Every texel of the solid cell is opaque, so its middle will do.
+        let (column, row) = font_loader::SOLID_CELL;
+        let middle = [
+            column * GLYPH_WIDTH + GLYPH_WIDTH / 2,
+            row * GLYPH_HEIGHT + GLYPH_HEIGHT / 2,
+        ];
+        let tex_coords = self.tex_coords(middle, middle);
+        self.push_quad([x, y], [x + width, y + height], tex_coords, color);
+    }
+
+    fn push_glyph(&mut self, left: u32, top: u32, (column, row): (u32, u32), color: [f32; 4]) {
+        let tex_coords = self.tex_coords(
+            [column * GLYPH_WIDTH, row * GLYPH_HEIGHT],
+            [(column + 1) * GLYPH_WIDTH, (row + 1) * GLYPH_HEIGHT],
+        );
+        let bottom_right = [left + GLYPH_WIDTH, top + GLYPH_HEIGHT];
+        self.push_quad([left, top], bottom_right, tex_coords, color);
+    }
+
+    /// Texture coordinates of the top left and bottom right of a rectangle
+    /// of the atlas given in pixels from its top left.
+    fn tex_coords(&self, top_left: [u32; 2], bottom_right: [u32; 2]) -> [[f32; 2]; 2] {
+        let (width, height) = self.font.dimensions();
+        // The atlas was uploaded bottom row first.
+        let convert = |[x, y]: [u32; 2]| [x as f32 / width as f32, 1.0 - y as f32 / height as f32];
+        [convert(top_left), convert(bottom_right)]
+    }
+
+    fn push_quad(
+        &mut self,
+        [left, top]: [u32; 2],
+        [right, bottom]: [u32; 2],
+        [[u_left, v_top], [u_right, v_bottom]]: [[f32; 2]; 2],
+        color: [f32; 4],
+    ) {
+        let [left, top, right, bottom] = [left, top, right, bottom].map(|pixel| pixel as f32);
+        let corner = |position: [f32; 2], tex_coords: [f32; 2]| TextVertex {
+            position,
+            tex_coords,
+            color,
+        };
+        let top_left = corner([left, top], [u_left, v_top]);
+        let top_right = corner([right, top], [u_right, v_top]);
+        let bottom_left = corner([left, bottom], [u_left, v_bottom]);
+        let bottom_right = corner([right, bottom], [u_right, v_bottom]);
+        self.quads.extend_from_slice(&[
+            top_left,
+            bottom_left,
+            bottom_right,
+            top_left,
+            bottom_right,
+            top_right,
+        ]);
+    }
+
+    /// Blends everything printed since the last call over `frame`, then
+    /// forgets it.
+    pub fn draw(&mut self, facade: &impl Facade, frame: &mut impl Surface) {
+        if self.quads.is_empty() {
+            return;
+        }
+        if self.buffer.len() < self.quads.len() {
+            let capacity = self.quads.len().next_power_of_two();
+            self.buffer = glium::VertexBuffer::empty_dynamic(facade, capacity).unwrap();
+        }
+        let vertices = self.buffer.slice(0..self.quads.len()).unwrap();
+        vertices.write(&self.quads);
+        let (width, height) = frame.get_dimensions();
+        let uniforms = uniform! {
+            u_screen: [width as f32, height as f32],
+            u_font: self.font.sampled()
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .minify_filter(MinifySamplerFilter::Nearest),
+        };
+        let params = glium::DrawParameters {
+            blend: glium::Blend::alpha_blending(),
+            ..Default::default()
+        };
+        frame
+            .draw(
+                vertices,
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.program,
+                &uniforms,
+                &params,
+            )
+            .unwrap();
+        self.quads.clear();
+    }
+}
@@ -0,0 +1,165 @@
+use std::fmt;
+
+use crate::math::{Mat4, Vec3};
+
+/// Placement of an object: moved so `origin` sits at zero, scaled, rotated
+/// about Z, X and Y in that order, then translated.
+#[derive(Debug, Clone, Copy)]
+pub struct Transform {
+    /// Point in the mesh that ends up at `translation` and that the object
+    /// rotates and scales around.
+    pub origin: Vec3,
+    pub translation: Vec3,
+    /// Euler angles in radians.
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::translation(self.translation)
+            * Mat4::rotation_y(self.rotation.y)
+            * Mat4::rotation_x(self.rotation.x)
+            * Mat4::rotation_z(self.rotation.z)
+            * Mat4::scale(self.scale)
+            * Mat4::translation(-self.origin)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            origin: Vec3::default(),
+            translation: Vec3::default(),
+            rotation: Vec3::default(),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+/// One placed copy of a mesh uploaded to the renderer. Objects with the same
+/// `mesh_id` share GPU buffers.
+#[derive(Debug, Clone, Copy)]
+pub struct Object {
+    pub mesh_id: usize,
+    pub transform: Transform,
+    /// Multiplies the mesh's own colors.
+    pub tint: [f32; 3],
+    /// Multiplies the materials' opacity, so the whole object fades.
+    pub opacity: f32,
+}
+
+/// Keeps a directional light from passing straight over the model, where
+/// its azimuth would flip.
+const MAX_LIGHT_ELEVATION: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+#[derive(Debug, Clone, Copy)]
+pub enum LightKind {
+    /// `direction` points towards the light, in world space.
+    Directional { direction: Vec3 },
+    /// Falls off as `1 / (1 + attenuation * distance²)`.
+    Point { position: Vec3, attenuation: f32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: [f32; 3],
+    /// Multiplies `color`; ambient light is left alone.
+    pub intensity: f32,
+}
+
+impl Light {
+    pub fn point(position: Vec3, attenuation: f32) -> Self {
+        Light {
+            kind: LightKind::Point {
+                position,
+                attenuation,
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Swings a directional light around the Y axis by `azimuth` radians and
+    /// up or down by `elevation`. Point lights stay where they are.
+    pub fn rotate(&mut self, azimuth: f32, elevation: f32) {
+        if let LightKind::Directional { direction } = &mut self.kind {
+            let [x, y, z] = direction.normalize().to_array();
+            let azimuth = x.atan2(z) + azimuth;
+            let elevation = (y.asin() + elevation).clamp(-MAX_LIGHT_ELEVATION, MAX_LIGHT_ELEVATION);
+            let (sin_azimuth, cos_azimuth) = azimuth.sin_cos();
+            let (sin_elevation, cos_elevation) = elevation.sin_cos();
+            *direction = Vec3::new(
+                cos_elevation * sin_azimuth,
+                sin_elevation,
+                cos_elevation * cos_azimuth,
+            );
+        }
+    }
+}
+
+impl Default for Light {
+    /// The directional light the viewer has always had.
+    fn default() -> Self {
+        Light {
+            // Where the old view-space light was, seen from the framing view.
+            kind: LightKind::Directional {
+                direction: Vec3::new(-1.0, 0.7, -0.6),
+            },
+            color: [1.0, 1.0, 1.0],
+            intensity: 1.0,
+        }
+    }
+}
+
+impl fmt::Display for Light {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            LightKind::Directional { direction: d } => {
+                write!(f, "directional light [{:.3}, {:.3}, {:.3}]", d.x, d.y, d.z)?;
+            }
+            LightKind::Point {
+                position: p,
+                attenuation,
+            } => write!(
+                f,
+                "point light at [{:.3}, {:.3}, {:.3}], attenuation {:.4}",
+                p.x, p.y, p.z, attenuation
+            )?,
+        }
+        let [r, g, b] = self.color;
+        write!(
+            f,
+            ", color [{:.2}, {:.2}, {:.2}], intensity {:.2}",
+            r, g, b, self.intensity
+        )
+    }
+}
+
+/// Everything the renderer draws in a frame.
+#[derive(Default)]
+pub struct Scene {
+    pub objects: Vec<Object>,
+    /// The renderer uses at most `renderer::MAX_LIGHTS` of these.
+    pub lights: Vec<Light>,
+}
+
+impl Scene {
+    pub fn add(&mut self, mesh_id: usize, transform: Transform) {
+        self.add_tinted(mesh_id, transform, [1.0, 1.0, 1.0]);
+    }
+
+    pub fn add_tinted(&mut self, mesh_id: usize, transform: Transform, tint: [f32; 3]) {
+        self.objects.push(Object {
+            mesh_id,
+            transform,
+            tint,
+            opacity: 1.0,
+        });
+    }
+
+    /// Removes the objects, keeping the lights.
+    pub fn clear(&mut self) {
+        self.objects.clear();
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use glium::backend::Facade;
+use glium::texture::RawImage2d;
+
+/// Names a screenshot after the current UTC time, e.g.
+/// `screenshot-20240131-235959.png`, in the working directory.
+pub fn timestamped_path() -> PathBuf {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let time = seconds % 86_400;
+    PathBuf::from(format!(
+        "screenshot-{:04}{:02}{:02}-{:02}{:02}{:02}.png",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    ))
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date, using
+/// Howard Hinnant's algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// Renders one frame into an offscreen `width` by `height` target and reads
+/// it back, without touching the window.
+pub fn render_offscreen<F>(
+    facade: &impl Facade,
+    (width, height): (u32, u32),
+    draw: F,
+) -> RawImage2d<'static, u8>
+where
+    F: FnOnce(&mut glium::framebuffer::SimpleFrameBuffer),
+{
+    let color = glium::texture::Texture2d::empty_with_format(
+        facade,
+        glium::texture::UncompressedFloatFormat::U8U8U8U8,
+        glium::texture::MipmapsOption::NoMipmap,
+        width,
+        height,
+    )
+    .unwrap();
+    // With a stencil, the only depth buffer glium makes on OpenGL ES.
+    let depth = glium::framebuffer::DepthStencilRenderBuffer::new(
+        facade,
+        glium::texture::DepthStencilFormat::I24I8,
+        width,
+        height,
+    )
+    .unwrap();
+    let mut target =
+        glium::framebuffer::SimpleFrameBuffer::with_depth_stencil_buffer(facade, &color, &depth)
+            .unwrap();
+    draw(&mut target);
+    color.read()
+}
+
+/// Writes an image read back from OpenGL to a PNG on a background thread,
+/// so a frame doesn't stall on the encoding. Prints where it went.
+pub fn save(image: RawImage2d<'static, u8>, path: PathBuf) -> JoinHandle<()> {
+    thread::spawn(move || match write_png(&image, &path) {
+        Ok(()) => println!("saved {}", path.display()),
+        Err(error) => eprintln!("failed to save {}: {}", path.display(), error),
+    })
+}
+
+/// Encodes an image read back from OpenGL as a PNG at `path`.
+pub fn write_png(image: &RawImage2d<u8>, path: &Path) -> image::ImageResult<()> {
+    let (width, height) = (image.width as usize, image.height as usize);
+    // OpenGL rows go bottom to top, and the alpha channel is whatever
+    // blending left behind, so drop it.
+    let mut pixels: Vec<u8> = Vec::with_capacity(width * height * 3);
+    for row in image.data.chunks_exact(width * 4).rev() {
+        for pixel in row.chunks_exact(4) {
+            pixels.extend_from_slice(&pixel[..3]);
+        }
+    }
+    image::save_buffer(
+        path,
+        &pixels,
+        width as u32,
+        height as u32,
+        image::ColorType::Rgb8,
+    )
+}
@@ -0,0 +1,157 @@
+use std::path::Path;
+
+use image::ImageFormat;
+
+use crate::math::Vec3;
+use crate::model_loader::{
+    Aabb, LoadProgress, Material, Model, ModelLoadError, Scene, SubMesh, Vertex3D, MAX_VERTICES,
+};
+
+/// How far, in world units, the terrain stretches along the image's longer
+/// side. Pixels are square, so the shorter side comes out in proportion.
+const TERRAIN_SIZE: f32 = 10.0;
+
+/// Whether `path` is an image `--terrain` can read, going by its extension.
+pub fn is_heightmap(path: &str) -> bool {
+    ImageFormat::from_path(path).is_ok()
+}
+
+/// Builds a terrain mesh from a greyscale heightmap, one vertex per pixel,
+/// with black at height 0 and white at `height_scale`. The texture covers it
+/// once, and the image's top row ends up at -Z, away from the default camera.
+pub fn load(
+    path: &str,
+    height_scale: f32,
+    progress: &LoadProgress,
+) -> Result<Scene, ModelLoadError> {
+    let format = ImageFormat::from_path(path)
+        .map_err(|error| ModelLoadError::InvalidData(format!("{}", error)))?;
+    let bytes = progress.read_file(Path::new(path))?;
+    let image = image::load_from_memory_with_format(&bytes, format)
+        .map_err(|error| ModelLoadError::InvalidData(format!("{}", error)))?;
+    drop(bytes);
+    // Sixteen bits a pixel, so 16-bit heightmaps keep their precision.
+    let heights = image.to_luma16();
+    drop(image);
+    let (width, depth) = (heights.width() as usize, heights.height() as usize);
+    if width < 2 || depth < 2 {
+        return Err(ModelLoadError::InvalidData(format!(
+            "heightmap is {}x{}, it needs at least 2x2 pixels",
+            width, depth
+        )));
+    }
+    let count = width * depth;
+    if count > MAX_VERTICES {
+        return Err(ModelLoadError::TooManyVertices { count });
+    }
+
+    let spacing = TERRAIN_SIZE / (width.max(depth) - 1) as f32;
+    let height = |column: usize, row: usize| {
+        heights.get_pixel(column as u32, row as u32)[0] as f32 / u16::MAX as f32 * height_scale
+    };
+    let mut vertices = Vec::with_capacity(count);
+    for row in 0..depth {
+        for column in 0..width {
+            // Central differences, one-sided at the edges.
+            let (left, right) = (column.saturating_sub(1), (column + 1).min(width - 1));
+            let (back, front) = (row.saturating_sub(1), (row + 1).min(depth - 1));
+            let slope_x =
+                (height(right, row) - height(left, row)) / ((right - left) as f32 * spacing);
+            let slope_z =
+                (height(column, front) - height(column, back)) / ((front - back) as f32 * spacing);
+            vertices.push(Vertex3D {
+                position: [
+                    (column as f32 - (width - 1) as f32 / 2.0) * spacing,
+                    height(column, row),
+                    (row as f32 - (depth - 1) as f32 / 2.0) * spacing,
+                ],
+                normal: Vec3::new(-slope_x, 1.0, -slope_z).normalize().into(),
+                tex_coords: [
+                    column as f32 / (width - 1) as f32,
+                    1.0 - row as f32 / (depth - 1) as f32,
+                ],
+            });
+        }
+    }
+    drop(heights);
+
+    let mut indexes = Vec::with_capacity((width - 1) * (depth - 1) * 6);
+    let index = |column: usize, row: usize| (row * width + column) as u32;
+    for row in 0..depth - 1 {
+        for column in 0..width - 1 {
+            indexes.extend_from_slice(&[
+                index(column, row),
+                index(column, row + 1),
+                index(column + 1, row),
+                index(column + 1, row),
+                index(column, row + 1),
+                index(column + 1, row + 1),
+            ]);
+        }
+    }
+
+    let model = Model {
+        bounds: Aabb::from_vertices(&vertices),
+        sub_meshes: vec![SubMesh {
+            material: 0,
+            range: 0..indexes.len(),
+        }],
+        vertices,
+        colors: Vec::new(),
+        indexes,
+        lines: Vec::new(),
+        materials: vec![Material {
+            name: "terrain".to_string(),
+            ambient: [0.1, 0.12, 0.08],
+            diffuse: [0.45, 0.5, 0.35],
+            specular: [0.1, 0.1, 0.1],
+            shininess: 4.0,
+            ..Default::default()
+        }],
+        has_normals: true,
+        has_tex_coords: true,
+        patches: Vec::new(),
+        lods: Vec::new(),
+        occlusion: Vec::new(),
+    };
+    Ok(Scene::single("terrain", model))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HILLS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/heightmaps/hills.png");
+
+    #[test]
+    fn hills_span_the_height_scale() {
+        let heights = image::open(HILLS).unwrap().to_luma16();
+        let darkest = heights.pixels().map(|pixel| pixel[0]).min().unwrap();
+        let brightest = heights.pixels().map(|pixel| pixel[0]).max().unwrap();
+        let (width, depth) = (heights.width() as usize, heights.height() as usize);
+        assert!(darkest < brightest);
+
+        for scale in [0.5, 1.0, 3.0] {
+            let scene = load(HILLS, scale, &LoadProgress::default()).unwrap();
+            let model = &scene.meshes[0].model;
+            assert_eq!(model.vertices.len(), width * depth);
+            assert_eq!(model.indexes.len(), (width - 1) * (depth - 1) * 6);
+            let level = |value: u16| value as f32 / u16::MAX as f32 * scale;
+            assert!((model.bounds.min[1] - level(darkest)).abs() < 1e-6);
+            assert!((model.bounds.max[1] - level(brightest)).abs() < 1e-6);
+            // Across, the terrain is the same size whatever its height.
+            let across = model.bounds.max[0] - model.bounds.min[0];
+            assert!((across - TERRAIN_SIZE).abs() < 1e-4, "{}", across);
+        }
+    }
+
+    #[test]
+    fn one_pixel_wide_heightmap_is_invalid() {
+        let path =
+            std::env::temp_dir().join(format!("opengl_rust-{}-line.png", std::process::id()));
+        image::GrayImage::new(1, 8).save(&path).unwrap();
+        let result = load(path.to_str().unwrap(), 1.0, &LoadProgress::default());
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(ModelLoadError::InvalidData(_))));
+    }
+}
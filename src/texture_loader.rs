@@ -0,0 +1,231 @@
+use std::path::{Path, PathBuf};
+
+use glium::backend::Facade;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::{CubeLayer, RawImage2d, SrgbCubemap, SrgbTexture2d, Texture2d};
+use glium::Surface;
+use image::RgbaImage;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod es_cubemap;
+
+/// The faces of a cubemap in the order of `CUBE_LAYERS`, by each of the
+/// names they are commonly saved under.
+const CUBE_FACE_NAMES: [[&str; 3]; 6] = [
+    ["px", "posx", "right"],
+    ["nx", "negx", "left"],
+    ["py", "posy", "top"],
+    ["ny", "negy", "bottom"],
+    ["pz", "posz", "front"],
+    ["nz", "negz", "back"],
+];
+const CUBE_LAYERS: [CubeLayer; 6] = [
+    CubeLayer::PositiveX,
+    CubeLayer::NegativeX,
+    CubeLayer::PositiveY,
+    CubeLayer::NegativeY,
+    CubeLayer::PositiveZ,
+    CubeLayer::NegativeZ,
+];
+const CUBE_FACE_EXTENSIONS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+/// Width of each face of `gradient_cubemap`.
+const GRADIENT_SIZE: u32 = 64;
+/// `gradient_cubemap`'s sky from straight up to the horizon, then the
+/// ground below it, as sRGB.
+const ZENITH: [f32; 3] = [0.25, 0.45, 0.75];
+const HORIZON: [f32; 3] = [0.75, 0.8, 0.85];
+const GROUND: [f32; 3] = [0.3, 0.28, 0.26];
+
+/// Loads an image file as an sRGB texture. Missing or unreadable files print
+/// a warning and fall back to plain white, as does `None`, so the shader can
+/// always sample a texture.
+pub fn load_texture(facade: &impl Facade, path: Option<&Path>) -> SrgbTexture2d {
+    match path.and_then(read_image) {
+        Some(raw) => SrgbTexture2d::new(facade, raw).unwrap(),
+        None => white_texture(facade),
+    }
+}
+
+/// Loads a texture of something other than colors, such as normals, as a
+/// linear texture. Falls back as `load_texture` does, but to a texel of
+/// `fallback`.
+pub fn load_linear_texture(
+    facade: &impl Facade,
+    path: Option<&Path>,
+    fallback: [u8; 4],
+) -> Texture2d {
+    let raw = path
+        .and_then(read_image)
+        .unwrap_or_else(|| RawImage2d::from_raw_rgba(fallback.to_vec(), (1, 1)));
+    Texture2d::new(facade, raw).unwrap()
+}
+
+/// Loads a tangent-space normal map, or a flat one that leaves the normals
+/// as they are.
+pub fn load_normal_map(facade: &impl Facade, path: Option<&Path>) -> Texture2d {
+    load_linear_texture(facade, path, [128, 128, 255, 255])
+}
+
+/// The image at `path` the way up OpenGL reads it, or `None` with a
+/// warning if it can't be read.
+fn read_image(path: &Path) -> Option<RawImage2d<'static, u8>> {
+    match image::open(path) {
+        Ok(image) => {
+            let image = image.to_rgba8();
+            let dimensions = image.dimensions();
+            // OBJ texture coordinates start at the bottom left, images at
+            // the top left.
+            Some(RawImage2d::from_raw_rgba_reversed(
+                &image.into_raw(),
+                dimensions,
+            ))
+        }
+        Err(error) => {
+            eprintln!(
+                "warning: could not load texture {}: {}",
+                path.display(),
+                error
+            );
+            None
+        }
+    }
+}
+
+pub fn white_texture(facade: &impl Facade) -> SrgbTexture2d {
+    let raw = RawImage2d::from_raw_rgba(vec![255u8, 255, 255, 255], (1, 1));
+    SrgbTexture2d::new(facade, raw).unwrap()
+}
+
+/// Loads a cubemap from six face images in `path`, named `px`, `nx`, `py`,
+/// ... or `right`, `left`, `top`, ..., or from a single image file laid out
+/// as a horizontal cross, each face the right way up for OpenGL.
+pub fn load_cubemap(facade: &impl Facade, path: &Path) -> Result<SrgbCubemap, String> {
+    let open = |path: &Path| {
+        image::open(path)
+            .map(|image| image.to_rgba8())
+            .map_err(|error| format!("{}: {}", path.display(), error))
+    };
+    let faces = match path.is_file() {
+        true => split_cross(&open(path)?)
+            .ok_or_else(|| format!("{}: expected a 4:3 cross of faces", path.display()))?,
+        false => CUBE_FACE_NAMES
+            .iter()
+            .map(|names| {
+                let face = find_face(path, names).ok_or_else(|| {
+                    format!("{}: no {} face image", path.display(), names.join("/"))
+                })?;
+                open(&face)
+            })
+            .collect::<Result<Vec<RgbaImage>, String>>()?,
+    };
+    let size = faces[0].width();
+    if faces
+        .iter()
+        .any(|face| face.width() != size || face.height() != size)
+    {
+        return Err(format!(
+            "{}: faces must be square and all the same size",
+            path.display()
+        ));
+    }
+    let faces = faces
+        .into_iter()
+        .map(|face| face.into_raw())
+        .collect::<Vec<Vec<u8>>>();
+    Ok(cubemap_from_faces(facade, size, faces))
+}
+
+/// A sky fading from blue overhead to pale at the horizon, over a plain
+/// ground, for when no skybox images are given.
+pub fn gradient_cubemap(facade: &impl Facade) -> SrgbCubemap {
+    let size = GRADIENT_SIZE;
+    let faces = CUBE_LAYERS
+        .iter()
+        .map(|&layer| {
+            let mut pixels = Vec::with_capacity((size * size * 4) as usize);
+            for t in 0..size {
+                for s in 0..size {
+                    // Face coordinates from -1 to 1, as OpenGL samples them.
+                    let sc = (s as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    let tc = (t as f32 + 0.5) / size as f32 * 2.0 - 1.0;
+                    let up = match layer {
+                        CubeLayer::PositiveY => 1.0,
+                        CubeLayer::NegativeY => -1.0,
+                        _ => -tc,
+                    };
+                    let height = up / (1.0 + sc * sc + tc * tc).sqrt();
+                    let color = match height >= 0.0 {
+                        true => mix(HORIZON, ZENITH, height.sqrt()),
+                        false => GROUND,
+                    };
+                    pixels.extend(color.map(|channel| (channel * 255.0).round() as u8));
+                    pixels.push(255);
+                }
+            }
+            pixels
+        })
+        .collect::<Vec<Vec<u8>>>();
+    cubemap_from_faces(facade, size, faces)
+}
+
+/// Cuts out the faces of a cross with +Y above and -Y below the row
+/// -X, +Z, +X, -Z, in the order of `CUBE_LAYERS`.
+fn split_cross(cross: &RgbaImage) -> Option<Vec<RgbaImage>> {
+    let size = cross.width() / 4;
+    if size == 0 || cross.width() != size * 4 || cross.height() != size * 3 {
+        return None;
+    }
+    let cells = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+    let faces = cells
+        .iter()
+        .map(|&(column, row)| {
+            image::imageops::crop_imm(cross, column * size, row * size, size, size).to_image()
+        })
+        .collect();
+    Some(faces)
+}
+
+fn find_face(directory: &Path, names: &[&str]) -> Option<PathBuf> {
+    names
+        .iter()
+        .flat_map(|name| {
+            CUBE_FACE_EXTENSIONS
+                .iter()
+                .map(move |extension| directory.join(format!("{}.{}", name, extension)))
+        })
+        .find(|path| path.is_file())
+}
+
+/// Copies each face's RGBA rows, top row first, into a cubemap by drawing
+/// it into the matching layer, or on OpenGL ES, where glium can do neither,
+/// by uploading them itself.
+fn cubemap_from_faces(facade: &impl Facade, size: u32, faces: Vec<Vec<u8>>) -> SrgbCubemap {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if facade.get_context().get_opengl_version().0 == glium::Api::GlEs {
+        if let Some(cubemap) = es_cubemap::from_faces(facade, size, &CUBE_LAYERS, &faces) {
+            return cubemap;
+        }
+    }
+    let cubemap = SrgbCubemap::empty(facade, size).unwrap();
+    for (layer, pixels) in CUBE_LAYERS.iter().zip(faces) {
+        let face =
+            SrgbTexture2d::new(facade, RawImage2d::from_raw_rgba(pixels, (size, size))).unwrap();
+        let source = SimpleFrameBuffer::new(facade, &face).unwrap();
+        let target = SimpleFrameBuffer::new(facade, cubemap.main_level().image(*layer)).unwrap();
+        source.blit_whole_color_to(
+            &target,
+            &glium::BlitTarget {
+                left: 0,
+                bottom: 0,
+                width: size as i32,
+                height: size as i32,
+            },
+            glium::uniforms::MagnifySamplerFilter::Nearest,
+        );
+    }
+    cubemap
+}
+
+fn mix(from: [f32; 3], to: [f32; 3], amount: f32) -> [f32; 3] {
+    [0, 1, 2].map(|i| from[i] + (to[i] - from[i]) * amount)
+}
@@ -0,0 +1,124 @@
+use std::ffi::c_void;
+
+use glium::backend::Facade;
+use glium::texture::{CubeLayer, Dimensions, MipmapsOption, SrgbCubemap, SrgbFormat};
+use khronos_egl as egl;
+
+const TEXTURE_CUBE_MAP: u32 = 0x8513;
+const TEXTURE_BINDING_CUBE_MAP: u32 = 0x8514;
+const TEXTURE_CUBE_MAP_POSITIVE_X: u32 = 0x8515;
+const TEXTURE_MIN_FILTER: u32 = 0x2801;
+const TEXTURE_MAX_LEVEL: u32 = 0x813D;
+const LINEAR: i32 = 0x2601;
+const PIXEL_UNPACK_BUFFER: u32 = 0x88EC;
+const PIXEL_UNPACK_BUFFER_BINDING: u32 = 0x88EF;
+const SRGB8_ALPHA8: i32 = 0x8C43;
+const RGBA: u32 = 0x1908;
+const UNSIGNED_BYTE: u32 = 0x1401;
+
+type Function = extern "system" fn();
+type GenTextures = unsafe extern "system" fn(i32, *mut u32);
+type BindTexture = unsafe extern "system" fn(u32, u32);
+type BindBuffer = unsafe extern "system" fn(u32, u32);
+type GetIntegerv = unsafe extern "system" fn(u32, *mut i32);
+type TexParameteri = unsafe extern "system" fn(u32, u32, i32);
+type TexImage2D = unsafe extern "system" fn(u32, i32, i32, i32, i32, i32, u32, u32, *const c_void);
+
+/// The few GL functions it takes to fill a cubemap, looked up through EGL,
+/// which every OpenGL ES context here comes from.
+struct Gl {
+    gen_textures: GenTextures,
+    bind_texture: BindTexture,
+    bind_buffer: BindBuffer,
+    get_integerv: GetIntegerv,
+    tex_parameteri: TexParameteri,
+    tex_image_2d: TexImage2D,
+}
+
+impl Gl {
+    fn load(egl: &egl::DynamicInstance<egl::EGL1_4>) -> Option<Self> {
+        use std::mem::transmute;
+        let function = |name: &str| egl.get_proc_address(name);
+        // Safety: each is the function of that name in the OpenGL ES 3.0
+        // headers, with the signature given there.
+        unsafe {
+            Some(Gl {
+                gen_textures: transmute::<Function, GenTextures>(function("glGenTextures")?),
+                bind_texture: transmute::<Function, BindTexture>(function("glBindTexture")?),
+                bind_buffer: transmute::<Function, BindBuffer>(function("glBindBuffer")?),
+                get_integerv: transmute::<Function, GetIntegerv>(function("glGetIntegerv")?),
+                tex_parameteri: transmute::<Function, TexParameteri>(function("glTexParameteri")?),
+                tex_image_2d: transmute::<Function, TexImage2D>(function("glTexImage2D")?),
+            })
+        }
+    }
+}
+
+/// A cubemap of RGBA `faces`, each `size` texels square and in the order of
+/// `layers`, for OpenGL ES, where glium can't make one: without texture
+/// storage it allocates all six faces with one call ES rejects, and the
+/// blits that would copy them in bind framebuffers with functions ES lacks.
+/// So the faces are uploaded here, leaving the bindings as glium last set
+/// them. `None` if EGL doesn't have the functions.
+pub fn from_faces(
+    facade: &impl Facade,
+    size: u32,
+    layers: &[CubeLayer],
+    faces: &[Vec<u8>],
+) -> Option<SrgbCubemap> {
+    let egl = unsafe { egl::DynamicInstance::<egl::EGL1_4>::load_required() }.ok()?;
+    let gl = Gl::load(&egl)?;
+    let context = facade.get_context();
+    let id = unsafe {
+        context.exec_in_context(|| {
+            let (mut texture, mut unpack_buffer) = (0, 0);
+            (gl.get_integerv)(TEXTURE_BINDING_CUBE_MAP, &mut texture);
+            (gl.get_integerv)(PIXEL_UNPACK_BUFFER_BINDING, &mut unpack_buffer);
+            let mut id = 0;
+            (gl.gen_textures)(1, &mut id);
+            (gl.bind_texture)(TEXTURE_CUBE_MAP, id);
+            (gl.bind_buffer)(PIXEL_UNPACK_BUFFER, 0);
+            (gl.tex_parameteri)(TEXTURE_CUBE_MAP, TEXTURE_MIN_FILTER, LINEAR);
+            (gl.tex_parameteri)(TEXTURE_CUBE_MAP, TEXTURE_MAX_LEVEL, 0);
+            for (layer, pixels) in layers.iter().zip(faces) {
+                (gl.tex_image_2d)(
+                    TEXTURE_CUBE_MAP_POSITIVE_X + face_offset(*layer),
+                    0,
+                    SRGB8_ALPHA8,
+                    size as i32,
+                    size as i32,
+                    0,
+                    RGBA,
+                    UNSIGNED_BYTE,
+                    pixels.as_ptr() as *const c_void,
+                );
+            }
+            (gl.bind_buffer)(PIXEL_UNPACK_BUFFER, unpack_buffer as u32);
+            (gl.bind_texture)(TEXTURE_CUBE_MAP, texture as u32);
+            id
+        })
+    };
+    let dimensions = Dimensions::Cubemap { dimension: size };
+    Some(unsafe {
+        SrgbCubemap::from_id(
+            facade,
+            SrgbFormat::U8U8U8U8,
+            id,
+            true,
+            MipmapsOption::NoMipmap,
+            dimensions,
+        )
+    })
+}
+
+/// How far `layer`'s target is from `TEXTURE_CUBE_MAP_POSITIVE_X`.
+fn face_offset(layer: CubeLayer) -> u32 {
+    match layer {
+        CubeLayer::PositiveX => 0,
+        CubeLayer::NegativeX => 1,
+        CubeLayer::PositiveY => 2,
+        CubeLayer::NegativeY => 3,
+        CubeLayer::PositiveZ => 4,
+        CubeLayer::NegativeZ => 5,
+    }
+}
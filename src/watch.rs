@@ -0,0 +1,61 @@
+use std::fs;
+use std::time::{Duration, Instant, SystemTime};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Model files watched for `--watch` by polling their modification times,
+/// the same way the shaders are.
+pub struct WatchedFiles {
+    paths: Vec<String>,
+    modified: Vec<Option<SystemTime>>,
+    last_poll: Instant,
+}
+
+impl WatchedFiles {
+    /// Starts from the files as they are now, which is what the load started
+    /// alongside this will read.
+    pub fn new(paths: &[String]) -> Self {
+        WatchedFiles {
+            paths: paths.to_vec(),
+            modified: modified_times(paths),
+            last_poll: Instant::now(),
+        }
+    }
+
+    pub fn paths(&self) -> &[String] {
+        &self.paths
+    }
+
+    /// Whether any of the files changed since the last time this returned
+    /// true, checking at most once per poll interval.
+    pub fn changed(&mut self) -> bool {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let modified = modified_times(&self.paths);
+        // An exporter can briefly remove the file while saving; check again
+        // once it's back. Primitives have no file and never change.
+        let missing = self
+            .modified
+            .iter()
+            .zip(&modified)
+            .any(|(before, now)| before.is_some() && now.is_none());
+        if missing || modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+}
+
+fn modified_times(paths: &[String]) -> Vec<Option<SystemTime>> {
+    paths
+        .iter()
+        .map(|path| {
+            fs::metadata(path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect()
+}
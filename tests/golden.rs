@@ -0,0 +1,207 @@
+//! Renders the teapot offscreen with the binary and compares it with a
+//! reference image, so a change to the shaders, camera or loader that moves
+//! pixels shows up. Drivers round differently, so pixels may be off by a few
+//! levels and a few may be off by more, along the edges. Where no context
+//! can be made at all, the test is skipped rather than failed. OpenGL ES
+//! renders from the rewritten shaders are held to the same reference.
+//! Lighting is also checked against itself, from two sides of a cube.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use opengl_rust::args::Args;
+use opengl_rust::camera::Camera;
+use opengl_rust::math::Vec3;
+use opengl_rust::renderer::Renderer;
+use opengl_rust::scene::{Light, LightKind, Scene, Transform};
+use opengl_rust::{context, primitives, screenshot};
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 192;
+/// How far a channel may be from the reference and still match.
+const TOLERANCE: u8 = 8;
+/// The share of pixels that may be further off than that.
+const OUTLIERS: f64 = 0.01;
+
+/// Renders `model` to a PNG with `extra` arguments, or `None` where there's
+/// no way to make a context here.
+fn render(name: &str, model: &Path, extra: &[&str]) -> Option<PathBuf> {
+    let directory =
+        std::env::temp_dir().join(format!("opengl_rust-{}-{}", std::process::id(), name));
+    std::fs::create_dir_all(&directory).unwrap();
+    let output = directory.join("render.png");
+    let (width, height) = (WIDTH.to_string(), HEIGHT.to_string());
+    // No display server, so the render always goes through the same
+    // surfaceless path, and run away from any viewer.toml in the checkout.
+    let result = Command::new(env!("CARGO_BIN_EXE_opengl_rust"))
+        .current_dir(&directory)
+        .env_remove("DISPLAY")
+        .env_remove("WAYLAND_DISPLAY")
+        .args(["--headless", "--no-cache", "--output"])
+        .arg(&output)
+        .args(["--width", &width, "--height", &height])
+        .args(extra)
+        .arg(model)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&result.stderr);
+    if !result.status.success() && stderr.contains("no surfaceless context") {
+        eprintln!("skipping, no context can be made here: {}", stderr.trim());
+        return None;
+    }
+    assert!(result.status.success(), "render failed: {}", stderr);
+    Some(output)
+}
+
+fn compare(rendered: &Path, reference: &Path) {
+    let actual = image::open(rendered).unwrap().to_rgba8();
+    let expected = image::open(reference).unwrap().to_rgba8();
+    assert_eq!(actual.dimensions(), expected.dimensions());
+    let outliers = actual
+        .pixels()
+        .zip(expected.pixels())
+        .filter(|(a, b)| a.0.iter().zip(b.0).any(|(a, b)| a.abs_diff(b) > TOLERANCE))
+        .count();
+    let share = outliers as f64 / (WIDTH * HEIGHT) as f64;
+    assert!(
+        share <= OUTLIERS,
+        "{} of {} pixels differ from {}",
+        outliers,
+        WIDTH * HEIGHT,
+        reference.display()
+    );
+}
+
+fn golden(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name)
+}
+
+#[test]
+fn teapot_matches_the_reference() {
+    let model = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/teapot-3.obj");
+    if let Some(rendered) = render("golden-teapot", &model, &[]) {
+        compare(&rendered, &golden("teapot.png"));
+    }
+}
+
+#[test]
+fn teapot_on_opengl_es_matches_the_desktop_reference() {
+    let model = Path::new(env!("CARGO_MANIFEST_DIR")).join("src/teapot-3.obj");
+    if let Some(rendered) = render("golden-teapot-gles", &model, &["--gles"]) {
+        compare(&rendered, &golden("teapot.png"));
+    }
+}
+
+/// The colors of each face of the cube at `yaw` around it, keyed by the
+/// face's normal, from the pixels a fifth or more of the way in from its
+/// edges.
+fn cube_faces(
+    renderer: &mut Renderer,
+    facade: &impl glium::backend::Facade,
+    scene: &Scene,
+    yaw: f32,
+) -> HashMap<[i32; 3], Vec<[u8; 3]>> {
+    let mut camera = Camera::looking_at([0.0, 3.5, 4.0], [0.0, 1.0, 0.0]);
+    camera.yaw = yaw;
+    let image = screenshot::render_offscreen(facade, (WIDTH, HEIGHT), |target| {
+        renderer.render(target, scene, &camera);
+    });
+    let mut faces: HashMap<[i32; 3], Vec<[u8; 3]>> = HashMap::new();
+    for y in (0..HEIGHT).step_by(2) {
+        for x in (0..WIDTH).step_by(2) {
+            let cursor = (x as f64 + 0.5, y as f64 + 0.5);
+            let Some(hit) = renderer.pick(scene, &camera, cursor) else {
+                continue;
+            };
+            if hit.barycentric.iter().any(|&weight| weight < 0.2) {
+                continue;
+            }
+            let [a, b, c] = hit.corners;
+            let normal = (b - a).cross(c - a).normalize();
+            let key = [normal.x, normal.y, normal.z].map(|value| value.round() as i32);
+            // The image's rows run from the bottom up.
+            let start = (((HEIGHT - 1 - y) * WIDTH + x) * 4) as usize;
+            let pixel = &image.data[start..start + 3];
+            faces
+                .entry(key)
+                .or_default()
+                .push([pixel[0], pixel[1], pixel[2]]);
+        }
+    }
+    faces
+}
+
+fn mean(pixels: &[[u8; 3]]) -> [f64; 3] {
+    let mut sum = [0.0; 3];
+    for pixel in pixels {
+        for channel in 0..3 {
+            sum[channel] += pixel[channel] as f64 / pixels.len() as f64;
+        }
+    }
+    sum
+}
+
+#[test]
+fn cube_faces_keep_their_lighting_as_the_camera_orbits() {
+    let mut args = Args::parse_from(Vec::new()).unwrap();
+    (args.width, args.height) = (WIDTH, HEIGHT);
+    let facade = match context::create_surfaceless(&args) {
+        Ok(facade) => facade,
+        Err(error) => {
+            eprintln!("skipping, no context can be made here: {}", error);
+            return;
+        }
+    };
+    let mut renderer = Renderer::new(&facade).unwrap();
+    renderer.resize((WIDTH, HEIGHT).into());
+    // Nothing that changes with where it's seen from but the lighting under
+    // test: no highlights, nothing reflected in the faces, and no grid for
+    // them to be seen through.
+    renderer.show_grid = false;
+    renderer.reflections = false;
+    renderer.environment_reflectivity = 0.0;
+    let mut cube = primitives::cube(2.0);
+    cube.materials[0].specular = [0.0; 3];
+    let mut scene = Scene::default();
+    // Standing on the ground, which would otherwise cut it in half.
+    let transform = Transform {
+        translation: Vec3::new(0.0, 1.0, 0.0),
+        ..Default::default()
+    };
+    scene.add(renderer.add_mesh(cube), transform);
+    // Above, toward -X and +Z: the top is lit most, +Z less and +X not at all.
+    scene.lights.push(Light {
+        kind: LightKind::Directional {
+            direction: Vec3::new(-1.0, 2.0, 1.0),
+        },
+        ..Default::default()
+    });
+
+    // Both from the +X+Z quarter, so they see the same three faces.
+    let first = cube_faces(&mut renderer, &facade, &scene, 0.6);
+    let second = cube_faces(&mut renderer, &facade, &scene, 1.0);
+    let mut brightness = Vec::new();
+    for face in [[1, 0, 0], [0, 1, 0], [0, 0, 1]] {
+        let (seen, seen_again) = (&first[&face], &second[&face]);
+        assert!(
+            seen.len() > 50 && seen_again.len() > 50,
+            "{:?} barely seen",
+            face
+        );
+        let expected = mean(seen);
+        for pixel in seen.iter().chain(seen_again) {
+            let off = (0..3).any(|channel| (pixel[channel] as f64 - expected[channel]).abs() > 2.0);
+            assert!(
+                !off,
+                "{:?} is {:?} in places, {:?} on average",
+                face, pixel, expected
+            );
+        }
+        brightness.push(expected.iter().sum::<f64>());
+    }
+    // The faces really are lit differently, the top most and +X least.
+    assert!(brightness[1] > brightness[2] + 10.0 && brightness[2] > brightness[0] + 10.0);
+}